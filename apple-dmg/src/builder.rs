@@ -0,0 +1,220 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! DMG creation.
+//!
+//! [DmgBuilder] collects files, lays them out in an HFS+ volume, and writes
+//! that volume as a compressed, read-only disk image.
+
+use {
+    crate::{hfs::HfsVolumeBuilder, udif::write_udzo, DmgResult, Error},
+    chrono::{DateTime, Utc},
+    std::{io::Write, path::Path},
+    tugger_file_manifest::{FileEntry, FileManifest},
+};
+
+/// Convert a relative filesystem path to a `/` delimited volume path.
+fn volume_path(path: &Path) -> DmgResult<String> {
+    let components = path
+        .components()
+        .map(|c| {
+            c.as_os_str()
+                .to_str()
+                .ok_or_else(|| Error::InvalidPath(path.display().to_string()))
+        })
+        .collect::<DmgResult<Vec<_>>>()?;
+
+    Ok(components.join("/"))
+}
+
+/// Entity for producing DMG files.
+///
+/// Files are held in memory until [Self::write] is called.
+#[derive(Clone, Debug)]
+pub struct DmgBuilder {
+    volume: HfsVolumeBuilder,
+}
+
+impl DmgBuilder {
+    /// Construct a new instance for an image with the given volume name.
+    ///
+    /// The volume name is what macOS displays when the image is mounted.
+    pub fn new(volume_name: impl ToString) -> Self {
+        Self {
+            volume: HfsVolumeBuilder::new(volume_name),
+        }
+    }
+
+    /// The name of the volume.
+    pub fn volume_name(&self) -> &str {
+        self.volume.volume_name()
+    }
+
+    /// Set the modified time recorded for all entries in the image.
+    pub fn mtime(&mut self, mtime: DateTime<Utc>) {
+        self.volume.mtime(mtime);
+    }
+
+    /// Add an empty directory to the image.
+    pub fn add_directory(&mut self, path: impl AsRef<Path>) -> DmgResult<()> {
+        self.volume.add_directory(&volume_path(path.as_ref())?)
+    }
+
+    /// Add a file to the image.
+    ///
+    /// Symlinks are preserved as symlinks.
+    pub fn add_file(
+        &mut self,
+        path: impl AsRef<Path>,
+        entry: impl Into<FileEntry>,
+    ) -> DmgResult<()> {
+        let path = volume_path(path.as_ref())?;
+        let entry = entry.into();
+
+        if let Some(target) = entry.link_target() {
+            let target = target
+                .to_str()
+                .ok_or_else(|| Error::InvalidPath(target.display().to_string()))?;
+
+            self.volume.add_symlink(&path, target)
+        } else {
+            self.volume
+                .add_file(&path, entry.resolve_content()?, entry.is_executable())
+        }
+    }
+
+    /// Add all files in a [FileManifest] to the image.
+    pub fn add_manifest(&mut self, manifest: &FileManifest) -> DmgResult<()> {
+        for (path, entry) in manifest.iter_entries() {
+            self.add_file(path, entry)?;
+        }
+
+        Ok(())
+    }
+
+    /// Add the content of a directory on the filesystem to the image.
+    ///
+    /// Files are added relative to `prefix`. If `prefix` is empty, the
+    /// directory's content is placed at the root of the image. Symlinks are
+    /// preserved. Empty directories are retained.
+    pub fn add_directory_contents(
+        &mut self,
+        source_dir: impl AsRef<Path>,
+        prefix: impl AsRef<Path>,
+    ) -> DmgResult<()> {
+        let source_dir = source_dir.as_ref();
+        let prefix = prefix.as_ref();
+
+        let walk =
+            walkdir::WalkDir::new(source_dir).sort_by(|a, b| a.file_name().cmp(b.file_name()));
+
+        for entry in walk {
+            let entry = entry?;
+            let path = entry.path();
+
+            let rel_path = path
+                .strip_prefix(source_dir)
+                .expect("walked path should be under source directory");
+            let dest_path = prefix.join(rel_path);
+
+            let file_type = entry.file_type();
+
+            if file_type.is_dir() {
+                if dest_path.as_os_str().is_empty() {
+                    continue;
+                }
+
+                self.add_directory(&dest_path)?;
+            } else if file_type.is_symlink() {
+                let mut file_entry = FileEntry::new_from_data(vec![], false);
+                file_entry.set_link_target(std::fs::read_link(path)?);
+
+                self.add_file(&dest_path, file_entry)?;
+            } else {
+                self.add_file(&dest_path, FileEntry::try_from(path)?)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write the disk image to a writer.
+    ///
+    /// The image is a zlib compressed, read-only UDIF image holding an HFSX
+    /// (case-sensitive HFS+) volume.
+    pub fn write<W: Write>(&self, writer: W) -> DmgResult<W> {
+        let volume = self.volume.to_bytes()?;
+
+        write_udzo(writer, &volume, "disk image (Apple_HFSX : 0)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::udif::{KolyTrailer, UdifReader},
+        std::io::Cursor,
+    };
+
+    #[test]
+    fn write_manifest() -> DmgResult<()> {
+        let mut manifest = FileManifest::new_with_links();
+        manifest.add_file_entry(
+            "MyApp.app/Contents/MacOS/myapp",
+            FileEntry::new_from_data(b"#!/bin/sh\n".to_vec(), true),
+        )?;
+        manifest.add_symlink("Applications", "/Applications")?;
+
+        let mut builder = DmgBuilder::new("MyApp");
+        builder.add_manifest(&manifest)?;
+        assert!(matches!(
+            builder.add_manifest(&manifest),
+            Err(Error::PathExists(_))
+        ));
+
+        let data = builder.write(vec![])?;
+
+        let mut cursor = Cursor::new(data);
+        let koly = KolyTrailer::read_from(&mut cursor)?;
+        assert_eq!(koly.code_signature_offset, 0);
+
+        let reader = UdifReader::new(&mut cursor)?;
+        let image = reader.read_image(&mut cursor)?;
+        assert_eq!(&image[1024..1026], b"HX");
+
+        let needle = b"#!/bin/sh\n";
+        assert!(image.windows(needle.len()).any(|w| w == needle));
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn add_directory_contents() -> DmgResult<()> {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("apple-dmg-test-")
+            .tempdir()?;
+        let source = temp_dir.path();
+
+        std::fs::create_dir_all(source.join("MyApp.app/Contents/MacOS"))?;
+        std::fs::create_dir_all(source.join("MyApp.app/Contents/Resources"))?;
+        std::fs::write(source.join("MyApp.app/Contents/MacOS/myapp"), b"myapp")?;
+        std::os::unix::fs::symlink("MacOS/myapp", source.join("MyApp.app/Contents/link"))?;
+
+        let mut builder = DmgBuilder::new("MyApp");
+        builder.add_directory_contents(source, "")?;
+        builder.add_directory_contents(source, "copy")?;
+
+        let volume = builder.volume.to_bytes()?;
+        let needle = b"MacOS/myapp";
+        assert!(volume.windows(needle.len()).any(|w| w == needle));
+
+        // Directories are created as needed, so adding them again is a no-op.
+        builder.add_directory("MyApp.app/Contents/Resources")?;
+        assert!(builder.add_file("MyApp.app/Contents/link", vec![]).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,1134 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! HFS+ volume writing.
+
+[HfsVolumeBuilder] produces a complete HFS+ filesystem image in memory. The
+produced volume is:
+
+* An *HFSX* volume, which is the case-sensitive variant of HFS+. Catalog keys
+  are compared by their raw UTF-16 code units, which avoids the need for
+  Apple's Unicode case folding tables. macOS mounts these volumes natively.
+* Unjournaled and marked as cleanly unmounted.
+* Laid out contiguously: every fork occupies a single extent, so the extents
+  overflow B-tree is always empty.
+
+The format is described by Apple's *Technical Note TN1150: HFS Plus Volume
+Format*.
+
+File names are stored as given. macOS stores names in a variant of Unicode
+normalization form D. So names containing non-ASCII characters should be
+decomposed by the caller.
+*/
+
+use {
+    crate::{DmgResult, Error},
+    chrono::{DateTime, Utc},
+    scroll::{Pread, Pwrite, SizeWith},
+    std::collections::{BTreeMap, HashMap},
+};
+
+/// Volume signature for HFSX volumes (`HX`).
+const HFSX_SIGNATURE: u16 = 0x4858;
+
+/// Volume format version for HFSX volumes.
+const HFSX_VERSION: u16 = 5;
+
+/// Allocation block size of produced volumes.
+const BLOCK_SIZE: u64 = 4096;
+
+/// Size of B-tree nodes. This is also the allocation block size.
+const NODE_SIZE: usize = 4096;
+
+/// Smallest volume we produce, in allocation blocks.
+const MIN_VOLUME_BLOCKS: u64 = 256;
+
+/// Offset of the volume header from the start of the volume.
+const VOLUME_HEADER_OFFSET: usize = 1024;
+
+/// Volume attribute: the volume was cleanly unmounted.
+const VOLUME_UNMOUNTED: u32 = 1 << 8;
+
+/// `10.0`, the last mounted version of volumes not using journaling.
+const LAST_MOUNTED_VERSION: u32 = 0x3130_2e30;
+
+/// Seconds between 1904-01-01 (the HFS epoch) and 1970-01-01.
+const HFS_EPOCH_OFFSET: i64 = 2_082_844_800;
+
+const ROOT_PARENT_ID: u32 = 1;
+const ROOT_FOLDER_ID: u32 = 2;
+const FIRST_USER_CATALOG_ID: u32 = 16;
+
+const FOLDER_RECORD: i16 = 1;
+const FILE_RECORD: i16 = 2;
+const FOLDER_THREAD_RECORD: i16 = 3;
+const FILE_THREAD_RECORD: i16 = 4;
+
+/// Catalog file record flag indicating a file thread record exists.
+const FILE_THREAD_EXISTS: u16 = 0x0002;
+
+const LEAF_NODE: i8 = -1;
+const INDEX_NODE: i8 = 0;
+const HEADER_NODE: i8 = 1;
+
+const CATALOG_MAX_KEY_LENGTH: u16 = 516;
+const EXTENTS_MAX_KEY_LENGTH: u16 = 10;
+
+/// B-tree attribute: key lengths are 16 bits.
+const BT_BIG_KEYS: u32 = 0x2;
+/// B-tree attribute: index node keys are variable length.
+const BT_VARIABLE_INDEX_KEYS: u32 = 0x4;
+
+/// Catalog key comparison using binary ordering of UTF-16 code units.
+const KEY_COMPARE_BINARY: u8 = 0xbc;
+
+/// Size of the descriptor at the start of every B-tree node.
+const NODE_DESCRIPTOR_SIZE: usize = 14;
+
+/// Size of the map record in the B-tree header node.
+const HEADER_MAP_SIZE: usize = NODE_SIZE - 256;
+
+const S_IFDIR: u16 = 0o040000;
+const S_IFREG: u16 = 0o100000;
+const S_IFLNK: u16 = 0o120000;
+
+/// Finder file type for symlinks (`slnk`).
+const SYMLINK_FILE_TYPE: u32 = 0x736c_6e6b;
+/// Finder creator for symlinks (`rhap`).
+const SYMLINK_CREATOR: u32 = 0x7268_6170;
+
+#[derive(Clone, Copy, Debug, Default, Pread, Pwrite, SizeWith)]
+struct ForkData {
+    logical_size: u64,
+    clump_size: u32,
+    total_blocks: u32,
+    /// 8 extents, each a pair of start block and block count.
+    extents: [u32; 16],
+}
+
+impl ForkData {
+    /// Describe a fork stored in a single extent.
+    fn contiguous(start_block: u64, block_count: u64, logical_size: u64) -> Self {
+        let mut extents = [0; 16];
+        if block_count > 0 {
+            extents[0] = start_block as _;
+            extents[1] = block_count as _;
+        }
+
+        Self {
+            logical_size,
+            clump_size: 0,
+            total_blocks: block_count as _,
+            extents,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Pread, Pwrite, SizeWith)]
+struct VolumeHeader {
+    signature: u16,
+    version: u16,
+    attributes: u32,
+    last_mounted_version: u32,
+    journal_info_block: u32,
+    create_date: u32,
+    modify_date: u32,
+    backup_date: u32,
+    checked_date: u32,
+    file_count: u32,
+    folder_count: u32,
+    block_size: u32,
+    total_blocks: u32,
+    free_blocks: u32,
+    next_allocation: u32,
+    rsrc_clump_size: u32,
+    data_clump_size: u32,
+    next_catalog_id: u32,
+    write_count: u32,
+    encodings_bitmap: u64,
+    finder_info: [u32; 8],
+    allocation_file: ForkData,
+    extents_file: ForkData,
+    catalog_file: ForkData,
+    attributes_file: ForkData,
+    startup_file: ForkData,
+}
+
+#[derive(Clone, Copy, Debug, Default, Pread, Pwrite, SizeWith)]
+struct NodeDescriptor {
+    f_link: u32,
+    b_link: u32,
+    kind: i8,
+    height: u8,
+    num_records: u16,
+    reserved: u16,
+}
+
+#[derive(Clone, Copy, Debug, Default, Pread, Pwrite, SizeWith)]
+struct BTreeHeader {
+    tree_depth: u16,
+    root_node: u32,
+    leaf_records: u32,
+    first_leaf_node: u32,
+    last_leaf_node: u32,
+    node_size: u16,
+    max_key_length: u16,
+    total_nodes: u32,
+    free_nodes: u32,
+    reserved1: u16,
+    clump_size: u32,
+    btree_type: u8,
+    key_compare_type: u8,
+    attributes: u32,
+    reserved3: [u32; 16],
+}
+
+#[derive(Clone, Copy, Debug, Default, Pread, Pwrite, SizeWith)]
+struct BsdInfo {
+    owner_id: u32,
+    group_id: u32,
+    admin_flags: u8,
+    owner_flags: u8,
+    file_mode: u16,
+    special: u32,
+}
+
+#[derive(Clone, Copy, Debug, Default, Pread, Pwrite, SizeWith)]
+struct CatalogFolder {
+    record_type: i16,
+    flags: u16,
+    valence: u32,
+    folder_id: u32,
+    create_date: u32,
+    content_mod_date: u32,
+    attribute_mod_date: u32,
+    access_date: u32,
+    backup_date: u32,
+    permissions: BsdInfo,
+    user_info: [u8; 16],
+    finder_info: [u8; 16],
+    text_encoding: u32,
+    reserved: u32,
+}
+
+#[derive(Clone, Copy, Debug, Default, Pread, Pwrite, SizeWith)]
+struct CatalogFile {
+    record_type: i16,
+    flags: u16,
+    reserved1: u32,
+    file_id: u32,
+    create_date: u32,
+    content_mod_date: u32,
+    attribute_mod_date: u32,
+    access_date: u32,
+    backup_date: u32,
+    permissions: BsdInfo,
+    file_type: u32,
+    file_creator: u32,
+    finder_flags: u16,
+    location_v: i16,
+    location_h: i16,
+    reserved_field: u16,
+    finder_info: [u8; 16],
+    text_encoding: u32,
+    reserved2: u32,
+    data_fork: ForkData,
+    resource_fork: ForkData,
+}
+
+/// Serialize a value implementing [Pwrite] into a new buffer.
+fn to_vec<T>(value: T) -> DmgResult<Vec<u8>>
+where
+    T: scroll::ctx::SizeWith<scroll::Endian>
+        + scroll::ctx::TryIntoCtx<scroll::Endian, Error = scroll::Error>,
+{
+    let mut data = vec![0u8; T::size_with(&scroll::BE)];
+    data.pwrite_with(value, 0, scroll::BE)?;
+
+    Ok(data)
+}
+
+/// Convert a time to an HFS+ timestamp.
+fn hfs_time(time: &DateTime<Utc>) -> u32 {
+    (time.timestamp() + HFS_EPOCH_OFFSET).clamp(0, u32::MAX as i64) as u32
+}
+
+/// Convert a filename to its on-disk representation.
+///
+/// `:` is the path separator of the Carbon APIs. POSIX paths containing `:`
+/// are stored as `/` on disk.
+fn hfs_name(name: &str) -> Vec<u16> {
+    name.chars()
+        .map(|c| if c == ':' { '/' } else { c })
+        .collect::<String>()
+        .encode_utf16()
+        .collect()
+}
+
+/// Produce a catalog key, including its leading key length field.
+fn catalog_key(parent_id: u32, name: &[u16]) -> Vec<u8> {
+    let mut key = Vec::with_capacity(8 + name.len() * 2);
+    key.extend_from_slice(&(6 + name.len() as u16 * 2).to_be_bytes());
+    key.extend_from_slice(&parent_id.to_be_bytes());
+    key.extend_from_slice(&(name.len() as u16).to_be_bytes());
+    for c in name {
+        key.extend_from_slice(&c.to_be_bytes());
+    }
+
+    key
+}
+
+/// Produce a folder or file thread record.
+fn thread_record(record_type: i16, parent_id: u32, name: &[u16]) -> Vec<u8> {
+    let mut record = Vec::with_capacity(10 + name.len() * 2);
+    record.extend_from_slice(&record_type.to_be_bytes());
+    record.extend_from_slice(&0u16.to_be_bytes());
+    record.extend_from_slice(&parent_id.to_be_bytes());
+    record.extend_from_slice(&(name.len() as u16).to_be_bytes());
+    for c in name {
+        record.extend_from_slice(&c.to_be_bytes());
+    }
+
+    record
+}
+
+/// Build a B-tree node from records.
+fn write_node(descriptor: NodeDescriptor, records: &[&[u8]]) -> DmgResult<Vec<u8>> {
+    let mut node = vec![0u8; NODE_SIZE];
+    node.pwrite_with(descriptor, 0, scroll::BE)?;
+
+    let mut offset = NODE_DESCRIPTOR_SIZE;
+
+    // Record offsets are stored in reverse order at the end of the node,
+    // followed by the offset of free space.
+    for (i, record) in records.iter().enumerate() {
+        node[offset..offset + record.len()].copy_from_slice(record);
+        node.pwrite_with(offset as u16, NODE_SIZE - 2 * (i + 1), scroll::BE)?;
+        offset += record.len();
+    }
+    node.pwrite_with(
+        offset as u16,
+        NODE_SIZE - 2 * (records.len() + 1),
+        scroll::BE,
+    )?;
+
+    Ok(node)
+}
+
+/// Build a B-tree header node.
+fn write_header_node(header: BTreeHeader) -> DmgResult<Vec<u8>> {
+    if header.total_nodes as usize > HEADER_MAP_SIZE * 8 {
+        return Err(Error::VolumeTooLarge);
+    }
+
+    let mut map = vec![0u8; HEADER_MAP_SIZE];
+    for node in 0..(header.total_nodes - header.free_nodes) as usize {
+        map[node / 8] |= 0x80 >> (node % 8);
+    }
+
+    write_node(
+        NodeDescriptor {
+            kind: HEADER_NODE,
+            num_records: 3,
+            ..Default::default()
+        },
+        &[&to_vec(header)?, &[0u8; 128], &map],
+    )
+}
+
+/// Records in a B-tree node, as pairs of key and serialized record.
+type NodeRecords<'a> = Vec<(&'a [u8], Vec<u8>)>;
+
+/// A B-tree with all its nodes, including the header node.
+struct BTree {
+    nodes: Vec<Vec<u8>>,
+}
+
+impl BTree {
+    /// Build a catalog B-tree from leaf records sorted by key.
+    fn catalog(records: &[(Vec<u8>, Vec<u8>)]) -> DmgResult<Self> {
+        // Each level is a list of nodes. Each node is a list of (key, record).
+        let mut levels: Vec<Vec<NodeRecords>> = vec![];
+
+        let leaf_records = records
+            .iter()
+            .map(|(key, data)| (key.as_slice(), [key.as_slice(), data.as_slice()].concat()))
+            .collect::<Vec<_>>();
+        levels.push(Self::pack(leaf_records));
+
+        // Node 0 is the header node. Nodes are numbered level by level,
+        // starting with the leaves.
+        let mut first_node_numbers = vec![1u32];
+
+        while levels.last().expect("at least 1 level exists").len() > 1 {
+            let level = levels.last().expect("at least 1 level exists");
+            let first_number = *first_node_numbers.last().expect("level is numbered");
+
+            let index_records = level
+                .iter()
+                .enumerate()
+                .map(|(i, node)| {
+                    let key = node[0].0;
+                    let record = [key, &(first_number + i as u32).to_be_bytes()].concat();
+                    (key, record)
+                })
+                .collect::<Vec<_>>();
+
+            first_node_numbers.push(first_number + level.len() as u32);
+            levels.push(Self::pack(index_records));
+        }
+
+        let mut nodes = vec![vec![]];
+
+        for (height, level) in levels.iter().enumerate() {
+            let first_number = first_node_numbers[height];
+
+            for (i, node) in level.iter().enumerate() {
+                let number = first_number + i as u32;
+
+                let descriptor = NodeDescriptor {
+                    f_link: if i + 1 < level.len() { number + 1 } else { 0 },
+                    b_link: if i > 0 { number - 1 } else { 0 },
+                    kind: if height == 0 { LEAF_NODE } else { INDEX_NODE },
+                    height: height as u8 + 1,
+                    num_records: node.len() as _,
+                    reserved: 0,
+                };
+
+                let records = node.iter().map(|(_, r)| r.as_slice()).collect::<Vec<_>>();
+                nodes.push(write_node(descriptor, &records)?);
+            }
+        }
+
+        let leaf_count = levels[0].len() as u32;
+
+        nodes[0] = write_header_node(BTreeHeader {
+            tree_depth: levels.len() as _,
+            root_node: *first_node_numbers.last().expect("level is numbered"),
+            leaf_records: records.len() as _,
+            first_leaf_node: 1,
+            last_leaf_node: leaf_count,
+            node_size: NODE_SIZE as _,
+            max_key_length: CATALOG_MAX_KEY_LENGTH,
+            total_nodes: nodes.len() as _,
+            free_nodes: 0,
+            clump_size: (nodes.len() * NODE_SIZE) as _,
+            key_compare_type: KEY_COMPARE_BINARY,
+            attributes: BT_BIG_KEYS | BT_VARIABLE_INDEX_KEYS,
+            ..Default::default()
+        })?;
+
+        Ok(Self { nodes })
+    }
+
+    /// Build an empty extents overflow B-tree.
+    fn empty_extents() -> DmgResult<Self> {
+        let header = write_header_node(BTreeHeader {
+            node_size: NODE_SIZE as _,
+            max_key_length: EXTENTS_MAX_KEY_LENGTH,
+            total_nodes: 1,
+            free_nodes: 0,
+            clump_size: NODE_SIZE as _,
+            attributes: BT_BIG_KEYS,
+            ..Default::default()
+        })?;
+
+        Ok(Self {
+            nodes: vec![header],
+        })
+    }
+
+    /// Distribute records into nodes, filling each node as much as possible.
+    fn pack(records: NodeRecords) -> Vec<NodeRecords> {
+        let mut nodes = vec![];
+        let mut node = vec![];
+        let mut used = NODE_DESCRIPTOR_SIZE + 2;
+
+        for record in records {
+            // Each record consumes its size plus a 2 byte offset.
+            let size = record.1.len() + 2;
+
+            if used + size > NODE_SIZE {
+                nodes.push(std::mem::take(&mut node));
+                used = NODE_DESCRIPTOR_SIZE + 2;
+            }
+
+            used += size;
+            node.push(record);
+        }
+
+        if !node.is_empty() {
+            nodes.push(node);
+        }
+
+        nodes
+    }
+
+    fn len(&self) -> u64 {
+        self.nodes.len() as u64
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Entry {
+    Directory,
+    File { data: Vec<u8>, executable: bool },
+    Symlink { target: String },
+}
+
+/// A catalog entry whose ID has been assigned.
+struct CatalogEntry<'a> {
+    id: u32,
+    parent_id: u32,
+    name: &'a str,
+    entry: &'a Entry,
+}
+
+/// Entity for producing HFS+ volumes.
+///
+/// Paths use `/` as a directory separator and are relative to the root of the
+/// volume. Parent directories are created automatically.
+#[derive(Clone, Debug)]
+pub struct HfsVolumeBuilder {
+    volume_name: String,
+    mtime: DateTime<Utc>,
+    entries: BTreeMap<Vec<String>, Entry>,
+}
+
+impl HfsVolumeBuilder {
+    /// Construct a new instance for a volume with the given name.
+    pub fn new(volume_name: impl ToString) -> Self {
+        Self {
+            volume_name: volume_name.to_string(),
+            mtime: Utc::now(),
+            entries: BTreeMap::new(),
+        }
+    }
+
+    /// The name of the volume.
+    pub fn volume_name(&self) -> &str {
+        &self.volume_name
+    }
+
+    /// Set the time recorded for the volume and all entries in it.
+    pub fn mtime(&mut self, mtime: DateTime<Utc>) {
+        self.mtime = mtime;
+    }
+
+    /// Whether the volume has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn path_components(path: &str) -> DmgResult<Vec<String>> {
+        let components = path
+            .split('/')
+            .filter(|c| !c.is_empty() && *c != ".")
+            .map(|c| {
+                if c == ".." {
+                    Err(Error::InvalidPath(path.to_string()))
+                } else if hfs_name(c).len() > 255 {
+                    Err(Error::NameTooLong(c.to_string()))
+                } else {
+                    Ok(c.to_string())
+                }
+            })
+            .collect::<DmgResult<Vec<_>>>()?;
+
+        if components.is_empty() {
+            Err(Error::InvalidPath(path.to_string()))
+        } else {
+            Ok(components)
+        }
+    }
+
+    /// Ensure all parent directories of a path exist.
+    fn ensure_parents(&mut self, path: &str, components: &[String]) -> DmgResult<()> {
+        for i in 1..components.len() {
+            match self.entries.get(&components[0..i]) {
+                Some(Entry::Directory) => {}
+                Some(_) => return Err(Error::NotADirectory(path.to_string())),
+                None => {
+                    self.entries
+                        .insert(components[0..i].to_vec(), Entry::Directory);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn add_entry(&mut self, path: &str, entry: Entry) -> DmgResult<()> {
+        let components = Self::path_components(path)?;
+        self.ensure_parents(path, &components)?;
+
+        if self.entries.contains_key(&components) {
+            return Err(Error::PathExists(path.to_string()));
+        }
+
+        self.entries.insert(components, entry);
+
+        Ok(())
+    }
+
+    /// Add an empty directory.
+    ///
+    /// Adding a directory that already exists is a no-op.
+    pub fn add_directory(&mut self, path: &str) -> DmgResult<()> {
+        let components = Self::path_components(path)?;
+        self.ensure_parents(path, &components)?;
+
+        match self.entries.get(&components) {
+            Some(Entry::Directory) => Ok(()),
+            Some(_) => Err(Error::PathExists(path.to_string())),
+            None => {
+                self.entries.insert(components, Entry::Directory);
+                Ok(())
+            }
+        }
+    }
+
+    /// Add a regular file with the given content.
+    pub fn add_file(&mut self, path: &str, data: Vec<u8>, executable: bool) -> DmgResult<()> {
+        self.add_entry(path, Entry::File { data, executable })
+    }
+
+    /// Add a symbolic link pointing to `target`.
+    pub fn add_symlink(&mut self, path: &str, target: &str) -> DmgResult<()> {
+        self.add_entry(
+            path,
+            Entry::Symlink {
+                target: target.to_string(),
+            },
+        )
+    }
+
+    /// Assign catalog node IDs to all entries.
+    ///
+    /// Returns the entries and the number of children of each folder.
+    fn catalog_entries(&self) -> DmgResult<(Vec<CatalogEntry<'_>>, HashMap<u32, u32>)> {
+        let mut ids = HashMap::new();
+        let mut valences = HashMap::new();
+        let mut entries = vec![];
+
+        // Iteration is sorted by path components, so parents are always seen
+        // before their children.
+        for (i, (components, entry)) in self.entries.iter().enumerate() {
+            let (name, parent) = components.split_last().expect("paths are never empty");
+
+            let parent_id = if parent.is_empty() {
+                ROOT_FOLDER_ID
+            } else {
+                *ids.get(parent)
+                    .expect("parent directories are always present")
+            };
+
+            let id = u32::try_from(i)
+                .ok()
+                .and_then(|i| i.checked_add(FIRST_USER_CATALOG_ID))
+                .ok_or(Error::VolumeTooLarge)?;
+
+            ids.insert(components.as_slice(), id);
+            *valences.entry(parent_id).or_insert(0) += 1;
+
+            entries.push(CatalogEntry {
+                id,
+                parent_id,
+                name,
+                entry,
+            });
+        }
+
+        Ok((entries, valences))
+    }
+
+    fn bsd_info(&self, mode: u16) -> BsdInfo {
+        BsdInfo {
+            owner_id: 0,
+            group_id: 0,
+            admin_flags: 0,
+            owner_flags: 0,
+            file_mode: mode,
+            special: 0,
+        }
+    }
+
+    fn folder_record(&self, id: u32, valence: u32) -> DmgResult<Vec<u8>> {
+        let time = hfs_time(&self.mtime);
+
+        to_vec(CatalogFolder {
+            record_type: FOLDER_RECORD,
+            valence,
+            folder_id: id,
+            create_date: time,
+            content_mod_date: time,
+            attribute_mod_date: time,
+            access_date: time,
+            permissions: self.bsd_info(S_IFDIR | 0o755),
+            ..Default::default()
+        })
+    }
+
+    fn file_record(&self, id: u32, entry: &Entry, data_fork: ForkData) -> DmgResult<Vec<u8>> {
+        let time = hfs_time(&self.mtime);
+
+        let (mode, file_type, file_creator) = match entry {
+            Entry::File {
+                executable: true, ..
+            } => (S_IFREG | 0o755, 0, 0),
+            Entry::File { .. } => (S_IFREG | 0o644, 0, 0),
+            Entry::Symlink { .. } => (S_IFLNK | 0o755, SYMLINK_FILE_TYPE, SYMLINK_CREATOR),
+            Entry::Directory => panic!("directories do not have file records"),
+        };
+
+        let mut permissions = self.bsd_info(mode);
+        // The link count.
+        permissions.special = 1;
+
+        to_vec(CatalogFile {
+            record_type: FILE_RECORD,
+            flags: FILE_THREAD_EXISTS,
+            file_id: id,
+            create_date: time,
+            content_mod_date: time,
+            attribute_mod_date: time,
+            access_date: time,
+            permissions,
+            file_type,
+            file_creator,
+            data_fork,
+            ..Default::default()
+        })
+    }
+
+    /// Build the sorted catalog leaf records.
+    fn catalog_records(
+        &self,
+        entries: &[CatalogEntry],
+        valences: &HashMap<u32, u32>,
+        forks: &HashMap<u32, ForkData>,
+    ) -> DmgResult<Vec<(Vec<u8>, Vec<u8>)>> {
+        let mut records = vec![];
+
+        let volume_name = hfs_name(&self.volume_name);
+        let root_valence = valences.get(&ROOT_FOLDER_ID).copied().unwrap_or(0);
+
+        records.push((
+            (ROOT_PARENT_ID, volume_name.clone()),
+            self.folder_record(ROOT_FOLDER_ID, root_valence)?,
+        ));
+        records.push((
+            (ROOT_FOLDER_ID, vec![]),
+            thread_record(FOLDER_THREAD_RECORD, ROOT_PARENT_ID, &volume_name),
+        ));
+
+        for entry in entries {
+            let name = hfs_name(entry.name);
+
+            let (record, thread_type) = match entry.entry {
+                Entry::Directory => (
+                    self.folder_record(entry.id, valences.get(&entry.id).copied().unwrap_or(0))?,
+                    FOLDER_THREAD_RECORD,
+                ),
+                Entry::File { .. } | Entry::Symlink { .. } => (
+                    self.file_record(
+                        entry.id,
+                        entry.entry,
+                        forks.get(&entry.id).copied().unwrap_or_default(),
+                    )?,
+                    FILE_THREAD_RECORD,
+                ),
+            };
+
+            records.push(((entry.parent_id, name.clone()), record));
+            records.push((
+                (entry.id, vec![]),
+                thread_record(thread_type, entry.parent_id, &name),
+            ));
+        }
+
+        // Catalog keys are ordered by parent ID then by name.
+        records.sort_by(|a, b| a.0.cmp(&b.0));
+
+        Ok(records
+            .into_iter()
+            .map(|((parent_id, name), record)| (catalog_key(parent_id, &name), record))
+            .collect())
+    }
+
+    /// Produce the raw volume image.
+    pub fn to_bytes(&self) -> DmgResult<Vec<u8>> {
+        let (entries, valences) = self.catalog_entries()?;
+
+        let fork_content = entries
+            .iter()
+            .filter_map(|entry| match entry.entry {
+                Entry::File { data, .. } => Some((entry.id, data.as_slice())),
+                Entry::Symlink { target } => Some((entry.id, target.as_bytes())),
+                Entry::Directory => None,
+            })
+            .collect::<Vec<_>>();
+
+        let data_blocks = fork_content
+            .iter()
+            .map(|(_, data)| (data.len() as u64 + BLOCK_SIZE - 1) / BLOCK_SIZE)
+            .sum::<u64>();
+
+        // The size of catalog records doesn't depend on fork locations. So we
+        // can build the catalog with placeholder forks to determine its size.
+        let catalog_blocks =
+            BTree::catalog(&self.catalog_records(&entries, &valences, &HashMap::new())?)?.len();
+        let extents = BTree::empty_extents()?;
+
+        // Block 0 holds the volume header. The last block holds the alternate
+        // volume header. The allocation bitmap size depends on the volume size.
+        let fixed_blocks = 2 + extents.len() + catalog_blocks + data_blocks;
+        let mut allocation_blocks = 1;
+        let total_blocks = loop {
+            let total = (fixed_blocks + allocation_blocks).max(MIN_VOLUME_BLOCKS);
+            let needed = (total + BLOCK_SIZE * 8 - 1) / (BLOCK_SIZE * 8);
+
+            if needed <= allocation_blocks {
+                break total;
+            }
+
+            allocation_blocks = needed;
+        };
+
+        if total_blocks > u32::MAX as u64 {
+            return Err(Error::VolumeTooLarge);
+        }
+
+        let allocation_start = 1;
+        let extents_start = allocation_start + allocation_blocks;
+        let catalog_start = extents_start + extents.len();
+        let data_start = catalog_start + catalog_blocks;
+
+        let mut forks = HashMap::new();
+        let mut next_block = data_start;
+        for (id, data) in &fork_content {
+            let blocks = (data.len() as u64 + BLOCK_SIZE - 1) / BLOCK_SIZE;
+            forks.insert(
+                *id,
+                ForkData::contiguous(next_block, blocks, data.len() as u64),
+            );
+            next_block += blocks;
+        }
+        let first_free_block = next_block;
+
+        let catalog = BTree::catalog(&self.catalog_records(&entries, &valences, &forks)?)?;
+        assert_eq!(catalog.len(), catalog_blocks);
+
+        let mut image = vec![0u8; (total_blocks * BLOCK_SIZE) as usize];
+
+        let mut write_blocks = |block: u64, data: &[u8]| {
+            let offset = (block * BLOCK_SIZE) as usize;
+            image[offset..offset + data.len()].copy_from_slice(data);
+        };
+
+        // Allocation bitmap. Everything up to the first free block is used,
+        // as is the block holding the alternate volume header.
+        let mut bitmap = vec![0u8; (allocation_blocks * BLOCK_SIZE) as usize];
+        for block in (0..first_free_block).chain(std::iter::once(total_blocks - 1)) {
+            bitmap[(block / 8) as usize] |= 0x80 >> (block % 8);
+        }
+        write_blocks(allocation_start, &bitmap);
+
+        for (i, node) in extents.nodes.iter().enumerate() {
+            write_blocks(extents_start + i as u64, node);
+        }
+        for (i, node) in catalog.nodes.iter().enumerate() {
+            write_blocks(catalog_start + i as u64, node);
+        }
+        for (id, data) in &fork_content {
+            write_blocks(forks[id].extents[0] as u64, data);
+        }
+
+        let time = hfs_time(&self.mtime);
+
+        let file_count = fork_content.len() as u32;
+        let folder_count = entries.len() as u32 - file_count;
+
+        let header = VolumeHeader {
+            signature: HFSX_SIGNATURE,
+            version: HFSX_VERSION,
+            attributes: VOLUME_UNMOUNTED,
+            last_mounted_version: LAST_MOUNTED_VERSION,
+            create_date: time,
+            modify_date: time,
+            checked_date: time,
+            file_count,
+            folder_count,
+            block_size: BLOCK_SIZE as _,
+            total_blocks: total_blocks as _,
+            free_blocks: (total_blocks - first_free_block - 1) as _,
+            next_allocation: first_free_block as _,
+            rsrc_clump_size: 65536,
+            data_clump_size: 65536,
+            next_catalog_id: FIRST_USER_CATALOG_ID + entries.len() as u32,
+            write_count: 1,
+            // MacRoman.
+            encodings_bitmap: 1,
+            allocation_file: ForkData::contiguous(
+                allocation_start,
+                allocation_blocks,
+                allocation_blocks * BLOCK_SIZE,
+            ),
+            extents_file: ForkData::contiguous(
+                extents_start,
+                extents.len(),
+                extents.len() * BLOCK_SIZE,
+            ),
+            catalog_file: ForkData::contiguous(
+                catalog_start,
+                catalog.len(),
+                catalog.len() * BLOCK_SIZE,
+            ),
+            ..Default::default()
+        };
+
+        let alternate_offset = image.len() - VOLUME_HEADER_OFFSET;
+        image.pwrite_with(header.clone(), VOLUME_HEADER_OFFSET, scroll::BE)?;
+        image.pwrite_with(header, alternate_offset, scroll::BE)?;
+
+        Ok(image)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, scroll::ctx::SizeWith as _};
+
+    /// Minimal catalog reader for verifying produced volumes.
+    struct Volume {
+        image: Vec<u8>,
+        header: VolumeHeader,
+    }
+
+    impl Volume {
+        fn new(image: Vec<u8>) -> DmgResult<Self> {
+            let header = image.pread_with::<VolumeHeader>(VOLUME_HEADER_OFFSET, scroll::BE)?;
+
+            Ok(Self { image, header })
+        }
+
+        fn fork(&self, fork: &ForkData) -> &[u8] {
+            let offset = (fork.extents[0] as u64 * BLOCK_SIZE) as usize;
+            &self.image[offset..offset + fork.logical_size as usize]
+        }
+
+        fn node(&self, number: u32) -> &[u8] {
+            let catalog = self.fork(&self.header.catalog_file);
+            &catalog[number as usize * NODE_SIZE..(number as usize + 1) * NODE_SIZE]
+        }
+
+        fn catalog_header(&self) -> DmgResult<BTreeHeader> {
+            Ok(self
+                .node(0)
+                .pread_with::<BTreeHeader>(NODE_DESCRIPTOR_SIZE, scroll::BE)?)
+        }
+
+        fn node_records(node: &[u8]) -> DmgResult<(NodeDescriptor, Vec<&[u8]>)> {
+            let descriptor = node.pread_with::<NodeDescriptor>(0, scroll::BE)?;
+
+            let offsets = (0..descriptor.num_records as usize + 1)
+                .map(|i| node.pread_with::<u16>(NODE_SIZE - 2 * (i + 1), scroll::BE))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let records = offsets
+                .windows(2)
+                .map(|w| &node[w[0] as usize..w[1] as usize])
+                .collect::<Vec<_>>();
+
+            Ok((descriptor, records))
+        }
+
+        /// Obtain (parent ID, name, record data) for all leaf records.
+        fn leaf_records(&self) -> DmgResult<Vec<(u32, String, &[u8])>> {
+            let mut res = vec![];
+            let mut number = self.catalog_header()?.first_leaf_node;
+
+            while number != 0 {
+                let (descriptor, records) = Self::node_records(self.node(number))?;
+                assert_eq!(descriptor.kind, LEAF_NODE);
+
+                for record in records {
+                    let key_length = record.pread_with::<u16>(0, scroll::BE)? as usize;
+                    let parent_id = record.pread_with::<u32>(2, scroll::BE)?;
+                    let name_length = record.pread_with::<u16>(6, scroll::BE)? as usize;
+                    let name = (0..name_length)
+                        .map(|i| record.pread_with::<u16>(8 + i * 2, scroll::BE))
+                        .collect::<Result<Vec<_>, _>>()?;
+
+                    res.push((
+                        parent_id,
+                        String::from_utf16(&name).unwrap(),
+                        &record[2 + key_length..],
+                    ));
+                }
+
+                number = descriptor.f_link;
+            }
+
+            Ok(res)
+        }
+
+        /// Resolve the file record for a path.
+        fn file(&self, path: &str) -> DmgResult<CatalogFile> {
+            let records = self.leaf_records()?;
+            let mut parent_id = ROOT_FOLDER_ID;
+
+            let components = path.split('/').collect::<Vec<_>>();
+            for (i, component) in components.iter().enumerate() {
+                let (_, _, data) = records
+                    .iter()
+                    .find(|(p, n, _)| *p == parent_id && n == component)
+                    .unwrap();
+
+                if i + 1 == components.len() {
+                    return Ok(data.pread_with::<CatalogFile>(0, scroll::BE)?);
+                }
+
+                parent_id = data.pread_with::<CatalogFolder>(0, scroll::BE)?.folder_id;
+            }
+
+            unreachable!();
+        }
+    }
+
+    #[test]
+    fn structure_sizes() {
+        assert_eq!(ForkData::size_with(&scroll::BE), 80);
+        assert_eq!(VolumeHeader::size_with(&scroll::BE), 512);
+        assert_eq!(NodeDescriptor::size_with(&scroll::BE), NODE_DESCRIPTOR_SIZE);
+        assert_eq!(BTreeHeader::size_with(&scroll::BE), 106);
+        assert_eq!(CatalogFolder::size_with(&scroll::BE), 88);
+        assert_eq!(CatalogFile::size_with(&scroll::BE), 248);
+    }
+
+    #[test]
+    fn empty_volume() -> DmgResult<()> {
+        let builder = HfsVolumeBuilder::new("Empty");
+        let volume = Volume::new(builder.to_bytes()?)?;
+
+        assert_eq!(volume.image.len() as u64, MIN_VOLUME_BLOCKS * BLOCK_SIZE);
+        assert_eq!(volume.header.signature, HFSX_SIGNATURE);
+        assert_eq!(volume.header.file_count, 0);
+        assert_eq!(volume.header.folder_count, 0);
+        assert_eq!(
+            &volume.image[VOLUME_HEADER_OFFSET..VOLUME_HEADER_OFFSET + 512],
+            &volume.image[volume.image.len() - VOLUME_HEADER_OFFSET..][..512]
+        );
+
+        let records = volume.leaf_records()?;
+        assert_eq!(records.len(), 2);
+        assert_eq!(
+            (records[0].0, records[0].1.as_str()),
+            (ROOT_PARENT_ID, "Empty")
+        );
+        assert_eq!((records[1].0, records[1].1.as_str()), (ROOT_FOLDER_ID, ""));
+
+        Ok(())
+    }
+
+    #[test]
+    fn files_and_directories() -> DmgResult<()> {
+        let mut builder = HfsVolumeBuilder::new("Test");
+        builder.add_file(
+            "MyApp.app/Contents/MacOS/myapp",
+            b"#!/bin/sh\n".to_vec(),
+            true,
+        )?;
+        builder.add_file("MyApp.app/Contents/Info.plist", vec![42u8; 10000], false)?;
+        builder.add_symlink("Applications", "/Applications")?;
+        builder.add_directory("empty")?;
+
+        assert!(matches!(
+            builder.add_file("empty", vec![], false),
+            Err(Error::PathExists(_))
+        ));
+        assert!(matches!(
+            builder.add_file("Applications/foo", vec![], false),
+            Err(Error::NotADirectory(_))
+        ));
+        assert!(matches!(
+            builder.add_file("../foo", vec![], false),
+            Err(Error::InvalidPath(_))
+        ));
+
+        let volume = Volume::new(builder.to_bytes()?)?;
+        assert_eq!(volume.header.file_count, 3);
+        assert_eq!(volume.header.folder_count, 4);
+
+        let records = volume.leaf_records()?;
+        let keys = records
+            .iter()
+            .map(|(parent, name, _)| (*parent, name.clone()))
+            .collect::<Vec<_>>();
+        let mut sorted = keys.clone();
+        sorted.sort();
+        assert_eq!(keys, sorted);
+        assert_eq!(records.len(), 2 * 8);
+
+        let root = records[0].2.pread_with::<CatalogFolder>(0, scroll::BE)?;
+        assert_eq!(root.valence, 3);
+
+        let exe = volume.file("MyApp.app/Contents/MacOS/myapp")?;
+        assert_eq!(exe.permissions.file_mode, S_IFREG | 0o755);
+        assert_eq!(volume.fork(&exe.data_fork), b"#!/bin/sh\n");
+
+        let plist = volume.file("MyApp.app/Contents/Info.plist")?;
+        assert_eq!(plist.permissions.file_mode, S_IFREG | 0o644);
+        assert_eq!(plist.data_fork.total_blocks, 3);
+        assert_eq!(volume.fork(&plist.data_fork), vec![42u8; 10000].as_slice());
+
+        let link = volume.file("Applications")?;
+        assert_eq!(link.permissions.file_mode, S_IFLNK | 0o755);
+        assert_eq!(link.file_type, SYMLINK_FILE_TYPE);
+        assert_eq!(volume.fork(&link.data_fork), b"/Applications");
+
+        Ok(())
+    }
+
+    #[test]
+    fn many_files() -> DmgResult<()> {
+        let mut builder = HfsVolumeBuilder::new("Many");
+        for i in 0..2000 {
+            builder.add_file(&format!("dir{}/file{}", i % 10, i), vec![], false)?;
+        }
+
+        let volume = Volume::new(builder.to_bytes()?)?;
+        let header = volume.catalog_header()?;
+        assert!(header.tree_depth > 1);
+        assert_eq!(header.leaf_records, 2 * (2000 + 10 + 1));
+        assert_eq!(volume.leaf_records()?.len(), header.leaf_records as usize);
+
+        // Every index record should point to a node whose first key matches.
+        let mut level = vec![header.root_node];
+        for height in (2..=header.tree_depth).rev() {
+            let mut children = vec![];
+
+            for number in level {
+                let (descriptor, records) = Volume::node_records(volume.node(number))?;
+                assert_eq!(descriptor.kind, INDEX_NODE);
+                assert_eq!(descriptor.height as u16, height);
+
+                for record in records {
+                    let (key, pointer) = record.split_at(record.len() - 4);
+                    let child = u32::from_be_bytes(pointer.try_into().unwrap());
+                    let (_, child_records) = Volume::node_records(volume.node(child))?;
+                    assert!(child_records[0].starts_with(key));
+                    children.push(child);
+                }
+            }
+
+            level = children;
+        }
+
+        assert_eq!(level.first(), Some(&header.first_leaf_node));
+        assert_eq!(level.last(), Some(&header.last_leaf_node));
+
+        Ok(())
+    }
+}
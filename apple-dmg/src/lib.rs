@@ -0,0 +1,89 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Apple disk images.
+//!
+//! Apple disk images - often existing as `.dmg` files - are a common
+//! distribution format for macOS applications. Users open the image, which
+//! mounts a (typically read-only) filesystem, then copy an application
+//! bundle out of it.
+//!
+//! This crate implements the creation of disk images without any Apple
+//! tooling.
+//!
+//! # File Format
+//!
+//! A `.dmg` file is a *Universal Disk Image Format* (UDIF) container holding
+//! the sectors of a virtual disk. See the [udif] module for details on that
+//! format.
+//!
+//! The virtual disk produced by this crate doesn't have a partition map.
+//! Instead, it holds a single HFS+ filesystem spanning the whole disk. See
+//! the [hfs] module for details on the filesystem.
+//!
+//! # Code Signing
+//!
+//! Disk images can carry an embedded code signature. This crate produces
+//! unsigned images. The `apple-codesign` crate can sign them.
+//!
+//! # Usage
+//!
+//! [DmgBuilder] is the main interface. Add files to it from a
+//! [tugger_file_manifest::FileManifest] or a filesystem directory, then call
+//! [DmgBuilder::write].
+
+pub mod builder;
+pub use builder::DmgBuilder;
+pub mod hfs;
+pub use hfs::HfsVolumeBuilder;
+pub mod udif;
+pub use udif::{KolyTrailer, UdifReader};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("scroll error: {0}")]
+    Scroll(#[from] scroll::Error),
+
+    #[error("plist error: {0}")]
+    Plist(#[from] plist::Error),
+
+    #[error("file manifest error: {0}")]
+    FileManifest(#[from] tugger_file_manifest::FileManifestError),
+
+    #[error("directory walking error: {0}")]
+    WalkDir(#[from] walkdir::Error),
+
+    #[error("bad magic; expected {0}")]
+    BadMagic(&'static str),
+
+    #[error("malformed property list: {0}")]
+    MalformedPlist(&'static str),
+
+    #[error("unsupported chunk type: {0:#x}")]
+    UnsupportedChunkType(u32),
+
+    #[error("image size is not a multiple of the sector size")]
+    ImageSize,
+
+    #[error("invalid path: {0}")]
+    InvalidPath(String),
+
+    #[error("file name too long: {0}")]
+    NameTooLong(String),
+
+    #[error("path already exists: {0}")]
+    PathExists(String),
+
+    #[error("parent of path is not a directory: {0}")]
+    NotADirectory(String),
+
+    #[error("volume too large")]
+    VolumeTooLarge,
+}
+
+/// Result type for this crate.
+pub type DmgResult<T> = std::result::Result<T, Error>;
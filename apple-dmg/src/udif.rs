@@ -0,0 +1,557 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Universal Disk Image Format (UDIF) handling.
+
+UDIF is the container format of `.dmg` files. A UDIF image consists of:
+
+1. The *data fork*, holding the (possibly compressed) disk content as a
+   series of chunks.
+2. An XML property list describing how the chunks of the data fork map to
+   sectors of the disk. The `blkx` resources in this plist are serialized
+   [BlkxTable].
+3. A 512 byte [KolyTrailer] at the very end of the file, locating the other
+   pieces.
+
+Code signatures are inserted between the property list and the trailer. See
+the `apple-codesign` crate for signing images produced by this module.
+*/
+
+use {
+    crate::{DmgResult, Error},
+    flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression},
+    scroll::{ctx::SizeWith as _, Pread, Pwrite, SizeWith},
+    std::io::{Read, Seek, SeekFrom, Write},
+};
+
+/// Size of the [KolyTrailer] structure in files.
+pub const KOLY_SIZE: usize = 512;
+
+/// Size of disk sectors.
+pub const SECTOR_SIZE: u64 = 512;
+
+/// Number of sectors in each chunk of written images.
+const CHUNK_SECTORS: u64 = 0x200;
+
+/// Checksum type for CRC-32.
+const CHECKSUM_CRC32: u32 = 2;
+
+/// Trailer flag indicating the image is a single file.
+const UDIF_FLAG_FLATTENED: u32 = 0x1;
+
+/// Image variant for device images.
+const IMAGE_VARIANT_DEVICE: u32 = 1;
+
+/// How a chunk of data in the data fork is stored.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ChunkType {
+    /// Sectors are all zeroes. There is no data in the data fork.
+    ZeroFill,
+    /// Sectors are stored uncompressed.
+    Raw,
+    /// Sectors are unallocated. They read as zeroes.
+    Ignore,
+    /// Sectors are compressed with Apple Data Compression.
+    Adc,
+    /// Sectors are compressed with zlib.
+    Zlib,
+    /// Sectors are compressed with bzip2.
+    Bzip2,
+    /// Sectors are compressed with LZFSE.
+    Lzfse,
+    /// A comment. Does not describe any sectors.
+    Comment,
+    /// Marks the end of the chunk list.
+    Terminator,
+    /// An unknown chunk type.
+    Unknown(u32),
+}
+
+impl From<u32> for ChunkType {
+    fn from(v: u32) -> Self {
+        match v {
+            0x0000_0000 => Self::ZeroFill,
+            0x0000_0001 => Self::Raw,
+            0x0000_0002 => Self::Ignore,
+            0x8000_0004 => Self::Adc,
+            0x8000_0005 => Self::Zlib,
+            0x8000_0006 => Self::Bzip2,
+            0x8000_0007 => Self::Lzfse,
+            0x7fff_fffe => Self::Comment,
+            0xffff_ffff => Self::Terminator,
+            _ => Self::Unknown(v),
+        }
+    }
+}
+
+impl From<ChunkType> for u32 {
+    fn from(v: ChunkType) -> Self {
+        match v {
+            ChunkType::ZeroFill => 0x0000_0000,
+            ChunkType::Raw => 0x0000_0001,
+            ChunkType::Ignore => 0x0000_0002,
+            ChunkType::Adc => 0x8000_0004,
+            ChunkType::Zlib => 0x8000_0005,
+            ChunkType::Bzip2 => 0x8000_0006,
+            ChunkType::Lzfse => 0x8000_0007,
+            ChunkType::Comment => 0x7fff_fffe,
+            ChunkType::Terminator => 0xffff_ffff,
+            ChunkType::Unknown(v) => v,
+        }
+    }
+}
+
+/// A checksum of some data.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Pread, Pwrite, SizeWith)]
+pub struct UdifChecksum {
+    pub checksum_type: u32,
+    /// Size of the checksum in bits.
+    pub size: u32,
+    pub data: [u32; 32],
+}
+
+impl UdifChecksum {
+    /// Construct an instance holding a CRC-32.
+    pub fn crc32(value: u32) -> Self {
+        let mut data = [0; 32];
+        data[0] = value;
+
+        Self {
+            checksum_type: CHECKSUM_CRC32,
+            size: 32,
+            data,
+        }
+    }
+}
+
+/// DMG trailer describing file content.
+///
+/// This is the main structure defining a DMG.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Pread, Pwrite, SizeWith)]
+pub struct KolyTrailer {
+    /// "koly"
+    pub signature: [u8; 4],
+    pub version: u32,
+    pub header_size: u32,
+    pub flags: u32,
+    pub running_data_fork_offset: u64,
+    pub data_fork_offset: u64,
+    pub data_fork_length: u64,
+    pub rsrc_fork_offset: u64,
+    pub rsrc_fork_length: u64,
+    pub segment_number: u32,
+    pub segment_count: u32,
+    pub segment_id: [u32; 4],
+    pub data_fork_checksum: UdifChecksum,
+    pub plist_offset: u64,
+    pub plist_length: u64,
+    pub reserved1: [u64; 8],
+    pub code_signature_offset: u64,
+    pub code_signature_size: u64,
+    pub reserved2: [u64; 5],
+    pub main_checksum: UdifChecksum,
+    pub image_variant: u32,
+    pub sector_count: u64,
+}
+
+impl KolyTrailer {
+    /// Construct an instance by reading from a seekable reader.
+    ///
+    /// The trailer is the final 512 bytes of the seekable stream.
+    pub fn read_from<R: Read + Seek>(reader: &mut R) -> DmgResult<Self> {
+        reader.seek(SeekFrom::End(-(KOLY_SIZE as i64)))?;
+
+        let mut data = [0u8; KOLY_SIZE];
+        reader.read_exact(&mut data)?;
+
+        let koly = data.pread_with::<KolyTrailer>(0, scroll::BE)?;
+
+        if &koly.signature != b"koly" {
+            return Err(Error::BadMagic("koly"));
+        }
+
+        Ok(koly)
+    }
+
+    /// Serialize the trailer to bytes.
+    pub fn to_bytes(&self) -> DmgResult<[u8; KOLY_SIZE]> {
+        let mut data = [0u8; KOLY_SIZE];
+        data.pwrite_with(self.clone(), 0, scroll::BE)?;
+
+        Ok(data)
+    }
+}
+
+/// Header of a [BlkxTable].
+#[derive(Clone, Debug, Default, Eq, PartialEq, Pread, Pwrite, SizeWith)]
+pub struct BlkxTableHeader {
+    /// "mish"
+    pub signature: [u8; 4],
+    pub version: u32,
+    pub first_sector_number: u64,
+    pub sector_count: u64,
+    pub data_offset: u64,
+    /// Number of sectors needed to hold the largest decompressed chunk.
+    pub buffers_needed: u32,
+    pub block_descriptors: u32,
+    pub reserved: [u32; 6],
+    /// Checksum of the uncompressed sectors described by this table.
+    pub checksum: UdifChecksum,
+    pub chunk_count: u32,
+}
+
+/// Describes where a run of sectors is stored in the data fork.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Pread, Pwrite, SizeWith)]
+pub struct BlkxChunk {
+    pub chunk_type: u32,
+    pub comment: u32,
+    /// First sector, relative to the table's first sector.
+    pub sector_number: u64,
+    pub sector_count: u64,
+    pub compressed_offset: u64,
+    pub compressed_length: u64,
+}
+
+impl BlkxChunk {
+    /// The type of this chunk.
+    pub fn chunk_type(&self) -> ChunkType {
+        ChunkType::from(self.chunk_type)
+    }
+}
+
+/// Describes how a range of sectors is stored in the data fork.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct BlkxTable {
+    pub header: BlkxTableHeader,
+    pub chunks: Vec<BlkxChunk>,
+}
+
+impl BlkxTable {
+    /// Parse an instance from its serialized form.
+    pub fn from_bytes(data: &[u8]) -> DmgResult<Self> {
+        let mut offset = 0;
+        let header = data.gread_with::<BlkxTableHeader>(&mut offset, scroll::BE)?;
+
+        if &header.signature != b"mish" {
+            return Err(Error::BadMagic("mish"));
+        }
+
+        let chunks = (0..header.chunk_count)
+            .map(|_| data.gread_with::<BlkxChunk>(&mut offset, scroll::BE))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self { header, chunks })
+    }
+
+    /// Serialize this instance to bytes.
+    pub fn to_bytes(&self) -> DmgResult<Vec<u8>> {
+        let header_size = BlkxTableHeader::size_with(&scroll::BE);
+        let chunk_size = BlkxChunk::size_with(&scroll::BE);
+
+        let mut data = vec![0u8; header_size + chunk_size * self.chunks.len()];
+
+        let mut offset = 0;
+        data.gwrite_with(self.header.clone(), &mut offset, scroll::BE)?;
+        for chunk in &self.chunks {
+            data.gwrite_with(*chunk, &mut offset, scroll::BE)?;
+        }
+
+        Ok(data)
+    }
+}
+
+/// Entity for reading UDIF images.
+#[derive(Clone, Debug)]
+pub struct UdifReader {
+    koly: KolyTrailer,
+    tables: Vec<(String, BlkxTable)>,
+}
+
+impl UdifReader {
+    /// Construct a new instance from a reader.
+    pub fn new<R: Read + Seek>(reader: &mut R) -> DmgResult<Self> {
+        let koly = KolyTrailer::read_from(reader)?;
+
+        reader.seek(SeekFrom::Start(koly.plist_offset))?;
+        let mut plist_data = vec![];
+        reader
+            .take(koly.plist_length)
+            .read_to_end(&mut plist_data)?;
+
+        let plist = plist::Value::from_reader_xml(std::io::Cursor::new(plist_data))?;
+
+        let tables = plist
+            .as_dictionary()
+            .and_then(|d| d.get("resource-fork"))
+            .and_then(|v| v.as_dictionary())
+            .and_then(|d| d.get("blkx"))
+            .and_then(|v| v.as_array())
+            .ok_or(Error::MalformedPlist("blkx resources not found"))?
+            .iter()
+            .map(|entry| {
+                let entry = entry
+                    .as_dictionary()
+                    .ok_or(Error::MalformedPlist("blkx entry is not a dictionary"))?;
+
+                let name = entry
+                    .get("Name")
+                    .and_then(|v| v.as_string())
+                    .unwrap_or_default()
+                    .to_string();
+                let data = entry
+                    .get("Data")
+                    .and_then(|v| v.as_data())
+                    .ok_or(Error::MalformedPlist("blkx entry has no Data"))?;
+
+                Ok((name, BlkxTable::from_bytes(data)?))
+            })
+            .collect::<DmgResult<Vec<_>>>()?;
+
+        Ok(Self { koly, tables })
+    }
+
+    /// The trailer of the image.
+    pub fn koly(&self) -> &KolyTrailer {
+        &self.koly
+    }
+
+    /// The named block tables describing the disk content.
+    pub fn blkx_tables(&self) -> &[(String, BlkxTable)] {
+        &self.tables
+    }
+
+    /// Read the uncompressed disk content.
+    pub fn read_image<R: Read + Seek>(&self, reader: &mut R) -> DmgResult<Vec<u8>> {
+        let mut image = vec![0u8; (self.koly.sector_count * SECTOR_SIZE) as usize];
+
+        for (_, table) in &self.tables {
+            for chunk in &table.chunks {
+                let start = ((table.header.first_sector_number + chunk.sector_number) * SECTOR_SIZE)
+                    as usize;
+                let end = start + (chunk.sector_count * SECTOR_SIZE) as usize;
+
+                let dest = image
+                    .get_mut(start..end)
+                    .ok_or(Error::MalformedPlist("chunk beyond end of image"))?;
+
+                reader.seek(SeekFrom::Start(
+                    self.koly.data_fork_offset + table.header.data_offset + chunk.compressed_offset,
+                ))?;
+                let mut source = reader.by_ref().take(chunk.compressed_length);
+
+                match chunk.chunk_type() {
+                    ChunkType::ZeroFill
+                    | ChunkType::Ignore
+                    | ChunkType::Comment
+                    | ChunkType::Terminator => {}
+                    ChunkType::Raw => source.read_exact(dest)?,
+                    ChunkType::Zlib => ZlibDecoder::new(source).read_exact(dest)?,
+                    chunk_type => return Err(Error::UnsupportedChunkType(chunk_type.into())),
+                }
+            }
+        }
+
+        Ok(image)
+    }
+}
+
+/// Write a disk image as a zlib compressed, read-only UDIF image.
+///
+/// This is equivalent to the `UDZO` format of `hdiutil`. `image` is the raw
+/// content of the disk and must be a multiple of [SECTOR_SIZE]. `name`
+/// describes the content, e.g. `disk image (Apple_HFSX : 0)`.
+///
+/// Output is deterministic for a given input.
+pub fn write_udzo<W: Write>(mut writer: W, image: &[u8], name: &str) -> DmgResult<W> {
+    if image.len() as u64 % SECTOR_SIZE != 0 {
+        return Err(Error::ImageSize);
+    }
+
+    let sector_count = image.len() as u64 / SECTOR_SIZE;
+
+    let mut data_fork_crc = crc32fast::Hasher::new();
+    let mut offset = 0u64;
+    let mut chunks = vec![];
+
+    for (i, chunk) in image
+        .chunks((CHUNK_SECTORS * SECTOR_SIZE) as usize)
+        .enumerate()
+    {
+        let mut blkx = BlkxChunk {
+            sector_number: i as u64 * CHUNK_SECTORS,
+            sector_count: chunk.len() as u64 / SECTOR_SIZE,
+            compressed_offset: offset,
+            ..Default::default()
+        };
+
+        if chunk.iter().all(|b| *b == 0) {
+            blkx.chunk_type = ChunkType::ZeroFill.into();
+        } else {
+            let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+            encoder.write_all(chunk)?;
+            let compressed = encoder.finish()?;
+
+            let (chunk_type, data) = if compressed.len() < chunk.len() {
+                (ChunkType::Zlib, compressed.as_slice())
+            } else {
+                (ChunkType::Raw, chunk)
+            };
+
+            writer.write_all(data)?;
+            data_fork_crc.update(data);
+
+            blkx.chunk_type = chunk_type.into();
+            blkx.compressed_length = data.len() as _;
+            offset += data.len() as u64;
+        }
+
+        chunks.push(blkx);
+    }
+
+    chunks.push(BlkxChunk {
+        chunk_type: ChunkType::Terminator.into(),
+        sector_number: sector_count,
+        compressed_offset: offset,
+        ..Default::default()
+    });
+
+    let image_crc = crc32fast::hash(image);
+
+    let table = BlkxTable {
+        header: BlkxTableHeader {
+            signature: *b"mish",
+            version: 1,
+            first_sector_number: 0,
+            sector_count,
+            data_offset: 0,
+            buffers_needed: CHUNK_SECTORS as u32 + 8,
+            block_descriptors: 0,
+            reserved: [0; 6],
+            checksum: UdifChecksum::crc32(image_crc),
+            chunk_count: chunks.len() as _,
+        },
+        chunks,
+    };
+
+    let mut entry = plist::Dictionary::new();
+    entry.insert("Attributes".into(), plist::Value::String("0x0050".into()));
+    entry.insert("CFName".into(), plist::Value::String(name.into()));
+    entry.insert("Data".into(), plist::Value::Data(table.to_bytes()?));
+    entry.insert("ID".into(), plist::Value::String("0".into()));
+    entry.insert("Name".into(), plist::Value::String(name.into()));
+
+    let mut resource_fork = plist::Dictionary::new();
+    resource_fork.insert(
+        "blkx".into(),
+        plist::Value::Array(vec![plist::Value::Dictionary(entry)]),
+    );
+
+    let mut root = plist::Dictionary::new();
+    root.insert(
+        "resource-fork".into(),
+        plist::Value::Dictionary(resource_fork),
+    );
+
+    let mut plist_data = vec![];
+    plist::Value::Dictionary(root).to_writer_xml(&mut plist_data)?;
+    writer.write_all(&plist_data)?;
+
+    let data_fork_crc = data_fork_crc.finalize();
+
+    // The main checksum is computed over the checksums of all block tables.
+    let main_crc = crc32fast::hash(&image_crc.to_be_bytes());
+
+    let koly = KolyTrailer {
+        signature: *b"koly",
+        version: 4,
+        header_size: KOLY_SIZE as _,
+        flags: UDIF_FLAG_FLATTENED,
+        data_fork_length: offset,
+        segment_number: 1,
+        segment_count: 1,
+        // Derived from content so output is reproducible.
+        segment_id: [image_crc, data_fork_crc, main_crc, sector_count as u32],
+        data_fork_checksum: UdifChecksum::crc32(data_fork_crc),
+        plist_offset: offset,
+        plist_length: plist_data.len() as _,
+        main_checksum: UdifChecksum::crc32(main_crc),
+        image_variant: IMAGE_VARIANT_DEVICE,
+        sector_count,
+        ..Default::default()
+    };
+
+    writer.write_all(&koly.to_bytes()?)?;
+
+    Ok(writer)
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::io::Cursor};
+
+    #[test]
+    fn structure_sizes() {
+        assert_eq!(UdifChecksum::size_with(&scroll::BE), 136);
+        assert_eq!(KolyTrailer::size_with(&scroll::BE), 500);
+        assert_eq!(BlkxTableHeader::size_with(&scroll::BE), 204);
+        assert_eq!(BlkxChunk::size_with(&scroll::BE), 40);
+    }
+
+    #[test]
+    fn write_and_read() -> DmgResult<()> {
+        // Chunks of zeroes, compressible data, and incompressible data.
+        let chunk_size = (CHUNK_SECTORS * SECTOR_SIZE) as usize;
+        let mut image = vec![0u8; 3 * chunk_size + 1024];
+        image[chunk_size + 1000..chunk_size + 100_000].fill(0x42);
+        let mut state = 1u32;
+        for b in image[2 * chunk_size..3 * chunk_size].iter_mut() {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            *b = (state >> 16) as u8;
+        }
+
+        let data = write_udzo(vec![], &image, "disk image")?;
+        assert!(data.len() < image.len());
+        assert_eq!(write_udzo(vec![], &image, "disk image")?, data);
+
+        let mut cursor = Cursor::new(data);
+        let reader = UdifReader::new(&mut cursor)?;
+
+        let koly = reader.koly();
+        assert_eq!(koly.sector_count * SECTOR_SIZE, image.len() as u64);
+        assert_eq!(koly.plist_offset, koly.data_fork_length);
+        assert_eq!(
+            koly.plist_offset + koly.plist_length + KOLY_SIZE as u64,
+            cursor.get_ref().len() as u64
+        );
+        assert_eq!(
+            koly.data_fork_checksum.data[0],
+            crc32fast::hash(&cursor.get_ref()[0..koly.data_fork_length as usize])
+        );
+
+        let tables = reader.blkx_tables();
+        assert_eq!(tables.len(), 1);
+        assert_eq!(tables[0].0, "disk image");
+
+        let chunk_types = tables[0]
+            .1
+            .chunks
+            .iter()
+            .map(|c| c.chunk_type())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            chunk_types,
+            vec![
+                ChunkType::ZeroFill,
+                ChunkType::Zlib,
+                ChunkType::Raw,
+                ChunkType::ZeroFill,
+                ChunkType::Terminator
+            ]
+        );
+
+        assert_eq!(reader.read_image(&mut cursor)?, image);
+
+        Ok(())
+    }
+}
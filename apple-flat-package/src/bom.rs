@@ -0,0 +1,524 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Bill of materials (`Bom`) files.
+//!
+//! A `Bom` is a binary *BOMStore* file enumerating the paths installed by a
+//! component. All integers are big-endian. The file consists of a 512 byte
+//! header, a series of data *blocks*, a table of *variables* mapping names to
+//! block indices, and a table of block locations.
+//!
+//! Installed paths live in a B-tree referenced by the `Paths` variable. Each
+//! leaf node references a pair of blocks for every path: one holding file
+//! metadata and one holding the parent path ID and the file name.
+//!
+//! Only writing is implemented.
+
+use {
+    crate::PkgResult,
+    std::{collections::BTreeMap, io::Write},
+};
+
+/// Maximum number of paths stored in a single B-tree node.
+const PATHS_PER_NODE: usize = 256;
+
+/// The type of a path in a bill of materials.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BomPathType {
+    File,
+    Directory,
+    Link,
+}
+
+impl BomPathType {
+    fn type_id(&self) -> u8 {
+        match self {
+            Self::File => 1,
+            Self::Directory => 2,
+            Self::Link => 3,
+        }
+    }
+
+    fn mode_bits(&self) -> u16 {
+        match self {
+            Self::File => 0o100000,
+            Self::Directory => 0o040000,
+            Self::Link => 0o120000,
+        }
+    }
+}
+
+/// Metadata describing a single path in a bill of materials.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BomPath {
+    pub path_type: BomPathType,
+    /// Permission bits of the path.
+    pub mode: u16,
+    pub user_id: u32,
+    pub group_id: u32,
+    /// Modification time as seconds since UNIX epoch.
+    pub mtime: u32,
+    /// Size in bytes of file content.
+    pub size: u32,
+    /// POSIX `cksum` CRC of file content.
+    pub checksum: u32,
+    /// Target of a symlink.
+    pub link_name: Option<String>,
+}
+
+impl BomPath {
+    fn new(path_type: BomPathType, mode: u16, mtime: u32) -> Self {
+        Self {
+            path_type,
+            mode,
+            user_id: 0,
+            group_id: 0,
+            mtime,
+            size: 0,
+            checksum: 0,
+            link_name: None,
+        }
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut data = vec![self.path_type.type_id(), 1];
+        // Architecture.
+        data.extend_from_slice(&0u16.to_be_bytes());
+        data.extend_from_slice(&(self.path_type.mode_bits() | (self.mode & 0o7777)).to_be_bytes());
+        data.extend_from_slice(&self.user_id.to_be_bytes());
+        data.extend_from_slice(&self.group_id.to_be_bytes());
+        data.extend_from_slice(&self.mtime.to_be_bytes());
+        data.extend_from_slice(&self.size.to_be_bytes());
+        data.push(1);
+        data.extend_from_slice(&self.checksum.to_be_bytes());
+
+        if let Some(link) = &self.link_name {
+            data.extend_from_slice(&(link.len() as u32 + 1).to_be_bytes());
+            data.extend_from_slice(link.as_bytes());
+            data.push(0);
+        } else {
+            data.extend_from_slice(&0u32.to_be_bytes());
+        }
+
+        data
+    }
+}
+
+/// Compute the CRC emitted by the POSIX `cksum` utility.
+pub fn posix_cksum(data: &[u8]) -> u32 {
+    fn update(crc: u32, byte: u8) -> u32 {
+        let mut crc = crc ^ ((byte as u32) << 24);
+        for _ in 0..8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ 0x04c1_1db7
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    }
+
+    let mut crc = data.iter().fold(0, |crc, b| update(crc, *b));
+
+    let mut len = data.len();
+    while len > 0 {
+        crc = update(crc, (len & 0xff) as u8);
+        len >>= 8;
+    }
+
+    !crc
+}
+
+/// Builds `Bom` files.
+///
+/// Paths are relative to the install root and are stored with a `./` prefix,
+/// mirroring the entries in the component's `Payload`. Parent directories are
+/// added automatically.
+#[derive(Clone, Debug)]
+pub struct BomBuilder {
+    paths: BTreeMap<String, BomPath>,
+    mtime: u32,
+}
+
+impl BomBuilder {
+    /// Construct a new instance recording the given modification time on directories.
+    pub fn new(mtime: u32) -> Self {
+        let mut paths = BTreeMap::new();
+        paths.insert(
+            ".".to_string(),
+            BomPath::new(BomPathType::Directory, 0o755, mtime),
+        );
+
+        Self { paths, mtime }
+    }
+
+    fn normalize_path(path: &str) -> String {
+        let path = path.trim_start_matches("./").trim_matches('/');
+
+        if path.is_empty() {
+            ".".to_string()
+        } else {
+            format!("./{}", path)
+        }
+    }
+
+    fn add_parents(&mut self, path: &str) {
+        let mut parent = path;
+
+        while let Some((dir, _)) = parent.rsplit_once('/') {
+            self.paths
+                .entry(dir.to_string())
+                .or_insert_with(|| BomPath::new(BomPathType::Directory, 0o755, self.mtime));
+            parent = dir;
+        }
+    }
+
+    /// Add a regular file with the given content.
+    pub fn add_file(&mut self, path: &str, data: &[u8], mode: u16, mtime: u32) {
+        let path = Self::normalize_path(path);
+        self.add_parents(&path);
+
+        let mut entry = BomPath::new(BomPathType::File, mode, mtime);
+        entry.size = data.len() as _;
+        entry.checksum = posix_cksum(data);
+
+        self.paths.insert(path, entry);
+    }
+
+    /// Add a directory.
+    pub fn add_directory(&mut self, path: &str, mode: u16, mtime: u32) {
+        let path = Self::normalize_path(path);
+        self.add_parents(&path);
+
+        self.paths
+            .insert(path, BomPath::new(BomPathType::Directory, mode, mtime));
+    }
+
+    /// Add a symlink pointing to `target`.
+    pub fn add_symlink(&mut self, path: &str, target: &str, mtime: u32) {
+        let path = Self::normalize_path(path);
+        self.add_parents(&path);
+
+        let mut entry = BomPath::new(BomPathType::Link, 0o755, mtime);
+        entry.size = target.len() as _;
+        entry.checksum = posix_cksum(target.as_bytes());
+        entry.link_name = Some(target.to_string());
+
+        self.paths.insert(path, entry);
+    }
+
+    /// Obtain the paths recorded in this instance.
+    pub fn paths(&self) -> impl Iterator<Item = (&str, &BomPath)> {
+        self.paths.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    /// Serialize the bill of materials to a writer.
+    pub fn write(&self, writer: &mut impl Write) -> PkgResult<()> {
+        writer.write_all(&self.to_bytes())?;
+
+        Ok(())
+    }
+
+    /// Serialize the bill of materials to bytes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut blocks = BlockStore::default();
+
+        // Every path is assigned a sequential ID. Entries reference the ID of
+        // their parent directory.
+        let mut ids = BTreeMap::new();
+        let mut entries = vec![];
+
+        for (index, (path, info)) in self.paths.iter().enumerate() {
+            let id = index as u32 + 1;
+            ids.insert(path.as_str(), id);
+
+            let (parent, name) = match path.rsplit_once('/') {
+                Some((parent, name)) => (ids[parent], name),
+                None => (0, path.as_str()),
+            };
+
+            let info2 = blocks.add(info.to_bytes());
+
+            let mut info1 = id.to_be_bytes().to_vec();
+            info1.extend_from_slice(&info2.to_be_bytes());
+            let info1 = blocks.add(info1);
+
+            let mut file = parent.to_be_bytes().to_vec();
+            file.extend_from_slice(name.as_bytes());
+            file.push(0);
+            let file = blocks.add(file);
+
+            entries.push((info1, file));
+        }
+
+        let paths = blocks.add_tree(&entries, 4096);
+        let hl_index = blocks.add_tree(&[], 4096);
+        let size64 = blocks.add_tree(&[], 128);
+
+        let v_tree = blocks.add_tree(&[], 128);
+        let mut v_index = 1u32.to_be_bytes().to_vec();
+        v_index.extend_from_slice(&v_tree.to_be_bytes());
+        v_index.extend_from_slice(&0u32.to_be_bytes());
+        v_index.push(0);
+        let v_index = blocks.add(v_index);
+
+        let mut bom_info = 1u32.to_be_bytes().to_vec();
+        bom_info.extend_from_slice(&(self.paths.len() as u32).to_be_bytes());
+        bom_info.extend_from_slice(&1u32.to_be_bytes());
+        bom_info.extend_from_slice(&[0u8; 16]);
+        let bom_info = blocks.add(bom_info);
+
+        let vars = [
+            ("BomInfo", bom_info),
+            ("Paths", paths),
+            ("HLIndex", hl_index),
+            ("VIndex", v_index),
+            ("Size64", size64),
+        ];
+
+        blocks.into_bytes(&vars)
+    }
+}
+
+/// Holds the data blocks of a BOMStore file.
+struct BlockStore {
+    blocks: Vec<Vec<u8>>,
+}
+
+impl Default for BlockStore {
+    fn default() -> Self {
+        // Block 0 is always the null block.
+        Self {
+            blocks: vec![vec![]],
+        }
+    }
+}
+
+impl BlockStore {
+    fn add(&mut self, data: Vec<u8>) -> u32 {
+        self.blocks.push(data);
+        self.blocks.len() as u32 - 1
+    }
+
+    /// Add a B-tree over `(index0, index1)` pairs and return the tree's block index.
+    fn add_tree(&mut self, entries: &[(u32, u32)], block_size: u32) -> u32 {
+        let mut leaves = vec![];
+
+        // Leaves are reserved up front so they can link to their siblings.
+        let chunks = entries.chunks(PATHS_PER_NODE).collect::<Vec<_>>();
+        let leaf_count = chunks.len().max(1);
+        for _ in 0..leaf_count {
+            leaves.push(self.add(vec![]));
+        }
+
+        for (i, leaf) in leaves.iter().enumerate() {
+            let chunk = chunks.get(i).copied().unwrap_or(&[]);
+            let forward = leaves.get(i + 1).copied().unwrap_or(0);
+            let backward = if i > 0 { leaves[i - 1] } else { 0 };
+
+            self.blocks[*leaf as usize] = Self::paths_node(true, forward, backward, chunk);
+        }
+
+        let child = if leaves.len() == 1 {
+            leaves[0]
+        } else {
+            // Branch entries point at each leaf, keyed by its last entry.
+            let branch = leaves
+                .iter()
+                .zip(chunks.iter())
+                .map(|(leaf, chunk)| (*leaf, chunk[chunk.len() - 1].1))
+                .collect::<Vec<_>>();
+
+            self.add(Self::paths_node(false, 0, 0, &branch))
+        };
+
+        let mut tree = b"tree".to_vec();
+        tree.extend_from_slice(&1u32.to_be_bytes());
+        tree.extend_from_slice(&child.to_be_bytes());
+        tree.extend_from_slice(&block_size.to_be_bytes());
+        tree.extend_from_slice(&(entries.len() as u32).to_be_bytes());
+        tree.push(0);
+
+        self.add(tree)
+    }
+
+    fn paths_node(leaf: bool, forward: u32, backward: u32, entries: &[(u32, u32)]) -> Vec<u8> {
+        let mut data = (leaf as u16).to_be_bytes().to_vec();
+        data.extend_from_slice(&(entries.len() as u16).to_be_bytes());
+        data.extend_from_slice(&forward.to_be_bytes());
+        data.extend_from_slice(&backward.to_be_bytes());
+
+        for (index0, index1) in entries {
+            data.extend_from_slice(&index0.to_be_bytes());
+            data.extend_from_slice(&index1.to_be_bytes());
+        }
+
+        data
+    }
+
+    fn into_bytes(self, vars: &[(&str, u32)]) -> Vec<u8> {
+        let mut data = vec![0u8; 512];
+        let mut pointers = vec![(0u32, 0u32)];
+
+        for block in self.blocks.iter().skip(1) {
+            pointers.push((data.len() as u32, block.len() as u32));
+            data.extend_from_slice(block);
+        }
+
+        let vars_offset = data.len() as u32;
+        data.extend_from_slice(&(vars.len() as u32).to_be_bytes());
+        for (name, index) in vars {
+            data.extend_from_slice(&index.to_be_bytes());
+            data.push(name.len() as u8);
+            data.extend_from_slice(name.as_bytes());
+        }
+        let vars_length = data.len() as u32 - vars_offset;
+
+        let index_offset = data.len() as u32;
+        data.extend_from_slice(&(pointers.len() as u32).to_be_bytes());
+        for (address, length) in &pointers {
+            data.extend_from_slice(&address.to_be_bytes());
+            data.extend_from_slice(&length.to_be_bytes());
+        }
+        // Empty free list.
+        data.extend_from_slice(&0u32.to_be_bytes());
+        let index_length = data.len() as u32 - index_offset;
+
+        let mut header = b"BOMStore".to_vec();
+        header.extend_from_slice(&1u32.to_be_bytes());
+        header.extend_from_slice(&(pointers.len() as u32 - 1).to_be_bytes());
+        header.extend_from_slice(&index_offset.to_be_bytes());
+        header.extend_from_slice(&index_length.to_be_bytes());
+        header.extend_from_slice(&vars_offset.to_be_bytes());
+        header.extend_from_slice(&vars_length.to_be_bytes());
+        data[0..header.len()].copy_from_slice(&header);
+
+        data
+    }
+}
+
+impl Default for BomBuilder {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u32_at(data: &[u8], offset: usize) -> u32 {
+        u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn block(data: &[u8], index: u32) -> &[u8] {
+        let table = u32_at(data, 16) as usize;
+        let address = u32_at(data, table + 4 + index as usize * 8) as usize;
+        let length = u32_at(data, table + 8 + index as usize * 8) as usize;
+
+        &data[address..address + length]
+    }
+
+    fn var(data: &[u8], wanted: &str) -> u32 {
+        let mut offset = u32_at(data, 24) as usize;
+        let count = u32_at(data, offset);
+        offset += 4;
+
+        for _ in 0..count {
+            let index = u32_at(data, offset);
+            let len = data[offset + 4] as usize;
+            let name = std::str::from_utf8(&data[offset + 5..offset + 5 + len]).unwrap();
+            offset += 5 + len;
+
+            if name == wanted {
+                return index;
+            }
+        }
+
+        panic!("variable {} not found", wanted);
+    }
+
+    /// Resolve full paths by walking the leaves of the `Paths` tree.
+    fn read_paths(data: &[u8]) -> Vec<String> {
+        let tree = block(data, var(data, "Paths"));
+        assert_eq!(&tree[0..4], b"tree");
+
+        let mut node = block(data, u32_at(tree, 8));
+        while node[1] == 0 {
+            node = block(data, u32_at(node, 12));
+        }
+
+        let mut names = BTreeMap::new();
+        let mut paths = vec![];
+
+        loop {
+            let count = u16::from_be_bytes([node[2], node[3]]) as usize;
+
+            for i in 0..count {
+                let info1 = block(data, u32_at(node, 12 + i * 8));
+                let file = block(data, u32_at(node, 16 + i * 8));
+
+                let id = u32_at(info1, 0);
+                let parent = u32_at(file, 0);
+                let name = std::str::from_utf8(&file[4..file.len() - 1]).unwrap();
+
+                let path = match names.get(&parent) {
+                    Some(p) => format!("{}/{}", p, name),
+                    None => name.to_string(),
+                };
+                names.insert(id, path.clone());
+                paths.push(path);
+            }
+
+            match u32_at(node, 4) {
+                0 => break,
+                forward => node = block(data, forward),
+            }
+        }
+
+        paths
+    }
+
+    #[test]
+    fn cksum() {
+        assert_eq!(posix_cksum(b""), 4294967295);
+        assert_eq!(posix_cksum(b"hello world\n"), 3733384285);
+    }
+
+    #[test]
+    fn write_paths() {
+        let mut builder = BomBuilder::new(0);
+        builder.add_file("bin/foo", b"foo", 0o755, 0);
+        builder.add_file("./lib/foo/bar.txt", b"bar", 0o644, 0);
+        builder.add_symlink("lib/foo/baz.txt", "bar.txt", 0);
+
+        let data = builder.to_bytes();
+        assert_eq!(&data[0..8], b"BOMStore");
+        assert_eq!(
+            read_paths(&data),
+            vec![
+                ".",
+                "./bin",
+                "./bin/foo",
+                "./lib",
+                "./lib/foo",
+                "./lib/foo/bar.txt",
+                "./lib/foo/baz.txt"
+            ]
+        );
+    }
+
+    #[test]
+    fn write_many_paths() {
+        let mut builder = BomBuilder::new(0);
+        for i in 0..1000 {
+            builder.add_file(&format!("file{:04}", i), b"", 0o644, 0);
+        }
+
+        let data = builder.to_bytes();
+        let paths = read_paths(&data);
+        assert_eq!(paths.len(), 1001);
+        assert_eq!(paths[1000], "./file0999");
+    }
+}
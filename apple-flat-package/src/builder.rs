@@ -0,0 +1,493 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Writing support for Apple flat package (`.pkg`) installers.
+//!
+//! [ComponentPackageBuilder] produces the files comprising a *component*:
+//! `PackageInfo`, `Bom`, `Payload`, and optionally `Scripts`. It can emit a
+//! standalone component installer (like `pkgbuild`) or be added to a
+//! [ProductPackageBuilder], which emits a *product* installer with a
+//! `Distribution` file (like `productbuild`).
+//!
+//! Produced archives are not signed. Sign them with
+//! `apple_xar::signing::XarSigner`.
+
+use {
+    crate::{
+        bom::BomBuilder,
+        package_info::{Payload, PostInstall, PreInstall, Script},
+        Error, PackageInfo, PkgResult,
+    },
+    apple_xar::builder::XarBuilder,
+    chrono::{DateTime, Utc},
+    cpio_archive::OdcBuilder,
+    flate2::{write::GzEncoder, Compression},
+    std::io::Write,
+    tugger_file_manifest::{FileEntry, FileManifest},
+    xml::{
+        common::XmlVersion,
+        writer::{EmitterConfig, XmlEvent},
+    },
+};
+
+/// Build a gzip compressed cpio archive from a [FileManifest].
+fn cpio_gz(manifest: &FileManifest, mtime: DateTime<Utc>) -> PkgResult<Vec<u8>> {
+    let mut builder = OdcBuilder::new(GzEncoder::new(vec![], Compression::default()));
+    builder.default_mtime(mtime);
+    builder.append_file_manifest(manifest)?;
+
+    Ok(builder.into_inner()?.finish()?)
+}
+
+/// Builds a *component package*, an installable unit within a flat package.
+#[derive(Clone, Debug)]
+pub struct ComponentPackageBuilder {
+    package_info: PackageInfo,
+    payload: FileManifest,
+    scripts: FileManifest,
+    mtime: DateTime<Utc>,
+}
+
+impl ComponentPackageBuilder {
+    /// Construct a new instance with a package identifier and version.
+    ///
+    /// Files are installed to `/` by default.
+    pub fn new(identifier: impl ToString, version: impl ToString) -> Self {
+        Self {
+            package_info: PackageInfo {
+                auth: "root".to_string(),
+                identifier: identifier.to_string(),
+                install_location: Some("/".to_string()),
+                version: version.to_string(),
+                ..Default::default()
+            },
+            payload: FileManifest::default(),
+            scripts: FileManifest::default(),
+            mtime: Utc::now(),
+        }
+    }
+
+    /// The identifier of this component.
+    pub fn identifier(&self) -> &str {
+        &self.package_info.identifier
+    }
+
+    /// The version of this component.
+    pub fn version(&self) -> &str {
+        &self.package_info.version
+    }
+
+    /// The name of the directory holding this component within a product package.
+    pub fn component_name(&self) -> String {
+        format!("{}.pkg", self.package_info.identifier)
+    }
+
+    /// Set the directory files in the payload are installed to.
+    pub fn install_location(&mut self, path: impl ToString) {
+        self.package_info.install_location = Some(path.to_string());
+    }
+
+    /// Set the modified time recorded in archives.
+    pub fn mtime(&mut self, mtime: DateTime<Utc>) {
+        self.mtime = mtime;
+    }
+
+    /// Add a file to the payload.
+    ///
+    /// `path` is relative to the install location.
+    pub fn add_file(
+        &mut self,
+        path: impl AsRef<std::path::Path>,
+        entry: FileEntry,
+    ) -> PkgResult<()> {
+        self.payload.add_file_entry(path, entry)?;
+
+        Ok(())
+    }
+
+    /// Add all files in a [FileManifest] to the payload.
+    pub fn add_manifest(&mut self, manifest: &FileManifest) -> PkgResult<()> {
+        self.payload.add_manifest(manifest)?;
+
+        Ok(())
+    }
+
+    /// Obtain the files in the payload.
+    pub fn payload_manifest(&self) -> &FileManifest {
+        &self.payload
+    }
+
+    /// Set the script that runs before files are installed.
+    pub fn preinstall_script(&mut self, content: impl Into<Vec<u8>>) -> PkgResult<()> {
+        self.scripts
+            .add_file_entry("preinstall", FileEntry::new_from_data(content.into(), true))?;
+
+        Ok(())
+    }
+
+    /// Set the script that runs after files are installed.
+    pub fn postinstall_script(&mut self, content: impl Into<Vec<u8>>) -> PkgResult<()> {
+        self.scripts.add_file_entry(
+            "postinstall",
+            FileEntry::new_from_data(content.into(), true),
+        )?;
+
+        Ok(())
+    }
+
+    /// Resolve the bill of materials describing the payload.
+    pub fn bom(&self) -> PkgResult<BomBuilder> {
+        let mtime = self.mtime.timestamp() as u32;
+        let mut bom = BomBuilder::new(mtime);
+
+        for (path, entry) in self.payload.iter_entries() {
+            let mode = if entry.is_executable() { 0o755 } else { 0o644 };
+            let data = entry.resolve_content()?;
+
+            bom.add_file(&path.display().to_string(), &data, mode, mtime);
+        }
+
+        Ok(bom)
+    }
+
+    /// Resolve the `PackageInfo` describing this component.
+    pub fn package_info(&self) -> PkgResult<PackageInfo> {
+        let mut info = self.package_info.clone();
+
+        let mut install_bytes = 0;
+        for (_, entry) in self.payload.iter_entries() {
+            install_bytes += entry.resolve_content()?.len() as u64;
+        }
+
+        info.payload = Some(Payload {
+            number_of_files: self.bom()?.paths().count() as _,
+            install_kbytes: (install_bytes + 1023) / 1024,
+        });
+
+        info.scripts = self
+            .scripts
+            .iter_entries()
+            .map(|(path, _)| {
+                let file = format!("./{}", path.display());
+
+                if path.to_string_lossy() == "preinstall" {
+                    Script::PreInstall(PreInstall {
+                        file,
+                        component_id: None,
+                    })
+                } else {
+                    Script::PostInstall(PostInstall {
+                        file,
+                        component_id: None,
+                    })
+                }
+            })
+            .collect::<Vec<_>>();
+
+        Ok(info)
+    }
+
+    /// Add the files comprising this component to a XAR archive.
+    ///
+    /// `prefix` is the directory in the archive to write files to. Use an
+    /// empty string to write a standalone component package.
+    pub fn write_to_xar<W: Write>(&self, xar: &mut XarBuilder<W>, prefix: &str) -> PkgResult<()> {
+        let path = |name: &str| {
+            if prefix.is_empty() {
+                name.to_string()
+            } else {
+                format!("{}/{}", prefix, name)
+            }
+        };
+
+        xar.append_file_from_data(&path("PackageInfo"), self.package_info()?.to_xml()?, 0o644)?;
+        xar.append_file_from_data(&path("Bom"), self.bom()?.to_bytes(), 0o644)?;
+        xar.append_file_from_data(&path("Payload"), cpio_gz(&self.payload, self.mtime)?, 0o644)?;
+
+        if !self.scripts.is_empty() {
+            xar.append_file_from_data(
+                &path("Scripts"),
+                cpio_gz(&self.scripts, self.mtime)?,
+                0o644,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Write a standalone component package to a writer.
+    pub fn write<W: Write>(&self, writer: W) -> PkgResult<W> {
+        let mut xar = XarBuilder::new(writer);
+        xar.mtime(self.mtime);
+        self.write_to_xar(&mut xar, "")?;
+
+        Ok(xar.finish()?)
+    }
+}
+
+/// Builds a *product package*, an installer consisting of 1 or more components.
+#[derive(Clone, Debug)]
+pub struct ProductPackageBuilder {
+    title: String,
+    components: Vec<ComponentPackageBuilder>,
+    host_architectures: Vec<String>,
+    mtime: DateTime<Utc>,
+}
+
+impl ProductPackageBuilder {
+    /// Construct a new instance with the title shown by the installer.
+    pub fn new(title: impl ToString) -> Self {
+        Self {
+            title: title.to_string(),
+            components: vec![],
+            host_architectures: vec![],
+            mtime: Utc::now(),
+        }
+    }
+
+    /// Add a component to be installed.
+    pub fn add_component(&mut self, component: ComponentPackageBuilder) -> PkgResult<()> {
+        if self
+            .components
+            .iter()
+            .any(|c| c.identifier() == component.identifier())
+        {
+            return Err(Error::DuplicateComponent(
+                component.identifier().to_string(),
+            ));
+        }
+
+        self.components.push(component);
+
+        Ok(())
+    }
+
+    /// Obtain the components in this installer.
+    pub fn components(&self) -> &[ComponentPackageBuilder] {
+        &self.components
+    }
+
+    /// Restrict installation to machines with the given architecture (e.g. `arm64`, `x86_64`).
+    pub fn add_host_architecture(&mut self, arch: impl ToString) {
+        self.host_architectures.push(arch.to_string());
+    }
+
+    /// Set the modified time recorded in archives.
+    pub fn mtime(&mut self, mtime: DateTime<Utc>) {
+        self.mtime = mtime;
+    }
+
+    /// Produce the `Distribution` XML describing the installer.
+    pub fn distribution_xml(&self) -> PkgResult<Vec<u8>> {
+        let mut writer = EmitterConfig::new()
+            .perform_indent(true)
+            .create_writer(std::io::BufWriter::new(vec![]));
+
+        writer.write(XmlEvent::StartDocument {
+            version: XmlVersion::Version10,
+            encoding: Some("utf-8"),
+            standalone: None,
+        })?;
+        writer
+            .write(XmlEvent::start_element("installer-gui-script").attr("minSpecVersion", "2"))?;
+
+        writer.write(XmlEvent::start_element("title"))?;
+        writer.write(XmlEvent::characters(&self.title))?;
+        writer.write(XmlEvent::end_element())?;
+
+        let host_architectures = self.host_architectures.join(",");
+        let mut options = XmlEvent::start_element("options")
+            .attr("customize", "never")
+            .attr("require-scripts", "false");
+        if !host_architectures.is_empty() {
+            options = options.attr("hostArchitectures", &host_architectures);
+        }
+        writer.write(options)?;
+        writer.write(XmlEvent::end_element())?;
+
+        writer.write(XmlEvent::start_element("choices-outline"))?;
+        writer.write(XmlEvent::start_element("line").attr("choice", "default"))?;
+        for component in &self.components {
+            writer.write(XmlEvent::start_element("line").attr("choice", component.identifier()))?;
+            writer.write(XmlEvent::end_element())?;
+        }
+        writer.write(XmlEvent::end_element())?;
+        writer.write(XmlEvent::end_element().name("choices-outline"))?;
+
+        writer.write(XmlEvent::start_element("choice").attr("id", "default"))?;
+        writer.write(XmlEvent::end_element())?;
+
+        for component in &self.components {
+            writer.write(
+                XmlEvent::start_element("choice")
+                    .attr("id", component.identifier())
+                    .attr("visible", "false"),
+            )?;
+            writer.write(XmlEvent::start_element("pkg-ref").attr("id", component.identifier()))?;
+            writer.write(XmlEvent::end_element())?;
+            writer.write(XmlEvent::end_element().name("choice"))?;
+        }
+
+        for component in &self.components {
+            writer.write(
+                XmlEvent::start_element("pkg-ref")
+                    .attr("id", component.identifier())
+                    .attr("version", component.version())
+                    .attr("onConclusion", "none"),
+            )?;
+            writer.write(XmlEvent::characters(&format!(
+                "#{}",
+                component.component_name()
+            )))?;
+            writer.write(XmlEvent::end_element())?;
+        }
+
+        writer.write(XmlEvent::end_element().name("installer-gui-script"))?;
+
+        writer
+            .into_inner()
+            .into_inner()
+            .map_err(|e| Error::Io(std::io::Error::new(std::io::ErrorKind::Other, e)))
+    }
+
+    /// Write the product package to a writer.
+    pub fn write<W: Write>(&self, writer: W) -> PkgResult<W> {
+        if self.components.is_empty() {
+            return Err(Error::NoComponents);
+        }
+
+        let mut xar = XarBuilder::new(writer);
+        xar.mtime(self.mtime);
+
+        xar.append_file_from_data("Distribution", self.distribution_xml()?, 0o644)?;
+
+        for component in &self.components {
+            component.write_to_xar(&mut xar, &component.component_name())?;
+        }
+
+        Ok(xar.finish()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::reader::{PkgFlavor, PkgReader},
+        std::io::{Cursor, Read},
+    };
+
+    fn component() -> PkgResult<ComponentPackageBuilder> {
+        let mut builder = ComponentPackageBuilder::new("com.example.app", "1.0");
+        builder.install_location("/usr/local");
+        builder.add_file(
+            "bin/app",
+            FileEntry::new_from_data(b"#!/bin/sh\n".to_vec(), true),
+        )?;
+        builder.add_file(
+            "lib/app/data.txt",
+            FileEntry::new_from_data(b"data".to_vec(), false),
+        )?;
+        builder.postinstall_script("#!/bin/sh\nexit 0\n")?;
+
+        Ok(builder)
+    }
+
+    #[test]
+    fn package_info_xml() -> PkgResult<()> {
+        let info = component()?.package_info()?;
+        let parsed = PackageInfo::from_reader(Cursor::new(info.to_xml()?))?;
+
+        assert_eq!(parsed.identifier, "com.example.app");
+        assert_eq!(parsed.version, "1.0");
+        assert_eq!(parsed.install_location, Some("/usr/local".to_string()));
+        let payload = parsed.payload.unwrap();
+        // ., ./bin, ./bin/app, ./lib, ./lib/app, ./lib/app/data.txt
+        assert_eq!(payload.number_of_files, 6);
+        assert_eq!(payload.install_kbytes, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_component() -> PkgResult<()> {
+        let mut cursor = component()?.write(Cursor::new(vec![]))?;
+        cursor.set_position(0);
+
+        let mut reader = PkgReader::new(cursor)?;
+        assert_eq!(reader.flavor(), PkgFlavor::Component);
+
+        let component = reader.root_component()?.unwrap();
+        assert_eq!(
+            component.package_info().unwrap().identifier,
+            "com.example.app"
+        );
+        assert!(component.bom().is_some());
+
+        let mut payload = component.payload_reader()?.unwrap();
+        let mut names = vec![];
+        while let Some(header) = payload.read_next()? {
+            names.push(header.name().to_string());
+            if header.name() == "./lib/app/data.txt" {
+                let mut data = vec![];
+                payload.read_to_end(&mut data)?;
+                assert_eq!(data, b"data");
+            }
+        }
+        assert_eq!(
+            names,
+            vec![
+                ".",
+                "./bin",
+                "./bin/app",
+                "./lib",
+                "./lib/app",
+                "./lib/app/data.txt"
+            ]
+        );
+
+        let mut scripts = component.scripts_reader()?.unwrap();
+        let mut names = vec![];
+        while let Some(header) = scripts.read_next()? {
+            names.push(header.name().to_string());
+        }
+        assert_eq!(names, vec![".", "./postinstall"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_product() -> PkgResult<()> {
+        let mut builder = ProductPackageBuilder::new("Example");
+        builder.add_host_architecture("x86_64");
+        builder.add_host_architecture("arm64");
+        builder.add_component(component()?)?;
+        assert!(builder.add_component(component()?).is_err());
+
+        let mut cursor = builder.write(Cursor::new(vec![]))?;
+        cursor.set_position(0);
+
+        let mut reader = PkgReader::new(cursor)?;
+        assert_eq!(reader.flavor(), PkgFlavor::Product);
+
+        let distribution = reader.distribution()?.unwrap();
+        assert_eq!(distribution.min_spec_version, 2);
+        assert_eq!(distribution.title.unwrap().title, "Example");
+        assert_eq!(
+            distribution.options.unwrap().host_architecutres,
+            Some("x86_64,arm64".to_string())
+        );
+        assert_eq!(distribution.pkg_ref.len(), 1);
+        assert_eq!(distribution.pkg_ref[0].id, "com.example.app");
+
+        let components = reader.component_packages()?;
+        assert_eq!(components.len(), 1);
+        assert_eq!(
+            components[0].package_info().unwrap().version,
+            "1.0".to_string()
+        );
+
+        Ok(())
+    }
+}
@@ -84,6 +84,10 @@
 //! * Installed files in components may also be compressed (but this file
 //!   content is treated as opaque by the flat package format).
 
+pub mod bom;
+pub use bom::BomBuilder;
+pub mod builder;
+pub use builder::{ComponentPackageBuilder, ProductPackageBuilder};
 pub mod component_package;
 pub use component_package::ComponentPackageReader;
 pub mod distribution;
@@ -110,8 +114,20 @@ pub enum Error {
     #[error("cpio archive error: {0}")]
     Cpio(#[from] cpio_archive::Error),
 
+    #[error("XML write error: {0}")]
+    XmlWrite(#[from] xml::writer::Error),
+
+    #[error("file manifest error: {0}")]
+    FileManifest(#[from] tugger_file_manifest::FileManifestError),
+
     #[error("failed to resolve known component (this should not happen)")]
     ComponentResolution,
+
+    #[error("component {0} already present in product")]
+    DuplicateComponent(String),
+
+    #[error("product package has no components")]
+    NoComponents,
 }
 
 /// Result type for this crate.
@@ -5,9 +5,13 @@
 //! `PkgInfo` XML files.
 
 use {
-    crate::{distribution::Bundle, PkgResult},
+    crate::{distribution::Bundle, Error, PkgResult},
     serde::{Deserialize, Serialize},
-    std::io::Read,
+    std::io::{Read, Write},
+    xml::{
+        common::XmlVersion,
+        writer::{EmitterConfig, EventWriter, XmlEvent},
+    },
 };
 
 /// Provides information about the package to install.
@@ -168,6 +172,102 @@ impl PackageInfo {
 
         Ok(Self::deserialize(&mut de)?)
     }
+
+    /// Serialize to XML.
+    pub fn to_xml(&self) -> PkgResult<Vec<u8>> {
+        let mut emitter = EmitterConfig::new()
+            .perform_indent(true)
+            .create_writer(std::io::BufWriter::new(vec![]));
+        self.write_xml(&mut emitter)?;
+
+        emitter
+            .into_inner()
+            .into_inner()
+            .map_err(|e| Error::Io(std::io::Error::new(std::io::ErrorKind::Other, e)))
+    }
+
+    /// Write XML to an XML writer.
+    ///
+    /// Only the attributes and the `payload` and `scripts` elements are written.
+    pub fn write_xml<W: Write>(&self, writer: &mut EventWriter<W>) -> PkgResult<()> {
+        writer.write(XmlEvent::StartDocument {
+            version: XmlVersion::Version10,
+            encoding: Some("utf-8"),
+            standalone: None,
+        })?;
+
+        let format_version = self.format_version.to_string();
+
+        let mut attributes = vec![
+            ("auth", self.auth.clone()),
+            ("format-version", format_version),
+            ("identifier", self.identifier.clone()),
+            ("version", self.version.clone()),
+        ];
+
+        if let Some(v) = &self.generator_version {
+            attributes.push(("generator-version", v.clone()));
+        }
+        if let Some(v) = &self.install_location {
+            attributes.push(("install-location", v.clone()));
+        }
+        if let Some(v) = &self.postinstall_action {
+            attributes.push(("postinstall-action", v.clone()));
+        }
+
+        for (name, value) in [
+            ("deleteObsoleteLanguages", self.delete_obsolete_languages),
+            ("followSymLinks", self.follow_symlinks),
+            ("minimumSystemVersion", self.minimum_system_version),
+            ("overwrite-permissions", self.overwrite_permissions),
+            ("preserve-xattr", self.preserve_xattr),
+            ("relocatable", self.relocatable),
+            ("useHFSPlusCompression", self.use_hfs_plus_compression),
+        ] {
+            if let Some(v) = value {
+                attributes.push((name, v.to_string()));
+            }
+        }
+
+        let mut element = XmlEvent::start_element("pkg-info");
+        for (name, value) in &attributes {
+            element = element.attr(*name, value);
+        }
+        writer.write(element)?;
+
+        if let Some(payload) = &self.payload {
+            writer.write(
+                XmlEvent::start_element("payload")
+                    .attr("numberOfFiles", &payload.number_of_files.to_string())
+                    .attr("installKBytes", &payload.install_kbytes.to_string()),
+            )?;
+            writer.write(XmlEvent::end_element())?;
+        }
+
+        if !self.scripts.is_empty() {
+            writer.write(XmlEvent::start_element("scripts"))?;
+
+            for script in &self.scripts {
+                let (name, file, component_id) = match script {
+                    Script::PreInstall(s) => ("preinstall", &s.file, &s.component_id),
+                    Script::PostInstall(s) => ("postinstall", &s.file, &s.component_id),
+                };
+
+                let mut element = XmlEvent::start_element(name).attr("file", file);
+                if let Some(id) = component_id {
+                    element = element.attr("component-id", id);
+                }
+                writer.write(element)?;
+                writer.write(XmlEvent::end_element())?;
+            }
+
+            writer.write(XmlEvent::end_element().name("scripts"))?;
+        }
+
+        writer.write(XmlEvent::end_element().name("pkg-info"))?;
+
+        Ok(())
+    }
 }
 
 /// File record.
@@ -0,0 +1,338 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! XAR archive writing.
+//!
+//! [XarBuilder] collects file content in memory and emits a complete XAR
+//! archive when finished. The produced archive is unsigned. To sign it, feed
+//! the output into a [crate::reader::XarReader] and then
+//! [crate::signing::XarSigner].
+
+use {
+    crate::{
+        format::{XarChecksum, XarHeader},
+        table_of_contents::{
+            Checksum, ChecksumType, File, FileChecksum, FileData, FileEncoding, FileType,
+            TableOfContents, XarToC,
+        },
+        Error, XarResult,
+    },
+    chrono::{DateTime, Utc},
+    flate2::{write::ZlibEncoder, Compression},
+    scroll::IOwrite,
+    std::io::Write,
+};
+
+/// The `xar!` file magic.
+const XAR_MAGIC: u32 = 0x78617221;
+
+/// Size of the header we emit.
+const XAR_HEADER_SIZE: u16 = 28;
+
+/// How file data is encoded in the heap.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum XarCompression {
+    /// Data is stored as-is.
+    None,
+
+    /// Data is compressed with zlib.
+    Zlib,
+}
+
+impl XarCompression {
+    fn encoding_style(&self) -> &'static str {
+        match self {
+            Self::None => "application/octet-stream",
+            // The media type is arguably wrong, as there is no gzip header. But
+            // this is what Apple's tooling uses.
+            Self::Zlib => "application/x-gzip",
+        }
+    }
+}
+
+/// Entity for writing XAR archives.
+///
+/// Files are added with [Self::append_file_from_data]. Parent directory
+/// entries are emitted automatically. Call [Self::finish] to write the
+/// archive to the underlying writer.
+pub struct XarBuilder<W: Write + Sized> {
+    writer: W,
+    checksum_type: ChecksumType,
+    compression: XarCompression,
+    mtime: DateTime<Utc>,
+    files: Vec<File>,
+    heap: Vec<u8>,
+    next_id: u64,
+}
+
+impl<W: Write + Sized> XarBuilder<W> {
+    /// Construct a new instance which will write an archive to a writer.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            checksum_type: ChecksumType::Sha1,
+            compression: XarCompression::Zlib,
+            mtime: Utc::now(),
+            files: vec![],
+            heap: vec![],
+            next_id: 1,
+        }
+    }
+
+    /// Set the checksum algorithm used for the table of contents and file data.
+    ///
+    /// This must be called before any files are added.
+    pub fn checksum_type(&mut self, checksum: ChecksumType) -> XarResult<()> {
+        if matches!(checksum, ChecksumType::None) {
+            return Err(Error::Unsupported("XAR archives require a checksum"));
+        }
+        if !self.files.is_empty() {
+            return Err(Error::Unsupported(
+                "cannot change checksum type after adding files",
+            ));
+        }
+
+        self.checksum_type = checksum;
+
+        Ok(())
+    }
+
+    /// Set the compression to use for subsequently added files.
+    pub fn compression(&mut self, compression: XarCompression) {
+        self.compression = compression;
+    }
+
+    /// Set the modified time recorded for subsequently added entries.
+    pub fn mtime(&mut self, mtime: DateTime<Utc>) {
+        self.mtime = mtime;
+    }
+
+    fn time_string(&self) -> String {
+        self.mtime.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+    }
+
+    fn new_entry(&mut self, name: &str, file_type: FileType, mode: u32) -> File {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        File {
+            id,
+            ctime: Some(self.time_string()),
+            mtime: Some(self.time_string()),
+            atime: Some(self.time_string()),
+            names: vec![name.to_string()],
+            file_type,
+            mode: Some(format!("{:04o}", mode)),
+            deviceno: None,
+            inode: None,
+            uid: Some(0),
+            gid: Some(0),
+            user: Some("root".to_string()),
+            group: Some("wheel".to_string()),
+            size: None,
+            data: None,
+            ea: None,
+            finder_create_time: None,
+            files: vec![],
+        }
+    }
+
+    /// Append a regular file with the given content and permissions mode.
+    ///
+    /// `path` uses `/` as a directory separator. Missing parent directories
+    /// are created automatically.
+    pub fn append_file_from_data(
+        &mut self,
+        path: &str,
+        data: impl AsRef<[u8]>,
+        mode: u32,
+    ) -> XarResult<()> {
+        let data = data.as_ref();
+
+        let components = path
+            .split('/')
+            .filter(|c| !c.is_empty() && *c != ".")
+            .collect::<Vec<_>>();
+        let (filename, parents) = components
+            .split_last()
+            .ok_or(Error::Unsupported("empty file path"))?;
+
+        // Ensure all parent directories exist, noting the index path to the
+        // directory that will hold the file.
+        let mut indices = vec![];
+        for name in parents {
+            let siblings = self.siblings_mut(&indices);
+
+            let index = match siblings
+                .iter()
+                .position(|f| f.names.iter().any(|n| n == name))
+            {
+                Some(index) => {
+                    if !matches!(siblings[index].file_type, FileType::Directory) {
+                        return Err(Error::Unsupported("path component is not a directory"));
+                    }
+                    index
+                }
+                None => {
+                    let entry = self.new_entry(name, FileType::Directory, 0o755);
+                    let siblings = self.siblings_mut(&indices);
+                    siblings.push(entry);
+                    siblings.len() - 1
+                }
+            };
+
+            indices.push(index);
+        }
+
+        if self
+            .siblings_mut(&indices)
+            .iter()
+            .any(|f| f.names.iter().any(|n| n == filename))
+        {
+            return Err(Error::Unsupported("file already exists in archive"));
+        }
+
+        let archived = match self.compression {
+            XarCompression::None => data.to_vec(),
+            XarCompression::Zlib => {
+                let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()?
+            }
+        };
+
+        let mut entry = self.new_entry(filename, FileType::File, mode);
+        entry.size = Some(data.len() as _);
+        entry.data = Some(FileData {
+            // Offsets are relative to the start of file data and are made
+            // absolute in finish() once the size of the TOC checksum is known.
+            offset: self.heap.len() as _,
+            size: data.len() as _,
+            length: archived.len() as _,
+            extracted_checksum: FileChecksum {
+                style: self.checksum_type,
+                checksum: hex::encode(self.checksum_type.digest_data(data)?),
+            },
+            archived_checksum: FileChecksum {
+                style: self.checksum_type,
+                checksum: hex::encode(self.checksum_type.digest_data(&archived)?),
+            },
+            encoding: FileEncoding {
+                style: self.compression.encoding_style().to_string(),
+            },
+        });
+
+        self.heap.extend_from_slice(&archived);
+        self.siblings_mut(&indices).push(entry);
+
+        Ok(())
+    }
+
+    /// Resolve the list of entries within a directory given a path of indices.
+    fn siblings_mut(&mut self, indices: &[usize]) -> &mut Vec<File> {
+        let mut files = &mut self.files;
+
+        for index in indices {
+            files = &mut files[*index].files;
+        }
+
+        files
+    }
+
+    /// Write the archive to the underlying writer and return the writer.
+    pub fn finish(mut self) -> XarResult<W> {
+        let digest_size = self.checksum_type.digest_data(b"")?.len() as u64;
+
+        let mut toc = TableOfContents::new(XarToC {
+            creation_time: self.mtime.format("%Y-%m-%dT%H:%M:%S").to_string(),
+            checksum: Checksum {
+                style: self.checksum_type,
+                offset: 0,
+                size: digest_size,
+            },
+            files: std::mem::take(&mut self.files),
+            signature: None,
+            x_signature: None,
+        });
+
+        // File data follows the TOC checksum in the heap.
+        toc.visit_files_mut(&|file: &mut File| {
+            if let Some(data) = &mut file.data {
+                data.offset += digest_size;
+            }
+        });
+
+        let toc_data = toc.to_xml()?;
+
+        let mut zlib = ZlibEncoder::new(vec![], Compression::default());
+        zlib.write_all(&toc_data)?;
+        let toc_compressed = zlib.finish()?;
+
+        let toc_digest = self.checksum_type.digest_data(&toc_compressed)?;
+
+        let header = XarHeader {
+            magic: XAR_MAGIC,
+            size: XAR_HEADER_SIZE,
+            version: 1,
+            toc_length_compressed: toc_compressed.len() as _,
+            toc_length_uncompressed: toc_data.len() as _,
+            checksum_algorithm_id: XarChecksum::from(self.checksum_type).into(),
+        };
+
+        self.writer.iowrite_with(header, scroll::BE)?;
+        self.writer.write_all(&toc_compressed)?;
+        self.writer.write_all(&toc_digest)?;
+        self.writer.write_all(&self.heap)?;
+
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::reader::XarReader, std::io::Cursor};
+
+    #[test]
+    fn write_and_read() -> XarResult<()> {
+        let mut builder = XarBuilder::new(Cursor::new(vec![]));
+        builder.append_file_from_data("Distribution", b"distribution", 0o644)?;
+        builder.compression(XarCompression::None);
+        builder.append_file_from_data("foo.pkg/Payload", b"payload", 0o644)?;
+        builder.append_file_from_data("foo.pkg/Bom", b"bom", 0o644)?;
+        assert!(builder
+            .append_file_from_data("foo.pkg/Bom", b"bom", 0o644)
+            .is_err());
+
+        let mut cursor = builder.finish()?;
+        cursor.set_position(0);
+
+        let mut reader = XarReader::new(cursor)?;
+        assert!(reader.verify_table_of_contents_checksum()?);
+
+        let files = reader
+            .files()?
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            files,
+            vec!["Distribution", "foo.pkg", "foo.pkg/Payload", "foo.pkg/Bom"]
+        );
+
+        assert_eq!(
+            reader.get_file_data_from_path("Distribution")?,
+            Some(b"distribution".to_vec())
+        );
+        assert_eq!(
+            reader.get_file_data_from_path("foo.pkg/Payload")?,
+            Some(b"payload".to_vec())
+        );
+        assert_eq!(
+            reader.get_file_data_from_path("foo.pkg/Bom")?,
+            Some(b"bom".to_vec())
+        );
+
+        Ok(())
+    }
+}
@@ -4,6 +4,7 @@
 
 /*! XAR file format */
 
+pub mod builder;
 pub mod format;
 pub mod reader;
 pub mod signing;
@@ -42,6 +42,11 @@ impl DerefMut for TableOfContents {
 }
 
 impl TableOfContents {
+    /// Construct an instance from its inner data structure.
+    pub fn new(toc: XarToC) -> Self {
+        Self { toc }
+    }
+
     /// Parse XML table of contents from a reader.
     pub fn from_reader(reader: impl Read) -> XarResult<Self> {
         Ok(serde_xml_rs::from_reader(reader)?)
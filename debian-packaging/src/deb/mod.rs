@@ -68,3 +68,33 @@ impl DebCompression {
         Ok(buffer)
     }
 }
+
+/// Resolve the Debian architecture name for a Rust target triple.
+///
+/// Returns `None` if the triple isn't a Linux target or its machine
+/// architecture isn't known.
+pub fn target_triple_to_debian_architecture(triple: &str) -> Option<&'static str> {
+    if !triple.contains("-linux-") {
+        return None;
+    }
+
+    let machine = triple.split('-').next()?;
+
+    match machine {
+        "x86_64" => Some("amd64"),
+        "i586" | "i686" => Some("i386"),
+        "aarch64" => Some("arm64"),
+        "armv7" => Some("armhf"),
+        "arm" if triple.ends_with("hf") => Some("armhf"),
+        "arm" => Some("armel"),
+        "mips" => Some("mips"),
+        "mipsel" => Some("mipsel"),
+        "mips64el" => Some("mips64el"),
+        "powerpc" => Some("powerpc"),
+        "powerpc64" => Some("ppc64"),
+        "powerpc64le" => Some("ppc64el"),
+        "riscv64gc" => Some("riscv64"),
+        "s390x" => Some("s390x"),
+        _ => None,
+    }
+}
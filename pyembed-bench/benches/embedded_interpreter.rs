@@ -38,7 +38,7 @@ fn python_interpreter_startup_teardown_packed_resources(packed_resources: &[u8])
 
 pub fn bench_embedded_interpreter(c: &mut Criterion) {
     let (packed_resources, _) =
-        resolve_packed_resources().expect("failed to resolve packed resources");
+        resolve_packed_resources(false).expect("failed to resolve packed resources");
 
     c.bench_function("pyembed.new_interpreter_plain", |b| {
         b.iter(|| python_interpreter_startup_teardown_plain().expect("Python interpreter run"))
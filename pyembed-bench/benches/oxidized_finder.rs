@@ -65,7 +65,9 @@ fn python_interpreter_import_all_modules(
 
 pub fn bench_oxidized_finder(c: &mut Criterion) {
     let (packed_resources, names) =
-        resolve_packed_resources().expect("failed to resolve packed resources");
+        resolve_packed_resources(false).expect("failed to resolve packed resources");
+    let (packed_resources_indexed, _) =
+        resolve_packed_resources(true).expect("failed to resolve packed resources");
     let importable_modules = filter_module_names(&names);
     println!(
         "{} bytes packed resources data for {} modules; {} importable",
@@ -90,6 +92,16 @@ pub fn bench_oxidized_finder(c: &mut Criterion) {
         b.iter(|| python_resources_state_index(&packed_resources).expect("failed to index data"))
     });
 
+    c.bench_function(
+        "oxidized_importer.PythonResourcesState.index_data.name_index",
+        |b| {
+            b.iter(|| {
+                python_resources_state_index(&packed_resources_indexed)
+                    .expect("failed to index data")
+            })
+        },
+    );
+
     c.bench_function(
         "oxidized_importer.PythonResourcesState.resolve_modules",
         |b| {
@@ -124,6 +136,30 @@ pub fn bench_oxidized_finder(c: &mut Criterion) {
         },
     );
 
+    c.bench_function(
+        "oxidized_importer.OxidizedFinder.in_memory.name_index.find_spec.all_modules",
+        |b| {
+            b.iter_with_setup(
+                || {
+                    get_interpreter_and_oxidized_finder(&packed_resources_indexed)
+                        .expect("failed to obtain OxidizedFinder")
+                },
+                |(interp, finder)| {
+                    interp.with_gil(|py| {
+                        let finder = finder.as_ref(py);
+
+                        for name in &names {
+                            finder
+                                .call_method("find_spec", (name, py.None()), None)
+                                .expect("find_spec call failed");
+                        }
+                    });
+                    std::mem::drop(interp);
+                },
+            )
+        },
+    );
+
     c.bench_function(
         "oxidized_importer.OxidizedFinder.in_memory.get_source.all_modules",
         |b| {
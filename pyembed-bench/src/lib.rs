@@ -134,7 +134,11 @@ pub fn get_interpreter_and_oxidized_finder<'interpreter, 'resources>(
     Ok((interp, finder))
 }
 
-pub fn resolve_packed_resources() -> Result<(Vec<u8>, Vec<String>)> {
+/// Resolve packed resources data for the standard library.
+///
+/// `name_index` defines whether to write a name index to the data, which
+/// enables lazy deserialization of resources.
+pub fn resolve_packed_resources(name_index: bool) -> Result<(Vec<u8>, Vec<String>)> {
     let dist = get_python_distribution()?;
 
     let mut collector = PythonResourceCollector::new(
@@ -166,7 +170,14 @@ pub fn resolve_packed_resources() -> Result<(Vec<u8>, Vec<String>)> {
     let compiled = collector.compile_resources(&mut compiler)?;
 
     let mut buffer = Vec::<u8>::new();
-    compiled.write_packed_resources(&mut buffer)?;
+    compiled.write_packed_resources(
+        &mut buffer,
+        &python_packed_resources::PackedResourcesWriteOptions {
+            name_index,
+            ..python_packed_resources::PackedResourcesWriteOptions::default()
+        },
+        false,
+    )?;
 
     let names = compiled.resources.keys().cloned().collect::<Vec<_>>();
 
@@ -7,13 +7,14 @@
 use {
     crate::NewInterpreterError,
     oxidized_importer::{PackedResourcesSource, PythonResourcesState},
-    pyo3::ffi as pyffi,
+    pyo3::{ffi as pyffi, prelude::*},
     python_packaging::interpreter::{
-        MemoryAllocatorBackend, MultiprocessingStartMethod, PythonInterpreterConfig,
-        PythonInterpreterProfile, TerminfoResolution,
+        ImportProfileFormat, MemoryAllocatorBackend, MultiprocessingStartMethod,
+        PythonInterpreterConfig, PythonInterpreterProfile, TerminfoResolution,
     },
     std::{
-        ffi::{CString, OsString},
+        ffi::{CString, NulError, OsString},
+        fmt,
         ops::Deref,
         path::PathBuf,
     },
@@ -35,6 +36,48 @@ pub struct ExtensionModule {
     pub init_func: unsafe extern "C" fn() -> *mut pyffi::PyObject,
 }
 
+/// A function populating a Python module implemented in Rust.
+///
+/// Functions annotated with pyo3's `#[pymodule]` have this signature.
+pub type RustModuleInit = fn(Python, &PyModule) -> PyResult<()>;
+
+/// Defines a Python module implemented in Rust.
+///
+/// Unlike [ExtensionModule], the module is defined by a safe Rust function,
+/// typically a pyo3 `#[pymodule]` function, and its name can refer to a
+/// submodule. e.g. `myapp._native.crypto`.
+#[derive(Clone)]
+pub struct RustModule {
+    /// Fully qualified name of the module.
+    pub name: CString,
+
+    /// Function populating the module.
+    pub init: RustModuleInit,
+}
+
+impl RustModule {
+    /// Define a module from its fully qualified name and a function populating it.
+    pub fn new(name: &str, init: RustModuleInit) -> Result<Self, NulError> {
+        Ok(Self {
+            name: CString::new(name)?,
+            init,
+        })
+    }
+}
+
+impl fmt::Debug for RustModule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RustModule")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A function providing the key used to decrypt packed resources data.
+///
+/// See [OxidizedPythonInterpreterConfig::packed_resources_encryption_key_provider].
+pub type PackedResourcesEncryptionKeyProvider = fn() -> Result<[u8; 32], String>;
+
 /// Configuration for a Python interpreter.
 ///
 /// This type is used to create a [crate::MainPythonInterpreter], which manages
@@ -282,14 +325,68 @@ pub struct OxidizedPythonInterpreterConfig<'a> {
     ///
     /// Default value: `vec![]`
     ///
-    /// [Self::resolve()] behavior: [PackedResourcesSource::MemoryMappedPath] members
-    /// have the special string `$ORIGIN` expanded to the string value that
-    /// [Self::origin] resolves to.
+    /// Sources are indexed in order. Resources having the same name in multiple
+    /// sources are merged, with fields from later sources overwriting fields
+    /// from earlier ones. [PackedResourcesSource::ZipPath] and
+    /// [PackedResourcesSource::WheelPath] members load resources from zip and
+    /// wheel archives (e.g. plugin bundles) and have the same merging semantics.
+    ///
+    /// [Self::resolve()] behavior: [PackedResourcesSource::MemoryMappedPath],
+    /// [PackedResourcesSource::ZipPath], and [PackedResourcesSource::WheelPath]
+    /// members have the special string `$ORIGIN` expanded to the string value
+    /// that [Self::origin] resolves to.
     ///
     /// This field is ignored during serialization.
     #[cfg_attr(feature = "serialization", serde(skip))]
     pub packed_resources: Vec<PackedResourcesSource<'a>>,
 
+    /// Whether to verify the integrity of packed resources data.
+    ///
+    /// If [true], SHA-256 digests embedded in [Self::packed_resources] are
+    /// verified when the data is loaded. Packed resources data lacking digests
+    /// fails verification.
+    ///
+    /// Default value: [false]
+    ///
+    /// Interpreter initialization behavior: if verification of any packed
+    /// resources data fails, interpreter initialization fails.
+    pub packed_resources_verify: bool,
+
+    /// Whether to re-index memory mapped packed resources files when they change.
+    ///
+    /// If [true], the custom meta path importer checks whether
+    /// [PackedResourcesSource::MemoryMappedPath] files in [Self::packed_resources]
+    /// have changed whenever it looks for a module. If so, resources are
+    /// re-indexed so new and changed modules can be imported without restarting
+    /// the process. Already imported modules are not reloaded.
+    ///
+    /// Regardless of this setting, `importlib.invalidate_caches()` re-indexes
+    /// changed files.
+    ///
+    /// This is intended for development. Files should be replaced atomically
+    /// (e.g. via a rename) rather than rewritten in place.
+    ///
+    /// Default value: [false]
+    pub packed_resources_watch: bool,
+
+    /// Function providing the key used to decrypt encrypted packed resources data.
+    ///
+    /// Packed resources data can have the blob sections holding module source,
+    /// bytecode, and other resource content encrypted. If set, this function is
+    /// called during interpreter initialization and the key it returns is used
+    /// to decrypt [Self::packed_resources]. Encryption is a means of obfuscating
+    /// embedded content. Anyone able to run this function can obtain the key.
+    ///
+    /// Default value: [None]
+    ///
+    /// Interpreter initialization behavior: if the function returns an error,
+    /// interpreter initialization fails. Initialization also fails if encrypted
+    /// data is present and no key is available or the key is incorrect.
+    ///
+    /// This field is ignored during serialization.
+    #[cfg_attr(feature = "serialization", serde(skip))]
+    pub packed_resources_encryption_key_provider: Option<PackedResourcesEncryptionKeyProvider>,
+
     /// Extra extension modules to make available to the interpreter.
     ///
     /// The values will effectively be passed to ``PyImport_ExtendInitTab()``.
@@ -304,6 +401,27 @@ pub struct OxidizedPythonInterpreterConfig<'a> {
     #[cfg_attr(feature = "serialization", serde(skip))]
     pub extra_extension_modules: Option<Vec<ExtensionModule>>,
 
+    /// Python modules implemented in Rust to make available to the interpreter.
+    ///
+    /// Entries are typically defined by pyo3 `#[pymodule]` functions. Names
+    /// can refer to submodules, such as `myapp._native.crypto`. Parent
+    /// packages must be importable: either regular Python packages or
+    /// entries in this list. Entries having other entries as submodules are
+    /// packages.
+    ///
+    /// Default value: [None]
+    ///
+    /// Interpreter initialization behavior: `PyImport_Inittab` will be extended
+    /// with entries from this list. This makes the modules available as
+    /// built-in extension modules, which are listed in
+    /// `sys.builtin_module_names` and indexed by `OxidizedFinder`. Submodules
+    /// can only be imported if [Self::oxidized_importer] is [true], because
+    /// the standard `BuiltinImporter` doesn't import submodules.
+    ///
+    /// This field is ignored during serialization.
+    #[cfg_attr(feature = "serialization", serde(skip))]
+    pub rust_modules: Option<Vec<RustModule>>,
+
     /// Command line arguments to initialize `sys.argv` with.
     ///
     /// Default value: [None]
@@ -421,6 +539,111 @@ pub struct OxidizedPythonInterpreterConfig<'a> {
     ///
     /// Default value: [None]
     pub write_modules_directory_env: Option<String>,
+
+    /// Path to a file to write import profiling data to.
+    ///
+    /// If set, `OxidizedFinder` records how long it takes to find, load, and
+    /// execute each module it imports and where each module's data came from
+    /// (memory, the filesystem, a built-in extension module, or a frozen module).
+    /// On interpreter shutdown, this data is written to the file.
+    ///
+    /// Default value: [None]
+    ///
+    /// [Self::resolve()] behavior: the token `$ORIGIN` is expanded to the
+    /// resolved value of [Self::origin].
+    ///
+    /// Interpreter initialization behavior: has no effect unless
+    /// [Self::oxidized_importer] is [true].
+    pub import_profile_path: Option<PathBuf>,
+
+    /// Environment variable holding the path to write import profiling data to.
+    ///
+    /// If this value is set and the environment variable it refers to is set,
+    /// import profiling is enabled as if [Self::import_profile_path] were set
+    /// to the value of the environment variable. The environment variable takes
+    /// precedence over [Self::import_profile_path].
+    ///
+    /// Default value: [None]
+    pub import_profile_path_env: Option<String>,
+
+    /// The format to write import profiling data in.
+    ///
+    /// Default value: [ImportProfileFormat::Json]
+    pub import_profile_format: ImportProfileFormat,
+
+    /// Names of modules that may be imported.
+    ///
+    /// Each entry matches the module having that name and all of its submodules.
+    /// e.g. `email` matches `email` and `email.parser`. Parent packages are not
+    /// matched: allowing `email.parser` does not allow importing `email`.
+    ///
+    /// If set, `OxidizedFinder` raises `ImportError` for any module not matching
+    /// an entry. This includes modules imported during interpreter initialization,
+    /// such as `encodings`, so these must be allowed.
+    ///
+    /// Default value: [None]
+    ///
+    /// Interpreter initialization behavior: has no effect unless
+    /// [Self::oxidized_importer] is [true].
+    pub import_policy_allow: Option<Vec<String>>,
+
+    /// Names of modules that may not be imported.
+    ///
+    /// Entries are matched as described by [Self::import_policy_allow] and take
+    /// precedence over it.
+    ///
+    /// If set, `OxidizedFinder` raises `ImportError` for any module matching an entry.
+    ///
+    /// Each denied import raises a `sys.audit()` event named
+    /// `oxidized_importer.import_denied` with the module name and the reason it
+    /// was denied as arguments. Install an audit hook to log denied imports.
+    ///
+    /// Default value: [None]
+    ///
+    /// Interpreter initialization behavior: has no effect unless
+    /// [Self::oxidized_importer] is [true].
+    pub import_policy_deny: Option<Vec<String>>,
+
+    /// Whether to install a crash handler.
+    ///
+    /// If enabled, Python's `faulthandler` is enabled to write the Python
+    /// stack of every thread to [Self::crash_handler_fd] when the process
+    /// crashes. On UNIX platforms, signal handlers are also installed to write
+    /// a crash report containing the native backtrace, the Python stack of
+    /// every thread, loaded extension modules, and a summary of the
+    /// interpreter configuration. See [Self::crash_report_directory].
+    ///
+    /// Default value: [false]
+    ///
+    /// Interpreter initialization behavior: the crash handler is installed
+    /// after the interpreter is initialized and removed after it is finalized.
+    pub crash_handler: bool,
+
+    /// File descriptor `faulthandler` writes to when the process crashes.
+    ///
+    /// The file descriptor must remain open for the lifetime of the
+    /// interpreter.
+    ///
+    /// Default value: [None], which means standard error.
+    ///
+    /// Interpreter initialization behavior: has no effect unless
+    /// [Self::crash_handler] is [true].
+    pub crash_handler_fd: Option<i32>,
+
+    /// Directory to write crash reports to.
+    ///
+    /// Crash reports are written to a `pyembed-crash-<pid>.txt` file in this
+    /// directory. If not set, crash reports are written to
+    /// [Self::crash_handler_fd], after the `faulthandler` output.
+    ///
+    /// Default value: [None]
+    ///
+    /// [Self::resolve()] behavior: the token `$ORIGIN` is expanded to the
+    /// resolved value of [Self::origin].
+    ///
+    /// Interpreter initialization behavior: has no effect unless
+    /// [Self::crash_handler] is [true].
+    pub crash_report_directory: Option<PathBuf>,
 }
 
 impl<'a> Default for OxidizedPythonInterpreterConfig<'a> {
@@ -444,7 +667,11 @@ impl<'a> Default for OxidizedPythonInterpreterConfig<'a> {
             oxidized_importer: false,
             filesystem_importer: true,
             packed_resources: vec![],
+            packed_resources_verify: false,
+            packed_resources_watch: false,
+            packed_resources_encryption_key_provider: None,
             extra_extension_modules: None,
+            rust_modules: None,
             argv: None,
             argvb: false,
             multiprocessing_auto_dispatch: true,
@@ -454,6 +681,14 @@ impl<'a> Default for OxidizedPythonInterpreterConfig<'a> {
             terminfo_resolution: TerminfoResolution::Dynamic,
             tcl_library: None,
             write_modules_directory_env: None,
+            import_profile_path: None,
+            import_profile_path_env: None,
+            import_profile_format: ImportProfileFormat::Json,
+            import_policy_allow: None,
+            import_policy_deny: None,
+            crash_handler: false,
+            crash_handler_fd: None,
+            crash_report_directory: None,
         }
     }
 }
@@ -496,15 +731,22 @@ impl<'a> OxidizedPythonInterpreterConfig<'a> {
 
         let origin_string = origin.display().to_string();
 
+        let expand_origin =
+            |p: PathBuf| PathBuf::from(p.display().to_string().replace("$ORIGIN", &origin_string));
+
         let packed_resources = self
             .packed_resources
             .into_iter()
             .map(|entry| match entry {
                 PackedResourcesSource::Memory(_) => entry,
                 PackedResourcesSource::MemoryMappedPath(p) => {
-                    PackedResourcesSource::MemoryMappedPath(PathBuf::from(
-                        p.display().to_string().replace("$ORIGIN", &origin_string),
-                    ))
+                    PackedResourcesSource::MemoryMappedPath(expand_origin(p))
+                }
+                PackedResourcesSource::ZipPath(p) => {
+                    PackedResourcesSource::ZipPath(expand_origin(p))
+                }
+                PackedResourcesSource::WheelPath(p) => {
+                    PackedResourcesSource::WheelPath(expand_origin(p))
                 }
             })
             .collect::<Vec<_>>();
@@ -526,6 +768,9 @@ impl<'a> OxidizedPythonInterpreterConfig<'a> {
             .as_ref()
             .map(|x| PathBuf::from(x.display().to_string().replace("$ORIGIN", &origin_string)));
 
+        let import_profile_path = self.import_profile_path.map(expand_origin);
+        let crash_report_directory = self.crash_report_directory.map(expand_origin);
+
         Ok(ResolvedOxidizedPythonInterpreterConfig {
             inner: Self {
                 exe: Some(exe),
@@ -537,6 +782,8 @@ impl<'a> OxidizedPythonInterpreterConfig<'a> {
                 argv,
                 packed_resources,
                 tcl_library,
+                import_profile_path,
+                crash_report_directory,
                 ..self
             },
         })
@@ -601,21 +848,33 @@ impl<'a> ResolvedOxidizedPythonInterpreterConfig<'a> {
             std::env::args_os().collect::<Vec<_>>()
         }
     }
-}
 
-impl<'a, 'config: 'a> TryFrom<&ResolvedOxidizedPythonInterpreterConfig<'config>>
-    for PythonResourcesState<'a, u8>
-{
-    type Error = NewInterpreterError;
+    /// Construct a [PythonResourcesState] indexing packed resources sources.
+    ///
+    /// Settings for verifying, watching, and decrypting packed resources data
+    /// are taken from this config. Sources are indexed in order, followed by
+    /// the interpreter's builtin and frozen modules.
+    pub(crate) fn new_resources_state<'b>(
+        &self,
+        sources: &[PackedResourcesSource<'b>],
+    ) -> Result<PythonResourcesState<'b, u8>, NewInterpreterError> {
+        let mut state = PythonResourcesState::default();
+        state.set_current_exe(self.exe().to_path_buf());
+        state.set_origin(self.origin().to_path_buf());
+        state.set_packed_resources_verify(self.packed_resources_verify);
+        state.set_watch_memory_mapped_files(self.packed_resources_watch);
 
-    fn try_from(
-        config: &ResolvedOxidizedPythonInterpreterConfig<'config>,
-    ) -> Result<Self, Self::Error> {
-        let mut state = Self::default();
-        state.set_current_exe(config.exe().to_path_buf());
-        state.set_origin(config.origin().to_path_buf());
+        if let Some(provider) = self.packed_resources_encryption_key_provider {
+            let key = provider().map_err(|e| {
+                NewInterpreterError::Dynamic(format!(
+                    "error obtaining packed resources encryption key: {}",
+                    e
+                ))
+            })?;
+            state.set_packed_resources_encryption_key(Some(key));
+        }
 
-        for source in &config.packed_resources {
+        for source in sources {
             match source {
                 PackedResourcesSource::Memory(data) => {
                     state
@@ -627,6 +886,16 @@ impl<'a, 'config: 'a> TryFrom<&ResolvedOxidizedPythonInterpreterConfig<'config>>
                         .index_path_memory_mapped(path)
                         .map_err(NewInterpreterError::Dynamic)?;
                 }
+                PackedResourcesSource::ZipPath(path) => {
+                    state
+                        .index_zip_path(path, false)
+                        .map_err(NewInterpreterError::Dynamic)?;
+                }
+                PackedResourcesSource::WheelPath(path) => {
+                    state
+                        .index_zip_path(path, true)
+                        .map_err(NewInterpreterError::Dynamic)?;
+                }
             }
         }
 
@@ -636,6 +905,88 @@ impl<'a, 'config: 'a> TryFrom<&ResolvedOxidizedPythonInterpreterConfig<'config>>
 
         Ok(state)
     }
+
+    /// Expand `$ORIGIN` in a path to the value of [Self::origin].
+    pub(crate) fn expand_origin(&self, path: PathBuf) -> PathBuf {
+        PathBuf::from(
+            path.display()
+                .to_string()
+                .replace("$ORIGIN", &self.origin().display().to_string()),
+        )
+    }
+}
+
+impl<'a, 'config: 'a> TryFrom<&ResolvedOxidizedPythonInterpreterConfig<'config>>
+    for PythonResourcesState<'a, u8>
+{
+    type Error = NewInterpreterError;
+
+    fn try_from(
+        config: &ResolvedOxidizedPythonInterpreterConfig<'config>,
+    ) -> Result<Self, Self::Error> {
+        config.new_resources_state(&config.packed_resources)
+    }
+}
+
+/// Configuration for a Python sub-interpreter.
+///
+/// This type is used to create a [crate::SubInterpreter], which runs in the
+/// same process as a [crate::MainPythonInterpreter] but has its own
+/// `sys.modules`, `sys.path`, and importers.
+pub struct SubInterpreterConfig<'a> {
+    /// Whether to install `oxidized_importer` in the sub-interpreter.
+    ///
+    /// If [true], a new `oxidized_importer.OxidizedFinder` indexing
+    /// [Self::packed_resources] will be installed on `sys.meta_path` as the
+    /// first element. This finder is independent of the finder installed in
+    /// the main interpreter. Its type is shared with the main interpreter,
+    /// which restricts how it can be used. See [crate::SubInterpreter].
+    ///
+    /// Requires [OxidizedPythonInterpreterConfig::oxidized_importer] to be
+    /// [true] for the main interpreter, as that is what makes the
+    /// `oxidized_importer` extension module available.
+    ///
+    /// Default value: [true]
+    pub oxidized_importer: bool,
+
+    /// Whether to install the path-based finder.
+    ///
+    /// If [false], path-based finders are removed from `sys.meta_path` and
+    /// `sys.path_hooks` after the sub-interpreter is created, so modules can
+    /// only be imported through `oxidized_importer`.
+    ///
+    /// Default value: [false]
+    pub filesystem_importer: bool,
+
+    /// References to packed resources data.
+    ///
+    /// Has the same semantics as [OxidizedPythonInterpreterConfig::packed_resources],
+    /// including the expansion of `$ORIGIN` to the origin of the main interpreter.
+    /// Verification and decryption settings are inherited from the main interpreter.
+    ///
+    /// Default value: `vec![]`
+    pub packed_resources: Vec<PackedResourcesSource<'a>>,
+
+    /// Explicit value for `sys.path`.
+    ///
+    /// If defined, `sys.path` in the sub-interpreter is replaced by these
+    /// paths, with `$ORIGIN` expanded to the origin of the main interpreter.
+    /// Otherwise `sys.path` is derived from the main interpreter's path
+    /// configuration.
+    ///
+    /// Default value: [None]
+    pub sys_paths: Option<Vec<PathBuf>>,
+}
+
+impl<'a> Default for SubInterpreterConfig<'a> {
+    fn default() -> Self {
+        Self {
+            oxidized_importer: true,
+            filesystem_importer: false,
+            packed_resources: vec![],
+            sys_paths: None,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -687,4 +1038,37 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_packed_resources_archive_origin() -> Result<()> {
+        let mut config = OxidizedPythonInterpreterConfig {
+            origin: Some(PathBuf::from("/other/origin")),
+            ..Default::default()
+        };
+
+        config
+            .packed_resources
+            .push(PackedResourcesSource::ZipPath(PathBuf::from(
+                "$ORIGIN/plugins/foo.zip",
+            )));
+        config
+            .packed_resources
+            .push(PackedResourcesSource::WheelPath(PathBuf::from(
+                "$ORIGIN/plugins/bar-1.0-py3-none-any.whl",
+            )));
+
+        let resolved = config.resolve()?;
+
+        assert_eq!(
+            resolved.packed_resources,
+            vec![
+                PackedResourcesSource::ZipPath(PathBuf::from("/other/origin/plugins/foo.zip")),
+                PackedResourcesSource::WheelPath(PathBuf::from(
+                    "/other/origin/plugins/bar-1.0-py3-none-any.whl"
+                )),
+            ]
+        );
+
+        Ok(())
+    }
 }
@@ -0,0 +1,576 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Crash handling for the embedded Python interpreter.
+
+When enabled, Python's `faulthandler` is enabled to write tracebacks of all
+threads to a file descriptor and, on UNIX platforms, signal handlers are
+installed to write a crash report when the process receives a fatal signal.
+
+Crash reports are plain text. The first line is `pyembed-crash-report: 1`,
+where `1` is the version of the format. Lines up to the first blank line are
+`key: value` pairs describing the crash (`signal`, `signal_name`, `pid`).
+Then follow sections. Each section begins with a line `[<name>]` and ends
+where the next section begins. Sections are:
+
+* `[native_backtrace]`: native stack frames of the crashing thread, as
+  written by `backtrace_symbols_fd()`.
+* `[python_threads]`: the Python stack of every thread, as written by
+  `faulthandler`.
+* `[extension_modules]`: `builtin: <name>` lines for extension modules
+  compiled into the interpreter and `shared_library: <path>` lines for
+  shared libraries having a Python extension module suffix that were loaded
+  when the handler was installed.
+* `[interpreter_config]`: `key: value` lines describing the interpreter
+  configuration. Keys may be repeated.
+* `[end]`: marks a complete report. Reports without it were truncated.
+
+Everything written from the signal handler avoids allocating memory.
+Information requiring the Python API or the dynamic loader is gathered when
+the handler is installed.
+*/
+
+use {
+    crate::config::ResolvedOxidizedPythonInterpreterConfig,
+    pyo3::{prelude::*, types::PyDict},
+};
+
+/// Installed crash handling. Dropping restores the previous signal handlers.
+///
+/// `faulthandler` is disabled by interpreter finalization. So instances
+/// should be dropped after the interpreter is finalized.
+pub(crate) struct CrashHandler {
+    _private: (),
+}
+
+impl CrashHandler {
+    /// Install crash handling for an initialized interpreter.
+    ///
+    /// The GIL must be held.
+    pub(crate) fn install(
+        py: Python,
+        config: &ResolvedOxidizedPythonInterpreterConfig,
+    ) -> PyResult<Self> {
+        let fd = config.crash_handler_fd.unwrap_or(2);
+
+        // Our signal handlers must be installed first so `faulthandler`
+        // chains to them after writing its tracebacks.
+        #[cfg(unix)]
+        unix::install(py, config, fd)?;
+
+        // Dropping restores the previous signal handlers if enabling
+        // faulthandler fails.
+        let handler = Self { _private: () };
+
+        let kwargs = PyDict::new(py);
+        kwargs.set_item("file", fd)?;
+        kwargs.set_item("all_threads", true)?;
+
+        py.import("faulthandler")?
+            .call_method("enable", (), Some(kwargs))?;
+
+        Ok(handler)
+    }
+}
+
+impl Drop for CrashHandler {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        unix::uninstall();
+    }
+}
+
+/// Write a crash report as if the process received signal `signum`.
+///
+/// Has no effect unless a [CrashHandler] is installed.
+#[cfg(all(unix, test))]
+pub(crate) fn write_crash_report(signum: std::os::raw::c_int) {
+    unix::write_crash_report(signum)
+}
+
+#[cfg(unix)]
+mod unix {
+    use {
+        crate::config::ResolvedOxidizedPythonInterpreterConfig,
+        pyo3::{ffi as pyffi, prelude::*},
+        std::{
+            os::{
+                raw::{c_char, c_int},
+                unix::ffi::OsStrExt,
+            },
+            sync::atomic::{AtomicBool, AtomicPtr, Ordering},
+        },
+    };
+
+    extern "C" {
+        fn _Py_DumpTracebackThreads(
+            fd: c_int,
+            interp: *mut pyffi::PyInterpreterState,
+            current_tstate: *mut pyffi::PyThreadState,
+        ) -> *const c_char;
+    }
+
+    #[cfg(target_os = "macos")]
+    extern "C" {
+        fn _dyld_image_count() -> u32;
+        fn _dyld_get_image_name(image_index: u32) -> *const c_char;
+    }
+
+    /// Signals we write crash reports for.
+    ///
+    /// These are the signals `faulthandler` handles.
+    const CRASH_SIGNALS: [(c_int, &str); 5] = [
+        (libc::SIGSEGV, "SIGSEGV"),
+        (libc::SIGBUS, "SIGBUS"),
+        (libc::SIGFPE, "SIGFPE"),
+        (libc::SIGILL, "SIGILL"),
+        (libc::SIGABRT, "SIGABRT"),
+    ];
+
+    /// Maximum length of the path of a crash report file.
+    const MAX_REPORT_PATH: usize = 4096;
+
+    /// Everything the signal handler needs, computed ahead of time.
+    struct CrashReportState {
+        /// Path of the report file without the pid and extension.
+        ///
+        /// If `None`, the report is written to `fd`.
+        path_prefix: Option<Vec<u8>>,
+        /// File descriptor `faulthandler` writes to.
+        fd: c_int,
+        /// Preformatted `builtin: <name>` lines.
+        builtin_modules: Vec<u8>,
+        /// Preformatted `shared_library: <path>` lines.
+        ///
+        /// The dynamic loader's APIs aren't async-signal-safe. So this is
+        /// computed when the handler is installed.
+        shared_libraries: Vec<u8>,
+        /// Preformatted `[interpreter_config]` section content.
+        interpreter_config: Vec<u8>,
+        /// Signal handlers in place before ours.
+        previous: Vec<(c_int, libc::sigaction)>,
+    }
+
+    static STATE: AtomicPtr<CrashReportState> = AtomicPtr::new(std::ptr::null_mut());
+
+    /// Whether a crash report is being written.
+    ///
+    /// Only the first crash is reported.
+    static HANDLING: AtomicBool = AtomicBool::new(false);
+
+    /// Writes to a file descriptor without allocating.
+    struct FdWriter(c_int);
+
+    impl FdWriter {
+        fn write(&self, mut data: &[u8]) {
+            while !data.is_empty() {
+                let res = unsafe { libc::write(self.0, data.as_ptr() as *const _, data.len()) };
+
+                if res > 0 {
+                    data = &data[res as usize..];
+                } else if res < 0
+                    && std::io::Error::last_os_error().raw_os_error() == Some(libc::EINTR)
+                {
+                    continue;
+                } else {
+                    return;
+                }
+            }
+        }
+
+        fn write_u64(&self, mut value: u64) {
+            let mut buf = [0u8; 20];
+            let mut pos = buf.len();
+
+            loop {
+                pos -= 1;
+                buf[pos] = b'0' + (value % 10) as u8;
+                value /= 10;
+
+                if value == 0 {
+                    break;
+                }
+            }
+
+            self.write(&buf[pos..]);
+        }
+    }
+
+    /// Append the preformatted `key: value` line to a buffer.
+    fn push_line(buf: &mut Vec<u8>, key: &str, value: &[u8]) {
+        buf.extend_from_slice(key.as_bytes());
+        buf.extend_from_slice(b": ");
+        // Newlines would break parsing.
+        buf.extend(value.iter().map(|c| if *c == b'\n' { b' ' } else { *c }));
+        buf.push(b'\n');
+    }
+
+    fn format_interpreter_config(
+        py: Python,
+        config: &ResolvedOxidizedPythonInterpreterConfig,
+    ) -> PyResult<Vec<u8>> {
+        let sys = py.import("sys")?;
+        let mut buf = vec![];
+
+        push_line(
+            &mut buf,
+            "python_version",
+            sys.getattr("version")?.extract::<String>()?.as_bytes(),
+        );
+        push_line(&mut buf, "exe", config.exe().as_os_str().as_bytes());
+        push_line(&mut buf, "origin", config.origin().as_os_str().as_bytes());
+        push_line(
+            &mut buf,
+            "allocator_backend",
+            config.allocator_backend.to_string().as_bytes(),
+        );
+        push_line(
+            &mut buf,
+            "oxidized_importer",
+            config.oxidized_importer.to_string().as_bytes(),
+        );
+        push_line(
+            &mut buf,
+            "filesystem_importer",
+            config.filesystem_importer.to_string().as_bytes(),
+        );
+        push_line(
+            &mut buf,
+            "packed_resources",
+            config.packed_resources.len().to_string().as_bytes(),
+        );
+
+        for path in sys.getattr("path")?.iter()? {
+            push_line(&mut buf, "sys_path", path?.str()?.to_str()?.as_bytes());
+        }
+
+        Ok(buf)
+    }
+
+    impl CrashReportState {
+        fn new(
+            py: Python,
+            config: &ResolvedOxidizedPythonInterpreterConfig,
+            fd: c_int,
+        ) -> PyResult<Self> {
+            let path_prefix = config
+                .crash_report_directory
+                .as_ref()
+                .map(|dir| dir.join("pyembed-crash-").as_os_str().as_bytes().to_vec());
+
+            let mut builtin_modules = vec![];
+            for name in py
+                .import("sys")?
+                .getattr("builtin_module_names")?
+                .extract::<Vec<String>>()?
+            {
+                push_line(&mut builtin_modules, "builtin", name.as_bytes());
+            }
+
+            let extension_suffixes = py
+                .import("importlib.machinery")?
+                .getattr("EXTENSION_SUFFIXES")?
+                .extract::<Vec<String>>()?;
+
+            let mut shared_libraries = vec![];
+            for path in loaded_shared_libraries() {
+                if extension_suffixes
+                    .iter()
+                    .any(|suffix| path.ends_with(suffix.as_bytes()))
+                {
+                    push_line(&mut shared_libraries, "shared_library", &path);
+                }
+            }
+
+            Ok(Self {
+                path_prefix,
+                fd,
+                builtin_modules,
+                shared_libraries,
+                interpreter_config: format_interpreter_config(py, config)?,
+                previous: vec![],
+            })
+        }
+
+        /// Open the file to write the report to.
+        ///
+        /// Returns the file descriptor and whether it should be closed.
+        fn open_report(&self) -> (c_int, bool) {
+            let prefix = match &self.path_prefix {
+                Some(prefix) => prefix,
+                None => return (self.fd, false),
+            };
+
+            let mut path = [0u8; MAX_REPORT_PATH];
+            let mut pid = [0u8; 20];
+            let mut pid_len = 0;
+            let mut value = unsafe { libc::getpid() } as u64;
+
+            loop {
+                pid[pid_len] = b'0' + (value % 10) as u8;
+                pid_len += 1;
+                value /= 10;
+
+                if value == 0 {
+                    break;
+                }
+            }
+
+            // Leave room for the NUL terminator.
+            let len = prefix.len() + pid_len + b".txt".len();
+            if len >= path.len() {
+                return (self.fd, false);
+            }
+
+            path[..prefix.len()].copy_from_slice(prefix);
+            for (i, c) in pid[..pid_len].iter().rev().enumerate() {
+                path[prefix.len() + i] = *c;
+            }
+            path[prefix.len() + pid_len..len].copy_from_slice(b".txt");
+
+            let fd = unsafe {
+                libc::open(
+                    path.as_ptr() as *const c_char,
+                    libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC | libc::O_CLOEXEC,
+                    0o644 as libc::c_uint,
+                )
+            };
+
+            if fd < 0 {
+                (self.fd, false)
+            } else {
+                (fd, true)
+            }
+        }
+
+        fn write_report(&self, signum: c_int) {
+            let (fd, close) = self.open_report();
+            let w = FdWriter(fd);
+
+            let signal_name = CRASH_SIGNALS
+                .iter()
+                .find(|(s, _)| *s == signum)
+                .map(|(_, name)| *name)
+                .unwrap_or("unknown");
+
+            w.write(b"pyembed-crash-report: 1\nsignal: ");
+            w.write_u64(signum as u64);
+            w.write(b"\nsignal_name: ");
+            w.write(signal_name.as_bytes());
+            w.write(b"\npid: ");
+            w.write_u64(unsafe { libc::getpid() } as u64);
+            w.write(b"\n\n");
+
+            w.write(b"[native_backtrace]\n");
+            write_native_backtrace(&w);
+
+            w.write(b"[python_threads]\n");
+            if unsafe { pyffi::Py_IsInitialized() } != 0 {
+                let err = unsafe {
+                    _Py_DumpTracebackThreads(fd, std::ptr::null_mut(), std::ptr::null_mut())
+                };
+
+                if !err.is_null() {
+                    let err = unsafe { std::ffi::CStr::from_ptr(err) };
+                    w.write(err.to_bytes());
+                    w.write(b"\n");
+                }
+            } else {
+                w.write(b"interpreter not initialized\n");
+            }
+
+            w.write(b"[extension_modules]\n");
+            w.write(&self.builtin_modules);
+            w.write(&self.shared_libraries);
+
+            w.write(b"[interpreter_config]\n");
+            w.write(&self.interpreter_config);
+            w.write(b"[end]\n");
+
+            if close {
+                unsafe {
+                    libc::close(fd);
+                }
+            }
+        }
+    }
+
+    /// Obtain the paths of loaded shared libraries.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn loaded_shared_libraries() -> Vec<Vec<u8>> {
+        unsafe extern "C" fn callback(
+            info: *mut libc::dl_phdr_info,
+            _size: libc::size_t,
+            data: *mut libc::c_void,
+        ) -> c_int {
+            let paths = &mut *(data as *mut Vec<Vec<u8>>);
+            let name = (*info).dlpi_name;
+
+            if !name.is_null() {
+                paths.push(std::ffi::CStr::from_ptr(name).to_bytes().to_vec());
+            }
+
+            0
+        }
+
+        let mut paths: Vec<Vec<u8>> = vec![];
+
+        unsafe {
+            libc::dl_iterate_phdr(Some(callback), &mut paths as *mut _ as *mut libc::c_void);
+        }
+
+        paths
+    }
+
+    /// Obtain the paths of loaded shared libraries.
+    #[cfg(target_os = "macos")]
+    fn loaded_shared_libraries() -> Vec<Vec<u8>> {
+        (0..unsafe { _dyld_image_count() })
+            .filter_map(|i| {
+                let name = unsafe { _dyld_get_image_name(i) };
+
+                if name.is_null() {
+                    None
+                } else {
+                    Some(
+                        unsafe { std::ffi::CStr::from_ptr(name) }
+                            .to_bytes()
+                            .to_vec(),
+                    )
+                }
+            })
+            .collect()
+    }
+
+    /// Obtain the paths of loaded shared libraries.
+    #[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos")))]
+    fn loaded_shared_libraries() -> Vec<Vec<u8>> {
+        vec![]
+    }
+
+    #[cfg(any(all(target_os = "linux", target_env = "gnu"), target_os = "macos"))]
+    fn write_native_backtrace(w: &FdWriter) {
+        let mut frames = [std::ptr::null_mut(); 128];
+
+        unsafe {
+            let count = libc::backtrace(frames.as_mut_ptr(), frames.len() as c_int);
+            libc::backtrace_symbols_fd(frames.as_ptr(), count, w.0);
+        }
+    }
+
+    #[cfg(not(any(all(target_os = "linux", target_env = "gnu"), target_os = "macos")))]
+    fn write_native_backtrace(w: &FdWriter) {
+        w.write(b"unavailable\n");
+    }
+
+    /// Restore the handler in place before ours for a signal.
+    unsafe fn restore_previous(state: &CrashReportState, signum: c_int) {
+        if let Some((_, action)) = state.previous.iter().find(|(s, _)| *s == signum) {
+            libc::sigaction(signum, action, std::ptr::null_mut());
+        } else {
+            libc::signal(signum, libc::SIG_DFL);
+        }
+    }
+
+    extern "C" fn handle_signal(signum: c_int) {
+        let state = STATE.load(Ordering::SeqCst);
+
+        unsafe {
+            if state.is_null() {
+                libc::signal(signum, libc::SIG_DFL);
+            } else {
+                if !HANDLING.swap(true, Ordering::SeqCst) {
+                    (*state).write_report(signum);
+                }
+
+                restore_previous(&*state, signum);
+            }
+
+            libc::raise(signum);
+        }
+    }
+
+    pub(super) fn install(
+        py: Python,
+        config: &ResolvedOxidizedPythonInterpreterConfig,
+        fd: c_int,
+    ) -> PyResult<()> {
+        let mut state = CrashReportState::new(py, config, fd)?;
+
+        // `backtrace()` may allocate the first time it is called. So call it
+        // now rather than from the signal handler.
+        #[cfg(any(all(target_os = "linux", target_env = "gnu"), target_os = "macos"))]
+        unsafe {
+            let mut frames = [std::ptr::null_mut(); 1];
+            libc::backtrace(frames.as_mut_ptr(), 1);
+        }
+
+        for (signum, _) in CRASH_SIGNALS.iter() {
+            unsafe {
+                let mut action: libc::sigaction = std::mem::zeroed();
+                action.sa_sigaction = handle_signal as extern "C" fn(c_int) as libc::sighandler_t;
+                action.sa_flags = libc::SA_ONSTACK | libc::SA_NODEFER;
+                libc::sigemptyset(&mut action.sa_mask);
+
+                let mut previous: libc::sigaction = std::mem::zeroed();
+                if libc::sigaction(*signum, &action, &mut previous) != 0 {
+                    let err = std::io::Error::last_os_error();
+
+                    // Don't leave handlers pointing at state we never publish.
+                    for (signum, action) in &state.previous {
+                        libc::sigaction(*signum, action, std::ptr::null_mut());
+                    }
+
+                    return Err(err.into());
+                }
+
+                state.previous.push((*signum, previous));
+            }
+        }
+
+        let previous = STATE.swap(Box::into_raw(Box::new(state)), Ordering::SeqCst);
+        if !previous.is_null() {
+            unsafe { drop(Box::from_raw(previous)) };
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn uninstall() {
+        let state = STATE.swap(std::ptr::null_mut(), Ordering::SeqCst);
+        if state.is_null() {
+            return;
+        }
+
+        unsafe {
+            for (signum, action) in &(*state).previous {
+                // Leave handlers installed after ours alone.
+                let mut current: libc::sigaction = std::mem::zeroed();
+                libc::sigaction(*signum, std::ptr::null(), &mut current);
+
+                if current.sa_sigaction
+                    == handle_signal as extern "C" fn(c_int) as libc::sighandler_t
+                {
+                    libc::sigaction(*signum, action, std::ptr::null_mut());
+                }
+            }
+
+            // A crash report may be being written by another thread.
+            if !HANDLING.load(Ordering::SeqCst) {
+                drop(Box::from_raw(state));
+            }
+        }
+    }
+
+    #[cfg(test)]
+    pub(super) fn write_crash_report(signum: c_int) {
+        let state = STATE.load(Ordering::SeqCst);
+
+        if !state.is_null() && !HANDLING.swap(true, Ordering::SeqCst) {
+            unsafe { (*state).write_report(signum) };
+            HANDLING.store(false, Ordering::SeqCst);
+        }
+    }
+}
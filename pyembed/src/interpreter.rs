@@ -8,14 +8,17 @@ use {
     crate::{
         config::{OxidizedPythonInterpreterConfig, ResolvedOxidizedPythonInterpreterConfig},
         conversion::osstring_to_bytes,
+        crash_handler::CrashHandler,
         error::NewInterpreterError,
         osutils::resolve_terminfo_dirs,
         pyalloc::PythonMemoryAllocator,
+        rust_module::{init_rust_module, register_rust_modules},
     },
     once_cell::sync::Lazy,
     oxidized_importer::{
-        install_path_hook, remove_external_importers, replace_meta_path_importers, ImporterState,
-        OxidizedFinder, PyInit_oxidized_importer, PythonResourcesState, OXIDIZED_IMPORTER_NAME,
+        install_path_hook, remove_external_importers, replace_meta_path_importers, AllocatorDomain,
+        AllocatorDomainStats, ImportPolicy, ImportProfiler, ImporterState, OxidizedFinder,
+        PyInit_oxidized_importer, PythonResourcesState, OXIDIZED_IMPORTER_NAME,
         OXIDIZED_IMPORTER_NAME_STR,
     },
     pyo3::{
@@ -29,6 +32,7 @@ use {
         io::Write,
         os::raw::c_char,
         path::{Path, PathBuf},
+        sync::Arc,
     },
 };
 
@@ -87,11 +91,17 @@ static GLOBAL_INTERPRETER_GUARD: Lazy<std::sync::Mutex<()>> =
 pub struct MainPythonInterpreter<'interpreter, 'resources: 'interpreter> {
     // It is possible to have a use-after-free if config is dropped before the
     // interpreter is finalized/dropped.
-    config: ResolvedOxidizedPythonInterpreterConfig<'resources>,
+    pub(crate) config: ResolvedOxidizedPythonInterpreterConfig<'resources>,
     interpreter_guard: Option<std::sync::MutexGuard<'interpreter, ()>>,
     pub(crate) allocator: Option<PythonMemoryAllocator>,
     /// File to write containing list of modules when the interpreter finalizes.
     write_modules_path: Option<PathBuf>,
+    /// Records timing of imports performed by `OxidizedFinder`.
+    import_profiler: Option<Arc<ImportProfiler>>,
+    /// File to write import profiling data to when the interpreter finalizes.
+    import_profile_path: Option<PathBuf>,
+    /// Installed crash handler. Removed after the interpreter finalizes.
+    crash_handler: Option<CrashHandler>,
 }
 
 impl<'interpreter, 'resources> MainPythonInterpreter<'interpreter, 'resources> {
@@ -120,6 +130,9 @@ impl<'interpreter, 'resources> MainPythonInterpreter<'interpreter, 'resources> {
             interpreter_guard: None,
             allocator: None,
             write_modules_path: None,
+            import_profiler: None,
+            import_profile_path: None,
+            crash_handler: None,
         };
 
         res.init()?;
@@ -149,6 +162,23 @@ impl<'interpreter, 'resources> MainPythonInterpreter<'interpreter, 'resources> {
             std::env::set_var("TCL_LIBRARY", tcl_library);
         }
 
+        // The environment variable takes precedence over the static path.
+        self.import_profile_path = self
+            .config
+            .import_profile_path_env
+            .as_ref()
+            .and_then(env::var_os)
+            .map(PathBuf::from)
+            .or_else(|| self.config.import_profile_path.clone());
+
+        if self.config.oxidized_importer && self.import_profile_path.is_some() {
+            self.import_profiler = Some(Arc::new(ImportProfiler::default()));
+        }
+
+        if let Some(rust_modules) = &self.config.rust_modules {
+            register_rust_modules(rust_modules).map_err(NewInterpreterError::Simple)?;
+        }
+
         set_pyimport_inittab(&self.config);
 
         // Pre-configure Python.
@@ -249,6 +279,14 @@ impl<'interpreter, 'resources> MainPythonInterpreter<'interpreter, 'resources> {
         self.write_modules_path =
             self.with_gil(|py| self.init_post_main(py, oxidized_finder_loaded))?;
 
+        if self.config.crash_handler {
+            self.crash_handler = Some(self.with_gil(|py| {
+                CrashHandler::install(py, &self.config).map_err(|err| {
+                    NewInterpreterError::new_from_pyerr(py, err, "installing crash handler")
+                })
+            })?);
+        }
+
         debug_assert_eq!(unsafe { pyffi::PyGILState_Check() }, 0);
 
         Ok(())
@@ -272,29 +310,40 @@ impl<'interpreter, 'resources> MainPythonInterpreter<'interpreter, 'resources> {
             NewInterpreterError::new_from_pyerr(py, err, "import of oxidized importer module")
         })?;
 
-        let cb = |importer_state: &mut ImporterState| match self.config.multiprocessing_start_method
-        {
-            MultiprocessingStartMethod::None => {}
-            MultiprocessingStartMethod::Fork
-            | MultiprocessingStartMethod::ForkServer
-            | MultiprocessingStartMethod::Spawn => {
-                importer_state.set_multiprocessing_set_start_method(Some(
-                    self.config.multiprocessing_start_method.to_string(),
-                ));
+        let cb = |importer_state: &mut ImporterState| {
+            importer_state.set_import_profiler(self.import_profiler.clone());
+
+            if self.config.import_policy_allow.is_some() || self.config.import_policy_deny.is_some()
+            {
+                importer_state.set_import_policy(Some(ImportPolicy::new(
+                    self.config.import_policy_allow.clone(),
+                    self.config.import_policy_deny.clone().unwrap_or_default(),
+                )));
             }
-            MultiprocessingStartMethod::Auto => {
-                // Windows uses "spawn" because "fork" isn't available.
-                // Everywhere else uses "fork." The default on macOS is "spawn." This
-                // is due to https://bugs.python.org/issue33725, which only affects
-                // Python framework builds. Our assumption is we aren't using a Python
-                // framework, so "spawn" is safe.
-                let method = if cfg!(target_family = "windows") {
-                    "spawn"
-                } else {
-                    "fork"
-                };
 
-                importer_state.set_multiprocessing_set_start_method(Some(method.to_string()));
+            match self.config.multiprocessing_start_method {
+                MultiprocessingStartMethod::None => {}
+                MultiprocessingStartMethod::Fork
+                | MultiprocessingStartMethod::ForkServer
+                | MultiprocessingStartMethod::Spawn => {
+                    importer_state.set_multiprocessing_set_start_method(Some(
+                        self.config.multiprocessing_start_method.to_string(),
+                    ));
+                }
+                MultiprocessingStartMethod::Auto => {
+                    // Windows uses "spawn" because "fork" isn't available.
+                    // Everywhere else uses "fork." The default on macOS is "spawn." This
+                    // is due to https://bugs.python.org/issue33725, which only affects
+                    // Python framework builds. Our assumption is we aren't using a Python
+                    // framework, so "spawn" is safe.
+                    let method = if cfg!(target_family = "windows") {
+                        "spawn"
+                    } else {
+                        "fork"
+                    };
+
+                    importer_state.set_multiprocessing_set_start_method(Some(method.to_string()));
+                }
             }
         };
 
@@ -489,6 +538,16 @@ impl<'interpreter, 'resources> MainPythonInterpreter<'interpreter, 'resources> {
         Python::with_gil(f)
     }
 
+    /// Obtain statistics about allocations in a memory allocator domain.
+    ///
+    /// Statistics are only available if an instrumented allocator backend
+    /// (see [crate::MemoryAllocatorBackend::Instrumented]) is installed for the domain.
+    pub fn allocator_stats(&self, domain: AllocatorDomain) -> Option<AllocatorDomainStats> {
+        self.allocator
+            .as_ref()
+            .and_then(|allocator| allocator.stats(domain))
+    }
+
     /// Runs `Py_RunMain()` and finalizes the interpreter.
     ///
     /// This will execute whatever is configured by the Python interpreter config
@@ -662,6 +721,17 @@ fn set_pyimport_inittab(config: &OxidizedPythonInterpreterConfig) {
         }
     }
 
+    // Add modules implemented in Rust from the config. They all share an
+    // initialization function.
+    if let Some(rust_modules) = &config.rust_modules {
+        for module in rust_modules {
+            extensions.push(pyffi::_inittab {
+                name: module.name.as_ptr() as *mut _,
+                initfun: Some(init_rust_module),
+            });
+        }
+    }
+
     // Add sentinel record with NULLs.
     extensions.push(pyffi::_inittab {
         name: std::ptr::null_mut(),
@@ -714,6 +784,17 @@ fn write_modules_to_path(py: Python, path: &Path) -> Result<(), &'static str> {
 
 impl<'interpreter, 'resources> Drop for MainPythonInterpreter<'interpreter, 'resources> {
     fn drop(&mut self) {
+        // Import profiling data is held in Rust, so it can be written even if the
+        // interpreter has already been finalized.
+        if let (Some(profiler), Some(path)) = (
+            self.import_profiler.as_ref(),
+            self.import_profile_path.as_ref(),
+        ) {
+            if let Err(e) = profiler.write_to_path(path, self.config.import_profile_format) {
+                eprintln!("error writing import profile: {}", e);
+            }
+        }
+
         // Interpreter may have been finalized already. Possibly through our invocation
         // of Py_RunMain(). Possibly something out-of-band beyond our control. We don't
         // muck with the interpreter after finalization because this will likely result
@@ -735,5 +816,9 @@ impl<'interpreter, 'resources> Drop for MainPythonInterpreter<'interpreter, 'res
             pyffi::PyGILState_Ensure();
             pyffi::Py_FinalizeEx();
         }
+
+        // Finalization disabled `faulthandler`. So our signal handlers can now
+        // be removed.
+        self.crash_handler.take();
     }
 }
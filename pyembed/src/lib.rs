@@ -56,11 +56,14 @@ The optional `serialization` feature controls whether configuration types
 #[allow(unused)]
 mod config;
 mod conversion;
+mod crash_handler;
 mod error;
 mod interpreter;
 mod interpreter_config;
 mod osutils;
 mod pyalloc;
+mod rust_module;
+mod sub_interpreter;
 pub mod technotes;
 #[cfg(test)]
 mod test;
@@ -69,19 +72,23 @@ mod test;
 pub use {
     crate::{
         config::{
-            ExtensionModule, OxidizedPythonInterpreterConfig,
-            ResolvedOxidizedPythonInterpreterConfig,
+            ExtensionModule, OxidizedPythonInterpreterConfig, PackedResourcesEncryptionKeyProvider,
+            ResolvedOxidizedPythonInterpreterConfig, RustModule, RustModuleInit,
+            SubInterpreterConfig,
         },
         error::NewInterpreterError,
         interpreter::MainPythonInterpreter,
         pyalloc::PythonMemoryAllocator,
+        sub_interpreter::SubInterpreter,
+    },
+    oxidized_importer::{
+        AllocatorDomain, AllocatorDomainStats, PackedResourcesSource, PythonResourcesState,
     },
-    oxidized_importer::{PackedResourcesSource, PythonResourcesState},
     python_packaging::{
         interpreter::{
-            Allocator, BytesWarning, CheckHashPycsMode, CoerceCLocale, MemoryAllocatorBackend,
-            MultiprocessingStartMethod, PythonInterpreterConfig, PythonInterpreterProfile,
-            TerminfoResolution,
+            Allocator, BaseMemoryAllocatorBackend, BytesWarning, CheckHashPycsMode, CoerceCLocale,
+            ImportProfileFormat, MemoryAllocatorBackend, MultiprocessingStartMethod,
+            PythonInterpreterConfig, PythonInterpreterProfile, TerminfoResolution,
         },
         resource::BytecodeOptimizationLevel,
     },
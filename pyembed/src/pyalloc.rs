@@ -86,12 +86,25 @@ Rust's allocator API). So even if Rust's global allocator is set to a custom
 allocator, it is preferred to install the Python allocator because its bindings
 to the allocator will be more efficient.
 
+# Instrumented Allocators
+
+Any of the above backends, including Python's default allocators, can be
+wrapped by an _instrumented_ allocator, which records statistics about
+allocations in each domain it is installed for. The statistics are stored in
+process-global counters provided by the `oxidized_importer` crate so they can be
+queried from Python.
+
+Since Python's allocator APIs don't pass the size of an allocation being
+released or resized, the instrumented allocator prepends a header holding the
+requested size to every allocation.
+
 */
 
 use {
     core::ffi::c_void,
+    oxidized_importer::{AllocatorDomain, AllocatorDomainCounters, AllocatorDomainStats},
     pyo3::ffi as pyffi,
-    python_packaging::interpreter::MemoryAllocatorBackend,
+    python_packaging::interpreter::{BaseMemoryAllocatorBackend, MemoryAllocatorBackend},
     std::{
         alloc,
         collections::HashMap,
@@ -451,6 +464,176 @@ extern "C" fn snmalloc_arena_free(_ctx: *mut c_void, ptr: *mut c_void, _size: us
     unsafe { snmalloc_sys::sn_free(ptr as *mut _) }
 }
 
+/// Size of the header prepended to instrumented allocations.
+///
+/// The header holds the requested size of the allocation. It is sized to
+/// preserve the alignment of the wrapped allocator.
+const INSTRUMENTED_HEADER_SIZE: usize = MIN_ALIGN;
+
+/// Obtain the statistics domain for a Python allocator domain.
+fn allocator_domain(domain: pyffi::PyMemAllocatorDomain) -> AllocatorDomain {
+    match domain {
+        pyffi::PyMemAllocatorDomain::PYMEM_DOMAIN_RAW => AllocatorDomain::Raw,
+        pyffi::PyMemAllocatorDomain::PYMEM_DOMAIN_MEM => AllocatorDomain::Mem,
+        pyffi::PyMemAllocatorDomain::PYMEM_DOMAIN_OBJ => AllocatorDomain::Obj,
+    }
+}
+
+/// Context of an instrumented allocator installed for a single domain.
+struct InstrumentedDomainState {
+    /// The allocator performing allocations.
+    inner: pyffi::PyMemAllocatorEx,
+
+    /// Where to record statistics.
+    counters: &'static AllocatorDomainCounters,
+}
+
+/// An instrumented `PyMemAllocatorEx` for a single domain.
+struct InstrumentedDomainAllocator {
+    allocator: pyffi::PyMemAllocatorEx,
+    _state: Box<InstrumentedDomainState>,
+}
+
+impl InstrumentedDomainAllocator {
+    fn new(inner: pyffi::PyMemAllocatorEx, domain: AllocatorDomain) -> Self {
+        let state = Box::new(InstrumentedDomainState {
+            inner,
+            counters: domain.counters(),
+        });
+
+        Self {
+            allocator: pyffi::PyMemAllocatorEx {
+                ctx: &*state as *const _ as *mut c_void,
+                malloc: Some(instrumented_malloc),
+                calloc: Some(instrumented_calloc),
+                realloc: Some(instrumented_realloc),
+                free: Some(instrumented_free),
+            },
+            _state: state,
+        }
+    }
+}
+
+/// Represents an allocator recording statistics about another allocator.
+struct InstrumentedAllocator {
+    /// The wrapped allocator. `None` if wrapping Python's default allocators.
+    ///
+    /// Instrumented allocators reference data structures owned by this.
+    _inner: Option<Box<PythonMemoryAllocator>>,
+
+    raw: InstrumentedDomainAllocator,
+    mem: InstrumentedDomainAllocator,
+    obj: InstrumentedDomainAllocator,
+
+    /// Arena allocator of the wrapped allocator.
+    ///
+    /// Arena allocations aren't instrumented.
+    arena: pyffi::PyObjectArenaAllocator,
+}
+
+/// Write the header of an instrumented allocation.
+///
+/// Returns the pointer to hand out to callers.
+unsafe fn write_instrumented_header(ptr: *mut c_void, size: usize) -> *mut c_void {
+    (ptr as *mut usize).write(size);
+
+    (ptr as *mut u8).add(INSTRUMENTED_HEADER_SIZE) as *mut c_void
+}
+
+/// Read the header of an instrumented allocation.
+///
+/// Returns the pointer obtained from the wrapped allocator and the size of
+/// the allocation.
+unsafe fn read_instrumented_header(ptr: *mut c_void) -> (*mut c_void, usize) {
+    let base = (ptr as *mut u8).sub(INSTRUMENTED_HEADER_SIZE);
+
+    (base as *mut c_void, (base as *const usize).read())
+}
+
+extern "C" fn instrumented_malloc(ctx: *mut c_void, size: usize) -> *mut c_void {
+    let state = unsafe { &*(ctx as *const InstrumentedDomainState) };
+
+    let total = match size.checked_add(INSTRUMENTED_HEADER_SIZE) {
+        Some(total) => total,
+        None => return std::ptr::null_mut(),
+    };
+
+    let ptr = unsafe { (state.inner.malloc.unwrap())(state.inner.ctx, total) };
+
+    if ptr.is_null() {
+        return ptr;
+    }
+
+    state.counters.record_allocation(size);
+
+    unsafe { write_instrumented_header(ptr, size) }
+}
+
+extern "C" fn instrumented_calloc(ctx: *mut c_void, nelem: usize, elsize: usize) -> *mut c_void {
+    let state = unsafe { &*(ctx as *const InstrumentedDomainState) };
+
+    let (size, total) = match nelem
+        .checked_mul(elsize)
+        .and_then(|size| Some((size, size.checked_add(INSTRUMENTED_HEADER_SIZE)?)))
+    {
+        Some(v) => v,
+        None => return std::ptr::null_mut(),
+    };
+
+    let ptr = unsafe { (state.inner.calloc.unwrap())(state.inner.ctx, 1, total) };
+
+    if ptr.is_null() {
+        return ptr;
+    }
+
+    state.counters.record_allocation(size);
+
+    unsafe { write_instrumented_header(ptr, size) }
+}
+
+extern "C" fn instrumented_realloc(
+    ctx: *mut c_void,
+    ptr: *mut c_void,
+    new_size: usize,
+) -> *mut c_void {
+    if ptr.is_null() {
+        return instrumented_malloc(ctx, new_size);
+    }
+
+    let state = unsafe { &*(ctx as *const InstrumentedDomainState) };
+
+    let total = match new_size.checked_add(INSTRUMENTED_HEADER_SIZE) {
+        Some(total) => total,
+        None => return std::ptr::null_mut(),
+    };
+
+    let (base, old_size) = unsafe { read_instrumented_header(ptr) };
+
+    let new_ptr = unsafe { (state.inner.realloc.unwrap())(state.inner.ctx, base, total) };
+
+    if new_ptr.is_null() {
+        return new_ptr;
+    }
+
+    state.counters.record_reallocation(old_size, new_size);
+
+    unsafe { write_instrumented_header(new_ptr, new_size) }
+}
+
+extern "C" fn instrumented_free(ctx: *mut c_void, ptr: *mut c_void) {
+    if ptr.is_null() {
+        return;
+    }
+
+    let state = unsafe { &*(ctx as *const InstrumentedDomainState) };
+
+    let (base, size) = unsafe { read_instrumented_header(ptr) };
+
+    state.counters.record_deallocation(size);
+
+    unsafe { (state.inner.free.unwrap())(state.inner.ctx, base) }
+}
+
 /// Represents a `PyMemAllocatorEx` that can be installed as a memory allocator.
 enum AllocatorInstance {
     /// Backed by a `PyMemAllocatorEx` struct.
@@ -459,6 +642,9 @@ enum AllocatorInstance {
 
     /// Backed by a custom wrapper type.
     Tracking(TrackingAllocator),
+
+    /// Records statistics about allocations of another allocator.
+    Instrumented(InstrumentedAllocator),
 }
 
 /// Represents a custom memory allocator that can be registered with Python.
@@ -481,6 +667,7 @@ impl PythonMemoryAllocator {
             MemoryAllocatorBackend::Mimalloc => Some(Self::mimalloc()),
             MemoryAllocatorBackend::Snmalloc => Some(Self::snmalloc()),
             MemoryAllocatorBackend::Rust => Some(Self::rust()),
+            MemoryAllocatorBackend::Instrumented(base) => Some(Self::instrumented(base)),
         }
     }
 
@@ -593,6 +780,71 @@ impl PythonMemoryAllocator {
         panic!("snmalloc allocator requested but it isn't compiled into this build configuration; try `cargo build --features allocator-snmalloc`");
     }
 
+    /// Construct a new instance recording statistics about another backend.
+    ///
+    /// When wrapping [BaseMemoryAllocatorBackend::Default], the allocators
+    /// currently installed by Python are wrapped. So this should be called
+    /// after `Py_PreInitialize()`.
+    pub fn instrumented(base: BaseMemoryAllocatorBackend) -> Self {
+        let inner = Self::from_backend(base.into()).map(Box::new);
+
+        let domain_allocator = |domain: pyffi::PyMemAllocatorDomain| {
+            let allocator = if let Some(inner) = &inner {
+                unsafe { *inner.as_memory_allocator(domain) }
+            } else {
+                let mut allocator = pyffi::PyMemAllocatorEx {
+                    ctx: std::ptr::null_mut(),
+                    malloc: None,
+                    calloc: None,
+                    realloc: None,
+                    free: None,
+                };
+                unsafe { pyffi::PyMem_GetAllocator(domain, &mut allocator) };
+                allocator
+            };
+
+            InstrumentedDomainAllocator::new(allocator, allocator_domain(domain))
+        };
+
+        let raw = domain_allocator(pyffi::PyMemAllocatorDomain::PYMEM_DOMAIN_RAW);
+        let mem = domain_allocator(pyffi::PyMemAllocatorDomain::PYMEM_DOMAIN_MEM);
+        let obj = domain_allocator(pyffi::PyMemAllocatorDomain::PYMEM_DOMAIN_OBJ);
+
+        let arena = if let Some(inner) = &inner {
+            unsafe { *inner.as_arena_allocator() }
+        } else {
+            let mut arena = pyffi::PyObjectArenaAllocator {
+                ctx: std::ptr::null_mut(),
+                alloc: None,
+                free: None,
+            };
+            unsafe { pyffi::PyObject_GetArenaAllocator(&mut arena) };
+            arena
+        };
+
+        Self {
+            backend: MemoryAllocatorBackend::Instrumented(base),
+            instance: AllocatorInstance::Instrumented(InstrumentedAllocator {
+                _inner: inner,
+                raw,
+                mem,
+                obj,
+                arena,
+            }),
+        }
+    }
+
+    /// Obtain allocation statistics for a domain.
+    ///
+    /// Returns `None` if this allocator isn't instrumented or isn't installed
+    /// for the domain.
+    pub fn stats(&self, domain: AllocatorDomain) -> Option<AllocatorDomainStats> {
+        match &self.instance {
+            AllocatorInstance::Instrumented(_) => oxidized_importer::allocator_stats(domain),
+            _ => None,
+        }
+    }
+
     /// Obtain the backend used for this instance.
     #[allow(unused)]
     pub fn backend(&self) -> MemoryAllocatorBackend {
@@ -603,8 +855,12 @@ impl PythonMemoryAllocator {
     ///
     /// This should be called before `Py_Initialize*()`.
     pub fn set_allocator(&self, domain: pyffi::PyMemAllocatorDomain) {
+        if let AllocatorInstance::Instrumented(_) = &self.instance {
+            allocator_domain(domain).counters().enable();
+        }
+
         unsafe {
-            pyffi::PyMem_SetAllocator(domain, self.as_memory_allocator() as *mut _);
+            pyffi::PyMem_SetAllocator(domain, self.as_memory_allocator(domain) as *mut _);
         }
     }
 
@@ -618,10 +874,18 @@ impl PythonMemoryAllocator {
     }
 
     /// Obtain the pointer to the `PyMemAllocatorEx` for this allocator.
-    fn as_memory_allocator(&self) -> *const pyffi::PyMemAllocatorEx {
+    fn as_memory_allocator(
+        &self,
+        domain: pyffi::PyMemAllocatorDomain,
+    ) -> *const pyffi::PyMemAllocatorEx {
         match &self.instance {
             AllocatorInstance::Simple(alloc, _) => alloc as *const _,
             AllocatorInstance::Tracking(alloc) => &alloc.allocator as *const _,
+            AllocatorInstance::Instrumented(alloc) => match domain {
+                pyffi::PyMemAllocatorDomain::PYMEM_DOMAIN_RAW => &alloc.raw.allocator as *const _,
+                pyffi::PyMemAllocatorDomain::PYMEM_DOMAIN_MEM => &alloc.mem.allocator as *const _,
+                pyffi::PyMemAllocatorDomain::PYMEM_DOMAIN_OBJ => &alloc.obj.allocator as *const _,
+            },
         }
     }
 
@@ -630,6 +894,7 @@ impl PythonMemoryAllocator {
         match &self.instance {
             AllocatorInstance::Simple(_, arena) => arena as *const _ as *mut _,
             AllocatorInstance::Tracking(alloc) => &alloc.arena as *const _ as *mut _,
+            AllocatorInstance::Instrumented(alloc) => &alloc.arena as *const _ as *mut _,
         }
     }
 }
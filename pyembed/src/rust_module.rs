@@ -0,0 +1,138 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Support for Python modules implemented in Rust.
+
+Entries in `PyImport_Inittab` only have a name and an initialization function
+taking no arguments. So every module defined by a [RustModule] shares the same
+initialization function, which uses multi-phase initialization (PEP 489). The
+module is created from the import spec, giving it its fully qualified name.
+The module's exec slot then looks up the Rust function populating the module
+by that name.
+*/
+
+use {
+    crate::config::{RustModule, RustModuleInit},
+    once_cell::sync::Lazy,
+    pyo3::{exceptions::PyRuntimeError, ffi as pyffi, prelude::*, types::PyList, GILPool},
+    std::{
+        collections::BTreeMap,
+        os::raw::{c_int, c_void},
+        panic::AssertUnwindSafe,
+        sync::Mutex,
+    },
+};
+
+/// Registered modules, keyed by fully qualified name.
+static RUST_MODULES: Lazy<Mutex<BTreeMap<String, RustModuleInit>>> =
+    Lazy::new(|| Mutex::new(BTreeMap::new()));
+
+/// Slots of [RUST_MODULE_DEF]. The exec slot is filled in by [init_rust_module()].
+static mut RUST_MODULE_SLOTS: [pyffi::PyModuleDef_Slot; 2] = [
+    pyffi::PyModuleDef_Slot {
+        slot: 0,
+        value: std::ptr::null_mut(),
+    },
+    pyffi::PyModuleDef_Slot {
+        slot: 0,
+        value: std::ptr::null_mut(),
+    },
+];
+
+/// Definition shared by all modules defined by a [RustModule].
+static mut RUST_MODULE_DEF: pyffi::PyModuleDef = pyffi::PyModuleDef {
+    m_base: pyffi::PyModuleDef_HEAD_INIT,
+    m_name: "pyembed.rust_module\0".as_ptr() as *const _,
+    m_doc: std::ptr::null(),
+    m_size: 0,
+    m_methods: std::ptr::null_mut(),
+    m_slots: std::ptr::null_mut(),
+    m_traverse: None,
+    m_clear: None,
+    m_free: None,
+};
+
+/// Register modules so they can be initialized by [init_rust_module()].
+pub(crate) fn register_rust_modules(modules: &[RustModule]) -> Result<(), &'static str> {
+    let mut registered = RUST_MODULES
+        .lock()
+        .map_err(|_| "unable to lock Rust modules")?;
+
+    for module in modules {
+        let name = module
+            .name
+            .to_str()
+            .map_err(|_| "Rust module name is not valid UTF-8")?;
+
+        registered.insert(name.to_string(), module.init);
+    }
+
+    Ok(())
+}
+
+/// Initialization function of modules defined by a [RustModule].
+pub(crate) extern "C" fn init_rust_module() -> *mut pyffi::PyObject {
+    unsafe {
+        let slots = std::ptr::addr_of_mut!(RUST_MODULE_SLOTS) as *mut pyffi::PyModuleDef_Slot;
+        *slots = pyffi::PyModuleDef_Slot {
+            slot: pyffi::Py_mod_exec,
+            value: exec_rust_module as *mut c_void,
+        };
+
+        let def = std::ptr::addr_of_mut!(RUST_MODULE_DEF);
+        (*def).m_slots = slots;
+
+        pyffi::PyModuleDef_Init(def)
+    }
+}
+
+fn exec(py: Python, module: &PyModule) -> PyResult<()> {
+    let name = module.name()?;
+
+    let (init, is_package) = {
+        let registered = RUST_MODULES
+            .lock()
+            .map_err(|_| PyRuntimeError::new_err("unable to lock Rust modules"))?;
+
+        let init = *registered.get(name).ok_or_else(|| {
+            PyRuntimeError::new_err(format!("{} is not a registered Rust module", name))
+        })?;
+
+        let prefix = format!("{}.", name);
+        let is_package = registered.keys().any(|k| k.starts_with(&prefix));
+
+        (init, is_package)
+    };
+
+    // Submodules can only be imported if the parent has a `__path__`.
+    if is_package {
+        module.setattr("__path__", PyList::empty(py))?;
+    }
+
+    init(py, module)
+}
+
+extern "C" fn exec_rust_module(module: *mut pyffi::PyObject) -> c_int {
+    let pool = unsafe { GILPool::new() };
+    let py = pool.python();
+
+    let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+        let module = unsafe { py.from_borrowed_ptr::<PyModule>(module) };
+        exec(py, module)
+    }))
+    .unwrap_or_else(|_| {
+        Err(PyRuntimeError::new_err(
+            "panic while initializing Rust module",
+        ))
+    });
+
+    match result {
+        Ok(()) => 0,
+        Err(err) => {
+            err.restore(py);
+            -1
+        }
+    }
+}
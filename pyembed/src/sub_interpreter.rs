@@ -0,0 +1,281 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Manage Python sub-interpreters.
+
+use {
+    crate::{config::SubInterpreterConfig, error::NewInterpreterError, MainPythonInterpreter},
+    oxidized_importer::{
+        install_path_hook, remove_external_importers, replace_meta_path_importers, ImporterState,
+        PackedResourcesSource, PythonResourcesState, OXIDIZED_IMPORTER_NAME_STR,
+    },
+    pyo3::{ffi as pyffi, prelude::*, types::PyList, GILPool},
+    std::{marker::PhantomData, path::PathBuf},
+};
+
+/// Create a new sub-interpreter.
+///
+/// The GIL must be held. On success, the thread state of the new interpreter
+/// is the current thread state.
+///
+/// `Py_NewInterpreter()` creates sub-interpreters sharing the main
+/// interpreter's GIL and allocator and allowing extension modules that don't
+/// support multi-phase initialization, which `oxidized_importer` relies on.
+unsafe fn new_interpreter() -> Result<*mut pyffi::PyThreadState, NewInterpreterError> {
+    let tstate = pyffi::Py_NewInterpreter();
+
+    if tstate.is_null() {
+        Err(NewInterpreterError::Simple(
+            "unable to create sub-interpreter",
+        ))
+    } else {
+        Ok(tstate)
+    }
+}
+
+/// Holds the GIL and restores the previously current thread state when dropped.
+struct ThreadStateGuard {
+    previous: *mut pyffi::PyThreadState,
+    gil_state: pyffi::PyGILState_STATE,
+}
+
+impl ThreadStateGuard {
+    /// Acquire the GIL, remembering the current thread state.
+    ///
+    /// Must not be called when the current thread state belongs to a
+    /// sub-interpreter, as this would deadlock.
+    unsafe fn acquire() -> Self {
+        let gil_state = pyffi::PyGILState_Ensure();
+
+        Self {
+            previous: pyffi::PyThreadState_Get(),
+            gil_state,
+        }
+    }
+}
+
+impl Drop for ThreadStateGuard {
+    fn drop(&mut self) {
+        unsafe {
+            pyffi::PyThreadState_Swap(self.previous);
+            pyffi::PyGILState_Release(self.gil_state);
+        }
+    }
+}
+
+/// Install importers and apply `sys` settings in a new sub-interpreter.
+fn init_sub_interpreter(
+    py: Python,
+    resources_state: Option<PythonResourcesState<u8>>,
+    filesystem_importer: bool,
+    sys_paths: Option<Vec<PathBuf>>,
+) -> PyResult<()> {
+    let sys_module = py.import("sys")?;
+
+    let oxidized_finder = if let Some(resources_state) = resources_state {
+        let oxidized_importer = py.import(OXIDIZED_IMPORTER_NAME_STR)?;
+
+        Some(replace_meta_path_importers(
+            py,
+            oxidized_importer,
+            Box::new(resources_state),
+            None::<fn(&mut ImporterState)>,
+        )?)
+    } else {
+        None
+    };
+
+    // Unlike the main interpreter, a sub-interpreter is fully initialized with
+    // the standard importers before we get a chance to install ours. Replacing
+    // the meta path importers removed the path-based finder, so restore it if
+    // wanted.
+    if filesystem_importer {
+        if oxidized_finder.is_some() {
+            let path_finder = py
+                .import("_frozen_importlib_external")?
+                .getattr("PathFinder")?;
+            sys_module
+                .getattr("meta_path")?
+                .call_method1("append", (path_finder,))?;
+        }
+    } else {
+        remove_external_importers(sys_module)?;
+    }
+
+    if let Some(finder) = oxidized_finder {
+        install_path_hook(finder.as_ref(), sys_module)?;
+    }
+
+    if let Some(paths) = sys_paths {
+        let paths = paths
+            .iter()
+            .map(|p| p.display().to_string())
+            .collect::<Vec<_>>();
+        sys_module.setattr("path", PyList::new(py, paths))?;
+    }
+
+    // Finders cached for `sys.path` entries were created by the path hooks in
+    // place before ours were installed.
+    sys_module
+        .getattr("path_importer_cache")?
+        .call_method0("clear")?;
+
+    sys_module.setattr("oxidized", true)?;
+
+    Ok(())
+}
+
+/// A Python sub-interpreter running alongside a [MainPythonInterpreter].
+///
+/// Sub-interpreters have their own `sys.modules`, `sys.path`, and importers,
+/// allowing isolated Python code to run in the same process as the main
+/// interpreter. Each sub-interpreter can have its own `OxidizedFinder` indexing
+/// its own packed resources.
+///
+/// # Usage
+///
+/// Construct instances via [SubInterpreter::new()] from a
+/// [SubInterpreterConfig] and a reference to the [MainPythonInterpreter]. The
+/// main interpreter must outlive the sub-interpreter.
+///
+/// Call [SubInterpreter::with_gil()] to run code in the sub-interpreter. The
+/// provided function is given a [pyo3::Python] bound to the sub-interpreter.
+///
+/// Dropping an instance calls `Py_EndInterpreter()` to tear down the
+/// sub-interpreter.
+///
+/// # Safety
+///
+/// Sub-interpreters are created via `Py_NewInterpreter()` and share the GIL
+/// of the main interpreter.
+///
+/// pyo3 doesn't support sub-interpreters. Types it defines, including
+/// `OxidizedFinder` and the other types of the `oxidized_importer` module,
+/// are created once per process and shared by all interpreters. This works
+/// because all interpreters share a GIL and allocator. But it is restricted:
+/// instances of these types must only be used by the interpreter that created
+/// them and must never be passed to another interpreter. Class attributes
+/// of these types are visible to, and can be modified from, every
+/// interpreter.
+///
+/// The thread state of a sub-interpreter is bound to the thread that created
+/// it. So instances can't be sent to other threads.
+///
+/// [SubInterpreter::with_gil()] must not be called from within another
+/// sub-interpreter's [SubInterpreter::with_gil()]. Doing so will deadlock.
+/// Calling it from within [MainPythonInterpreter::with_gil()] is fine.
+///
+/// Python objects from a sub-interpreter must not outlive
+/// [SubInterpreter::with_gil()]. For example, do not return a [Py] from the
+/// function. Any threads started in the sub-interpreter must have exited by
+/// the time it is dropped.
+///
+/// The sub-interpreter is initialized with the standard importers before
+/// `OxidizedFinder` is installed. So the standard library modules needed
+/// during interpreter initialization (e.g. `encodings`) must be importable
+/// from the main interpreter's path configuration.
+pub struct SubInterpreter<'main, 'resources> {
+    /// Thread state of the sub-interpreter.
+    tstate: *mut pyffi::PyThreadState,
+    _main: PhantomData<&'main ()>,
+    _resources: PhantomData<&'resources [u8]>,
+}
+
+impl<'main, 'resources> SubInterpreter<'main, 'resources> {
+    /// Construct a sub-interpreter from a configuration.
+    ///
+    /// Paths in the configuration are resolved against the main interpreter's
+    /// configuration. The GIL is not held when this returns.
+    pub fn new(
+        main: &'main MainPythonInterpreter<'_, '_>,
+        config: SubInterpreterConfig<'resources>,
+    ) -> Result<SubInterpreter<'main, 'resources>, NewInterpreterError> {
+        let tstate = unsafe {
+            let _guard = ThreadStateGuard::acquire();
+            new_interpreter()?
+        };
+
+        let interpreter = SubInterpreter {
+            tstate,
+            _main: PhantomData,
+            _resources: PhantomData,
+        };
+
+        let packed_resources = config
+            .packed_resources
+            .into_iter()
+            .map(|source| match source {
+                PackedResourcesSource::Memory(_) => source,
+                PackedResourcesSource::MemoryMappedPath(p) => {
+                    PackedResourcesSource::MemoryMappedPath(main.config.expand_origin(p))
+                }
+                PackedResourcesSource::ZipPath(p) => {
+                    PackedResourcesSource::ZipPath(main.config.expand_origin(p))
+                }
+                PackedResourcesSource::WheelPath(p) => {
+                    PackedResourcesSource::WheelPath(main.config.expand_origin(p))
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let sys_paths = config.sys_paths.map(|paths| {
+            paths
+                .into_iter()
+                .map(|p| main.config.expand_origin(p))
+                .collect::<Vec<_>>()
+        });
+
+        // If initialization fails, the sub-interpreter is torn down when dropped.
+        interpreter.with_gil(|py| {
+            let resources_state = if config.oxidized_importer {
+                Some(main.config.new_resources_state(&packed_resources)?)
+            } else {
+                None
+            };
+
+            init_sub_interpreter(py, resources_state, config.filesystem_importer, sys_paths)
+                .map_err(|err| {
+                    NewInterpreterError::new_from_pyerr(py, err, "initializing sub-interpreter")
+                })
+        })?;
+
+        Ok(interpreter)
+    }
+
+    /// Run a function with the GIL held and the sub-interpreter active.
+    ///
+    /// This allows running Python code in the sub-interpreter via the PyO3
+    /// Rust APIs. The previously active thread state is restored afterwards.
+    pub fn with_gil<F, R>(&self, f: F) -> R
+    where
+        F: for<'py> FnOnce(Python<'py>) -> R,
+    {
+        // The pool must be dropped while the sub-interpreter is active, so it
+        // is declared after the guard.
+        let _guard = unsafe {
+            let guard = ThreadStateGuard::acquire();
+            pyffi::PyThreadState_Swap(self.tstate);
+            guard
+        };
+        let pool = unsafe { GILPool::new() };
+
+        f(pool.python())
+    }
+}
+
+impl<'main, 'resources> Drop for SubInterpreter<'main, 'resources> {
+    fn drop(&mut self) {
+        // Don't muck with a finalized interpreter. See MainPythonInterpreter's
+        // Drop implementation.
+        if unsafe { pyffi::Py_IsInitialized() } == 0 {
+            return;
+        }
+
+        unsafe {
+            let _guard = ThreadStateGuard::acquire();
+            pyffi::PyThreadState_Swap(self.tstate);
+            pyffi::Py_EndInterpreter(self.tstate);
+        }
+    }
+}
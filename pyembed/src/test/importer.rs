@@ -4,7 +4,7 @@
 
 use {
     super::{default_interpreter_config, run_py_test},
-    crate::MainPythonInterpreter,
+    crate::{MainPythonInterpreter, RustModule},
     anyhow::Result,
     pyo3::prelude::*,
     rusty_fork::rusty_fork_test,
@@ -32,6 +32,32 @@ fn get_importer(interp: &MainPythonInterpreter) -> Result<PyObject> {
     })
 }
 
+#[pyfunction]
+fn rot13(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            'a'..='m' | 'A'..='M' => (c as u8 + 13) as char,
+            'n'..='z' | 'N'..='Z' => (c as u8 - 13) as char,
+            _ => c,
+        })
+        .collect()
+}
+
+#[pymodule]
+fn test_rust_native(_py: Python, m: &PyModule) -> PyResult<()> {
+    m.add("value", 42)?;
+
+    Ok(())
+}
+
+#[pymodule]
+fn test_rust_native_crypto(_py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(rot13, m)?)?;
+
+    Ok(())
+}
+
 rusty_fork_test! {
 
     /// We can load our oxidized importer with no resources.
@@ -77,6 +103,69 @@ rusty_fork_test! {
         });
     }
 
+    /// Imports matching the import policy deny list raise ImportError.
+    #[test]
+    fn import_policy_deny() {
+        let mut config = default_interpreter_config();
+        config.oxidized_importer = true;
+        config.import_policy_deny = Some(vec!["json".to_string()]);
+        let interp = MainPythonInterpreter::new(config).unwrap();
+
+        interp.with_gil(|py| {
+            py.run(
+                "import sys\n\
+                 denied = []\n\
+                 def hook(event, args):\n    \
+                     if event == 'oxidized_importer.import_denied':\n        \
+                         denied.append(args)\n\
+                 sys.addaudithook(hook)\n\
+                 try:\n    \
+                     import json.decoder\n    \
+                     raise AssertionError('import of json should fail')\n\
+                 except ImportError as e:\n    \
+                     assert str(e) == 'import of json denied by import policy: matches deny pattern json', str(e)\n\
+                 assert denied == [('json', 'matches deny pattern json')], denied\n\
+                 assert 'json' not in sys.modules\n",
+                None,
+                None,
+            )
+            .unwrap();
+        });
+    }
+
+    /// Modules implemented in Rust are importable builtins, including submodules.
+    #[test]
+    fn rust_modules() {
+        let mut config = default_interpreter_config();
+        config.oxidized_importer = true;
+        config.rust_modules = Some(vec![
+            RustModule::new("myapp", test_rust_native).unwrap(),
+            RustModule::new("myapp._native.crypto", test_rust_native_crypto).unwrap(),
+            RustModule::new("myapp._native", test_rust_native).unwrap(),
+        ]);
+        let interp = MainPythonInterpreter::new(config).unwrap();
+
+        interp.with_gil(|py| {
+            py.run(
+                "import importlib.machinery, importlib.util, sys\n\
+                 for name in ('myapp', 'myapp._native', 'myapp._native.crypto'):\n    \
+                     assert name in sys.builtin_module_names, name\n    \
+                     spec = importlib.util.find_spec(name)\n    \
+                     assert spec.origin == 'built-in', spec\n    \
+                     assert spec.loader is importlib.machinery.BuiltinImporter, spec\n\
+                 import myapp._native.crypto\n\
+                 assert myapp._native.crypto.__name__ == 'myapp._native.crypto'\n\
+                 assert myapp._native.crypto.rot13('hello') == 'uryyb'\n\
+                 assert myapp._native.value == 42\n\
+                 assert myapp.__path__ == []\n\
+                 assert not hasattr(myapp._native.crypto, '__path__')\n",
+                None,
+                None,
+            )
+            .unwrap();
+        });
+    }
+
     /// Run test_importer_builtins.py.
     #[test]
     fn builtins_py() {
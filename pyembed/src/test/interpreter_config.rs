@@ -4,14 +4,17 @@
 
 use {
     super::{default_interpreter_config, set_sys_paths, PYTHON_INTERPRETER_PATH},
-    crate::{MainPythonInterpreter, OxidizedPythonInterpreterConfig},
+    crate::{AllocatorDomain, MainPythonInterpreter, OxidizedPythonInterpreterConfig},
     pyo3::{
         ffi as pyffi,
         prelude::*,
         types::{PyBytes, PyList, PyString, PyStringData},
     },
     python_packaging::{
-        interpreter::{BytesWarning, MemoryAllocatorBackend, PythonInterpreterProfile},
+        interpreter::{
+            BaseMemoryAllocatorBackend, BytesWarning, MemoryAllocatorBackend,
+            PythonInterpreterProfile,
+        },
         resource::BytecodeOptimizationLevel,
     },
     rusty_fork::rusty_fork_test,
@@ -301,6 +304,74 @@ rusty_fork_test! {
         assert_eq!(interp.allocator.as_ref().unwrap().backend(), MemoryAllocatorBackend::Snmalloc);
     }
 
+    #[test]
+    fn test_allocator_instrumented_default() {
+        let mut config = default_interpreter_config();
+
+        config.allocator_backend =
+            MemoryAllocatorBackend::Instrumented(BaseMemoryAllocatorBackend::Default);
+        config.allocator_raw = true;
+        config.allocator_obj = true;
+
+        let interp = MainPythonInterpreter::new(config).unwrap();
+
+        assert_eq!(
+            interp.allocator.as_ref().unwrap().backend(),
+            MemoryAllocatorBackend::Instrumented(BaseMemoryAllocatorBackend::Default)
+        );
+
+        let stats = interp.allocator_stats(AllocatorDomain::Obj).unwrap();
+        assert!(stats.allocations > 0);
+        assert!(stats.peak_bytes >= stats.current_bytes);
+        assert_eq!(
+            stats.size_histogram.iter().sum::<u64>(),
+            stats.allocations + stats.reallocations
+        );
+        assert!(interp.allocator_stats(AllocatorDomain::Raw).is_some());
+        assert!(interp.allocator_stats(AllocatorDomain::Mem).is_none());
+
+        interp.with_gil(|py| {
+            let stats = py
+                .import("oxidized_importer")
+                .unwrap()
+                .getattr("allocator_stats")
+                .unwrap()
+                .call0()
+                .unwrap();
+
+            let domains = stats.call_method0("keys").unwrap();
+            assert_eq!(reprs(domains).unwrap(), vec!["raw", "obj"]);
+
+            let obj = stats.get_item("obj").unwrap();
+            assert!(obj.get_item("allocations").unwrap().extract::<u64>().unwrap() > 0);
+            assert_eq!(
+                obj.get_item("size_histogram")
+                    .unwrap()
+                    .extract::<Vec<(usize, u64)>>()
+                    .unwrap()
+                    .len(),
+                oxidized_importer::ALLOCATION_SIZE_BUCKETS
+            );
+        });
+    }
+
+    #[test]
+    fn test_allocator_instrumented_rust() {
+        let mut config = default_interpreter_config();
+
+        config.allocator_backend =
+            MemoryAllocatorBackend::Instrumented(BaseMemoryAllocatorBackend::Rust);
+        config.allocator_raw = true;
+        config.allocator_mem = true;
+        config.allocator_obj = true;
+
+        let interp = MainPythonInterpreter::new(config).unwrap();
+
+        for domain in [AllocatorDomain::Raw, AllocatorDomain::Mem, AllocatorDomain::Obj] {
+            assert!(interp.allocator_stats(domain).unwrap().allocations > 0);
+        }
+    }
+
     #[test]
     fn test_allocator_debug() {
         let mut config = default_interpreter_config();
@@ -35,6 +35,70 @@ rusty_fork_test! {
         std::mem::drop(interp);
     }
 
+    #[test]
+    fn import_profile_written_on_drop() {
+        let path = std::env::temp_dir()
+            .join(format!("pyembed-import-profile-{}.json", std::process::id()));
+
+        let mut config = default_interpreter_config();
+        config.oxidized_importer = true;
+        config.import_profile_path = Some(path.clone());
+
+        let interp = MainPythonInterpreter::new(config).unwrap();
+        interp.with_gil(|py| {
+            py.import("errno").unwrap();
+        });
+        std::mem::drop(interp);
+
+        let data = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(data.contains(
+            "\"name\": \"errno\", \"phase\": \"find\", \"source\": \"builtin\""
+        ));
+    }
+
+    #[test]
+    fn crash_handler_enables_faulthandler() {
+        let mut config = default_interpreter_config();
+        config.crash_handler = true;
+
+        let interp = MainPythonInterpreter::new(config).unwrap();
+        interp.with_gil(|py| {
+            let enabled = py
+                .import("faulthandler")
+                .unwrap()
+                .call_method0("is_enabled")
+                .unwrap();
+            assert!(enabled.extract::<bool>().unwrap());
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn crash_report_written() {
+        let path = std::env::temp_dir().join(format!("pyembed-crash-{}.txt", std::process::id()));
+
+        let mut config = default_interpreter_config();
+        config.crash_handler = true;
+        config.crash_report_directory = Some(std::env::temp_dir());
+
+        let interp = MainPythonInterpreter::new(config).unwrap();
+        crate::crash_handler::write_crash_report(libc::SIGSEGV);
+        std::mem::drop(interp);
+
+        let data = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(data.starts_with("pyembed-crash-report: 1\nsignal: 11\nsignal_name: SIGSEGV\n"));
+        assert!(data.contains("\n[native_backtrace]\n"));
+        assert!(data.contains("\n[python_threads]\n"));
+        assert!(data.contains(" (most recent call first):\n"));
+        assert!(data.contains("\n[extension_modules]\nbuiltin: "));
+        assert!(data.contains("\n[interpreter_config]\npython_version: 3."));
+        assert!(data.ends_with("\n[end]\n"));
+    }
+
     #[test]
     fn multiprocessing_py() {
         run_py_test("test_multiprocessing.py").unwrap()
@@ -12,6 +12,7 @@ mod importer;
 mod interpreter_config;
 mod main_python_interpreter;
 mod python_resources;
+mod sub_interpreter;
 
 pub const PYTHON_INTERPRETER_PATH: &str = env!("PYTHON_INTERPRETER_PATH");
 
@@ -6,6 +6,7 @@ use {
     crate::OxidizedPythonInterpreterConfig,
     anyhow::{anyhow, Result},
     oxidized_importer::{PackedResourcesSource, PyTempDir, PythonResourcesState},
+    python_packaging::resource::BytecodeOptimizationLevel,
     python_packed_resources::Resource,
     rusty_fork::rusty_fork_test,
 };
@@ -46,6 +47,126 @@ fn multiple_resource_blobs() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn name_index_resource_blobs() -> Result<()> {
+    let resources = vec![
+        Resource {
+            name: "foo".into(),
+            is_python_module: true,
+            in_memory_source: Some(vec![42].into()),
+            ..Default::default()
+        },
+        Resource {
+            name: "bar".into(),
+            is_python_module: true,
+            in_memory_source: Some(vec![42, 42].into()),
+            ..Default::default()
+        },
+    ];
+    let mut data0 = vec![];
+    python_packed_resources::write_packed_resources_v3(
+        &resources,
+        &mut data0,
+        &python_packed_resources::PackedResourcesWriteOptions {
+            name_index: true,
+            ..python_packed_resources::PackedResourcesWriteOptions::default()
+        },
+    )?;
+
+    let mut state1 = PythonResourcesState::default();
+    state1
+        .add_resource(Resource {
+            name: "foo".into(),
+            is_python_package: true,
+            ..Default::default()
+        })
+        .unwrap();
+    let data1 = state1.serialize_resources(true, true)?;
+
+    let config = OxidizedPythonInterpreterConfig::default().resolve()?;
+
+    let mut resources = PythonResourcesState::try_from(&config)?;
+    resources.index_data(&data0).unwrap();
+    resources.index_data(&data1).unwrap();
+
+    assert!(resources.has_resource("foo"));
+    assert!(resources.has_resource("bar"));
+    assert!(!resources.has_resource("baz"));
+
+    // Resources in multiple blobs are merged.
+    let module = resources
+        .resolve_importable_module("foo", BytecodeOptimizationLevel::Zero)
+        .unwrap();
+    assert!(module.is_package);
+
+    Ok(())
+}
+
+#[test]
+fn encrypted_resource_blobs() -> Result<()> {
+    let resources = vec![Resource {
+        name: "foo".into(),
+        is_shared_library: true,
+        in_memory_shared_library: Some(b"library data".to_vec().into()),
+        ..Default::default()
+    }];
+
+    // Resources are indexed lazily with a name index and eagerly without one.
+    // Both behave the same.
+    for name_index in [false, true] {
+        let mut data = vec![];
+        python_packed_resources::write_packed_resources_v3(
+            &resources,
+            &mut data,
+            &python_packed_resources::PackedResourcesWriteOptions {
+                content_digests: true,
+                name_index,
+                encryption_key: Some([0x42; 32]),
+                ..python_packed_resources::PackedResourcesWriteOptions::default()
+            },
+        )?;
+
+        let mut config = OxidizedPythonInterpreterConfig::default();
+        config
+            .packed_resources
+            .push(PackedResourcesSource::Memory(&data));
+        config.packed_resources_encryption_key_provider = Some(|| Ok([0x42; 32]));
+
+        let resolved = config.clone().resolve()?;
+        let resources = PythonResourcesState::try_from(&resolved)?;
+        assert_eq!(
+            resources.resolve_in_memory_shared_library_data("foo"),
+            Some(b"library data".as_ref())
+        );
+
+        // Resources can't be indexed without the key or with the wrong key.
+        config.packed_resources_encryption_key_provider = None;
+        let resolved = config.clone().resolve()?;
+        assert_eq!(
+            PythonResourcesState::try_from(&resolved)
+                .err()
+                .map(|e| e.to_string()),
+            Some("encrypted blob section requires an encryption key".to_string())
+        );
+
+        config.packed_resources_encryption_key_provider = Some(|| Ok([0x43; 32]));
+        let resolved = config.clone().resolve()?;
+        assert_eq!(
+            PythonResourcesState::try_from(&resolved)
+                .err()
+                .map(|e| e.to_string()),
+            Some("incorrect encryption key for encrypted blob section".to_string())
+        );
+
+        // Errors obtaining the key are fatal.
+        config.packed_resources_encryption_key_provider = Some(|| Err("no key".to_string()));
+        let resolved = config.resolve()?;
+        assert!(PythonResourcesState::try_from(&resolved).is_err());
+    }
+
+    Ok(())
+}
+
 #[test]
 fn test_memory_mapped_file_resources() -> Result<()> {
     let current_dir = std::env::current_exe()?
@@ -99,6 +220,163 @@ fn test_memory_mapped_file_resources() -> Result<()> {
     Ok(())
 }
 
+#[cfg(feature = "zipimport")]
+#[test]
+fn test_zip_archive_resources() -> Result<()> {
+    use std::io::Write;
+
+    let resources_dir = std::env::current_exe()?
+        .parent()
+        .ok_or_else(|| anyhow!("unable to find current exe parent"))?
+        .join("resources");
+    if !resources_dir.exists() {
+        std::fs::create_dir(&resources_dir)?;
+    }
+
+    let write_zip = |path: &std::path::Path, files: &[(&str, &[u8])]| -> Result<()> {
+        let mut zf = zip::ZipWriter::new(std::fs::File::create(path)?);
+
+        for (name, data) in files {
+            zf.start_file(*name, zip::write::FileOptions::default())?;
+            zf.write_all(data)?;
+        }
+
+        zf.finish()?;
+
+        Ok(())
+    };
+
+    let zip_path = resources_dir.join("test_zip_archive_resources.zip");
+    write_zip(
+        &zip_path,
+        &[
+            ("foo/__init__.py", b"import os"),
+            ("foo/data.txt", b"data"),
+            ("ns/bar.py", b"pass"),
+        ],
+    )?;
+
+    let wheel_path = resources_dir.join("baz-1.0-py3-none-any.whl");
+    write_zip(
+        &wheel_path,
+        &[
+            ("baz.py", b"pass"),
+            ("baz-1.0.dist-info/METADATA", b"Name: baz"),
+            ("baz-1.0.data/purelib/qux.py", b"pass"),
+            ("foo/__init__.py", b"import sys"),
+        ],
+    )?;
+
+    let mut config = OxidizedPythonInterpreterConfig::default();
+    config
+        .packed_resources
+        .push(PackedResourcesSource::ZipPath(zip_path));
+    config
+        .packed_resources
+        .push(PackedResourcesSource::WheelPath(wheel_path));
+
+    let resolved = config.resolve()?;
+    let resources = PythonResourcesState::try_from(&resolved)?;
+
+    for name in ["foo", "ns", "ns.bar", "baz", "qux"] {
+        assert!(resources.has_resource(name), "{} should be indexed", name);
+    }
+    assert!(resources.is_package_resource("foo", "data.txt"));
+    assert!(resources
+        .resolve_package_distribution_resource("baz", "METADATA")?
+        .is_some());
+
+    // Resources in multiple sources are merged.
+    let module = resources
+        .resolve_importable_module("foo", BytecodeOptimizationLevel::Zero)
+        .unwrap();
+    assert!(module.is_package);
+
+    Ok(())
+}
+
+#[cfg(feature = "zipimport")]
+#[test]
+fn test_reload_retains_failed_sources() -> Result<()> {
+    use std::io::Write;
+
+    let resources_dir = std::env::current_exe()?
+        .parent()
+        .ok_or_else(|| anyhow!("unable to find current exe parent"))?
+        .join("resources");
+    if !resources_dir.exists() {
+        std::fs::create_dir(&resources_dir)?;
+    }
+
+    let packed_data = |name: &str| -> Result<Vec<u8>> {
+        let mut state = PythonResourcesState::default();
+        state
+            .add_resource(Resource {
+                name: name.to_string().into(),
+                is_python_module: true,
+                in_memory_source: Some(vec![42].into()),
+                ..Default::default()
+            })
+            .unwrap();
+
+        state.serialize_resources(true, true)
+    };
+
+    let write_zip = |path: &std::path::Path| -> Result<()> {
+        let mut zf = zip::ZipWriter::new(std::fs::File::create(path)?);
+        zf.start_file("bar.py", zip::write::FileOptions::default())?;
+        zf.write_all(b"pass")?;
+        zf.finish()?;
+
+        Ok(())
+    };
+
+    let mmap_path = resources_dir.join("test_reload_retains_failed_sources");
+    std::fs::write(&mmap_path, packed_data("foo")?)?;
+    let zip_path = resources_dir.join("test_reload_retains_failed_sources.zip");
+    write_zip(&zip_path)?;
+
+    let mut config = OxidizedPythonInterpreterConfig::default();
+    config
+        .packed_resources
+        .push(PackedResourcesSource::MemoryMappedPath(mmap_path.clone()));
+    config
+        .packed_resources
+        .push(PackedResourcesSource::ZipPath(zip_path.clone()));
+
+    let resolved = config.resolve()?;
+    let mut resources = PythonResourcesState::try_from(&resolved)?;
+
+    for name in ["foo", "bar", "_imp", "_frozen_importlib"] {
+        assert!(resources.has_resource(name), "{} should be indexed", name);
+    }
+
+    // Replace the memory mapped file and delete the zip archive. Sources
+    // indexed after the zip archive, including the interpreter's builtin and
+    // frozen modules, are still re-indexed.
+    let temp_path = resources_dir.join("test_reload_retains_failed_sources.tmp");
+    std::fs::write(&temp_path, packed_data("foo2")?)?;
+    std::fs::rename(&temp_path, &mmap_path)?;
+    std::fs::remove_file(&zip_path)?;
+
+    assert!(resources.reload_changed_memory_mapped_files().is_err());
+    assert!(!resources.has_resource("foo"));
+    assert!(!resources.has_resource("bar"));
+    for name in ["foo2", "_imp", "_frozen_importlib"] {
+        assert!(resources.has_resource(name), "{} should be indexed", name);
+    }
+
+    // The failed source is retried by a later call.
+    write_zip(&zip_path)?;
+    assert!(resources.reload_changed_memory_mapped_files().unwrap());
+    for name in ["foo2", "bar", "_imp", "_frozen_importlib"] {
+        assert!(resources.has_resource(name), "{} should be indexed", name);
+    }
+    assert!(!resources.reload_changed_memory_mapped_files().unwrap());
+
+    Ok(())
+}
+
 fn get_interpreter<'interp, 'rsrc>() -> crate::MainPythonInterpreter<'interp, 'rsrc> {
     let mut config = crate::OxidizedPythonInterpreterConfig::default();
     config.interpreter_config.parse_argv = Some(false);
@@ -0,0 +1,144 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    super::default_interpreter_config,
+    crate::{MainPythonInterpreter, SubInterpreter, SubInterpreterConfig},
+    anyhow::Result,
+    oxidized_importer::PackedResourcesSource,
+    pyo3::{ffi as pyffi, prelude::*},
+    python_packed_resources::Resource,
+    rusty_fork::rusty_fork_test,
+    std::path::PathBuf,
+};
+
+fn new_interpreter<'interpreter, 'resources>(
+) -> Result<MainPythonInterpreter<'interpreter, 'resources>> {
+    let mut config = default_interpreter_config();
+    config.oxidized_importer = true;
+    let interp = MainPythonInterpreter::new(config)?;
+
+    Ok(interp)
+}
+
+/// Packed resources data defining a `tenant` module with a `VALUE` attribute.
+fn tenant_resources(value: &str) -> Result<Vec<u8>> {
+    let resources = vec![Resource {
+        name: "tenant".into(),
+        is_python_module: true,
+        in_memory_source: Some(format!("VALUE = '{}'\n", value).into_bytes().into()),
+        ..Default::default()
+    }];
+
+    let mut data = vec![];
+    python_packed_resources::write_packed_resources_v3(
+        &resources,
+        &mut data,
+        &python_packed_resources::PackedResourcesWriteOptions::default(),
+    )?;
+
+    Ok(data)
+}
+
+rusty_fork_test! {
+    #[test]
+    fn sub_interpreter_isolation() {
+        let interp = new_interpreter().unwrap();
+        interp.with_gil(|py| {
+            py.import("sys").unwrap().setattr("main_marker", true).unwrap();
+        });
+
+        let sub = SubInterpreter::new(&interp, SubInterpreterConfig::default()).unwrap();
+        assert_eq!(unsafe { pyffi::PyGILState_Check() }, 0);
+
+        sub.with_gil(|py| {
+            let sys = py.import("sys").unwrap();
+            assert!(!sys.hasattr("main_marker").unwrap());
+            assert!(sys.getattr("oxidized").unwrap().extract::<bool>().unwrap());
+
+            let meta_path = sys.getattr("meta_path").unwrap();
+            assert_eq!(meta_path.len().unwrap(), 1);
+            assert_eq!(
+                meta_path.get_item(0).unwrap().get_type().name().unwrap(),
+                "OxidizedFinder"
+            );
+        });
+
+        interp.with_gil(|py| {
+            assert!(py.import("sys").unwrap().hasattr("main_marker").unwrap());
+        });
+
+        std::mem::drop(sub);
+        assert_eq!(unsafe { pyffi::PyGILState_Check() }, 0);
+
+        interp.with_gil(|py| {
+            py.import("sys").unwrap();
+        });
+    }
+
+    #[test]
+    fn sub_interpreter_packed_resources() {
+        let data_a = tenant_resources("a").unwrap();
+        let data_b = tenant_resources("b").unwrap();
+
+        let interp = new_interpreter().unwrap();
+
+        let sub_a = SubInterpreter::new(
+            &interp,
+            SubInterpreterConfig {
+                packed_resources: vec![PackedResourcesSource::Memory(&data_a)],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let sub_b = SubInterpreter::new(
+            &interp,
+            SubInterpreterConfig {
+                packed_resources: vec![PackedResourcesSource::Memory(&data_b)],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        for (sub, value) in [(&sub_a, "a"), (&sub_b, "b")] {
+            sub.with_gil(|py| {
+                let tenant = py.import("tenant").unwrap();
+                assert_eq!(
+                    tenant.getattr("VALUE").unwrap().extract::<String>().unwrap(),
+                    value
+                );
+            });
+        }
+
+        interp.with_gil(|py| {
+            assert!(py.import("tenant").is_err());
+        });
+    }
+
+    #[test]
+    fn sub_interpreter_sys_paths() {
+        let interp = new_interpreter().unwrap();
+        let origin = interp.config.origin().clone();
+
+        let sub = SubInterpreter::new(
+            &interp,
+            SubInterpreterConfig {
+                sys_paths: Some(vec![PathBuf::from("$ORIGIN/tenant")]),
+                filesystem_importer: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        sub.with_gil(|py| {
+            let sys = py.import("sys").unwrap();
+            let paths = sys.getattr("path").unwrap().extract::<Vec<String>>().unwrap();
+            assert_eq!(paths, vec![origin.join("tenant").display().to_string()]);
+
+            // The path-based finder is retained.
+            let meta_path = sys.getattr("meta_path").unwrap();
+            assert_eq!(meta_path.len().unwrap(), 2);
+        });
+    }
+}
@@ -9,6 +9,7 @@ import tempfile
 import unittest
 
 from oxidized_importer import (
+    OxidizedResource,
     OxidizedResourceCollector,
     OxidizedFinder,
     find_resources_in_path,
@@ -81,6 +82,56 @@ class TestImporterConstruction(unittest.TestCase):
         f = OxidizedFinder()
         f.index_file_memory_mapped(path)
 
+    def get_module_data(self, name: str) -> bytes:
+        resource = OxidizedResource()
+        resource.is_module = True
+        resource.name = name
+        resource.in_memory_source = b"pass"
+
+        finder = OxidizedFinder()
+        finder.add_resource(resource)
+
+        return finder.serialize_indexed_resources()
+
+    def replace_file(self, path: pathlib.Path, data: bytes):
+        temp_path = path.with_suffix(".tmp")
+        temp_path.write_bytes(data)
+        os.replace(temp_path, path)
+
+    def test_index_file_memory_mapped_reload(self):
+        path = self.td / "reload"
+        self.replace_file(path, self.get_module_data("foo"))
+
+        f = OxidizedFinder()
+        f.index_file_memory_mapped(path)
+        self.assertIsNotNone(f.find_spec("foo", None))
+
+        # Size differs, so the change is detected regardless of mtime resolution.
+        self.replace_file(path, self.get_module_data("foobar"))
+
+        # Changes aren't seen until caches are invalidated.
+        self.assertIsNotNone(f.find_spec("foo", None))
+        self.assertIsNone(f.find_spec("foobar", None))
+
+        f.invalidate_caches()
+        self.assertIsNone(f.find_spec("foo", None))
+        self.assertIsNotNone(f.find_spec("foobar", None))
+
+    def test_index_file_memory_mapped_watch(self):
+        path = self.td / "watch"
+        self.replace_file(path, self.get_module_data("foo"))
+
+        f = OxidizedFinder()
+        self.assertFalse(f.watch_memory_mapped_files)
+        f.watch_memory_mapped_files = True
+        self.assertTrue(f.watch_memory_mapped_files)
+
+        f.index_file_memory_mapped(path)
+        self.replace_file(path, self.get_module_data("foobar"))
+
+        self.assertIsNone(f.find_spec("foo", None))
+        self.assertIsNotNone(f.find_spec("foobar", None))
+
 
 if __name__ == "__main__":
     unittest.main()
@@ -44,6 +44,7 @@ SYMBOL_ATTRIBUTES = {
         "path_hook_base_str",
         "pkg_resources_import_auto_register",
         "serialize_indexed_resources",
+        "watch_memory_mapped_files",
     },
     "OxidizedPathEntryFinder": {
         "_package",
@@ -2,9 +2,11 @@
 # License, v. 2.0. If a copy of the MPL was not distributed with this
 # file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+import hashlib
 import importlib.machinery
 import marshal
 import pathlib
+import tempfile
 import unittest
 
 from oxidized_importer import (
@@ -588,6 +590,68 @@ class TestImporterResources(unittest.TestCase):
         with self.assertRaises(TypeError):
             resource.relative_path_distribution_resources = {"foo": None}
 
+    def test_resource_original_source_path(self):
+        resource = OxidizedResource()
+        self.assertIsNone(resource.original_source_path)
+
+        resource.original_source_path = "/src/foo.py"
+        self.assertEqual(resource.original_source_path, "/src/foo.py")
+
+        resource.original_source_path = None
+        self.assertIsNone(resource.original_source_path)
+
+        with self.assertRaises(AttributeError):
+            del resource.original_source_path
+
+        with self.assertRaises(TypeError):
+            resource.original_source_path = True
+
+    def test_resource_original_source_digest_sha256(self):
+        resource = OxidizedResource()
+        self.assertIsNone(resource.original_source_digest_sha256)
+
+        digest = hashlib.sha256(b"import os").digest()
+        resource.original_source_digest_sha256 = digest
+        self.assertEqual(resource.original_source_digest_sha256, digest)
+
+        resource.original_source_digest_sha256 = None
+        self.assertIsNone(resource.original_source_digest_sha256)
+
+        with self.assertRaises(ValueError):
+            resource.original_source_digest_sha256 = b"foo"
+
+        with self.assertRaises(TypeError):
+            resource.original_source_digest_sha256 = "foo"
+
+    def test_original_source_fallback(self):
+        with tempfile.TemporaryDirectory(prefix="oxidized_importer-test-") as td:
+            source_path = pathlib.Path(td) / "my_module.py"
+            source = b"print('hello from my_module')\n"
+            source_path.write_bytes(source)
+
+            f = OxidizedFinder()
+            resource = OxidizedResource()
+            resource.is_module = True
+            resource.name = "my_module"
+            resource.in_memory_bytecode = marshal.dumps(
+                compile(source, "my_module", "exec")
+            )
+            resource.original_source_path = str(source_path)
+            resource.original_source_digest_sha256 = hashlib.sha256(source).digest()
+            f.add_resource(resource)
+
+            self.assertEqual(f.get_filename("my_module"), str(source_path))
+            self.assertEqual(f.get_source("my_module"), source.decode("utf-8"))
+
+            # Source no longer matching the digest is ignored.
+            source_path.write_bytes(b"print('changed')\n")
+            self.assertIsNone(f.get_source("my_module"))
+            self.assertEqual(f.get_filename("my_module"), str(source_path))
+
+            # As is missing source.
+            source_path.unlink()
+            self.assertIsNone(f.get_source("my_module"))
+
     def test_add_resource_bad_type(self):
         f = OxidizedFinder()
 
@@ -0,0 +1,388 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Analysis of built binaries.
+
+This module implements the `pyoxidizer analyze` command. In addition to
+auditing shared library dependencies, it reports on the packed resources
+data and interpreter configuration embedded in executables built by
+PyOxidizer.
+
+Embedded data is found by scanning for its magic bytes rather than by
+consulting object file metadata. This works the same for ELF, Mach-O, and
+PE binaries.
+*/
+
+use {
+    crate::{
+        packed_resources::{resource_fields, resource_in_memory_size, FieldValue},
+        py_packaging::config::INTERPRETER_CONFIG_MARKER,
+    },
+    anyhow::{anyhow, Context, Result},
+    python_packed_resources::{Resource, HEADER_V3},
+    std::{collections::BTreeMap, io::Write, path::Path},
+};
+
+/// Find the offsets of all occurrences of a byte sequence.
+fn find_all<'a>(data: &'a [u8], needle: &'a [u8]) -> impl Iterator<Item = usize> + 'a {
+    data.windows(needle.len())
+        .enumerate()
+        .filter_map(move |(offset, window)| if window == needle { Some(offset) } else { None })
+}
+
+/// Packed resources data found in a binary.
+pub struct EmbeddedResources<'a> {
+    /// Offset of the start of the data within the binary.
+    pub offset: usize,
+
+    /// Resources parsed from the data.
+    pub resources: Result<Vec<Resource<'a, u8>>>,
+}
+
+/// Find packed resources data embedded in a binary.
+///
+/// Occurrences of the packed resources header not followed by well-formed
+/// packed resources data (e.g. the header constant used by the parser
+/// itself) are ignored. Data whose resources can't be decoded, such as
+/// encrypted data, is returned with the error encountered.
+pub fn find_embedded_resources(data: &[u8]) -> Vec<EmbeddedResources<'_>> {
+    find_all(data, HEADER_V3)
+        .filter_map(|offset| {
+            let resources = python_packed_resources::load_resources(&data[offset..]).ok()?;
+
+            if resources.expected_resources_count() == 0 {
+                return None;
+            }
+
+            Some(EmbeddedResources {
+                offset,
+                resources: resources
+                    .collect::<Result<Vec<_>, &'static str>>()
+                    .map_err(|e| anyhow!("error parsing resource: {}", e)),
+            })
+        })
+        .collect()
+}
+
+/// Find the description of the interpreter configuration embedded in a binary.
+pub fn find_interpreter_config(data: &[u8]) -> Option<&str> {
+    let marker = INTERPRETER_CONFIG_MARKER.as_bytes();
+
+    find_all(data, marker).find_map(|offset| {
+        let start = offset + marker.len();
+        let end = start + data[start..].iter().position(|b| *b == 0)?;
+
+        std::str::from_utf8(&data[start..end]).ok()
+    })
+}
+
+/// Aggregate statistics for resources belonging to a top-level package.
+#[derive(Debug, Default)]
+struct PackageStats {
+    modules: usize,
+    in_memory_size: usize,
+    filesystem_entries: usize,
+}
+
+/// Describe where an extension module is loaded from.
+fn extension_module_location(resource: &Resource<u8>) -> String {
+    if resource.is_python_builtin_extension_module {
+        "built-in".to_string()
+    } else if let Some(data) = &resource.in_memory_extension_module_shared_library {
+        format!("in-memory shared library, {} bytes", data.len())
+    } else if let Some(path) = &resource.relative_path_extension_module_shared_library {
+        format!("filesystem, {}", path.display())
+    } else {
+        "unknown".to_string()
+    }
+}
+
+/// Print a report on resources embedded in a binary.
+pub fn write_resources_report(resources: &[Resource<u8>], dest: &mut impl Write) -> Result<()> {
+    let mut packages = BTreeMap::<String, PackageStats>::new();
+    let mut extension_modules = BTreeMap::new();
+    let (mut in_memory_fields, mut in_memory_size, mut filesystem_fields) = (0, 0, 0);
+    let mut bytecode_levels = [0; 3];
+
+    for resource in resources {
+        let is_python = resource.is_python_module
+            || resource.is_python_package
+            || resource.is_python_extension_module
+            || resource.is_python_builtin_extension_module
+            || resource.is_python_frozen_module;
+
+        let key = if is_python {
+            resource
+                .name
+                .split('.')
+                .next()
+                .unwrap_or_default()
+                .to_string()
+        } else if resource.is_shared_library {
+            "(shared libraries)".to_string()
+        } else {
+            "(files)".to_string()
+        };
+
+        let fields = resource_fields(resource);
+        let filesystem_entries = fields
+            .values()
+            .filter(|value| matches!(value, FieldValue::RelativePath(_)))
+            .count();
+
+        let stats = packages.entry(key).or_default();
+        if is_python {
+            stats.modules += 1;
+        }
+        stats.in_memory_size += resource_in_memory_size(resource);
+        stats.filesystem_entries += filesystem_entries;
+
+        in_memory_fields += fields
+            .values()
+            .filter(|value| matches!(value, FieldValue::InMemory(_)))
+            .count();
+        in_memory_size += resource_in_memory_size(resource);
+        filesystem_fields += filesystem_entries;
+
+        if resource.is_python_extension_module || resource.is_python_builtin_extension_module {
+            extension_modules.insert(
+                resource.name.to_string(),
+                extension_module_location(resource),
+            );
+        }
+
+        for (level, present) in [
+            resource.in_memory_bytecode.is_some()
+                || resource.relative_path_module_bytecode.is_some(),
+            resource.in_memory_bytecode_opt1.is_some()
+                || resource.relative_path_module_bytecode_opt1.is_some(),
+            resource.in_memory_bytecode_opt2.is_some()
+                || resource.relative_path_module_bytecode_opt2.is_some(),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            if present {
+                bytecode_levels[level] += 1;
+            }
+        }
+    }
+
+    writeln!(dest, "Packages")?;
+    writeln!(dest, "========")?;
+    writeln!(
+        dest,
+        "{:<40} {:>8} {:>16} {:>12}",
+        "name", "modules", "in-memory bytes", "filesystem"
+    )?;
+    for (name, stats) in &packages {
+        writeln!(
+            dest,
+            "{:<40} {:>8} {:>16} {:>12}",
+            name, stats.modules, stats.in_memory_size, stats.filesystem_entries
+        )?;
+    }
+    writeln!(dest)?;
+
+    writeln!(dest, "Extension Modules")?;
+    writeln!(dest, "=================")?;
+    if extension_modules.is_empty() {
+        writeln!(dest, "(none)")?;
+    }
+    for (name, location) in &extension_modules {
+        writeln!(dest, "{} ({})", name, location)?;
+    }
+    writeln!(dest)?;
+
+    writeln!(dest, "Resource Locations")?;
+    writeln!(dest, "==================")?;
+    writeln!(
+        dest,
+        "in-memory: {} entries, {} bytes",
+        in_memory_fields, in_memory_size
+    )?;
+    writeln!(
+        dest,
+        "filesystem: {} entries (paths relative to the binary)",
+        filesystem_fields
+    )?;
+    writeln!(dest)?;
+
+    writeln!(dest, "Bytecode Optimization Levels")?;
+    writeln!(dest, "============================")?;
+    for (level, count) in bytecode_levels.iter().enumerate() {
+        writeln!(dest, "{}: {} modules", level, count)?;
+    }
+
+    Ok(())
+}
+
+/// Print a report on PyOxidizer data embedded in a binary.
+pub fn write_embedded_report(data: &[u8], dest: &mut impl Write) -> Result<()> {
+    writeln!(dest, "Interpreter Configuration")?;
+    writeln!(dest, "=========================")?;
+    if let Some(config) = find_interpreter_config(data) {
+        writeln!(dest, "{}", config)?;
+    } else {
+        writeln!(dest, "(not found)")?;
+    }
+    writeln!(dest)?;
+
+    let embedded = find_embedded_resources(data);
+
+    writeln!(dest, "Packed Resources")?;
+    writeln!(dest, "================")?;
+    if embedded.is_empty() {
+        writeln!(
+            dest,
+            "no embedded packed resources found; resources may be loaded from files next to the binary"
+        )?;
+    }
+
+    for entry in embedded {
+        match entry.resources {
+            Ok(resources) => {
+                writeln!(
+                    dest,
+                    "found {} resources at offset {:#x}",
+                    resources.len(),
+                    entry.offset
+                )?;
+                writeln!(dest)?;
+
+                write_resources_report(&resources, dest)?;
+            }
+            Err(e) => {
+                writeln!(
+                    dest,
+                    "unable to decode packed resources at offset {:#x}: {}",
+                    entry.offset, e
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Implements `pyoxidizer analyze`.
+pub fn analyze_path(path: &Path) -> Result<()> {
+    let data = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+
+    match goblin::Object::parse(&data).context("parsing binary")? {
+        goblin::Object::Elf(_) | goblin::Object::PE(_) | goblin::Object::Mach(_) => {
+            tugger_binary_analysis::analyze_data(&data);
+        }
+        _ => {
+            return Err(anyhow!(
+                "{} is not an ELF, Mach-O, or PE binary",
+                path.display()
+            ));
+        }
+    }
+
+    println!();
+
+    write_embedded_report(&data, &mut std::io::stdout())
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        std::{borrow::Cow, path::PathBuf},
+    };
+
+    fn test_binary() -> Result<Vec<u8>> {
+        let resources = vec![
+            Resource {
+                name: Cow::from("foo"),
+                is_python_module: true,
+                is_python_package: true,
+                in_memory_bytecode: Some(Cow::from(b"bytecode".to_vec())),
+                ..Resource::default()
+            },
+            Resource {
+                name: Cow::from("foo.bar"),
+                is_python_module: true,
+                in_memory_bytecode: Some(Cow::from(b"bytecode".to_vec())),
+                in_memory_bytecode_opt2: Some(Cow::from(b"bc".to_vec())),
+                ..Resource::default()
+            },
+            Resource {
+                name: Cow::from("_ssl"),
+                is_python_extension_module: true,
+                relative_path_extension_module_shared_library: Some(Cow::from(PathBuf::from(
+                    "lib/_ssl.so",
+                ))),
+                ..Resource::default()
+            },
+            Resource {
+                name: Cow::from("_io"),
+                is_python_builtin_extension_module: true,
+                ..Resource::default()
+            },
+        ];
+
+        // Simulate the parser's header constant and other data preceding the real data.
+        let mut data = b"\x7fELF".to_vec();
+        data.extend(HEADER_V3);
+        data.extend(b"\xffgarbage");
+        data.extend(INTERPRETER_CONFIG_MARKER.as_bytes());
+        data.extend(b"pyembed::OxidizedPythonInterpreterConfig {}\0");
+
+        python_packed_resources::write_packed_resources_v3(
+            &resources,
+            &mut data,
+            &python_packed_resources::PackedResourcesWriteOptions::default(),
+        )?;
+        data.extend(b"trailing data");
+
+        Ok(data)
+    }
+
+    #[test]
+    fn find_data() -> Result<()> {
+        let data = test_binary()?;
+
+        assert_eq!(
+            find_interpreter_config(&data),
+            Some("pyembed::OxidizedPythonInterpreterConfig {}")
+        );
+
+        let embedded = find_embedded_resources(&data);
+        assert_eq!(embedded.len(), 1);
+        let names = embedded[0]
+            .resources
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|r| r.name.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["foo", "foo.bar", "_ssl", "_io"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn report() -> Result<()> {
+        let data = test_binary()?;
+
+        let mut out = vec![];
+        write_embedded_report(&data, &mut out)?;
+        let out = String::from_utf8(out)?;
+
+        assert!(out.contains("pyembed::OxidizedPythonInterpreterConfig {}\n"));
+        assert!(out.contains("found 4 resources at offset"));
+        assert!(out.contains(&format!("{:<40} {:>8} {:>16} {:>12}\n", "foo", 2, 18, 0)));
+        assert!(out.contains(&format!("{:<40} {:>8} {:>16} {:>12}\n", "_ssl", 1, 0, 1)));
+        assert!(out.contains("_io (built-in)\n"));
+        assert!(out.contains("_ssl (filesystem, lib/_ssl.so)\n"));
+        assert!(out.contains("in-memory: 3 entries, 18 bytes\n"));
+        assert!(out.contains("0: 2 modules\n1: 0 modules\n2: 1 modules\n"));
+
+        Ok(())
+    }
+}
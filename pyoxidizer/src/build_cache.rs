@@ -0,0 +1,376 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Caching of build artifacts across PyOxidizer invocations.
+
+The build cache lives in the PyOxidizer cache directory and holds:
+
+* Python bytecode, content addressed by everything that influences its
+  compilation. See [python_packaging::bytecode::CachingBytecodeCompiler].
+* Rust projects used to build executables. Keeping these around allows
+  Cargo to perform incremental builds.
+
+Generated build artifacts are only written when their content changes.
+This preserves file modification times, which is what allows Cargo to
+determine nothing needs to be rebuilt.
+
+Decisions made by the cache are recorded so they can be explained to the
+user via `pyoxidizer build --explain`.
+*/
+
+use {
+    anyhow::{anyhow, Context, Result},
+    fs2::FileExt,
+    std::{
+        fmt::{Display, Formatter},
+        fs::File,
+        path::{Path, PathBuf},
+        sync::{Arc, Mutex},
+    },
+};
+
+/// Maximum number of individual bytecode compilations to list in explanations.
+const EXPLAIN_MAX_COMPILED: usize = 10;
+
+/// Result of writing an artifact with [write_if_changed].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ArtifactStatus {
+    /// The file didn't exist and was created.
+    Created,
+    /// The file existed with different content and was replaced.
+    Modified,
+    /// The file existed with identical content and was left alone.
+    Unchanged,
+}
+
+impl ArtifactStatus {
+    /// Whether the artifact was (re)written.
+    pub fn is_rebuilt(&self) -> bool {
+        !matches!(self, Self::Unchanged)
+    }
+}
+
+impl Display for ArtifactStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Created => "not previously built",
+            Self::Modified => "content changed",
+            Self::Unchanged => "content unchanged",
+        })
+    }
+}
+
+/// Write data to a file if its content differs from what is already there.
+///
+/// Leaving identical files untouched preserves their modification times,
+/// which prevents downstream consumers like Cargo from rebuilding.
+pub fn write_if_changed(path: impl AsRef<Path>, data: impl AsRef<[u8]>) -> Result<ArtifactStatus> {
+    let path = path.as_ref();
+    let data = data.as_ref();
+
+    let status = match std::fs::read(path) {
+        Ok(existing) if existing == data => return Ok(ArtifactStatus::Unchanged),
+        Ok(_) => ArtifactStatus::Modified,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => ArtifactStatus::Created,
+        Err(e) => return Err(e).with_context(|| format!("reading {}", path.display())),
+    };
+
+    std::fs::write(path, data).with_context(|| format!("writing {}", path.display()))?;
+
+    Ok(status)
+}
+
+/// Synchronize the content of a directory into another.
+///
+/// Only files whose content differs are written. Files in `dest_dir` not
+/// present in `source_dir` are left alone. Entries whose names are in
+/// `ignore` are skipped.
+pub fn sync_directory(
+    source_dir: &Path,
+    dest_dir: &Path,
+    ignore: &[&str],
+) -> Result<Vec<(PathBuf, ArtifactStatus)>> {
+    let mut res = vec![];
+
+    let walk = walkdir::WalkDir::new(source_dir)
+        .sort_by(|a, b| a.file_name().cmp(b.file_name()))
+        .into_iter()
+        .filter_entry(|entry| {
+            !ignore
+                .iter()
+                .any(|name| entry.file_name() == std::ffi::OsStr::new(name))
+        });
+
+    for entry in walk {
+        let entry = entry?;
+
+        let rel_path = entry.path().strip_prefix(source_dir)?;
+        let dest_path = dest_dir.join(rel_path);
+
+        if entry.file_type().is_dir() {
+            std::fs::create_dir_all(&dest_path)
+                .with_context(|| format!("creating {}", dest_path.display()))?;
+        } else {
+            let data = std::fs::read(entry.path())
+                .with_context(|| format!("reading {}", entry.path().display()))?;
+
+            res.push((dest_path.clone(), write_if_changed(&dest_path, data)?));
+        }
+    }
+
+    Ok(res)
+}
+
+/// A decision made by the build cache.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum BuildCacheEvent {
+    /// A generated artifact was written or left alone.
+    Artifact {
+        path: PathBuf,
+        status: ArtifactStatus,
+    },
+    /// Bytecode was compiled, consulting the bytecode cache.
+    Bytecode {
+        /// Number of compilations satisfied by the cache.
+        hits: usize,
+        /// Filenames of modules not in the cache.
+        compiled: Vec<String>,
+    },
+}
+
+impl Display for BuildCacheEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Artifact { path, status } => write!(
+                f,
+                "{}: {} ({})",
+                if status.is_rebuilt() {
+                    "rebuilt"
+                } else {
+                    "reused"
+                },
+                path.display(),
+                status
+            ),
+            Self::Bytecode { hits, compiled } => {
+                write!(
+                    f,
+                    "reused: bytecode for {} modules (in bytecode cache)",
+                    hits
+                )?;
+
+                if !compiled.is_empty() {
+                    write!(
+                        f,
+                        "\nrebuilt: bytecode for {} modules (not in bytecode cache)",
+                        compiled.len()
+                    )?;
+
+                    for filename in compiled.iter().take(EXPLAIN_MAX_COMPILED) {
+                        write!(f, "\n    {}", filename)?;
+                    }
+                    if compiled.len() > EXPLAIN_MAX_COMPILED {
+                        write!(
+                            f,
+                            "\n    ... and {} more",
+                            compiled.len() - EXPLAIN_MAX_COMPILED
+                        )?;
+                    }
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Holds an exclusive lock on a directory in the build cache.
+///
+/// The lock is released when this instance is dropped.
+pub struct BuildCacheLock {
+    file: File,
+}
+
+impl Drop for BuildCacheLock {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+    }
+}
+
+/// Manages the PyOxidizer build cache.
+///
+/// Clones share recorded events.
+#[derive(Clone, Debug)]
+pub struct BuildCache {
+    /// Root directory of the cache.
+    root: PathBuf,
+
+    /// Whether to print explanations of cache decisions.
+    explain: bool,
+
+    /// Decisions made by the cache.
+    events: Arc<Mutex<Vec<BuildCacheEvent>>>,
+}
+
+impl BuildCache {
+    /// Construct a new instance rooted at a directory.
+    pub fn new(root: impl AsRef<Path>) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+            explain: false,
+            events: Arc::new(Mutex::new(vec![])),
+        }
+    }
+
+    /// Directory holding content addressed Python bytecode.
+    pub fn bytecode_dir(&self) -> PathBuf {
+        self.root.join("bytecode")
+    }
+
+    /// Directory holding the Rust project used to build an executable.
+    pub fn project_dir(&self, bin_name: &str, target_triple: &str) -> PathBuf {
+        self.root
+            .join("projects")
+            .join(target_triple)
+            .join(bin_name)
+    }
+
+    /// Obtain an exclusive lock on a directory in the cache.
+    ///
+    /// The directory is created if it doesn't exist.
+    pub fn lock_directory(&self, path: &Path) -> Result<BuildCacheLock> {
+        std::fs::create_dir_all(path).with_context(|| format!("creating {}", path.display()))?;
+
+        let lock_path = path.join(".lock");
+        let file = File::create(&lock_path)
+            .with_context(|| format!("could not create {}", lock_path.display()))?;
+        file.lock_exclusive()
+            .with_context(|| format!("failed to obtain lock for {}", lock_path.display()))?;
+
+        Ok(BuildCacheLock { file })
+    }
+
+    /// Whether cache decisions should be explained.
+    pub fn explain(&self) -> bool {
+        self.explain
+    }
+
+    /// Set whether cache decisions should be explained.
+    pub fn set_explain(&mut self, value: bool) {
+        self.explain = value;
+    }
+
+    /// Record a decision made by the cache.
+    pub fn record(&self, event: BuildCacheEvent) -> Result<()> {
+        self.events
+            .lock()
+            .map_err(|e| anyhow!("unable to lock build cache events: {}", e))?
+            .push(event);
+
+        Ok(())
+    }
+
+    /// Record the writing of an artifact.
+    pub fn record_artifact(&self, path: impl AsRef<Path>, status: ArtifactStatus) -> Result<()> {
+        self.record(BuildCacheEvent::Artifact {
+            path: path.as_ref().to_path_buf(),
+            status,
+        })
+    }
+
+    /// Obtain all recorded events.
+    pub fn events(&self) -> Result<Vec<BuildCacheEvent>> {
+        Ok(self
+            .events
+            .lock()
+            .map_err(|e| anyhow!("unable to lock build cache events: {}", e))?
+            .clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_if_changed_status() -> Result<()> {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("pyoxidizer-test")
+            .tempdir()?;
+        let path = temp_dir.path().join("file");
+
+        assert_eq!(write_if_changed(&path, b"foo")?, ArtifactStatus::Created);
+        let mtime = std::fs::metadata(&path)?.modified()?;
+
+        assert_eq!(write_if_changed(&path, b"foo")?, ArtifactStatus::Unchanged);
+        assert_eq!(std::fs::metadata(&path)?.modified()?, mtime);
+
+        assert_eq!(write_if_changed(&path, b"bar")?, ArtifactStatus::Modified);
+        assert_eq!(std::fs::read(&path)?, b"bar");
+
+        Ok(())
+    }
+
+    #[test]
+    fn sync_directory_status() -> Result<()> {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("pyoxidizer-test")
+            .tempdir()?;
+        let source = temp_dir.path().join("source");
+        let dest = temp_dir.path().join("dest");
+
+        std::fs::create_dir_all(source.join("src"))?;
+        std::fs::create_dir_all(source.join(".git"))?;
+        std::fs::write(source.join("Cargo.toml"), b"[package]")?;
+        std::fs::write(source.join("src").join("main.rs"), b"fn main() {}")?;
+        std::fs::write(source.join(".git").join("HEAD"), b"ref")?;
+
+        let res = sync_directory(&source, &dest, &[".git"])?;
+        assert_eq!(
+            res,
+            vec![
+                (dest.join("Cargo.toml"), ArtifactStatus::Created),
+                (dest.join("src").join("main.rs"), ArtifactStatus::Created),
+            ]
+        );
+        assert!(!dest.join(".git").exists());
+
+        std::fs::write(source.join("Cargo.toml"), b"[package]\nname = 'foo'")?;
+        let res = sync_directory(&source, &dest, &[".git"])?;
+        assert_eq!(
+            res,
+            vec![
+                (dest.join("Cargo.toml"), ArtifactStatus::Modified),
+                (dest.join("src").join("main.rs"), ArtifactStatus::Unchanged),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn events() -> Result<()> {
+        let cache = BuildCache::new("/cache");
+        let clone = cache.clone();
+
+        clone.record_artifact("/artifacts/packed-resources", ArtifactStatus::Unchanged)?;
+        cache.record(BuildCacheEvent::Bytecode {
+            hits: 2,
+            compiled: vec!["foo.py".to_string()],
+        })?;
+
+        let events = cache.events()?;
+        assert_eq!(events.len(), 2);
+        assert_eq!(
+            events[0].to_string(),
+            "reused: /artifacts/packed-resources (content unchanged)"
+        );
+        assert_eq!(
+            events[1].to_string(),
+            "reused: bytecode for 2 modules (in bytecode cache)\nrebuilt: bytecode for 1 modules (not in bytecode cache)\n    foo.py"
+        );
+
+        Ok(())
+    }
+}
@@ -5,7 +5,7 @@
 use {
     crate::{
         environment::{default_target_triple, PYOXIDIZER_VERSION},
-        project_building, projectmgmt,
+        packed_resources, project_building, projectmgmt,
     },
     anyhow::{anyhow, Context, Result},
     clap::{Arg, ArgMatches, Command},
@@ -23,6 +23,12 @@ existing PyOxidizer enabled project.
 
 This command will invoke Rust's build system tool (Cargo) to build
 the project.
+
+Python bytecode and the Rust projects used to build executables are cached
+in PyOxidizer's cache directory and reused across invocations. Generated
+build artifacts are only rewritten when their content changes, allowing
+Cargo to skip work when nothing changed. Use --explain to print what was
+rebuilt and why.
 ";
 
 const INIT_RUST_PROJECT_ABOUT: &str = "\
@@ -68,6 +74,19 @@ appropriate for your use case, you can always define a configuration file to
 customize them and use `run-build-script` to produce similar output files.
 ";
 
+const LOCK_ABOUT: &str = "\
+Build a PyOxidizer project and update its lock file.
+
+This command performs a build and records the Python distributions and
+Python packages resolved for the target triple in a `pyoxidizer.lock` file
+next to the configuration file. Entries for other target triples are
+preserved. Run it after changing the packages or Python distribution a
+project uses.
+
+When a lock file exists, `pyoxidizer build` constrains `pip` to the locked
+package versions and fails if resolved content doesn't match the lock file.
+";
+
 const RUN_BUILD_SCRIPT_ABOUT: &str = "\
 Runs a crate build script to generate Python artifacts.
 
@@ -79,6 +98,18 @@ This command executes the functionality to derive various artifacts and
 emits special lines that tell the Rust build system how to consume them.
 ";
 
+const RESOURCES_ABOUT: &str = "\
+Inspect packed resources data.
+
+Packed resources data is the binary format used to embed Python resources
+in built binaries and is typically found in a `packed-resources` file in
+a build's output directory.
+
+These commands can be used to see which resources are present in a build,
+where their data lives, and how two builds differ. e.g. to explain why a
+binary grew between releases.
+";
+
 const RESOURCES_SCAN_ABOUT: &str = "\
 Scan a directory or file for Python resources.
 
@@ -246,6 +277,11 @@ pub fn run_cli() -> Result<()> {
                     .long("release")
                     .help("Build a release binary"),
             )
+            .arg(
+                Arg::new("explain")
+                    .long("explain")
+                    .help("Print what was rebuilt and why"),
+            )
             .arg(
                 Arg::new("path")
                     .long("path")
@@ -365,6 +401,38 @@ pub fn run_cli() -> Result<()> {
             ),
     );
 
+    let app = app.subcommand(add_env_args(
+        Command::new("lock")
+            .about("Build a PyOxidizer project and refresh its lock file")
+            .long_about(LOCK_ABOUT)
+            .arg(
+                Arg::new("target_triple")
+                    .long("target-triple")
+                    .takes_value(true)
+                    .help("Rust target triple to build for"),
+            )
+            .arg(
+                Arg::new("release")
+                    .long("release")
+                    .help("Build a release binary"),
+            )
+            .arg(
+                Arg::new("path")
+                    .long("path")
+                    .takes_value(true)
+                    .default_value(".")
+                    .value_name("PATH")
+                    .help("Directory containing project to lock"),
+            )
+            .arg(
+                Arg::new("targets")
+                    .value_name("TARGET")
+                    .multiple_occurrences(true)
+                    .multiple_values(true)
+                    .help("Target to resolve"),
+            ),
+    ));
+
     let app = app.subcommand(
         Command::new("python-distribution-extract")
             .about("Extract a Python distribution archive to a directory")
@@ -409,6 +477,80 @@ pub fn run_cli() -> Result<()> {
             ),
     );
 
+    let app = app.subcommand(
+        Command::new("resources")
+            .about("Inspect packed resources data")
+            .long_about(RESOURCES_ABOUT)
+            .subcommand_required(true)
+            .arg_required_else_help(true)
+            .subcommand(
+                Command::new("list")
+                    .about("List resources in packed resources data")
+                    .arg(
+                        Arg::new("path")
+                            .required(true)
+                            .value_name("PATH")
+                            .help("Path to packed resources file"),
+                    ),
+            )
+            .subcommand(
+                Command::new("show")
+                    .about("Show details of a resource in packed resources data")
+                    .arg(
+                        Arg::new("path")
+                            .required(true)
+                            .value_name("PATH")
+                            .help("Path to packed resources file"),
+                    )
+                    .arg(
+                        Arg::new("name")
+                            .required(true)
+                            .value_name("NAME")
+                            .help("Name of resource to show"),
+                    ),
+            )
+            .subcommand(
+                Command::new("extract")
+                    .about("Extract in-memory data from packed resources data to a directory")
+                    .arg(
+                        Arg::new("name")
+                            .long("name")
+                            .takes_value(true)
+                            .multiple_occurrences(true)
+                            .value_name("NAME")
+                            .help("Name of resource to extract (default: all resources)"),
+                    )
+                    .arg(
+                        Arg::new("path")
+                            .required(true)
+                            .value_name("PATH")
+                            .help("Path to packed resources file"),
+                    )
+                    .arg(
+                        Arg::new("dest_path")
+                            .required(true)
+                            .value_name("DESTINATION_PATH")
+                            .help("Directory to write extracted data to"),
+                    ),
+            )
+            .subcommand(
+                Command::new("diff")
+                    .about("Show differences between two packed resources data")
+                    .arg(
+                        Arg::new("a")
+                            .required(true)
+                            .value_name("PATH_A")
+                            .help("Path to original packed resources file"),
+                    )
+                    .arg(
+                        Arg::new("b")
+                            .required(true)
+                            .value_name("PATH_B")
+                            .help("Path to new packed resources file"),
+                    ),
+            ),
+    );
+
     let app = app.subcommand(add_env_args(
         Command::new("run-build-script")
             .about("Run functionality that a build script would perform")
@@ -522,14 +664,13 @@ pub fn run_cli() -> Result<()> {
         "analyze" => {
             let path = args.value_of("path").unwrap();
             let path = PathBuf::from(path);
-            tugger_binary_analysis::analyze_file(path);
-
-            Ok(())
+            crate::analyze::analyze_path(&path)
         }
 
         "build" => {
             let starlark_vars = starlark_vars(args)?;
             let release = args.is_present("release");
+            env.set_explain_builds(args.is_present("explain"));
             let target_triple = args.value_of("target_triple");
             let path = args.value_of("path").unwrap();
             let resolve_targets = args
@@ -618,6 +759,26 @@ pub fn run_cli() -> Result<()> {
             projectmgmt::init_rust_project(&env, project_path)
         }
 
+        "lock" => {
+            let starlark_vars = starlark_vars(args)?;
+            let release = args.is_present("release");
+            let target_triple = args.value_of("target_triple");
+            let path = args.value_of("path").unwrap();
+            let resolve_targets = args
+                .values_of("targets")
+                .map(|values| values.map(|x| x.to_string()).collect());
+
+            projectmgmt::lock(
+                &env,
+                Path::new(path),
+                target_triple,
+                resolve_targets,
+                starlark_vars,
+                release,
+                verbose,
+            )
+        }
+
         "python-distribution-extract" => {
             let download_default = args.is_present("download-default");
             let archive_path = args.value_of("archive-path");
@@ -646,6 +807,34 @@ pub fn run_cli() -> Result<()> {
             projectmgmt::python_distribution_licenses(&env, path)
         }
 
+        "resources" => {
+            let (command, args) = args
+                .subcommand()
+                .ok_or_else(|| anyhow!("invalid sub-command"))?;
+
+            match command {
+                "list" => packed_resources::list(Path::new(args.value_of("path").unwrap())),
+                "show" => packed_resources::show(
+                    Path::new(args.value_of("path").unwrap()),
+                    args.value_of("name").unwrap(),
+                ),
+                "extract" => {
+                    let names: Vec<&str> = args.values_of("name").unwrap_or_default().collect();
+
+                    packed_resources::extract_path(
+                        Path::new(args.value_of("path").unwrap()),
+                        Path::new(args.value_of("dest_path").unwrap()),
+                        &names,
+                    )
+                }
+                "diff" => packed_resources::diff(
+                    Path::new(args.value_of("a").unwrap()),
+                    Path::new(args.value_of("b").unwrap()),
+                ),
+                _ => Err(anyhow!("invalid sub-command")),
+            }
+        }
+
         "run-build-script" => {
             let starlark_vars = starlark_vars(args)?;
             let build_script = args.value_of("build-script-name").unwrap();
@@ -5,7 +5,10 @@
 //! Resolve details about the PyOxidizer execution environment.
 
 use {
-    crate::{project_layout::PyembedLocation, py_packaging::distribution::AppleSdkInfo},
+    crate::{
+        build_cache::BuildCache, project_layout::PyembedLocation,
+        py_packaging::distribution::AppleSdkInfo,
+    },
     anyhow::{anyhow, Context, Result},
     apple_sdk::{AppleSdk, ParsedSdk, SdkSearch, SdkSearchLocation, SdkSorting},
     log::{info, warn},
@@ -264,6 +267,9 @@ pub struct Environment {
     /// Directory to use for caching things.
     cache_dir: PathBuf,
 
+    /// Cache of build artifacts.
+    build_cache: BuildCache,
+
     /// Whether we should use a Rust installation we manage ourselves.
     managed_rust: bool,
 
@@ -288,10 +294,13 @@ impl Environment {
 
         let managed_rust = std::env::var("PYOXIDIZER_SYSTEM_RUST").is_err();
 
+        let build_cache = BuildCache::new(cache_dir.join("build"));
+
         Ok(Self {
             pyoxidizer_source,
             cargo_target_directory: cargo_target_directory()?,
             cache_dir,
+            build_cache,
             managed_rust,
             rust_environment: Arc::new(RwLock::new(None)),
         })
@@ -314,6 +323,16 @@ impl Environment {
         self.cache_dir.join("rust")
     }
 
+    /// Cache of build artifacts.
+    pub fn build_cache(&self) -> &BuildCache {
+        &self.build_cache
+    }
+
+    /// Set whether decisions made by the build cache should be explained.
+    pub fn set_explain_builds(&mut self, value: bool) {
+        self.build_cache.set_explain(value);
+    }
+
     /// Do not use a managed Rust.
     ///
     /// When called, [self.ensure_rust_toolchain()] will attempt to locate a
@@ -11,14 +11,19 @@ distribution and embedding it in a larger binary, oftentimes an executable.
 This library exposes that functionality to other tools.
 */
 
+pub mod analyze;
+pub mod build_cache;
 mod default_python_distributions;
 pub mod environment;
 pub mod licensing;
+pub mod lock_file;
+pub mod packed_resources;
 pub mod project_building;
 pub mod project_layout;
 pub mod projectmgmt;
 pub mod py_packaging;
 pub mod python_distributions;
+pub mod sbom;
 pub mod starlark;
 
 #[cfg(test)]
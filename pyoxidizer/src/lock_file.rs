@@ -0,0 +1,602 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Lock files recording third-party content resolved by a build.
+
+A lock file lives next to the PyOxidizer configuration file and records the
+Python distributions and Python package distributions that were resolved when
+evaluating the configuration for each target triple. Subsequent builds
+constrain `pip` to the locked versions and fail if anything resolves to content
+not matching the lock file.
+*/
+
+use {
+    crate::py_packaging::distribution::{PythonDistribution, PythonDistributionLocation},
+    anyhow::{anyhow, Context, Result},
+    log::warn,
+    python_packaging::{requirement::normalize_project_name, resource::PythonResource},
+    serde::{Deserialize, Serialize},
+    sha2::Digest,
+    std::{
+        collections::BTreeMap,
+        path::{Path, PathBuf},
+        sync::Mutex,
+    },
+};
+
+/// Filename of lock files.
+pub const LOCK_FILE_NAME: &str = "pyoxidizer.lock";
+
+/// Version of the lock file format.
+const LOCK_FILE_VERSION: u32 = 1;
+
+/// How a build should interact with a lock file.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LockFileMode {
+    /// Verify resolved content against an existing lock file.
+    ///
+    /// The lock file is never written.
+    Enforce,
+
+    /// Resolve content without verifying it and write it to the lock file.
+    ///
+    /// Entries for other target triples in an existing lock file are preserved.
+    Update,
+}
+
+/// A Python distribution recorded in a lock file.
+#[derive(Clone, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct LockedPythonDistribution {
+    /// Rust target triple the distribution runs on.
+    pub target_triple: String,
+    /// Full Python version of the distribution.
+    pub python_version: String,
+    /// URL or filesystem path the distribution was obtained from.
+    pub location: String,
+    /// SHA-256 of the distribution archive.
+    pub sha256: String,
+}
+
+/// A Python package distribution recorded in a lock file.
+#[derive(Clone, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct LockedPackage {
+    /// Rust target triple the package was resolved for.
+    pub target_triple: String,
+    /// Normalized name of the package.
+    pub name: String,
+    /// Version of the package.
+    pub version: String,
+    /// SHA-256 of the package's `.dist-info/RECORD` file.
+    ///
+    /// `RECORD` holds the digest of every file in the package, so this
+    /// captures the content of the installed package. Entries depending on
+    /// where the package was installed from or to are excluded. See
+    /// [record_digest()].
+    pub sha256: String,
+}
+
+/// The content of a lock file.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct LockFile {
+    pub version: u32,
+    pub python_distributions: Vec<LockedPythonDistribution>,
+    pub packages: Vec<LockedPackage>,
+}
+
+impl Default for LockFile {
+    fn default() -> Self {
+        Self {
+            version: LOCK_FILE_VERSION,
+            python_distributions: vec![],
+            packages: vec![],
+        }
+    }
+}
+
+impl LockFile {
+    /// Parse a lock file from a filesystem path.
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let data = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+
+        let lock: Self = serde_json::from_slice(&data)
+            .with_context(|| format!("parsing lock file {}", path.display()))?;
+
+        if lock.version != LOCK_FILE_VERSION {
+            return Err(anyhow!(
+                "unsupported lock file version {} in {}; run `pyoxidizer lock` to regenerate it",
+                lock.version,
+                path.display()
+            ));
+        }
+
+        Ok(lock)
+    }
+
+    /// Write the lock file to a filesystem path.
+    pub fn write_to_path(&self, path: &Path) -> Result<()> {
+        let mut data = serde_json::to_string_pretty(self)?;
+        data.push('\n');
+
+        std::fs::write(path, data).with_context(|| format!("writing {}", path.display()))
+    }
+
+    /// Find the locked Python distribution for a target triple.
+    pub fn python_distribution(&self, target_triple: &str) -> Option<&LockedPythonDistribution> {
+        self.python_distributions
+            .iter()
+            .find(|d| d.target_triple == target_triple)
+    }
+
+    /// Find a locked package for a target triple by name.
+    pub fn package(&self, target_triple: &str, name: &str) -> Option<&LockedPackage> {
+        let name = normalize_project_name(name);
+
+        self.packages
+            .iter()
+            .find(|p| p.target_triple == target_triple && p.name == name)
+    }
+
+    /// Obtain the content of a `pip` constraints file pinning locked packages.
+    pub fn pip_constraints(&self, target_triple: &str) -> String {
+        self.packages
+            .iter()
+            .filter(|p| p.target_triple == target_triple)
+            .map(|p| format!("{}=={}\n", p.name, p.version))
+            .collect()
+    }
+
+    fn add_python_distribution(&mut self, dist: LockedPythonDistribution) {
+        self.python_distributions
+            .retain(|d| d.target_triple != dist.target_triple);
+        self.python_distributions.push(dist);
+        self.python_distributions.sort();
+    }
+
+    fn add_package(&mut self, package: LockedPackage) {
+        self.packages
+            .retain(|p| !(p.target_triple == package.target_triple && p.name == package.name));
+        self.packages.push(package);
+        self.packages.sort();
+    }
+
+    /// Replace the entries for a target triple with those of another lock file.
+    fn merge(&mut self, target_triple: &str, other: &LockFile) {
+        self.packages.retain(|p| p.target_triple != target_triple);
+
+        for dist in &other.python_distributions {
+            self.add_python_distribution(dist.clone());
+        }
+        for package in &other.packages {
+            self.add_package(package.clone());
+        }
+    }
+}
+
+/// Whether a `RECORD` entry's content depends on the installation.
+///
+/// `direct_url.json` records the URL or path a package was installed from.
+/// Files installed outside the package root (e.g. scripts with a `#!` line
+/// naming the interpreter) and bytecode (which embeds source paths) depend on
+/// where the package was installed.
+fn is_installation_dependent_record_entry(line: &str) -> bool {
+    // Entries are `path,hash,size`. Paths may contain commas; hashes and sizes
+    // don't.
+    let path = line
+        .rsplitn(3, ',')
+        .last()
+        .unwrap_or(line)
+        .trim_matches('"');
+
+    path.ends_with(".dist-info/direct_url.json")
+        || path.starts_with("../")
+        || path.ends_with(".pyc")
+}
+
+/// Compute the digest of a package from the content of its `RECORD` file.
+///
+/// Entries whose content depends on the installation are ignored, so the
+/// same package has the same digest on every machine.
+pub fn record_digest(record: &[u8]) -> Result<String> {
+    let record = std::str::from_utf8(record).context("RECORD is not valid UTF-8")?;
+
+    let mut lines = record
+        .lines()
+        .filter(|line| !line.is_empty() && !is_installation_dependent_record_entry(line))
+        .collect::<Vec<_>>();
+    lines.sort_unstable();
+
+    let mut hasher = sha2::Sha256::new();
+    for line in lines {
+        hasher.update(line.as_bytes());
+        hasher.update(b"\n");
+    }
+
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Derive lock file entries for package distributions within resources.
+pub fn locked_packages_from_resources(
+    target_triple: &str,
+    resources: &[PythonResource],
+) -> Result<Vec<LockedPackage>> {
+    let mut packages = BTreeMap::new();
+
+    for resource in resources {
+        if let PythonResource::PackageDistributionResource(r) = resource {
+            if r.name == "RECORD" {
+                packages.insert(
+                    normalize_project_name(&r.package),
+                    LockedPackage {
+                        target_triple: target_triple.to_string(),
+                        name: normalize_project_name(&r.package),
+                        version: r.version.clone(),
+                        sha256: record_digest(&r.data.resolve_content()?)
+                            .with_context(|| format!("hashing RECORD of {}", r.package))?,
+                    },
+                );
+            }
+        }
+    }
+
+    Ok(packages.into_values().collect())
+}
+
+/// Tracks lock file state during evaluation of a configuration file.
+#[derive(Debug)]
+pub struct LockFileContext {
+    path: PathBuf,
+    target_triple: String,
+    mode: LockFileMode,
+    /// The lock file that existed when evaluation started.
+    existing: Option<LockFile>,
+    /// Content resolved during this evaluation.
+    resolved: Mutex<LockFile>,
+}
+
+impl LockFileContext {
+    /// Construct an instance for evaluating a configuration file for a target triple.
+    ///
+    /// An existing lock file next to the configuration file is loaded.
+    pub fn new(config_path: &Path, target_triple: &str, mode: LockFileMode) -> Result<Self> {
+        let path = config_path
+            .parent()
+            .ok_or_else(|| anyhow!("unable to resolve parent directory of config file"))?
+            .join(LOCK_FILE_NAME);
+
+        let existing = if !path.exists() {
+            None
+        } else if mode == LockFileMode::Enforce {
+            Some(LockFile::from_path(&path)?)
+        } else {
+            match LockFile::from_path(&path) {
+                Ok(lock) => Some(lock),
+                Err(e) => {
+                    warn!("replacing unusable lock file: {:?}", e);
+                    None
+                }
+            }
+        };
+
+        Ok(Self {
+            path,
+            target_triple: target_triple.to_string(),
+            mode,
+            existing,
+            resolved: Mutex::new(LockFile::default()),
+        })
+    }
+
+    /// Path of the lock file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The lock file being enforced, if any.
+    pub fn locked(&self) -> Option<&LockFile> {
+        if self.mode == LockFileMode::Enforce {
+            self.existing.as_ref()
+        } else {
+            None
+        }
+    }
+
+    fn mismatch(&self, message: String) -> anyhow::Error {
+        anyhow!(
+            "resolved content does not match lock file {}: {}; run `pyoxidizer lock` to update it",
+            self.path.display(),
+            message
+        )
+    }
+
+    /// Record a resolved Python distribution, verifying it against the lock file.
+    pub fn record_python_distribution(
+        &self,
+        location: &PythonDistributionLocation,
+        dist: &dyn PythonDistribution,
+    ) -> Result<()> {
+        let (location, sha256) = match location {
+            PythonDistributionLocation::Local { local_path, sha256 } => (local_path, sha256),
+            PythonDistributionLocation::Url { url, sha256 } => (url, sha256),
+        };
+
+        self.record_locked_python_distribution(LockedPythonDistribution {
+            target_triple: dist.target_triple().to_string(),
+            python_version: dist.python_version().to_string(),
+            location: location.clone(),
+            sha256: sha256.clone(),
+        })
+    }
+
+    fn record_locked_python_distribution(&self, entry: LockedPythonDistribution) -> Result<()> {
+        if let Some(locked) = self.locked() {
+            match locked.python_distribution(&entry.target_triple) {
+                Some(existing) if existing.sha256 == entry.sha256 => {}
+                Some(existing) => {
+                    return Err(self.mismatch(format!(
+                        "Python distribution for {} has SHA-256 {}; expected {}",
+                        entry.target_triple, entry.sha256, existing.sha256
+                    )));
+                }
+                None => {
+                    return Err(self.mismatch(format!(
+                        "no Python distribution locked for {}",
+                        entry.target_triple
+                    )));
+                }
+            }
+        }
+
+        self.resolved
+            .lock()
+            .map_err(|e| anyhow!("unable to lock: {}", e))?
+            .add_python_distribution(entry);
+
+        Ok(())
+    }
+
+    /// Record resolved packages, verifying them against the lock file.
+    pub fn record_packages(&self, packages: Vec<LockedPackage>) -> Result<()> {
+        if let Some(locked) = self.locked() {
+            for package in &packages {
+                match locked.package(&package.target_triple, &package.name) {
+                    Some(existing) if existing == package => {}
+                    Some(existing) => {
+                        return Err(self.mismatch(format!(
+                            "{} {} (sha256={}) resolved; expected {} (sha256={})",
+                            package.name,
+                            package.version,
+                            package.sha256,
+                            existing.version,
+                            existing.sha256
+                        )));
+                    }
+                    None => {
+                        return Err(self.mismatch(format!(
+                            "{} {} is not locked for {}",
+                            package.name, package.version, package.target_triple
+                        )));
+                    }
+                }
+            }
+        }
+
+        let mut resolved = self
+            .resolved
+            .lock()
+            .map_err(|e| anyhow!("unable to lock: {}", e))?;
+
+        for package in packages {
+            resolved.add_package(package);
+        }
+
+        Ok(())
+    }
+
+    /// Write a `pip` constraints file pinning locked packages into a directory.
+    ///
+    /// Returns `None` if no lock file is being enforced.
+    pub fn write_pip_constraints(&self, dir: &Path) -> Result<Option<PathBuf>> {
+        if let Some(locked) = self.locked() {
+            let path = dir.join("pyoxidizer-lock-constraints.txt");
+            std::fs::write(&path, locked.pip_constraints(&self.target_triple))
+                .with_context(|| format!("writing {}", path.display()))?;
+
+            Ok(Some(path))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Write the lock file if it is being updated.
+    ///
+    /// Content resolved during evaluation replaces the entries for the
+    /// evaluated target triple in the existing lock file.
+    pub fn finish(&self) -> Result<()> {
+        if self.mode == LockFileMode::Enforce {
+            return Ok(());
+        }
+
+        let resolved = self
+            .resolved
+            .lock()
+            .map_err(|e| anyhow!("unable to lock: {}", e))?;
+
+        let mut lock = self.existing.clone().unwrap_or_default();
+        lock.merge(&self.target_triple, &resolved);
+
+        lock.write_to_path(&self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINUX: &str = "x86_64-unknown-linux-gnu";
+    const MACOS: &str = "x86_64-apple-darwin";
+
+    fn package(target_triple: &str, name: &str, version: &str, sha256: &str) -> LockedPackage {
+        LockedPackage {
+            target_triple: target_triple.to_string(),
+            name: name.to_string(),
+            version: version.to_string(),
+            sha256: sha256.to_string(),
+        }
+    }
+
+    fn python_distribution(target_triple: &str, sha256: &str) -> LockedPythonDistribution {
+        LockedPythonDistribution {
+            target_triple: target_triple.to_string(),
+            python_version: "3.10.4".to_string(),
+            location: format!("https://example.com/{}.tar.zst", target_triple),
+            sha256: sha256.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("pyoxidizer-test")
+            .tempdir()?;
+        let config_path = temp_dir.path().join("pyoxidizer.bzl");
+        let lock_path = temp_dir.path().join(LOCK_FILE_NAME);
+
+        // Builds don't write a lock file.
+        let context = LockFileContext::new(&config_path, LINUX, LockFileMode::Enforce)?;
+        assert!(context.locked().is_none());
+        context.record_packages(vec![package(LINUX, "foo", "1.0", "aa")])?;
+        context.finish()?;
+        assert!(!lock_path.exists());
+
+        let context = LockFileContext::new(&config_path, LINUX, LockFileMode::Update)?;
+        assert!(context.locked().is_none());
+        context.record_packages(vec![
+            package(LINUX, "foo", "1.0", "aa"),
+            package(LINUX, "bar", "2.0", "bb"),
+        ])?;
+        context.finish()?;
+
+        let lock = LockFile::from_path(&lock_path)?;
+        assert_eq!(
+            lock.packages,
+            vec![
+                package(LINUX, "bar", "2.0", "bb"),
+                package(LINUX, "foo", "1.0", "aa")
+            ]
+        );
+        assert_eq!(lock.pip_constraints(LINUX), "bar==2.0\nfoo==1.0\n");
+
+        let context = LockFileContext::new(&config_path, LINUX, LockFileMode::Enforce)?;
+        assert!(context.locked().is_some());
+        context.record_packages(vec![package(LINUX, "foo", "1.0", "aa")])?;
+        assert!(context
+            .record_packages(vec![package(LINUX, "foo", "1.1", "aa")])
+            .is_err());
+        assert!(context
+            .record_packages(vec![package(LINUX, "foo", "1.0", "cc")])
+            .is_err());
+        assert!(context
+            .record_packages(vec![package(LINUX, "baz", "1.0", "dd")])
+            .is_err());
+
+        let constraints = context.write_pip_constraints(temp_dir.path())?.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(constraints)?,
+            "bar==2.0\nfoo==1.0\n"
+        );
+
+        // Updating doesn't verify against the existing lock file.
+        let context = LockFileContext::new(&config_path, LINUX, LockFileMode::Update)?;
+        assert!(context.locked().is_none());
+        context.record_packages(vec![package(LINUX, "foo", "1.1", "ee")])?;
+        context.finish()?;
+
+        let lock = LockFile::from_path(&lock_path)?;
+        assert_eq!(lock.packages, vec![package(LINUX, "foo", "1.1", "ee")]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_multiple_target_triples() -> Result<()> {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("pyoxidizer-test")
+            .tempdir()?;
+        let config_path = temp_dir.path().join("pyoxidizer.bzl");
+        let lock_path = temp_dir.path().join(LOCK_FILE_NAME);
+
+        for (triple, sha256) in [(LINUX, "aa"), (MACOS, "bb")] {
+            let context = LockFileContext::new(&config_path, triple, LockFileMode::Update)?;
+            context.record_locked_python_distribution(python_distribution(triple, sha256))?;
+            context.record_packages(vec![package(triple, "foo", "1.0", sha256)])?;
+            context.finish()?;
+        }
+
+        let lock = LockFile::from_path(&lock_path)?;
+        assert_eq!(
+            lock.python_distributions,
+            vec![
+                python_distribution(MACOS, "bb"),
+                python_distribution(LINUX, "aa")
+            ]
+        );
+        assert_eq!(
+            lock.packages,
+            vec![
+                package(MACOS, "foo", "1.0", "bb"),
+                package(LINUX, "foo", "1.0", "aa")
+            ]
+        );
+
+        for (triple, sha256) in [(LINUX, "aa"), (MACOS, "bb")] {
+            let context = LockFileContext::new(&config_path, triple, LockFileMode::Enforce)?;
+            context.record_locked_python_distribution(python_distribution(triple, sha256))?;
+            context.record_packages(vec![package(triple, "foo", "1.0", sha256)])?;
+        }
+
+        let context = LockFileContext::new(&config_path, MACOS, LockFileMode::Enforce)?;
+        assert!(context
+            .record_packages(vec![package(MACOS, "foo", "1.0", "aa")])
+            .is_err());
+
+        // Updating one triple preserves the others.
+        let context = LockFileContext::new(&config_path, LINUX, LockFileMode::Update)?;
+        context.record_locked_python_distribution(python_distribution(LINUX, "cc"))?;
+        context.finish()?;
+
+        let lock = LockFile::from_path(&lock_path)?;
+        assert_eq!(
+            lock.python_distributions,
+            vec![
+                python_distribution(MACOS, "bb"),
+                python_distribution(LINUX, "cc")
+            ]
+        );
+        assert_eq!(lock.packages, vec![package(MACOS, "foo", "1.0", "bb")]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_digest() -> Result<()> {
+        let record = b"foo/__init__.py,sha256=abc,10\n\
+            foo-1.0.dist-info/METADATA,sha256=def,20\n\
+            foo-1.0.dist-info/RECORD,,\n";
+
+        let digest = record_digest(record)?;
+
+        let other = b"foo-1.0.dist-info/RECORD,,\n\
+            foo-1.0.dist-info/direct_url.json,sha256=xyz,30\n\
+            foo/__pycache__/__init__.cpython-310.pyc,sha256=ghi,40\n\
+            ../../bin/foo,sha256=jkl,50\n\
+            foo/__init__.py,sha256=abc,10\n\
+            foo-1.0.dist-info/METADATA,sha256=def,20\n";
+
+        assert_eq!(record_digest(other)?, digest);
+        assert_ne!(record_digest(b"foo/__init__.py,sha256=abd,10\n")?, digest);
+
+        Ok(())
+    }
+}
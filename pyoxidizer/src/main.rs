@@ -27,15 +27,20 @@ is implemented in C. Rust is simply a tool to achieve an end goal, albeit
 a rather effective and powerful tool.
 */
 
+mod analyze;
+mod build_cache;
 mod cli;
 mod default_python_distributions;
 mod environment;
 mod licensing;
+mod lock_file;
+mod packed_resources;
 mod project_building;
 mod project_layout;
 mod projectmgmt;
 mod py_packaging;
 mod python_distributions;
+mod sbom;
 pub mod starlark;
 #[cfg(test)]
 mod testutil;
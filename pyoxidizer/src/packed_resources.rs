@@ -0,0 +1,662 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Inspection of packed resources data.
+
+This module implements the `pyoxidizer resources` commands, which print
+information about the resources in a packed resources file, extract their
+data, and compare two packed resources files.
+*/
+
+use {
+    anyhow::{anyhow, Context, Result},
+    python_packed_resources::Resource,
+    std::{
+        borrow::Cow,
+        collections::{BTreeMap, BTreeSet},
+        io::Write,
+        path::{Component, Path, PathBuf},
+    },
+};
+
+/// Where the data for a field of a resource lives.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum FieldValue<'a> {
+    /// Data is stored in the packed resources data.
+    InMemory(&'a [u8]),
+    /// Data is stored in a file at a path relative to the resources data.
+    RelativePath(Cow<'a, str>),
+    /// A name referenced by the resource.
+    Name,
+}
+
+impl<'a> FieldValue<'a> {
+    fn in_memory_size(&self) -> usize {
+        match self {
+            Self::InMemory(data) => data.len(),
+            Self::RelativePath(_) | Self::Name => 0,
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Self::InMemory(data) => format!("{} bytes (in-memory)", data.len()),
+            Self::RelativePath(path) => format!("{} (relative path)", path),
+            Self::Name => "(name)".to_string(),
+        }
+    }
+}
+
+/// Obtain the names of boolean flags set on a resource.
+fn resource_flags(resource: &Resource<u8>) -> Vec<&'static str> {
+    [
+        (resource.is_python_module, "python-module"),
+        (resource.is_python_package, "python-package"),
+        (
+            resource.is_python_namespace_package,
+            "python-namespace-package",
+        ),
+        (
+            resource.is_python_builtin_extension_module,
+            "python-builtin-extension-module",
+        ),
+        (resource.is_python_frozen_module, "python-frozen-module"),
+        (
+            resource.is_python_extension_module,
+            "python-extension-module",
+        ),
+        (resource.is_shared_library, "shared-library"),
+        (resource.is_utf8_filename_data, "utf8-filename-data"),
+        (resource.file_executable, "file-executable"),
+    ]
+    .into_iter()
+    .filter_map(|(set, name)| if set { Some(name) } else { None })
+    .collect()
+}
+
+/// Obtain the data-bearing fields of a resource, keyed by a descriptive label.
+///
+/// Fields holding maps get an entry for every key in the map. e.g.
+/// `in_memory_package_resources/foo.txt`.
+pub(crate) fn resource_fields<'a>(resource: &'a Resource<u8>) -> BTreeMap<String, FieldValue<'a>> {
+    let mut fields = BTreeMap::new();
+
+    let mut add_data = |label: &str, value: &'a Option<Cow<'a, [u8]>>| {
+        if let Some(data) = value {
+            fields.insert(label.to_string(), FieldValue::InMemory(data.as_ref()));
+        }
+    };
+
+    add_data("in_memory_source", &resource.in_memory_source);
+    add_data("in_memory_bytecode", &resource.in_memory_bytecode);
+    add_data("in_memory_bytecode_opt1", &resource.in_memory_bytecode_opt1);
+    add_data("in_memory_bytecode_opt2", &resource.in_memory_bytecode_opt2);
+    add_data(
+        "in_memory_extension_module_shared_library",
+        &resource.in_memory_extension_module_shared_library,
+    );
+    add_data(
+        "in_memory_shared_library",
+        &resource.in_memory_shared_library,
+    );
+    add_data("file_data_embedded", &resource.file_data_embedded);
+
+    for (label, value) in [
+        (
+            "in_memory_package_resources",
+            &resource.in_memory_package_resources,
+        ),
+        (
+            "in_memory_distribution_resources",
+            &resource.in_memory_distribution_resources,
+        ),
+    ] {
+        if let Some(entries) = value {
+            for (key, data) in entries {
+                fields.insert(
+                    format!("{}/{}", label, key),
+                    FieldValue::InMemory(data.as_ref()),
+                );
+            }
+        }
+    }
+
+    for (label, value) in [
+        (
+            "relative_path_module_source",
+            &resource.relative_path_module_source,
+        ),
+        (
+            "relative_path_module_bytecode",
+            &resource.relative_path_module_bytecode,
+        ),
+        (
+            "relative_path_module_bytecode_opt1",
+            &resource.relative_path_module_bytecode_opt1,
+        ),
+        (
+            "relative_path_module_bytecode_opt2",
+            &resource.relative_path_module_bytecode_opt2,
+        ),
+        (
+            "relative_path_extension_module_shared_library",
+            &resource.relative_path_extension_module_shared_library,
+        ),
+    ] {
+        if let Some(path) = value {
+            fields.insert(
+                label.to_string(),
+                FieldValue::RelativePath(path.to_string_lossy()),
+            );
+        }
+    }
+
+    for (label, value) in [
+        (
+            "relative_path_package_resources",
+            &resource.relative_path_package_resources,
+        ),
+        (
+            "relative_path_distribution_resources",
+            &resource.relative_path_distribution_resources,
+        ),
+    ] {
+        if let Some(entries) = value {
+            for (key, path) in entries {
+                fields.insert(
+                    format!("{}/{}", label, key),
+                    FieldValue::RelativePath(path.to_string_lossy()),
+                );
+            }
+        }
+    }
+
+    if let Some(path) = &resource.file_data_utf8_relative_path {
+        fields.insert(
+            "file_data_utf8_relative_path".to_string(),
+            FieldValue::RelativePath(Cow::Borrowed(path.as_ref())),
+        );
+    }
+
+    if let Some(names) = &resource.shared_library_dependency_names {
+        for name in names {
+            fields.insert(
+                format!("shared_library_dependency_names/{}", name),
+                FieldValue::Name,
+            );
+        }
+    }
+
+    fields
+}
+
+/// Total size of in-memory data held by a resource.
+pub(crate) fn resource_in_memory_size(resource: &Resource<u8>) -> usize {
+    resource_fields(resource)
+        .values()
+        .map(|value| value.in_memory_size())
+        .sum()
+}
+
+/// Parse packed resources data into resources, sorted by name.
+fn load_resources(data: &[u8]) -> Result<Vec<Resource<'_, u8>>> {
+    let mut resources = python_packed_resources::load_resources(data)
+        .map_err(|e| anyhow!("error loading packed resources data: {}", e))?
+        .collect::<Result<Vec<_>, &'static str>>()
+        .map_err(|e| anyhow!("error parsing resource: {}", e))?;
+
+    resources.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(resources)
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>> {
+    std::fs::read(path).with_context(|| format!("reading {}", path.display()))
+}
+
+/// Print a one line summary of every resource in packed resources data.
+pub fn write_list(data: &[u8], dest: &mut impl Write) -> Result<()> {
+    let resources = load_resources(data)?;
+
+    let mut total = 0;
+
+    for resource in &resources {
+        let size = resource_in_memory_size(resource);
+        total += size;
+
+        writeln!(
+            dest,
+            "{:>10} {} [{}]",
+            size,
+            resource.name,
+            resource_flags(resource).join(", ")
+        )?;
+    }
+
+    writeln!(
+        dest,
+        "{} resources; {} bytes of in-memory data; {} bytes total",
+        resources.len(),
+        total,
+        data.len()
+    )?;
+
+    Ok(())
+}
+
+/// Print details about a single named resource in packed resources data.
+pub fn write_show(data: &[u8], name: &str, dest: &mut impl Write) -> Result<()> {
+    let resources = load_resources(data)?;
+
+    let resource = resources
+        .iter()
+        .find(|r| r.name == name)
+        .ok_or_else(|| anyhow!("resource {} not found", name))?;
+
+    writeln!(dest, "name: {}", resource.name)?;
+    writeln!(dest, "flags: {}", resource_flags(resource).join(", "))?;
+    if let Some(digest) = &resource.content_digest_sha256 {
+        writeln!(dest, "content digest (SHA-256): {}", hex::encode(digest))?;
+    }
+    if let Some(path) = &resource.original_source_path {
+        writeln!(dest, "original source: {}", path)?;
+    }
+    if let Some(digest) = &resource.original_source_digest_sha256 {
+        writeln!(
+            dest,
+            "original source digest (SHA-256): {}",
+            hex::encode(digest)
+        )?;
+    }
+    writeln!(
+        dest,
+        "in-memory data: {} bytes",
+        resource_in_memory_size(resource)
+    )?;
+
+    for (label, value) in resource_fields(resource) {
+        writeln!(dest, "{}: {}", label, value.describe())?;
+    }
+
+    Ok(())
+}
+
+/// Resolve a relative filesystem path from a label, refusing paths escaping the destination.
+fn safe_relative_path(value: &str) -> Result<PathBuf> {
+    let path = PathBuf::from(value);
+
+    if path
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        Ok(path)
+    } else {
+        Err(anyhow!("refusing to extract to unsafe path: {}", value))
+    }
+}
+
+/// Extract in-memory data from packed resources data to a directory.
+///
+/// Each resource is extracted to a directory named after the resource.
+/// Each in-memory field is written to a file named after the field in that
+/// directory.
+///
+/// If `names` is non-empty, only the resources having these names are
+/// extracted.
+pub fn extract(data: &[u8], dest_dir: &Path, names: &[&str], log: &mut impl Write) -> Result<()> {
+    let resources = load_resources(data)?;
+
+    for name in names {
+        if !resources.iter().any(|r| r.name == *name) {
+            return Err(anyhow!("resource {} not found", name));
+        }
+    }
+
+    for resource in &resources {
+        if !names.is_empty() && !names.contains(&resource.name.as_ref()) {
+            continue;
+        }
+
+        let resource_dir = dest_dir.join(safe_relative_path(&resource.name)?);
+
+        for (label, value) in resource_fields(resource) {
+            if let FieldValue::InMemory(field_data) = value {
+                let path = resource_dir.join(safe_relative_path(&label)?);
+
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)
+                        .with_context(|| format!("creating {}", parent.display()))?;
+                }
+
+                std::fs::write(&path, field_data)
+                    .with_context(|| format!("writing {}", path.display()))?;
+                writeln!(log, "wrote {}", path.display())?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Print the differences between two packed resources data.
+///
+/// Resources are compared by name. For changed resources, the changed flags
+/// and fields are printed. A summary of how the size of the data changed
+/// is printed at the end.
+pub fn write_diff(a: &[u8], b: &[u8], dest: &mut impl Write) -> Result<()> {
+    let a_resources = load_resources(a)?;
+    let b_resources = load_resources(b)?;
+
+    let a_map = a_resources
+        .iter()
+        .map(|r| (r.name.as_ref(), r))
+        .collect::<BTreeMap<_, _>>();
+    let b_map = b_resources
+        .iter()
+        .map(|r| (r.name.as_ref(), r))
+        .collect::<BTreeMap<_, _>>();
+
+    let names = a_map
+        .keys()
+        .chain(b_map.keys())
+        .copied()
+        .collect::<BTreeSet<_>>();
+
+    let (mut added, mut removed, mut changed) = (0, 0, 0);
+    let (mut added_size, mut removed_size, mut changed_delta) = (0, 0, 0i64);
+
+    for name in names {
+        match (a_map.get(name), b_map.get(name)) {
+            (None, Some(new)) => {
+                let size = resource_in_memory_size(new);
+                added += 1;
+                added_size += size;
+                writeln!(dest, "+ {} ({} bytes)", name, size)?;
+            }
+            (Some(old), None) => {
+                let size = resource_in_memory_size(old);
+                removed += 1;
+                removed_size += size;
+                writeln!(dest, "- {} ({} bytes)", name, size)?;
+            }
+            (Some(old), Some(new)) => {
+                let old_fields = resource_fields(old);
+                let new_fields = resource_fields(new);
+                let old_flags = resource_flags(old);
+                let new_flags = resource_flags(new);
+
+                if old_fields == new_fields && old_flags == new_flags {
+                    continue;
+                }
+
+                let delta =
+                    resource_in_memory_size(new) as i64 - resource_in_memory_size(old) as i64;
+                changed += 1;
+                changed_delta += delta;
+                writeln!(dest, "~ {} ({:+} bytes)", name, delta)?;
+
+                if old_flags != new_flags {
+                    writeln!(
+                        dest,
+                        "    flags: [{}] -> [{}]",
+                        old_flags.join(", "),
+                        new_flags.join(", ")
+                    )?;
+                }
+
+                let labels = old_fields
+                    .keys()
+                    .chain(new_fields.keys())
+                    .collect::<BTreeSet<_>>();
+
+                for label in labels {
+                    match (old_fields.get(label), new_fields.get(label)) {
+                        (None, Some(value)) => {
+                            writeln!(dest, "    + {}: {}", label, value.describe())?;
+                        }
+                        (Some(value), None) => {
+                            writeln!(dest, "    - {}: {}", label, value.describe())?;
+                        }
+                        (Some(old_value), Some(new_value)) if old_value != new_value => {
+                            writeln!(
+                                dest,
+                                "    ~ {}: {} -> {}",
+                                label,
+                                old_value.describe(),
+                                new_value.describe()
+                            )?;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            (None, None) => {}
+        }
+    }
+
+    writeln!(
+        dest,
+        "{} added ({} bytes); {} removed ({} bytes); {} changed ({:+} bytes)",
+        added, added_size, removed, removed_size, changed, changed_delta
+    )?;
+    writeln!(
+        dest,
+        "total size: {} -> {} bytes ({:+} bytes)",
+        a.len(),
+        b.len(),
+        b.len() as i64 - a.len() as i64
+    )?;
+
+    Ok(())
+}
+
+/// Implements `pyoxidizer resources list`.
+pub fn list(path: &Path) -> Result<()> {
+    write_list(&read_file(path)?, &mut std::io::stdout())
+}
+
+/// Implements `pyoxidizer resources show`.
+pub fn show(path: &Path, name: &str) -> Result<()> {
+    write_show(&read_file(path)?, name, &mut std::io::stdout())
+}
+
+/// Implements `pyoxidizer resources extract`.
+pub fn extract_path(path: &Path, dest_dir: &Path, names: &[&str]) -> Result<()> {
+    extract(&read_file(path)?, dest_dir, names, &mut std::io::stdout())
+}
+
+/// Implements `pyoxidizer resources diff`.
+pub fn diff(a: &Path, b: &Path) -> Result<()> {
+    write_diff(&read_file(a)?, &read_file(b)?, &mut std::io::stdout())
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::collections::HashMap};
+
+    fn packed_data(resources: &[Resource<u8>]) -> Result<Vec<u8>> {
+        let mut data = vec![];
+        python_packed_resources::write_packed_resources_v3(
+            resources,
+            &mut data,
+            &python_packed_resources::PackedResourcesWriteOptions::default(),
+        )?;
+
+        Ok(data)
+    }
+
+    fn test_resources() -> Vec<Resource<'static, u8>> {
+        let mut package_resources = HashMap::new();
+        package_resources.insert(Cow::from("data/foo.txt"), Cow::from(b"foo".to_vec()));
+
+        vec![
+            Resource {
+                name: Cow::from("foo"),
+                is_python_module: true,
+                is_python_package: true,
+                in_memory_source: Some(Cow::from(b"import os".to_vec())),
+                in_memory_package_resources: Some(package_resources),
+                ..Resource::default()
+            },
+            Resource {
+                name: Cow::from("bar"),
+                is_python_module: true,
+                relative_path_module_source: Some(Cow::from(Path::new("lib/bar.py"))),
+                original_source_path: Some(Cow::from("/src/bar.py")),
+                original_source_digest_sha256: Some([0xaa; 32]),
+                ..Resource::default()
+            },
+        ]
+    }
+
+    #[test]
+    fn test_list() -> Result<()> {
+        let data = packed_data(&test_resources())?;
+
+        let mut out = vec![];
+        write_list(&data, &mut out)?;
+
+        assert_eq!(
+            String::from_utf8(out)?,
+            format!(
+                "         0 bar [python-module]\n\
+                 \x20       12 foo [python-module, python-package]\n\
+                 2 resources; 12 bytes of in-memory data; {} bytes total\n",
+                data.len()
+            )
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_show() -> Result<()> {
+        let data = packed_data(&test_resources())?;
+
+        let mut out = vec![];
+        write_show(&data, "foo", &mut out)?;
+
+        assert_eq!(
+            String::from_utf8(out)?,
+            "name: foo\n\
+             flags: python-module, python-package\n\
+             in-memory data: 12 bytes\n\
+             in_memory_package_resources/data/foo.txt: 3 bytes (in-memory)\n\
+             in_memory_source: 9 bytes (in-memory)\n"
+        );
+
+        let mut out = vec![];
+        write_show(&data, "bar", &mut out)?;
+        let out = String::from_utf8(out)?;
+        assert!(out.contains("relative_path_module_source: lib/bar.py (relative path)\n"));
+        assert!(out.contains("original source: /src/bar.py\n"));
+        assert!(out.contains(&format!(
+            "original source digest (SHA-256): {}\n",
+            "aa".repeat(32)
+        )));
+
+        assert!(write_show(&data, "missing", &mut vec![]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract() -> Result<()> {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("pyoxidizer-test")
+            .tempdir()?;
+        let data = packed_data(&test_resources())?;
+
+        extract(&data, temp_dir.path(), &[], &mut vec![])?;
+
+        assert_eq!(
+            std::fs::read(temp_dir.path().join("foo").join("in_memory_source"))?,
+            b"import os"
+        );
+        assert_eq!(
+            std::fs::read(
+                temp_dir
+                    .path()
+                    .join("foo")
+                    .join("in_memory_package_resources")
+                    .join("data")
+                    .join("foo.txt")
+            )?,
+            b"foo"
+        );
+        assert!(!temp_dir.path().join("bar").exists());
+
+        assert!(extract(&data, temp_dir.path(), &["missing"], &mut vec![]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_unsafe_path() -> Result<()> {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("pyoxidizer-test")
+            .tempdir()?;
+        let data = packed_data(&[Resource {
+            name: Cow::from("../escape"),
+            is_utf8_filename_data: true,
+            file_data_embedded: Some(Cow::from(b"data".to_vec())),
+            ..Resource::default()
+        }])?;
+
+        assert!(extract(&data, temp_dir.path(), &[], &mut vec![]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff() -> Result<()> {
+        let a = packed_data(&test_resources())?;
+
+        let mut resources = test_resources();
+        resources[0].in_memory_source = Some(Cow::from(b"import os, sys".to_vec()));
+        resources[0].in_memory_bytecode = Some(Cow::from(b"bytecode".to_vec()));
+        resources.remove(1);
+        resources.push(Resource {
+            name: Cow::from("baz"),
+            is_python_module: true,
+            in_memory_source: Some(Cow::from(b"pass".to_vec())),
+            ..Resource::default()
+        });
+        let b = packed_data(&resources)?;
+
+        let mut out = vec![];
+        write_diff(&a, &b, &mut out)?;
+
+        assert_eq!(
+            String::from_utf8(out)?,
+            format!(
+                "- bar (0 bytes)\n\
+                 + baz (4 bytes)\n\
+                 ~ foo (+13 bytes)\n\
+                 \x20   + in_memory_bytecode: 8 bytes (in-memory)\n\
+                 \x20   ~ in_memory_source: 9 bytes (in-memory) -> 14 bytes (in-memory)\n\
+                 1 added (4 bytes); 1 removed (0 bytes); 1 changed (+13 bytes)\n\
+                 total size: {} -> {} bytes ({:+} bytes)\n",
+                a.len(),
+                b.len(),
+                b.len() as i64 - a.len() as i64
+            )
+        );
+
+        let mut out = vec![];
+        write_diff(&a, &a, &mut out)?;
+        assert_eq!(
+            String::from_utf8(out)?,
+            format!(
+                "0 added (0 bytes); 0 removed (0 bytes); 0 changed (+0 bytes)\n\
+                 total size: {} -> {} bytes (+0 bytes)\n",
+                a.len(),
+                a.len()
+            )
+        );
+
+        Ok(())
+    }
+}
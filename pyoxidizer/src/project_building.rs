@@ -4,6 +4,7 @@
 
 use {
     crate::{
+        build_cache::sync_directory,
         environment::{canonicalize_path, Environment, RustEnvironment},
         licensing::{licenses_from_cargo_manifest, log_licensing_info},
         project_layout::initialize_project,
@@ -12,6 +13,7 @@ use {
             distribution::AppleSdkInfo,
             embedding::{EmbeddedPythonContext, DEFAULT_PYTHON_CONFIG_FILENAME},
         },
+        sbom::rust_crate_components,
         starlark::eval::{EvaluationContext, EvaluationContextBuilder},
     },
     anyhow::{anyhow, Context, Result},
@@ -275,9 +277,12 @@ pub fn build_executable_with_rust_project<'a>(
     let mut embedded_data = exe
         .to_embedded_python_context(env, opt_level)
         .context("obtaining embedded python context")?;
-    embedded_data
+    for (path, status) in embedded_data
         .write_files(artifacts_path)
-        .context("writing embedded python context files")?;
+        .context("writing embedded python context files")?
+    {
+        env.build_cache().record_artifact(path, status)?;
+    }
 
     let build_env = BuildEnvironment::new(
         env,
@@ -379,6 +384,22 @@ pub fn build_executable_with_rust_project<'a>(
         embedded_data.add_licensed_component(component)?;
     }
 
+    // Record the Rust crates that were built in the bill of materials.
+    if embedded_data.sbom_filename.is_some() {
+        let lock_path = project_path.join("Cargo.lock");
+
+        if lock_path.exists() {
+            for component in rust_crate_components(&lock_path, embedded_data.licensing())? {
+                embedded_data.add_sbom_component(component)?;
+            }
+        } else {
+            warn!(
+                "{} not found; omitting Rust crates from software bill of materials",
+                lock_path.display()
+            );
+        }
+    }
+
     // Inform user about licensing info.
     log_licensing_info(embedded_data.licensing());
 
@@ -390,7 +411,10 @@ pub fn build_executable_with_rust_project<'a>(
     })
 }
 
-/// Build a Python executable using a temporary Rust project.
+/// Build a Python executable using a Rust project managed by PyOxidizer.
+///
+/// The Rust project lives in the build cache so Cargo can perform incremental
+/// builds across invocations.
 ///
 /// Returns the binary data constituting the built executable.
 pub fn build_python_executable<'a>(
@@ -406,16 +430,23 @@ pub fn build_python_executable<'a>(
         .context("resolving Rust toolchain")?
         .cargo_exe;
 
+    let cache_path = env.build_cache().project_dir(bin_name, target_triple);
+    let _lock = env.build_cache().lock_directory(&cache_path)?;
+
     let temp_dir = env.temporary_directory("pyoxidizer")?;
 
     // Directory needs to have name of project.
-    let project_path = temp_dir.path().join(bin_name);
-    let build_path = temp_dir.path().join("build");
-    let artifacts_path = temp_dir.path().join("artifacts");
+    let project_path = cache_path.join(bin_name);
+    let build_path = cache_path.join("build");
+    let artifacts_path = cache_path.join("artifacts");
+
+    // Initialize a fresh project then copy over changed files. This preserves
+    // the modification times of unchanged files so Cargo doesn't rebuild.
+    let init_path = temp_dir.path().join(bin_name);
 
     initialize_project(
         &env.pyoxidizer_source,
-        &project_path,
+        &init_path,
         &cargo_exe,
         None,
         &[],
@@ -423,6 +454,12 @@ pub fn build_python_executable<'a>(
     )
     .context("initializing project")?;
 
+    for (path, status) in sync_directory(&init_path, &project_path, &[".git", ".gitignore"])
+        .context("synchronizing project")?
+    {
+        env.build_cache().record_artifact(path, status)?;
+    }
+
     let mut build = build_executable_with_rust_project(
         env,
         &project_path,
@@ -442,7 +479,7 @@ pub fn build_python_executable<'a>(
     )
     .context("building executable with Rust project")?;
 
-    // Blank out the path since it is in the temporary directory.
+    // Blank out the path since it is in the build cache.
     build.exe_path = None;
 
     temp_dir.close().context("closing temporary directory")?;
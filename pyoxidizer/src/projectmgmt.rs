@@ -8,6 +8,7 @@ use {
     crate::{
         environment::{canonicalize_path, default_target_triple, Environment, PyOxidizerSource},
         licensing::{licenses_from_cargo_manifest, log_licensing_info},
+        lock_file::{LockFileContext, LockFileMode},
         project_building::find_pyoxidizer_config_file_env,
         project_layout::{initialize_project, write_new_pyoxidizer_config_file},
         py_packaging::{
@@ -22,6 +23,7 @@ use {
         starlark::eval::EvaluationContextBuilder,
     },
     anyhow::{anyhow, Context, Result},
+    log::{info, warn},
     python_packaging::licensing::LicenseFlavor,
     python_packaging::{
         filesystem_scanning::find_python_resources,
@@ -34,6 +36,7 @@ use {
         fs::create_dir_all,
         io::{Cursor, Read},
         path::{Path, PathBuf},
+        sync::Arc,
     },
     tugger_file_manifest::{FileData, FileManifest},
 };
@@ -105,6 +108,9 @@ pub fn list_targets(env: &Environment, project_path: &Path) -> Result<()> {
 ///
 /// This is a glorified wrapper around `cargo build`. Our goal is to get the
 /// output from repackaging to give the user something for debugging.
+///
+/// Resolved content is verified against the project's lock file, if it has
+/// one.
 #[allow(clippy::too_many_arguments)]
 pub fn build(
     env: &Environment,
@@ -114,6 +120,56 @@ pub fn build(
     extra_vars: HashMap<String, Option<String>>,
     release: bool,
     verbose: bool,
+) -> Result<()> {
+    build_with_lock_file(
+        env,
+        project_path,
+        target_triple,
+        resolve_targets,
+        extra_vars,
+        release,
+        verbose,
+        LockFileMode::Enforce,
+    )
+}
+
+/// Build a PyOxidizer enabled project and update its lock file.
+///
+/// Python distributions and packages may resolve to new content. The lock
+/// file entries for the target triple are replaced with the resolved content.
+/// Entries for other target triples are preserved.
+#[allow(clippy::too_many_arguments)]
+pub fn lock(
+    env: &Environment,
+    project_path: &Path,
+    target_triple: Option<&str>,
+    resolve_targets: Option<Vec<String>>,
+    extra_vars: HashMap<String, Option<String>>,
+    release: bool,
+    verbose: bool,
+) -> Result<()> {
+    build_with_lock_file(
+        env,
+        project_path,
+        target_triple,
+        resolve_targets,
+        extra_vars,
+        release,
+        verbose,
+        LockFileMode::Update,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn build_with_lock_file(
+    env: &Environment,
+    project_path: &Path,
+    target_triple: Option<&str>,
+    resolve_targets: Option<Vec<String>>,
+    extra_vars: HashMap<String, Option<String>>,
+    release: bool,
+    verbose: bool,
+    lock_file_mode: LockFileMode,
 ) -> Result<()> {
     let config_path = find_pyoxidizer_config_file_env(project_path).ok_or_else(|| {
         anyhow!(
@@ -123,11 +179,18 @@ pub fn build(
     })?;
     let target_triple = resolve_target(target_triple)?;
 
+    let lock_file = Arc::new(LockFileContext::new(
+        &config_path,
+        &target_triple,
+        lock_file_mode,
+    )?);
+
     let mut context = EvaluationContextBuilder::new(env, config_path.clone(), target_triple)
         .extra_vars(extra_vars)
         .release(release)
         .verbose(verbose)
         .resolve_targets_optional(resolve_targets)
+        .lock_file(lock_file.clone())
         .into_context()?;
 
     context.evaluate_file(&config_path)?;
@@ -136,7 +199,17 @@ pub fn build(
         context.build_resolved_target(&target)?;
     }
 
-    Ok(())
+    if env.build_cache().explain() {
+        for event in env.build_cache().events()? {
+            warn!("{}", event);
+        }
+    }
+
+    if lock_file_mode == LockFileMode::Update {
+        info!("writing lock file {}", lock_file.path().display());
+    }
+
+    lock_file.finish()
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -158,11 +231,18 @@ pub fn run(
     })?;
     let target_triple = resolve_target(target_triple)?;
 
+    let lock_file = Arc::new(LockFileContext::new(
+        &config_path,
+        &target_triple,
+        LockFileMode::Enforce,
+    )?);
+
     let mut context = EvaluationContextBuilder::new(env, config_path.clone(), target_triple)
         .extra_vars(extra_vars)
         .release(release)
         .verbose(verbose)
         .resolve_target_optional(target)
+        .lock_file(lock_file)
         .into_context()?;
 
     context.evaluate_file(&config_path)?;
@@ -23,6 +23,7 @@ use {
             AddResourceAction, PrePackagedResource, PythonResourceAddCollectionContext,
         },
     },
+    python_packed_resources::BlobCompression,
     std::{collections::HashMap, path::Path, sync::Arc},
     tugger_file_manifest::File,
     tugger_windows::VcRedistributablePlatform,
@@ -229,12 +230,51 @@ pub trait PythonBinaryBuilder {
     /// Set the path of a filename to write containing a licensing report.
     fn set_licenses_filename(&mut self, value: Option<String>);
 
+    /// Obtain the filename to write a software bill of materials to.
+    fn sbom_filename(&self) -> Option<&str>;
+
+    /// Set the filename to write a software bill of materials to.
+    fn set_sbom_filename(&mut self, value: Option<String>);
+
     /// How packed Python resources will be loaded by the binary.
     fn packed_resources_load_mode(&self) -> &PackedResourcesLoadMode;
 
     /// Set how packed Python resources will be loaded by the binary.
     fn set_packed_resources_load_mode(&mut self, load_mode: PackedResourcesLoadMode);
 
+    /// Compression applied to data in packed Python resources.
+    fn packed_resources_compression(&self) -> BlobCompression;
+
+    /// Set the compression applied to data in packed Python resources.
+    fn set_packed_resources_compression(&mut self, compression: BlobCompression);
+
+    /// Whether packed Python resources contain a name index.
+    fn packed_resources_name_index(&self) -> bool;
+
+    /// Set whether packed Python resources contain a name index.
+    ///
+    /// A name index allows resources to be loaded on demand at run-time
+    /// instead of all being loaded when the importer is initialized.
+    fn set_packed_resources_name_index(&mut self, value: bool);
+
+    /// Whether packed Python resources contain source maps.
+    fn packed_resources_source_map(&self) -> bool;
+
+    /// Set whether packed Python resources contain source maps.
+    ///
+    /// Source maps record the path and digest of the original source file of
+    /// each Python module so embedded bytecode can be mapped back to it.
+    fn set_packed_resources_source_map(&mut self, value: bool);
+
+    /// Key used to encrypt packed Python resources.
+    fn packed_resources_encryption_key(&self) -> Option<&[u8; 32]>;
+
+    /// Set the key used to encrypt packed Python resources.
+    ///
+    /// If set, blob sections holding resource content are encrypted and the
+    /// key is embedded in the binary so the data can be decrypted at run-time.
+    fn set_packed_resources_encryption_key(&mut self, key: Option<[u8; 32]>);
+
     /// Obtain an iterator over all resource entries that will be embedded in the binary.
     ///
     /// This likely does not return extension modules that are statically linked
@@ -275,6 +315,15 @@ pub trait PythonBinaryBuilder {
         extra_envs: &HashMap<String, String>,
     ) -> Result<Vec<PythonResource>>;
 
+    /// Resolves requirements against a directory of wheels without running `pip`.
+    ///
+    /// Returns resources discovered from the selected wheels.
+    fn install_wheels(
+        &mut self,
+        wheelhouse: &Path,
+        requirements: &[String],
+    ) -> Result<Vec<PythonResource>>;
+
     /// Reads Python resources from the filesystem.
     fn read_package_root(
         &mut self,
@@ -11,18 +11,20 @@ use {
     itertools::Itertools,
     python_packaging::{
         interpreter::{
-            Allocator, BytesWarning, CheckHashPycsMode, CoerceCLocale, MemoryAllocatorBackend,
-            MultiprocessingStartMethod, PythonInterpreterConfig, PythonInterpreterProfile,
-            TerminfoResolution,
+            Allocator, BytesWarning, CheckHashPycsMode, CoerceCLocale, ImportProfileFormat,
+            MemoryAllocatorBackend, MultiprocessingStartMethod, PythonInterpreterConfig,
+            PythonInterpreterProfile, TerminfoResolution,
         },
         resource::BytecodeOptimizationLevel,
     },
-    std::{
-        io::Write,
-        path::{Path, PathBuf},
-    },
+    std::path::{Path, PathBuf},
 };
 
+/// Marker preceding the description of the interpreter configuration embedded in binaries.
+///
+/// The description is terminated by a NUL byte.
+pub const INTERPRETER_CONFIG_MARKER: &str = "PYOXIDIZER_INTERPRETER_CONFIG\0";
+
 /// Determine the default memory allocator for a target triple.
 pub fn default_memory_allocator(target_triple: &str) -> MemoryAllocatorBackend {
     // Jemalloc doesn't work on Windows.
@@ -64,6 +66,20 @@ fn optional_pathbuf_to_string(value: &Option<PathBuf>) -> String {
     }
 }
 
+fn memory_allocator_backend_to_string(value: &MemoryAllocatorBackend) -> String {
+    match value {
+        MemoryAllocatorBackend::Jemalloc => "pyembed::MemoryAllocatorBackend::Jemalloc".to_string(),
+        MemoryAllocatorBackend::Mimalloc => "pyembed::MemoryAllocatorBackend::Mimalloc".to_string(),
+        MemoryAllocatorBackend::Snmalloc => "pyembed::MemoryAllocatorBackend::Snmalloc".to_string(),
+        MemoryAllocatorBackend::Rust => "pyembed::MemoryAllocatorBackend::Rust".to_string(),
+        MemoryAllocatorBackend::Default => "pyembed::MemoryAllocatorBackend::Default".to_string(),
+        MemoryAllocatorBackend::Instrumented(base) => format!(
+            "pyembed::MemoryAllocatorBackend::Instrumented(pyembed::BaseMemoryAllocatorBackend::{:?})",
+            base
+        ),
+    }
+}
+
 fn optional_vec_string_to_string(value: &Option<Vec<String>>) -> String {
     match value {
         Some(value) => format!(
@@ -127,6 +143,9 @@ pub struct PyembedPythonInterpreterConfig {
     pub oxidized_importer: bool,
     pub filesystem_importer: bool,
     pub packed_resources: Vec<PyembedPackedResourcesSource>,
+    pub packed_resources_verify: bool,
+    pub packed_resources_watch: bool,
+    pub packed_resources_encryption_key: Option<[u8; 32]>,
     pub argvb: bool,
     pub multiprocessing_auto_dispatch: bool,
     pub multiprocessing_start_method: MultiprocessingStartMethod,
@@ -135,6 +154,14 @@ pub struct PyembedPythonInterpreterConfig {
     pub terminfo_resolution: TerminfoResolution,
     pub tcl_library: Option<PathBuf>,
     pub write_modules_directory_env: Option<String>,
+    pub import_profile_path: Option<PathBuf>,
+    pub import_profile_path_env: Option<String>,
+    pub import_profile_format: ImportProfileFormat,
+    pub import_policy_allow: Option<Vec<String>>,
+    pub import_policy_deny: Option<Vec<String>>,
+    pub crash_handler: bool,
+    pub crash_handler_fd: Option<i32>,
+    pub crash_report_directory: Option<PathBuf>,
 }
 
 impl Default for PyembedPythonInterpreterConfig {
@@ -162,6 +189,9 @@ impl Default for PyembedPythonInterpreterConfig {
             oxidized_importer: true,
             filesystem_importer: false,
             packed_resources: vec![],
+            packed_resources_verify: false,
+            packed_resources_watch: false,
+            packed_resources_encryption_key: None,
             argvb: false,
             multiprocessing_auto_dispatch: true,
             multiprocessing_start_method: MultiprocessingStartMethod::Auto,
@@ -170,6 +200,14 @@ impl Default for PyembedPythonInterpreterConfig {
             terminfo_resolution: TerminfoResolution::None,
             tcl_library: None,
             write_modules_directory_env: None,
+            import_profile_path: None,
+            import_profile_path_env: None,
+            import_profile_format: ImportProfileFormat::Json,
+            import_policy_allow: None,
+            import_policy_deny: None,
+            crash_handler: false,
+            crash_handler_fd: None,
+            crash_report_directory: None,
         }
     }
 }
@@ -177,6 +215,13 @@ impl Default for PyembedPythonInterpreterConfig {
 impl PyembedPythonInterpreterConfig {
     /// Convert the instance to Rust code that constructs a `pyembed::OxidizedPythonInterpreterConfig`.
     pub fn to_oxidized_python_interpreter_config_rs(&self) -> Result<String> {
+        self.render_oxidized_python_interpreter_config_rs(false)
+    }
+
+    /// Render Rust code constructing the config, optionally redacting secrets.
+    ///
+    /// Redacted code isn't valid Rust and is only suitable for describing the config.
+    fn render_oxidized_python_interpreter_config_rs(&self, redact_secrets: bool) -> Result<String> {
         // This code is complicated enough. Let's not worry about format! in format!.
         #[allow(unknown_lints, clippy::format_in_format_args)]
         let code = format!(
@@ -251,7 +296,11 @@ impl PyembedPythonInterpreterConfig {
             oxidized_importer: {},\n    \
             filesystem_importer: {},\n    \
             packed_resources: {},\n    \
+            packed_resources_verify: {},\n    \
+            packed_resources_watch: {},\n    \
+            packed_resources_encryption_key_provider: {},\n    \
             extra_extension_modules: None,\n    \
+            rust_modules: None,\n    \
             argv: None,\n    \
             argvb: {},\n    \
             multiprocessing_auto_dispatch: {},\n    \
@@ -261,6 +310,14 @@ impl PyembedPythonInterpreterConfig {
             terminfo_resolution: {},\n    \
             tcl_library: {},\n    \
             write_modules_directory_env: {},\n    \
+            import_profile_path: {},\n    \
+            import_profile_path_env: {},\n    \
+            import_profile_format: {},\n    \
+            import_policy_allow: {},\n    \
+            import_policy_deny: {},\n    \
+            crash_handler: {},\n    \
+            crash_handler_fd: {},\n    \
+            crash_report_directory: {},\n    \
             }}\n\
             ",
             match self.config.profile {
@@ -367,13 +424,7 @@ impl PyembedPythonInterpreterConfig {
             optional_vec_string_to_string(&self.config.warn_options),
             optional_bool_to_string(&self.config.write_bytecode),
             optional_vec_string_to_string(&self.config.x_options),
-            match self.allocator_backend {
-                MemoryAllocatorBackend::Jemalloc => "pyembed::MemoryAllocatorBackend::Jemalloc",
-                MemoryAllocatorBackend::Mimalloc => "pyembed::MemoryAllocatorBackend::Mimalloc",
-                MemoryAllocatorBackend::Snmalloc => "pyembed::MemoryAllocatorBackend::Snmalloc",
-                MemoryAllocatorBackend::Rust => "pyembed::MemoryAllocatorBackend::Rust",
-                MemoryAllocatorBackend::Default => "pyembed::MemoryAllocatorBackend::Default",
-            },
+            memory_allocator_backend_to_string(&self.allocator_backend),
             self.allocator_raw,
             self.allocator_mem,
             self.allocator_obj,
@@ -389,6 +440,16 @@ impl PyembedPythonInterpreterConfig {
                     .map(|e| e.to_string())
                     .join(", ")
             ),
+            self.packed_resources_verify,
+            self.packed_resources_watch,
+            match &self.packed_resources_encryption_key {
+                Some(_) if redact_secrets => "Some(<redacted>)".to_string(),
+                Some(key) => format!(
+                    "Some(|| Ok([{}]))",
+                    key.iter().map(|b| format!("{:#04x}", b)).join(", ")
+                ),
+                None => "None".to_string(),
+            },
             self.argvb,
             self.multiprocessing_auto_dispatch,
             match self.multiprocessing_start_method {
@@ -414,32 +475,58 @@ impl PyembedPythonInterpreterConfig {
             },
             optional_pathbuf_to_string(&self.tcl_library),
             optional_string_to_string(&self.write_modules_directory_env),
+            optional_pathbuf_to_string(&self.import_profile_path),
+            optional_string_to_string(&self.import_profile_path_env),
+            match self.import_profile_format {
+                ImportProfileFormat::Json => "pyembed::ImportProfileFormat::Json",
+                ImportProfileFormat::ChromeTrace => "pyembed::ImportProfileFormat::ChromeTrace",
+            },
+            optional_vec_string_to_string(&self.import_policy_allow),
+            optional_vec_string_to_string(&self.import_policy_deny),
+            self.crash_handler,
+            match &self.crash_handler_fd {
+                Some(value) => format!("Some({})", value),
+                None => "None".to_string(),
+            },
+            optional_pathbuf_to_string(&self.crash_report_directory),
         );
 
         Ok(code)
     }
 
-    /// Write a Rust file containing a function for obtaining the default `OxidizedPythonInterpreterConfig`.
-    pub fn write_default_python_config_rs(&self, path: impl AsRef<Path>) -> Result<()> {
-        let mut f = std::fs::File::create(path.as_ref())?;
+    /// Obtain Rust source code for a function returning the default `OxidizedPythonInterpreterConfig`.
+    pub fn to_default_python_config_rs(&self) -> Result<String> {
+        let config_rs = self.to_oxidized_python_interpreter_config_rs()?;
 
-        let indented = self
-            .to_oxidized_python_interpreter_config_rs()?
+        let indented = config_rs
             .split('\n')
             .map(|line| "    ".to_string() + line)
             .join("\n");
 
-        f.write_fmt(format_args!(
+        // A description of the configuration is embedded in the binary so it can be
+        // reported by `pyoxidizer analyze`. Secrets like the resources encryption key
+        // are redacted so the description doesn't disclose them.
+        let description = format!(
+            "{}{}\0",
+            INTERPRETER_CONFIG_MARKER,
+            self.render_oxidized_python_interpreter_config_rs(true)?
+        );
+
+        Ok(format!(
             "/// Obtain the default Python configuration\n\
              ///\n\
              /// The crate is compiled with a default Python configuration embedded\n\
              /// in the crate. This function will return an instance of that\n\
              /// configuration.\n\
-             pub fn default_python_config<'a>() -> pyembed::OxidizedPythonInterpreterConfig<'a> {{\n{}\n}}\n",
-            indented
-        ))?;
-
-        Ok(())
+             pub fn default_python_config<'a>() -> pyembed::OxidizedPythonInterpreterConfig<'a> {{\n{}\n}}\n\
+             \n\
+             /// Description of the default Python configuration, for binary analysis tools.\n\
+             #[used]\n\
+             #[allow(dead_code)]\n\
+             static PYOXIDIZER_INTERPRETER_CONFIG: &str = \"{}\";\n",
+            indented,
+            description.escape_default()
+        ))
     }
 }
 
@@ -515,6 +602,48 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_default_python_config_description() -> Result<()> {
+        let config = PyembedPythonInterpreterConfig {
+            tcl_library: Some(PathBuf::from("c:\\windows")),
+            ..Default::default()
+        };
+
+        let code = config.to_default_python_config_rs()?;
+
+        assert_contains(
+            &code,
+            "static PYOXIDIZER_INTERPRETER_CONFIG: &str = \"PYOXIDIZER_INTERPRETER_CONFIG\\u{0}",
+        )?;
+        assert_contains(
+            &code,
+            "tcl_library: Some(std::path::PathBuf::from(\\\"c:\\\\\\\\windows\\\")),",
+        )
+    }
+
+    #[test]
+    fn test_default_python_config_description_redacts_key() -> Result<()> {
+        let config = PyembedPythonInterpreterConfig {
+            packed_resources_encryption_key: Some([0x42; 32]),
+            ..Default::default()
+        };
+
+        let code = config.to_default_python_config_rs()?;
+
+        let description = code
+            .lines()
+            .find(|line| line.starts_with("static PYOXIDIZER_INTERPRETER_CONFIG"))
+            .expect("description should be present");
+        assert!(!description.contains("0x42"));
+        assert_contains(
+            description,
+            "packed_resources_encryption_key_provider: Some(<redacted>),",
+        )?;
+
+        // The key is still available to the generated config function.
+        assert_contains(&code, "Some(|| Ok([0x42, 0x42,")
+    }
+
     // TODO enable once CI has a linkable Python.
     #[test]
     #[ignore]
@@ -597,12 +726,23 @@ mod tests {
                     "$ORIGIN/packed-resources",
                 )),
             ],
+            packed_resources_verify: true,
+            packed_resources_watch: true,
+            packed_resources_encryption_key: Some([0x42; 32]),
             argvb: true,
             sys_frozen: false,
             sys_meipass: true,
             terminfo_resolution: TerminfoResolution::Dynamic,
             tcl_library: Some("path".into()),
             write_modules_directory_env: Some("env".into()),
+            import_profile_path: Some("$ORIGIN/imports.json".into()),
+            import_profile_path_env: Some("PROFILE".into()),
+            import_profile_format: ImportProfileFormat::ChromeTrace,
+            import_policy_allow: Some(vec!["encodings".into(), "json".into()]),
+            import_policy_deny: Some(vec!["ctypes".into()]),
+            crash_handler: true,
+            crash_handler_fd: Some(3),
+            crash_report_directory: Some("$ORIGIN/crashes".into()),
             multiprocessing_auto_dispatch: false,
             multiprocessing_start_method: MultiprocessingStartMethod::Spawn,
         };
@@ -5,7 +5,11 @@
 /*! Functionality for embedding Python in a binary. */
 
 use {
-    crate::py_packaging::config::PyembedPythonInterpreterConfig,
+    crate::{
+        build_cache::{write_if_changed, ArtifactStatus},
+        py_packaging::config::PyembedPythonInterpreterConfig,
+        sbom::{SbomComponent, SoftwareBillOfMaterials},
+    },
     anyhow::{anyhow, Context, Result},
     pyo3_build_config::{
         BuildFlags, InterpreterConfig as PyO3InterpreterConfig, PythonImplementation, PythonVersion,
@@ -14,6 +18,7 @@ use {
         licensing::{LicensedComponent, LicensedComponents},
         resource_collection::CompiledResourcesCollection,
     },
+    python_packed_resources::{BlobCompression, PackedResourcesWriteOptions},
     std::path::{Path, PathBuf},
     tugger_file_manifest::{FileEntry, FileManifest},
 };
@@ -58,8 +63,13 @@ impl LinkingAnnotation {
 pub trait LinkablePython {
     /// Write any files that need to exist to support linking.
     ///
-    /// Files will be written to the directory specified.
-    fn write_files(&self, dest_dir: &Path, target_triple: &str) -> Result<()>;
+    /// Files will be written to the directory specified. Returns the paths
+    /// of files and whether they were changed.
+    fn write_files(
+        &self,
+        dest_dir: &Path,
+        target_triple: &str,
+    ) -> Result<Vec<(PathBuf, ArtifactStatus)>>;
 
     /// Obtain linker annotations needed to link this libpython.
     ///
@@ -111,8 +121,12 @@ impl LinkSharedLibraryPath {
 }
 
 impl LinkablePython for LinkSharedLibraryPath {
-    fn write_files(&self, _dest_dir: &Path, _target_triple: &str) -> Result<()> {
-        Ok(())
+    fn write_files(
+        &self,
+        _dest_dir: &Path,
+        _target_triple: &str,
+    ) -> Result<Vec<(PathBuf, ArtifactStatus)>> {
+        Ok(vec![])
     }
 
     fn linking_annotations(&self, _dest_dir: &Path, alias: bool) -> Result<Vec<LinkingAnnotation>> {
@@ -163,13 +177,16 @@ impl LinkStaticLibraryData {
 }
 
 impl LinkablePython for LinkStaticLibraryData {
-    fn write_files(&self, dest_dir: &Path, target_triple: &str) -> Result<()> {
+    fn write_files(
+        &self,
+        dest_dir: &Path,
+        target_triple: &str,
+    ) -> Result<Vec<(PathBuf, ArtifactStatus)>> {
         let lib_path = self.library_path(dest_dir, target_triple);
 
-        std::fs::write(&lib_path, &self.library_data)
-            .with_context(|| format!("writing {}", lib_path.display()))?;
+        let status = write_if_changed(&lib_path, &self.library_data)?;
 
-        Ok(())
+        Ok(vec![(lib_path, status)])
     }
 
     fn linking_annotations(&self, dest_dir: &Path, alias: bool) -> Result<Vec<LinkingAnnotation>> {
@@ -197,7 +214,11 @@ pub enum LibpythonLinkSettings {
 }
 
 impl LinkablePython for LibpythonLinkSettings {
-    fn write_files(&self, dest_dir: &Path, target_triple: &str) -> Result<()> {
+    fn write_files(
+        &self,
+        dest_dir: &Path,
+        target_triple: &str,
+    ) -> Result<Vec<(PathBuf, ArtifactStatus)>> {
         match self {
             Self::ExistingDynamic(l) => l.write_files(dest_dir, target_triple),
             Self::StaticData(l) => l.write_files(dest_dir, target_triple),
@@ -238,6 +259,15 @@ pub struct EmbeddedPythonContext<'a> {
     /// Python resources that need to be serialized to a file.
     pub pending_resources: Vec<(CompiledResourcesCollection<'a>, PathBuf)>,
 
+    /// Compression to apply when serializing `pending_resources`.
+    pub packed_resources_compression: BlobCompression,
+
+    /// Whether to write a name index when serializing `pending_resources`.
+    pub packed_resources_name_index: bool,
+
+    /// Whether to write source maps when serializing `pending_resources`.
+    pub packed_resources_source_map: bool,
+
     /// Extra files to install next to produced binary.
     pub extra_files: FileManifest,
 
@@ -266,6 +296,12 @@ pub struct EmbeddedPythonContext<'a> {
 
     /// Licensing metadata for components to be built/embedded.
     pub licensing: LicensedComponents,
+
+    /// Name of file to write a software bill of materials to.
+    pub sbom_filename: Option<String>,
+
+    /// Software bill of materials for components to be built/embedded.
+    pub sbom: SoftwareBillOfMaterials,
 }
 
 impl<'a> EmbeddedPythonContext<'a> {
@@ -316,73 +352,124 @@ impl<'a> EmbeddedPythonContext<'a> {
     }
 
     /// Ensure packed resources files are written.
-    pub fn write_packed_resources(&self, dest_dir: impl AsRef<Path>) -> Result<()> {
+    ///
+    /// Files are only written if their content changed.
+    pub fn write_packed_resources(
+        &self,
+        dest_dir: impl AsRef<Path>,
+    ) -> Result<Vec<(PathBuf, ArtifactStatus)>> {
+        let mut res = vec![];
+
         for (collection, path) in &self.pending_resources {
             let dest_path = dest_dir.as_ref().join(path);
 
-            let mut writer = std::io::BufWriter::new(
-                std::fs::File::create(&dest_path)
-                    .with_context(|| format!("opening {} for writing", dest_path.display()))?,
-            );
+            let mut buffer = vec![];
             collection
-                .write_packed_resources(&mut writer)
+                .write_packed_resources(
+                    &mut buffer,
+                    &PackedResourcesWriteOptions {
+                        compression: Some(self.packed_resources_compression),
+                        content_digests: self.config.packed_resources_verify,
+                        name_index: self.packed_resources_name_index,
+                        encryption_key: self.config.packed_resources_encryption_key,
+                        ..PackedResourcesWriteOptions::default()
+                    },
+                    self.packed_resources_source_map,
+                )
                 .context("writing packed resources")?;
+
+            let status = write_if_changed(&dest_path, &buffer)?;
+            res.push((dest_path, status));
         }
 
-        Ok(())
+        Ok(res)
     }
 
     /// Ensure files required by libpython are written.
-    pub fn write_libpython(&self, dest_dir: impl AsRef<Path>) -> Result<()> {
+    pub fn write_libpython(
+        &self,
+        dest_dir: impl AsRef<Path>,
+    ) -> Result<Vec<(PathBuf, ArtifactStatus)>> {
         self.link_settings
             .write_files(dest_dir.as_ref(), &self.target_triple)
     }
 
     /// Write the file containing the default interpreter configuration Rust struct.
-    pub fn write_interpreter_config_rs(&self, dest_dir: impl AsRef<Path>) -> Result<()> {
-        self.config
-            .write_default_python_config_rs(self.interpreter_config_rs_path(&dest_dir))?;
+    pub fn write_interpreter_config_rs(
+        &self,
+        dest_dir: impl AsRef<Path>,
+    ) -> Result<(PathBuf, ArtifactStatus)> {
+        let path = self.interpreter_config_rs_path(&dest_dir);
+        let status = write_if_changed(&path, self.config.to_default_python_config_rs()?)?;
 
-        Ok(())
+        Ok((path, status))
     }
 
     /// Write the PyO3 configuration file.
-    pub fn write_pyo3_config(&self, dest_dir: impl AsRef<Path>) -> Result<()> {
+    pub fn write_pyo3_config(
+        &self,
+        dest_dir: impl AsRef<Path>,
+    ) -> Result<(PathBuf, ArtifactStatus)> {
         let dest_dir = dest_dir.as_ref();
 
-        let mut fh = std::fs::File::create(self.pyo3_config_path(dest_dir))?;
+        let mut buffer = vec![];
         self.pyo3_interpreter_config(dest_dir)?
-            .to_writer(&mut fh)
+            .to_writer(&mut buffer)
             .map_err(|e| anyhow!("error writing PyO3 config file: {}", e))?;
 
-        Ok(())
+        let path = self.pyo3_config_path(dest_dir);
+        let status = write_if_changed(&path, &buffer)?;
+
+        Ok((path, status))
     }
 
     /// Write an aggregated licensing document, if enabled.
-    pub fn write_licensing(&self, dest_dir: impl AsRef<Path>) -> Result<()> {
+    pub fn write_licensing(
+        &self,
+        dest_dir: impl AsRef<Path>,
+    ) -> Result<Option<(PathBuf, ArtifactStatus)>> {
         if let Some(filename) = &self.licensing_filename {
             let text = self.licensing.aggregate_license_document(false)?;
 
-            std::fs::write(dest_dir.as_ref().join(filename), text.as_bytes())?;
-        }
+            let path = dest_dir.as_ref().join(filename);
+            let status = write_if_changed(&path, text.as_bytes())?;
 
-        Ok(())
+            Ok(Some((path, status)))
+        } else {
+            Ok(None)
+        }
     }
 
     /// Write out files needed to build a binary against our configuration.
-    pub fn write_files(&self, dest_dir: &Path) -> Result<()> {
-        self.write_packed_resources(&dest_dir)
-            .context("write_packed_resources()")?;
-        self.write_libpython(&dest_dir)
-            .context("write_libpython()")?;
-        self.write_interpreter_config_rs(&dest_dir)
-            .context("write_interpreter_config_rs()")?;
-        self.write_pyo3_config(&dest_dir)
-            .context("write_pyo3_config()")?;
-        self.write_licensing(&dest_dir)
-            .context("write_licensing()")?;
+    ///
+    /// Files whose content is unchanged are left alone so their modification
+    /// times are preserved. Returns the paths of written files and whether
+    /// they were changed.
+    pub fn write_files(&self, dest_dir: &Path) -> Result<Vec<(PathBuf, ArtifactStatus)>> {
+        let mut res = vec![];
+
+        res.extend(
+            self.write_packed_resources(&dest_dir)
+                .context("write_packed_resources()")?,
+        );
+        res.extend(
+            self.write_libpython(&dest_dir)
+                .context("write_libpython()")?,
+        );
+        res.push(
+            self.write_interpreter_config_rs(&dest_dir)
+                .context("write_interpreter_config_rs()")?,
+        );
+        res.push(
+            self.write_pyo3_config(&dest_dir)
+                .context("write_pyo3_config()")?,
+        );
+        res.extend(
+            self.write_licensing(&dest_dir)
+                .context("write_licensing()")?,
+        );
 
-        Ok(())
+        Ok(res)
     }
 
     /// Obtain licensing information for this instance.
@@ -412,6 +499,38 @@ impl<'a> EmbeddedPythonContext<'a> {
             )?;
         }
 
+        // The bill of materials incorporates licensing info.
+        self.synchronize_sbom()?;
+
+        Ok(())
+    }
+
+    /// Obtain the software bill of materials for this instance.
+    pub fn sbom(&self) -> &SoftwareBillOfMaterials {
+        &self.sbom
+    }
+
+    /// Add a component to the software bill of materials.
+    pub fn add_sbom_component(&mut self, component: SbomComponent) -> Result<()> {
+        self.sbom.add_component(component);
+
+        self.synchronize_sbom()?;
+
+        Ok(())
+    }
+
+    /// Ensure the software bill of materials file is in sync with registered components.
+    pub fn synchronize_sbom(&mut self) -> Result<()> {
+        if let Some(filename) = &self.sbom_filename {
+            self.extra_files.add_file_entry(
+                filename,
+                FileEntry::new_from_data(
+                    self.sbom.to_cyclonedx_json(&self.licensing)?.as_bytes(),
+                    false,
+                ),
+            )?;
+        }
+
         Ok(())
     }
 }
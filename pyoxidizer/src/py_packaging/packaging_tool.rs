@@ -16,14 +16,19 @@ use {
     duct::{cmd, ReaderHandle},
     log::warn,
     python_packaging::{
-        filesystem_scanning::find_python_resources, policy::PythonPackagingPolicy,
-        resource::PythonResource, wheel::WheelArchive,
+        filesystem_scanning::find_python_resources,
+        policy::PythonPackagingPolicy,
+        requirement::{MarkerEnvironment, Requirement},
+        resource::PythonResource,
+        wheel::WheelArchive,
+        wheel_resolver::{WheelResolver, WheelTagCompatibility},
     },
     std::{
         collections::{hash_map::RandomState, HashMap},
         hash::BuildHasher,
         io::{BufRead, BufReader},
         path::{Path, PathBuf},
+        str::FromStr,
     },
 };
 
@@ -192,6 +197,100 @@ pub fn pip_download<'a>(
     Ok(res)
 }
 
+/// Derive PEP 508 environment marker values for a Python distribution.
+fn marker_environment(dist: &dyn PythonDistribution) -> MarkerEnvironment {
+    let triple = dist.target_triple();
+
+    let (sys_platform, platform_system, os_name) = if triple.contains("-windows-") {
+        ("win32", "Windows", "nt")
+    } else if triple.contains("-apple-") {
+        ("darwin", "Darwin", "posix")
+    } else {
+        ("linux", "Linux", "posix")
+    };
+
+    let arch = triple.split('-').next().unwrap_or_default();
+    let platform_machine = match (arch, sys_platform) {
+        ("x86_64", "win32") => "AMD64",
+        ("i686", "win32") => "x86",
+        ("aarch64", "win32") => "ARM64",
+        ("aarch64", "darwin") => "arm64",
+        (arch, _) => arch,
+    };
+
+    MarkerEnvironment {
+        implementation_name: dist.python_implementation().to_string(),
+        implementation_version: dist.python_version().to_string(),
+        os_name: os_name.to_string(),
+        platform_machine: platform_machine.to_string(),
+        platform_python_implementation: if dist.python_implementation() == "cpython" {
+            "CPython".to_string()
+        } else {
+            dist.python_implementation().to_string()
+        },
+        platform_system: platform_system.to_string(),
+        python_full_version: dist.python_version().to_string(),
+        python_version: dist.python_major_minor_version(),
+        sys_platform: sys_platform.to_string(),
+        ..MarkerEnvironment::default()
+    }
+}
+
+/// Resolve requirements against a directory of wheels and collect resources from them.
+///
+/// Unlike [pip_download], this does not run `pip`. Requirements and their
+/// dependencies are resolved natively against `.whl` files in `wheelhouse`,
+/// selecting wheels compatible with `target_dist`.
+pub fn install_wheels<'a>(
+    target_dist: &dyn PythonDistribution,
+    policy: &PythonPackagingPolicy,
+    wheelhouse: &Path,
+    requirements: &[String],
+) -> Result<Vec<PythonResource<'a>>> {
+    let requirements = requirements
+        .iter()
+        .map(|s| Requirement::from_str(s))
+        .collect::<Result<Vec<_>>>()?;
+
+    let major_minor = target_dist.python_major_minor_version();
+    let (major, minor) = major_minor
+        .split_once('.')
+        .and_then(|(major, minor)| Some((major.parse::<u32>().ok()?, minor.parse::<u32>().ok()?)))
+        .ok_or_else(|| anyhow!("unable to parse Python version {}", major_minor))?;
+
+    let tags = WheelTagCompatibility::cpython(
+        major,
+        minor,
+        target_dist.python_abi_tag().unwrap_or("none"),
+        target_dist.python_platform_compatibility_tag(),
+    );
+
+    let mut resolver = WheelResolver::new(tags, marker_environment(target_dist));
+    resolver.add_directory(wheelhouse)?;
+
+    let mut res = Vec::new();
+
+    for wheel in resolver.resolve(&requirements)? {
+        warn!(
+            "installing {} {} from {}",
+            wheel.name,
+            wheel.version,
+            wheel.path.display()
+        );
+
+        let archive = WheelArchive::from_path(&wheel.path)?;
+
+        res.extend(archive.python_resources(
+            target_dist.cache_tag(),
+            &target_dist.python_module_suffixes()?,
+            policy.file_scanner_emit_files(),
+            policy.file_scanner_classify_files(),
+        )?);
+    }
+
+    Ok(res)
+}
+
 /// Run `pip install` and return found resources.
 pub fn pip_install<'a, S: BuildHasher>(
     env: &Environment,
@@ -17,17 +17,22 @@ use {
         filtering::{filter_btreemap, resolve_resource_names_from_files},
         libpython::link_libpython,
         packaging_tool::{
-            find_resources, pip_download, pip_install, read_virtualenv, setup_py_install,
+            find_resources, install_wheels, pip_download, pip_install, read_virtualenv,
+            setup_py_install,
         },
         standalone_distribution::StandaloneDistribution,
     },
-    crate::environment::Environment,
+    crate::{
+        build_cache::BuildCacheEvent,
+        environment::Environment,
+        sbom::{python_distribution_component, python_package_components, SoftwareBillOfMaterials},
+    },
     anyhow::{anyhow, Context, Result},
     log::warn,
     once_cell::sync::Lazy,
     pyo3_build_config::{BuildFlag, BuildFlags, PythonImplementation, PythonVersion},
     python_packaging::{
-        bytecode::BytecodeCompiler,
+        bytecode::{BytecodeCompiler, CachingBytecodeCompiler},
         interpreter::MemoryAllocatorBackend,
         libpython::LibPythonBuildContext,
         licensing::{
@@ -44,6 +49,7 @@ use {
             PythonResourceCollector,
         },
     },
+    python_packed_resources::{BlobCompression, PackedResourcesWriteOptions},
     std::{
         collections::{BTreeMap, BTreeSet, HashMap},
         path::{Path, PathBuf},
@@ -105,6 +111,15 @@ pub struct StandalonePythonExecutableBuilder {
     /// How packed resources will be loaded at run-time.
     resources_load_mode: PackedResourcesLoadMode,
 
+    /// Compression to apply to packed resources data.
+    resources_compression: BlobCompression,
+
+    /// Whether to write a name index in packed resources data.
+    resources_name_index: bool,
+
+    /// Whether to write source maps in packed resources data.
+    resources_source_map: bool,
+
     /// Holds state necessary to link libpython.
     core_build_context: LibPythonBuildContext,
 
@@ -123,6 +138,9 @@ pub struct StandalonePythonExecutableBuilder {
     /// Filename to write out with licensing information.
     licenses_filename: Option<String>,
 
+    /// Filename to write out with a software bill of materials.
+    sbom_filename: Option<String>,
+
     /// Value for the `windows_subsystem` Rust attribute for generated Rust projects.
     windows_subsystem: String,
 
@@ -204,6 +222,8 @@ impl StandalonePythonExecutableBuilder {
 
         let allow_new_builtin_extension_modules = link_mode == LibpythonLinkMode::Static;
 
+        let sbom_filename = Some(format!("{}.cdx.json", exe_name));
+
         let mut builder = Box::new(Self {
             host_triple,
             target_triple,
@@ -222,11 +242,15 @@ impl StandalonePythonExecutableBuilder {
             resources_load_mode: PackedResourcesLoadMode::EmbeddedInBinary(
                 "packed-resources".to_string(),
             ),
+            resources_compression: BlobCompression::None,
+            resources_name_index: false,
+            resources_source_map: false,
             core_build_context: LibPythonBuildContext::default(),
             extension_build_contexts: BTreeMap::new(),
             config,
             host_python_exe,
             licenses_filename: Some("COPYING.txt".into()),
+            sbom_filename,
             windows_subsystem: "console".to_string(),
             tcl_files_path: None,
             windows_runtime_dlls_mode: WindowsRuntimeDllsMode::WhenPresent,
@@ -513,6 +537,14 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
         self.licenses_filename = value;
     }
 
+    fn sbom_filename(&self) -> Option<&str> {
+        self.sbom_filename.as_deref()
+    }
+
+    fn set_sbom_filename(&mut self, value: Option<String>) {
+        self.sbom_filename = value;
+    }
+
     fn packed_resources_load_mode(&self) -> &PackedResourcesLoadMode {
         &self.resources_load_mode
     }
@@ -521,6 +553,38 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
         self.resources_load_mode = load_mode;
     }
 
+    fn packed_resources_compression(&self) -> BlobCompression {
+        self.resources_compression
+    }
+
+    fn set_packed_resources_compression(&mut self, compression: BlobCompression) {
+        self.resources_compression = compression;
+    }
+
+    fn packed_resources_name_index(&self) -> bool {
+        self.resources_name_index
+    }
+
+    fn set_packed_resources_name_index(&mut self, value: bool) {
+        self.resources_name_index = value;
+    }
+
+    fn packed_resources_source_map(&self) -> bool {
+        self.resources_source_map
+    }
+
+    fn set_packed_resources_source_map(&mut self, value: bool) {
+        self.resources_source_map = value;
+    }
+
+    fn packed_resources_encryption_key(&self) -> Option<&[u8; 32]> {
+        self.config.packed_resources_encryption_key.as_ref()
+    }
+
+    fn set_packed_resources_encryption_key(&mut self, key: Option<[u8; 32]>) {
+        self.config.packed_resources_encryption_key = key;
+    }
+
     fn iter_resources<'a>(
         &'a self,
     ) -> Box<dyn Iterator<Item = (&'a String, &'a PrePackagedResource)> + 'a> {
@@ -585,6 +649,25 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
         Ok(resources)
     }
 
+    fn install_wheels(
+        &mut self,
+        wheelhouse: &Path,
+        requirements: &[String],
+    ) -> Result<Vec<PythonResource>> {
+        let resources = install_wheels(
+            &*self.target_distribution,
+            self.python_packaging_policy(),
+            wheelhouse,
+            requirements,
+        )
+        .context("installing wheels")?;
+
+        self.index_package_license_info_from_resources(&resources)
+            .context("indexing package license metadata")?;
+
+        Ok(resources)
+    }
+
     fn read_package_root(
         &mut self,
         path: &Path,
@@ -860,15 +943,15 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
     }
 
     fn requires_jemalloc(&self) -> bool {
-        self.config.allocator_backend == MemoryAllocatorBackend::Jemalloc
+        self.config.allocator_backend.uninstrumented() == MemoryAllocatorBackend::Jemalloc
     }
 
     fn requires_mimalloc(&self) -> bool {
-        self.config.allocator_backend == MemoryAllocatorBackend::Mimalloc
+        self.config.allocator_backend.uninstrumented() == MemoryAllocatorBackend::Mimalloc
     }
 
     fn requires_snmalloc(&self) -> bool {
-        self.config.allocator_backend == MemoryAllocatorBackend::Snmalloc
+        self.config.allocator_backend.uninstrumented() == MemoryAllocatorBackend::Snmalloc
     }
 
     fn licensed_components(&self) -> Result<LicensedComponents> {
@@ -898,9 +981,17 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
 
         let compiled_resources = {
             let temp_dir = env.temporary_directory("pyoxidizer-bytecode-compile")?;
-            let mut compiler = BytecodeCompiler::new(self.host_python_exe_path(), temp_dir.path())?;
+            let mut compiler = CachingBytecodeCompiler::new(
+                BytecodeCompiler::new(self.host_python_exe_path(), temp_dir.path())?,
+                env.build_cache().bytecode_dir(),
+            );
             let resources = self.resources_collector.compile_resources(&mut compiler)?;
 
+            env.build_cache().record(BuildCacheEvent::Bytecode {
+                hits: compiler.hits(),
+                compiled: compiler.compiled().to_vec(),
+            })?;
+
             temp_dir.close().context("closing temporary directory")?;
 
             resources
@@ -926,7 +1017,17 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
                 // We need to materialize the file in extra_files. So compile now.
                 let mut buffer = vec![];
                 compiled_resources
-                    .write_packed_resources(&mut buffer)
+                    .write_packed_resources(
+                        &mut buffer,
+                        &PackedResourcesWriteOptions {
+                            compression: Some(self.resources_compression),
+                            content_digests: config.packed_resources_verify,
+                            name_index: self.resources_name_index,
+                            encryption_key: config.packed_resources_encryption_key,
+                            ..PackedResourcesWriteOptions::default()
+                        },
+                        self.resources_source_map,
+                    )
                     .context("serializing packed resources")?;
                 extra_files.add_file_entry(Path::new(path), buffer)?;
 
@@ -1030,10 +1131,23 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
             python_build_flags.0.insert(BuildFlag::COUNT_ALLOCS);
         }
 
+        let mut sbom = SoftwareBillOfMaterials::new(&self.exe_name);
+        sbom.add_component(python_distribution_component(&self.target_distribution));
+        for component in python_package_components(
+            self.resources_collector
+                .iter_resources()
+                .map(|(_, resource)| resource),
+        )? {
+            sbom.add_component(component);
+        }
+
         let mut context = EmbeddedPythonContext {
             config,
             link_settings,
             pending_resources,
+            packed_resources_compression: self.resources_compression,
+            packed_resources_name_index: self.resources_name_index,
+            packed_resources_source_map: self.resources_source_map,
             extra_files,
             host_triple: self.host_triple.clone(),
             target_triple: self.target_triple.clone(),
@@ -1043,6 +1157,8 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
             python_build_flags,
             licensing_filename: self.licenses_filename.clone(),
             licensing: self.licensed_components()?,
+            sbom_filename: self.sbom_filename.clone(),
+            sbom,
         };
 
         context.synchronize_licensing()?;
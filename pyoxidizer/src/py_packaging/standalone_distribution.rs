@@ -497,6 +497,9 @@ pub struct StandaloneDistribution {
 
     /// Configuration variables used by Python.
     config_vars: HashMap<String, String>,
+
+    /// Location this distribution was obtained from, if known.
+    pub source_location: Option<PythonDistributionLocation>,
 }
 
 impl StandaloneDistribution {
@@ -507,7 +510,10 @@ impl StandaloneDistribution {
         let (archive_path, extract_path) =
             resolve_python_distribution_from_location(location, distributions_dir)?;
 
-        Self::from_tar_zst_file(&archive_path, &extract_path)
+        let mut dist = Self::from_tar_zst_file(&archive_path, &extract_path)?;
+        dist.source_location = Some(location.clone());
+
+        Ok(dist)
     }
 
     /// Create an instance from a .tar.zst file.
@@ -993,6 +999,7 @@ impl StandaloneDistribution {
             module_suffixes,
             crt_features: pi.crt_features,
             config_vars: pi.python_config_vars,
+            source_location: None,
         })
     }
 
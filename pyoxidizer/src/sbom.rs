@@ -0,0 +1,496 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Software bill of materials (SBOM) generation.
+
+A software bill of materials enumerates the third-party components that
+went into a built executable. We emit [CycloneDX](https://cyclonedx.org/)
+JSON documents covering the Python distribution, Python package
+distributions, Rust crates, and licensing metadata for all of them.
+
+Output is deterministic: components are sorted and the serial number is
+derived from the content of the document.
+*/
+
+use {
+    crate::{
+        environment::PYOXIDIZER_VERSION,
+        py_packaging::{
+            distribution::PythonDistributionLocation,
+            standalone_distribution::StandaloneDistribution,
+        },
+    },
+    anyhow::{Context, Result},
+    python_packaging::{
+        licensing::{ComponentFlavor, LicenseFlavor, LicensedComponent, LicensedComponents},
+        package_metadata::PythonPackageMetadata,
+        requirement::normalize_project_name,
+        resource_collection::PrePackagedResource,
+    },
+    serde_json::{json, Map, Value},
+    sha2::Digest,
+    std::{collections::BTreeMap, path::Path},
+};
+
+/// CycloneDX specification version we emit.
+const CYCLONEDX_SPEC_VERSION: &str = "1.4";
+
+/// Name of property recording the SHA-256 of a Python package's `RECORD` file.
+const PROPERTY_RECORD_SHA256: &str = "pyoxidizer:dist-info-record-sha256";
+
+/// Describes the type of a component in a bill of materials.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum SbomComponentFlavor {
+    /// A Python distribution.
+    PythonDistribution,
+    /// A Python package distribution.
+    PythonPackage,
+    /// A Rust crate.
+    RustCrate,
+    /// A generic software library.
+    Library,
+}
+
+/// A component in a software bill of materials.
+///
+/// Components sort by flavor, name, then version.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct SbomComponent {
+    /// The type of component.
+    pub flavor: SbomComponentFlavor,
+    /// Name of the component.
+    pub name: String,
+    /// Version of the component.
+    pub version: Option<String>,
+    /// SHA-256 of the component's content.
+    pub sha256: Option<String>,
+    /// URL the component was obtained from.
+    pub download_url: Option<String>,
+    /// Additional key-value metadata.
+    pub properties: BTreeMap<String, String>,
+}
+
+impl SbomComponent {
+    /// Construct a new instance with only a name.
+    pub fn new(flavor: SbomComponentFlavor, name: impl ToString) -> Self {
+        Self {
+            flavor,
+            name: name.to_string(),
+            version: None,
+            sha256: None,
+            download_url: None,
+            properties: BTreeMap::new(),
+        }
+    }
+
+    /// Obtain the package URL for this component.
+    pub fn purl(&self) -> Option<String> {
+        let name = match self.flavor {
+            SbomComponentFlavor::PythonDistribution => format!("pkg:generic/{}", self.name),
+            SbomComponentFlavor::PythonPackage => {
+                format!("pkg:pypi/{}", normalize_project_name(&self.name))
+            }
+            SbomComponentFlavor::RustCrate => format!("pkg:cargo/{}", self.name),
+            SbomComponentFlavor::Library => return None,
+        };
+
+        Some(if let Some(version) = &self.version {
+            format!("{}@{}", name, version)
+        } else {
+            name
+        })
+    }
+
+    /// Obtain the string uniquely identifying this component within a document.
+    pub fn bom_ref(&self) -> String {
+        self.purl()
+            .unwrap_or_else(|| format!("library:{}", self.name))
+    }
+
+    /// Find the licensed component corresponding to this component.
+    fn find_licensed_component<'a>(
+        &self,
+        licensing: &'a LicensedComponents,
+    ) -> Option<&'a LicensedComponent> {
+        let flavor = match self.flavor {
+            SbomComponentFlavor::PythonDistribution => {
+                ComponentFlavor::PythonDistribution(self.name.clone())
+            }
+            SbomComponentFlavor::PythonPackage => ComponentFlavor::PythonModule(self.name.clone()),
+            SbomComponentFlavor::RustCrate => ComponentFlavor::RustCrate(self.name.clone()),
+            SbomComponentFlavor::Library => ComponentFlavor::Library(self.name.clone()),
+        };
+
+        licensing.iter_components().find(|c| c.flavor() == &flavor)
+    }
+
+    fn to_cyclonedx(&self, licensing: &LicensedComponents) -> Value {
+        let mut component = Map::new();
+
+        component.insert(
+            "type".into(),
+            match self.flavor {
+                SbomComponentFlavor::PythonDistribution => "framework",
+                _ => "library",
+            }
+            .into(),
+        );
+        component.insert("bom-ref".into(), self.bom_ref().into());
+        component.insert("name".into(), self.name.clone().into());
+        if let Some(version) = &self.version {
+            component.insert("version".into(), version.clone().into());
+        }
+        if let Some(purl) = self.purl() {
+            component.insert("purl".into(), purl.into());
+        }
+        if let Some(sha256) = &self.sha256 {
+            component.insert(
+                "hashes".into(),
+                json!([{"alg": "SHA-256", "content": sha256}]),
+            );
+        }
+
+        let mut references = vec![];
+        if let Some(url) = &self.download_url {
+            references.push(json!({"type": "distribution", "url": url}));
+        }
+
+        if let Some(licensed) = self.find_licensed_component(licensing) {
+            if let Some(licenses) = cyclonedx_licenses(licensed.license()) {
+                component.insert("licenses".into(), licenses);
+            }
+            if !licensed.authors().is_empty() {
+                component.insert("author".into(), licensed.authors().join(", ").into());
+            }
+            if let Some(url) = licensed.homepage() {
+                references.push(json!({"type": "website", "url": url}));
+            }
+        }
+
+        if !references.is_empty() {
+            component.insert("externalReferences".into(), references.into());
+        }
+
+        if !self.properties.is_empty() {
+            component.insert(
+                "properties".into(),
+                self.properties
+                    .iter()
+                    .map(|(name, value)| json!({"name": name, "value": value}))
+                    .collect::<Vec<_>>()
+                    .into(),
+            );
+        }
+
+        Value::Object(component)
+    }
+}
+
+/// Convert a license to a CycloneDX `licenses` value.
+fn cyclonedx_licenses(license: &LicenseFlavor) -> Option<Value> {
+    match license {
+        LicenseFlavor::None => None,
+        LicenseFlavor::Spdx(expression) | LicenseFlavor::OtherExpression(expression) => {
+            Some(json!([{"expression": expression.to_string()}]))
+        }
+        LicenseFlavor::PublicDomain => Some(json!([{"license": {"name": "Public Domain"}}])),
+        LicenseFlavor::Unknown(terms) => Some(
+            terms
+                .iter()
+                .map(|term| json!({"license": {"name": term}}))
+                .collect::<Vec<_>>()
+                .into(),
+        ),
+    }
+}
+
+/// Derive a component describing a Python distribution.
+pub fn python_distribution_component(dist: &StandaloneDistribution) -> SbomComponent {
+    let mut component = SbomComponent::new(
+        SbomComponentFlavor::PythonDistribution,
+        &dist.python_implementation,
+    );
+    component.version = Some(dist.version.clone());
+
+    match &dist.source_location {
+        Some(PythonDistributionLocation::Url { url, sha256 }) => {
+            component.sha256 = Some(sha256.clone());
+            component.download_url = Some(url.clone());
+        }
+        Some(PythonDistributionLocation::Local { sha256, .. }) => {
+            component.sha256 = Some(sha256.clone());
+        }
+        None => {}
+    }
+
+    component
+}
+
+/// Derive components for Python package distributions within collected resources.
+///
+/// Packages are identified by their `.dist-info` or `.egg-info` metadata. Packages
+/// whose metadata isn't packaged are not present in the output.
+pub fn python_package_components<'a>(
+    resources: impl Iterator<Item = &'a PrePackagedResource>,
+) -> Result<Vec<SbomComponent>> {
+    let mut res = vec![];
+
+    for resource in resources {
+        let mut files = BTreeMap::new();
+
+        if let Some(entries) = &resource.in_memory_distribution_resources {
+            files.extend(entries.iter().map(|(name, data)| (name.as_str(), data)));
+        }
+        if let Some(entries) = &resource.relative_path_distribution_resources {
+            files.extend(
+                entries
+                    .iter()
+                    .map(|(name, (_, data))| (name.as_str(), data)),
+            );
+        }
+
+        let metadata = if let Some(data) = files.get("METADATA").or_else(|| files.get("PKG-INFO")) {
+            PythonPackageMetadata::from_metadata(&data.resolve_content()?)
+                .with_context(|| format!("parsing metadata of package {}", resource.name))?
+        } else {
+            continue;
+        };
+
+        let mut component = SbomComponent::new(SbomComponentFlavor::PythonPackage, &resource.name);
+        component.version = metadata.version().map(|v| v.to_string());
+
+        if let Some(data) = files.get("RECORD") {
+            component.properties.insert(
+                PROPERTY_RECORD_SHA256.to_string(),
+                hex::encode(sha2::Sha256::digest(&data.resolve_content()?)),
+            );
+        }
+
+        res.push(component);
+    }
+
+    Ok(res)
+}
+
+/// Derive components for Rust crates from a `Cargo.lock` file.
+///
+/// Only crates present in `licensing` are emitted. This filters out crates that
+/// aren't built for the target and the crate for the executable itself.
+pub fn rust_crate_components(
+    lock_path: &Path,
+    licensing: &LicensedComponents,
+) -> Result<Vec<SbomComponent>> {
+    let lock = cargo_lock::Lockfile::load(lock_path)
+        .with_context(|| format!("loading {}", lock_path.display()))?;
+
+    Ok(lock
+        .packages
+        .into_iter()
+        .filter(|package| {
+            licensing.iter_components().any(|c| {
+                c.flavor() == &ComponentFlavor::RustCrate(package.name.as_str().to_string())
+            })
+        })
+        .map(|package| {
+            let mut component =
+                SbomComponent::new(SbomComponentFlavor::RustCrate, package.name.as_str());
+            component.version = Some(package.version.to_string());
+            component.sha256 = package.checksum.as_ref().map(|c| c.to_string());
+
+            if matches!(&package.source, Some(source) if source.is_default_registry()) {
+                component.download_url = Some(format!(
+                    "https://crates.io/api/v1/crates/{}/{}/download",
+                    package.name, package.version
+                ));
+            }
+
+            component
+        })
+        .collect::<Vec<_>>())
+}
+
+/// A software bill of materials for a built executable.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SoftwareBillOfMaterials {
+    /// Name of the application being described.
+    application_name: String,
+
+    /// Components of the application.
+    components: Vec<SbomComponent>,
+}
+
+impl SoftwareBillOfMaterials {
+    /// Construct an empty instance describing an application.
+    pub fn new(application_name: impl ToString) -> Self {
+        Self {
+            application_name: application_name.to_string(),
+            components: vec![],
+        }
+    }
+
+    /// Add a component.
+    ///
+    /// An existing component with the same identity is replaced.
+    pub fn add_component(&mut self, component: SbomComponent) {
+        self.components
+            .retain(|c| c.bom_ref() != component.bom_ref());
+        self.components.push(component);
+        self.components.sort();
+    }
+
+    /// Obtain a CycloneDX JSON document.
+    ///
+    /// `licensing` supplies licenses for components. Components in `licensing`
+    /// describing generic libraries are also emitted.
+    pub fn to_cyclonedx_json(&self, licensing: &LicensedComponents) -> Result<String> {
+        let application_name = &self.application_name;
+
+        let mut sbom = self.clone();
+        for licensed in licensing.iter_components() {
+            if let ComponentFlavor::Library(name) = licensed.flavor() {
+                sbom.add_component(SbomComponent::new(SbomComponentFlavor::Library, name));
+            }
+        }
+
+        let components = sbom
+            .components
+            .iter()
+            .map(|c| c.to_cyclonedx(licensing))
+            .collect::<Vec<_>>();
+        let refs = sbom
+            .components
+            .iter()
+            .map(|c| c.bom_ref())
+            .collect::<Vec<_>>();
+
+        let mut doc = json!({
+            "bomFormat": "CycloneDX",
+            "specVersion": CYCLONEDX_SPEC_VERSION,
+            "version": 1,
+            "metadata": {
+                "tools": [{"name": "PyOxidizer", "version": PYOXIDIZER_VERSION}],
+                "component": {
+                    "type": "application",
+                    "bom-ref": application_name,
+                    "name": application_name,
+                },
+            },
+            "components": components,
+            "dependencies": [{"ref": application_name, "dependsOn": refs}],
+        });
+
+        let serial = uuid::Uuid::new_v5(&uuid::Uuid::NAMESPACE_OID, doc.to_string().as_bytes());
+        doc.as_object_mut()
+            .expect("document is an object")
+            .insert("serialNumber".into(), format!("urn:uuid:{}", serial).into());
+
+        let mut data = serde_json::to_string_pretty(&doc)?;
+        data.push('\n');
+
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, tugger_file_manifest::FileData};
+
+    #[test]
+    fn python_packages() -> Result<()> {
+        let mut distribution_resources = BTreeMap::new();
+        distribution_resources.insert(
+            "METADATA".to_string(),
+            FileData::from(b"Metadata-Version: 2.1\nName: Foo_Bar\nVersion: 1.0\n".to_vec()),
+        );
+        distribution_resources.insert("RECORD".to_string(), FileData::from(b"".to_vec()));
+
+        let resources = [
+            PrePackagedResource {
+                name: "Foo_Bar".to_string(),
+                in_memory_distribution_resources: Some(distribution_resources),
+                ..PrePackagedResource::default()
+            },
+            PrePackagedResource {
+                name: "baz".to_string(),
+                ..PrePackagedResource::default()
+            },
+        ];
+
+        let components = python_package_components(resources.iter())?;
+        assert_eq!(components.len(), 1);
+        assert_eq!(components[0].name, "Foo_Bar");
+        assert_eq!(components[0].version, Some("1.0".to_string()));
+        assert_eq!(
+            components[0].purl(),
+            Some("pkg:pypi/foo-bar@1.0".to_string())
+        );
+        assert_eq!(
+            components[0].properties.get(PROPERTY_RECORD_SHA256),
+            Some(&"e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855".to_string())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn cyclonedx() -> Result<()> {
+        let mut sbom = SoftwareBillOfMaterials::new("myapp");
+
+        let mut component = SbomComponent::new(SbomComponentFlavor::RustCrate, "libc");
+        component.version = Some("0.2.0".to_string());
+        component.sha256 = Some("deadbeef".to_string());
+        sbom.add_component(component);
+
+        let mut component = SbomComponent::new(SbomComponentFlavor::PythonDistribution, "cpython");
+        component.version = Some("3.10.5".to_string());
+        sbom.add_component(component.clone());
+        sbom.add_component(component);
+
+        let mut licensing = LicensedComponents::default();
+        licensing.add_component(LicensedComponent::new_spdx(
+            ComponentFlavor::RustCrate("libc".to_string()),
+            "MIT OR Apache-2.0",
+        )?);
+        licensing.add_component(LicensedComponent::new_spdx(
+            ComponentFlavor::Library("ssl".to_string()),
+            "OpenSSL",
+        )?);
+
+        let data = sbom.to_cyclonedx_json(&licensing)?;
+        assert_eq!(data, sbom.to_cyclonedx_json(&licensing)?);
+
+        let doc: Value = serde_json::from_str(&data)?;
+        assert_eq!(doc["bomFormat"], "CycloneDX");
+        assert!(doc["serialNumber"]
+            .as_str()
+            .unwrap()
+            .starts_with("urn:uuid:"));
+        assert_eq!(doc["metadata"]["component"]["name"], "myapp");
+
+        let components = doc["components"].as_array().unwrap();
+        assert_eq!(components.len(), 3);
+        assert_eq!(components[0]["purl"], "pkg:generic/cpython@3.10.5");
+        assert_eq!(components[0]["type"], "framework");
+        assert_eq!(components[1]["purl"], "pkg:cargo/libc@0.2.0");
+        assert_eq!(components[1]["hashes"][0]["content"], "deadbeef");
+        assert_eq!(
+            components[1]["licenses"][0]["expression"],
+            "MIT OR Apache-2.0"
+        );
+        assert_eq!(components[2]["bom-ref"], "library:ssl");
+        assert_eq!(components[2]["licenses"][0]["expression"], "OpenSSL");
+
+        assert_eq!(
+            doc["dependencies"][0]["dependsOn"],
+            json!([
+                "pkg:generic/cpython@3.10.5",
+                "pkg:cargo/libc@0.2.0",
+                "library:ssl"
+            ])
+        );
+
+        Ok(())
+    }
+}
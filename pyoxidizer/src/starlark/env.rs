@@ -3,7 +3,7 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use {
-    crate::py_packaging::distribution::DistributionCache,
+    crate::{lock_file::LockFileContext, py_packaging::distribution::DistributionCache},
     anyhow::{Context, Result},
     starlark::{
         environment::{Environment, EnvironmentError, TypeValues},
@@ -57,6 +57,9 @@ pub struct PyOxidizerEnvironmentContext {
     /// few seconds in debug builds. And this adds up, especially in tests!
     pub distribution_cache: Arc<DistributionCache>,
 
+    /// Lock file to record resolved content to and enforce.
+    pub lock_file: Option<Arc<LockFileContext>>,
+
     /// Extra variables to inject into Starlark environment.
     extra_vars: HashMap<String, Option<String>>,
 }
@@ -72,6 +75,7 @@ impl PyOxidizerEnvironmentContext {
         build_release: bool,
         build_opt_level: &str,
         distribution_cache: Option<Arc<DistributionCache>>,
+        lock_file: Option<Arc<LockFileContext>>,
         extra_vars: HashMap<String, Option<String>>,
     ) -> Result<PyOxidizerEnvironmentContext> {
         let parent = config_path
@@ -100,6 +104,7 @@ impl PyOxidizerEnvironmentContext {
             build_release,
             build_opt_level: build_opt_level.to_string(),
             distribution_cache,
+            lock_file,
             extra_vars,
         })
     }
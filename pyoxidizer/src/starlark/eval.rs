@@ -5,6 +5,7 @@
 use {
     crate::{
         environment::default_target_triple,
+        lock_file::LockFileContext,
         py_packaging::distribution::DistributionCache,
         starlark::env::{
             populate_environment, register_starlark_dialect, PyOxidizerContext,
@@ -45,6 +46,7 @@ pub struct EvaluationContextBuilder {
     build_script_mode: bool,
     build_opt_level: String,
     distribution_cache: Option<Arc<DistributionCache>>,
+    lock_file: Option<Arc<LockFileContext>>,
     extra_vars: HashMap<String, Option<String>>,
 }
 
@@ -64,6 +66,7 @@ impl EvaluationContextBuilder {
             build_script_mode: false,
             build_opt_level: "0".to_string(),
             distribution_cache: None,
+            lock_file: None,
             extra_vars: HashMap::new(),
         }
     }
@@ -134,6 +137,12 @@ impl EvaluationContextBuilder {
         self
     }
 
+    #[must_use]
+    pub fn lock_file(mut self, lock_file: Arc<LockFileContext>) -> Self {
+        self.lock_file = Some(lock_file);
+        self
+    }
+
     #[must_use]
     pub fn extra_vars(mut self, extra_vars: HashMap<String, Option<String>>) -> Self {
         self.extra_vars = extra_vars;
@@ -173,6 +182,7 @@ impl EvaluationContext {
             builder.release,
             &builder.build_opt_level,
             builder.distribution_cache,
+            builder.lock_file,
             builder.extra_vars,
         )?;
 
@@ -85,6 +85,21 @@ impl PythonDistributionValue {
                     })?
                     .clone_trait(),
             );
+
+            if let Some(lock_file) = &pyoxidizer_context.lock_file {
+                lock_file
+                    .record_python_distribution(
+                        &self.source,
+                        &**self.distribution.as_ref().unwrap(),
+                    )
+                    .map_err(|e| {
+                        ValueError::from(RuntimeError {
+                            code: "PYOXIDIZER_BUILD",
+                            message: format!("{:?}", e),
+                            label: label.to_string(),
+                        })
+                    })?;
+            }
         }
 
         Ok(self.distribution.as_ref().unwrap().clone())
@@ -72,7 +72,9 @@ impl PythonEmbeddedResourcesValue {
 
         std::fs::create_dir_all(&output_path)
             .with_context(|| format!("creating output directory: {}", output_path.display()))?;
-        embedded.write_files(&output_path)?;
+        for (path, status) in embedded.write_files(&output_path)? {
+            context.env().build_cache().record_artifact(path, status)?;
+        }
 
         Ok(ResolvedTarget {
             run_mode: RunMode::None,
@@ -18,14 +18,17 @@ use {
     },
     crate::{
         licensing::licenses_from_cargo_manifest,
+        lock_file::locked_packages_from_resources,
         project_building::build_python_executable,
         py_packaging::binary::PythonBinaryBuilder,
         py_packaging::binary::{PackedResourcesLoadMode, WindowsRuntimeDllsMode},
     },
     anyhow::{anyhow, Context, Result},
+    debian_packaging::deb::target_triple_to_debian_architecture,
     linked_hash_map::LinkedHashMap,
     log::{info, warn},
-    python_packaging::resource::PythonModuleSource,
+    python_packaging::resource::{PythonModuleSource, PythonResource},
+    python_packed_resources::BlobCompression,
     starlark::{
         environment::TypeValues,
         eval::call_stack::CallStack,
@@ -54,12 +57,15 @@ use {
     },
     tugger::starlark::{
         code_signing::{handle_signable_event, SigningAction, SigningContext},
+        debian_package_builder::DebianPackageBuilderValue,
         file_manifest::FileManifestValue,
+        rpm_builder::RpmBuilderValue,
         wix_bundle_builder::WiXBundleBuilderValue,
         wix_msi_builder::WiXMsiBuilderValue,
     },
     tugger_code_signing::SigningDestination,
-    tugger_file_manifest::FileData,
+    tugger_file_manifest::{FileData, FileEntry, FileManifest},
+    tugger_rpm::target_triple_to_rpm_arch,
     tugger_wix::target_triple_to_wix_arch,
 };
 
@@ -76,6 +82,42 @@ where
     })
 }
 
+/// Obtain `pip` arguments constraining resolution to packages in the lock file.
+///
+/// The returned temporary directory holds the constraints file and must
+/// outlive the `pip` invocation.
+fn lock_file_pip_args(
+    context: &PyOxidizerEnvironmentContext,
+) -> Result<(Option<tempfile::TempDir>, Vec<String>)> {
+    if let Some(lock_file) = &context.lock_file {
+        if lock_file.locked().is_some() {
+            let temp_dir = context.env().temporary_directory("pyoxidizer-lock")?;
+
+            if let Some(path) = lock_file.write_pip_constraints(temp_dir.path())? {
+                return Ok((
+                    Some(temp_dir),
+                    vec!["--constraint".to_string(), path.display().to_string()],
+                ));
+            }
+        }
+    }
+
+    Ok((None, vec![]))
+}
+
+/// Record packages within resources to the lock file, verifying them against it.
+fn record_locked_packages(
+    context: &PyOxidizerEnvironmentContext,
+    target_triple: &str,
+    resources: &[PythonResource],
+) -> Result<()> {
+    if let Some(lock_file) = &context.lock_file {
+        lock_file.record_packages(locked_packages_from_resources(target_triple, resources)?)?;
+    }
+
+    Ok(())
+}
+
 pub fn build_internal(
     exe: MutexGuard<Box<dyn PythonBinaryBuilder>>,
     type_values: &TypeValues,
@@ -108,6 +150,22 @@ pub fn build_internal(
         .context(format!("writing {}", dest_path.display()))?;
     tugger_file_manifest::set_executable(&mut fh).context("making binary executable")?;
 
+    if let Some(filename) = &build.binary_data.sbom_filename {
+        let sbom_path = output_path.join(filename);
+        warn!(
+            "writing software bill of materials to {}",
+            sbom_path.display()
+        );
+        std::fs::write(
+            &sbom_path,
+            build
+                .binary_data
+                .sbom()
+                .to_cyclonedx_json(build.binary_data.licensing())?,
+        )
+        .with_context(|| format!("writing {}", sbom_path.display()))?;
+    }
+
     Ok((
         ResolvedTarget {
             run_mode: RunMode::Path {
@@ -177,9 +235,22 @@ impl TypedValue for PythonExecutableValue {
 
         match attribute {
             "licenses_filename" => Ok(exe.licenses_filename().to_value()),
+            "packed_resources_compression" => {
+                Ok(Value::from(match exe.packed_resources_compression() {
+                    BlobCompression::None => "none",
+                    BlobCompression::Zstd => "zstd",
+                }))
+            }
+            "packed_resources_encryption_key" => match exe.packed_resources_encryption_key() {
+                Some(key) => Ok(Value::from(hex::encode(key))),
+                None => Ok(Value::from(NoneType::None)),
+            },
             "packed_resources_load_mode" => {
                 Ok(Value::from(exe.packed_resources_load_mode().to_string()))
             }
+            "packed_resources_name_index" => Ok(Value::from(exe.packed_resources_name_index())),
+            "packed_resources_source_map" => Ok(Value::from(exe.packed_resources_source_map())),
+            "sbom_filename" => Ok(exe.sbom_filename().to_value()),
             "tcl_files_path" => match exe.tcl_files_path() {
                 Some(value) => Ok(Value::from(value.to_string())),
                 None => Ok(Value::from(NoneType::None)),
@@ -200,7 +271,12 @@ impl TypedValue for PythonExecutableValue {
         Ok(matches!(
             attribute,
             "licenses_filename"
+                | "packed_resources_compression"
+                | "packed_resources_encryption_key"
                 | "packed_resources_load_mode"
+                | "packed_resources_name_index"
+                | "packed_resources_source_map"
+                | "sbom_filename"
                 | "tcl_files_path"
                 | "windows_runtime_dlls_mode"
                 | "windows_subsystem"
@@ -217,6 +293,42 @@ impl TypedValue for PythonExecutableValue {
 
                 Ok(())
             }
+            "packed_resources_compression" => {
+                exe.set_packed_resources_compression(match value.to_string().as_str() {
+                    "none" => BlobCompression::None,
+                    "zstd" => BlobCompression::Zstd,
+                    v => {
+                        return Err(ValueError::from(RuntimeError {
+                            code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                            message: format!("unknown compression value: {}", v),
+                            label: format!("{}.{}", Self::TYPE, attribute),
+                        }))
+                    }
+                });
+
+                Ok(())
+            }
+            "packed_resources_encryption_key" => {
+                let key = match optional_str_arg(attribute, &value)? {
+                    Some(value) => Some(
+                        hex::decode(&value)
+                            .ok()
+                            .and_then(|key| <[u8; 32]>::try_from(key).ok())
+                            .ok_or_else(|| {
+                                ValueError::from(RuntimeError {
+                                    code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                                    message: "encryption key must be 64 hex characters".to_string(),
+                                    label: format!("{}.{}", Self::TYPE, attribute),
+                                })
+                            })?,
+                    ),
+                    None => None,
+                };
+
+                exe.set_packed_resources_encryption_key(key);
+
+                Ok(())
+            }
             "packed_resources_load_mode" => {
                 exe.set_packed_resources_load_mode(
                     PackedResourcesLoadMode::try_from(value.to_string().as_str()).map_err(|e| {
@@ -230,6 +342,22 @@ impl TypedValue for PythonExecutableValue {
 
                 Ok(())
             }
+            "packed_resources_name_index" => {
+                exe.set_packed_resources_name_index(value.to_bool());
+
+                Ok(())
+            }
+            "packed_resources_source_map" => {
+                exe.set_packed_resources_source_map(value.to_bool());
+
+                Ok(())
+            }
+            "sbom_filename" => {
+                let value = optional_str_arg("sbom_filename", &value)?;
+                exe.set_sbom_filename(value);
+
+                Ok(())
+            }
             "tcl_files_path" => {
                 exe.set_tcl_files_path(value.to_optional());
 
@@ -366,7 +494,17 @@ impl PythonExecutableValue {
         let mut exe = self.inner(LABEL)?;
 
         let resources = error_context("PythonExecutable.pip_download()", || {
-            exe.pip_download(pyoxidizer_context.env(), pyoxidizer_context.verbose, &args)
+            let (_constraints_dir, mut pip_args) = lock_file_pip_args(pyoxidizer_context)?;
+            pip_args.extend(args);
+
+            let resources = exe.pip_download(
+                pyoxidizer_context.env(),
+                pyoxidizer_context.verbose,
+                &pip_args,
+            )?;
+            record_locked_packages(pyoxidizer_context, exe.target_triple(), &resources)?;
+
+            Ok(resources)
         })?;
 
         let resources = resources
@@ -425,12 +563,69 @@ impl PythonExecutableValue {
         let mut exe = self.inner(LABEL)?;
 
         let resources = error_context(LABEL, || {
-            exe.pip_install(
+            let (_constraints_dir, mut pip_args) = lock_file_pip_args(pyoxidizer_context)?;
+            pip_args.extend(args);
+
+            let resources = exe.pip_install(
                 pyoxidizer_context.env(),
                 pyoxidizer_context.verbose,
-                &args,
+                &pip_args,
                 &extra_envs,
-            )
+            )?;
+            record_locked_packages(pyoxidizer_context, exe.target_triple(), &resources)?;
+
+            Ok(resources)
+        })?;
+
+        let resources = resources
+            .iter()
+            .filter(|r| is_resource_starlark_compatible(r))
+            .map(|r| {
+                python_resource_to_value(
+                    LABEL,
+                    type_values,
+                    call_stack,
+                    r,
+                    &python_packaging_policy,
+                )
+            })
+            .collect::<Result<Vec<Value>, ValueError>>()?;
+
+        Ok(Value::from(resources))
+    }
+
+    /// PythonExecutable.install_wheels(wheelhouse, requirements)
+    pub fn install_wheels(
+        &mut self,
+        type_values: &TypeValues,
+        call_stack: &mut CallStack,
+        wheelhouse: String,
+        requirements: &Value,
+    ) -> ValueResult {
+        const LABEL: &str = "PythonExecutable.install_wheels()";
+
+        required_list_arg("requirements", "string", requirements)?;
+
+        let requirements = requirements
+            .iter()?
+            .iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<String>>();
+
+        let pyoxidizer_context_value = get_context(type_values)?;
+        let pyoxidizer_context = pyoxidizer_context_value
+            .downcast_ref::<PyOxidizerEnvironmentContext>()
+            .ok_or(ValueError::IncorrectParameterType)?;
+
+        let python_packaging_policy = self.python_packaging_policy();
+
+        let mut exe = self.inner(LABEL)?;
+
+        let resources = error_context(LABEL, || {
+            let resources = exe.install_wheels(Path::new(&wheelhouse), &requirements)?;
+            record_locked_packages(pyoxidizer_context, exe.target_triple(), &resources)?;
+
+            Ok(resources)
         })?;
 
         let resources = resources
@@ -857,6 +1052,116 @@ impl PythonExecutableValue {
         Ok(manifest_value.clone())
     }
 
+    /// Resolve files to install for a Linux distribution package.
+    ///
+    /// The executable and its file-based resources are installed under
+    /// `usr/lib/<package>`. The executable may locate resources relative to
+    /// itself, so a launcher script is installed in `usr/bin` instead of the
+    /// binary itself.
+    fn linux_package_manifest(
+        &self,
+        type_values: &TypeValues,
+        label: &str,
+        package: &str,
+    ) -> Result<FileManifest, ValueError> {
+        let name = self.inner(label)?.name();
+        let install_prefix = format!("usr/lib/{}", package);
+
+        let manifest_value = self.to_file_manifest(type_values, install_prefix.clone())?;
+        let manifest = manifest_value.downcast_ref::<FileManifestValue>().unwrap();
+        let mut manifest = manifest.inner(label)?.clone();
+
+        let launcher = format!("#!/bin/sh\nexec /{}/{} \"$@\"\n", install_prefix, name);
+
+        error_context(label, || {
+            manifest
+                .add_file_entry(
+                    Path::new("usr/bin").join(&name),
+                    FileEntry::new_from_data(launcher.into_bytes(), true),
+                )
+                .context("adding launcher script")
+        })?;
+
+        Ok(manifest)
+    }
+
+    /// PythonExecutable.to_deb_builder(package, version, maintainer, description)
+    pub fn to_deb_builder(
+        &self,
+        type_values: &TypeValues,
+        package: String,
+        version: String,
+        maintainer: String,
+        description: String,
+    ) -> ValueResult {
+        const LABEL: &str = "PythonExecutable.to_deb_builder()";
+
+        let triple = self.inner(LABEL)?.target_triple().to_string();
+        let arch = error_context(LABEL, || {
+            target_triple_to_debian_architecture(&triple)
+                .ok_or_else(|| anyhow!("unable to resolve Debian architecture for {}", triple))
+        })?;
+
+        let manifest = self.linux_package_manifest(type_values, LABEL, &package)?;
+
+        let builder_value = DebianPackageBuilderValue::new_from_args(
+            package,
+            version,
+            arch.to_string(),
+            maintainer,
+            description,
+        )?;
+        let mut builder = builder_value
+            .downcast_mut::<DebianPackageBuilderValue>()
+            .unwrap()
+            .unwrap();
+
+        error_context(LABEL, || {
+            builder
+                .files
+                .add_manifest(&manifest)
+                .context("adding files to package")
+        })?;
+
+        Ok(builder_value.clone())
+    }
+
+    /// PythonExecutable.to_rpm_builder(name, version, license, description)
+    pub fn to_rpm_builder(
+        &self,
+        type_values: &TypeValues,
+        name: String,
+        version: String,
+        license: String,
+        description: String,
+    ) -> ValueResult {
+        const LABEL: &str = "PythonExecutable.to_rpm_builder()";
+
+        let triple = self.inner(LABEL)?.target_triple().to_string();
+        let arch = error_context(LABEL, || {
+            target_triple_to_rpm_arch(&triple)
+                .ok_or_else(|| anyhow!("unable to resolve RPM architecture for {}", triple))
+        })?;
+
+        let manifest = self.linux_package_manifest(type_values, LABEL, &name)?;
+
+        let builder_value =
+            RpmBuilderValue::new_from_args(name, version, license, arch.to_string(), description)?;
+        let mut builder = builder_value
+            .downcast_mut::<RpmBuilderValue>()
+            .unwrap()
+            .unwrap();
+
+        error_context(LABEL, || {
+            builder
+                .files
+                .add_manifest(&manifest)
+                .context("adding files to package")
+        })?;
+
+        Ok(builder_value.clone())
+    }
+
     /// PythonExecutable.to_wix_bundle_builder(id_prefix, name, version, manufacturer, msi_builder_callback)
     #[allow(clippy::too_many_arguments)]
     pub fn to_wix_bundle_builder(
@@ -1044,6 +1349,17 @@ starlark_module! { python_executable_env =>
         this.pip_install(env, cs, &args, &extra_envs)
     }
 
+    PythonExecutable.install_wheels(
+        env env,
+        call_stack cs,
+        this,
+        wheelhouse: String,
+        requirements
+    ) {
+        let mut this = this.downcast_mut::<PythonExecutableValue>().unwrap().unwrap();
+        this.install_wheels(env, cs, wheelhouse, &requirements)
+    }
+
     PythonExecutable.read_package_root(
         env env,
         call_stack cs,
@@ -1128,6 +1444,30 @@ starlark_module! { python_executable_env =>
         this.to_file_manifest(env, prefix)
     }
 
+    PythonExecutable.to_deb_builder(
+        env env,
+        this,
+        package: String,
+        version: String,
+        maintainer: String,
+        description: String
+    ) {
+        let this = this.downcast_ref::<PythonExecutableValue>().unwrap();
+        this.to_deb_builder(env, package, version, maintainer, description)
+    }
+
+    PythonExecutable.to_rpm_builder(
+        env env,
+        this,
+        name: String,
+        version: String,
+        license: String,
+        description: String
+    ) {
+        let this = this.downcast_ref::<PythonExecutableValue>().unwrap();
+        this.to_rpm_builder(env, name, version, license, description)
+    }
+
     PythonExecutable.to_wix_bundle_builder(
         env env,
         call_stack cs,
@@ -1170,6 +1510,7 @@ mod tests {
         super::super::testutil::*,
         super::*,
         crate::{python_distributions::PYTHON_DISTRIBUTIONS, testutil::*},
+        python_packaging::wheel_builder::WheelBuilder,
     };
 
     #[test]
@@ -1305,6 +1646,76 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_install_wheels() -> Result<()> {
+        let temp_dir = get_env()?.temporary_directory("pyoxidizer-test")?;
+        let wheelhouse = temp_dir.path();
+
+        let mut builder = WheelBuilder::new("foo", "1.0");
+        builder.add_file_dist_info(
+            "METADATA",
+            b"Metadata-Version: 2.1\nName: foo\nVersion: 1.0\nRequires-Dist: bar>=2\n".as_ref(),
+        )?;
+        builder.add_file("foo/__init__.py", b"import bar".as_ref())?;
+        builder.write_wheel_into_directory(wheelhouse)?;
+
+        for version in ["1.0", "2.0"] {
+            let mut builder = WheelBuilder::new("bar", version);
+            builder.add_file("bar.py", b"# bar".as_ref())?;
+            builder.write_wheel_into_directory(wheelhouse)?;
+        }
+
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+        env.eval("dist = default_python_distribution()")?;
+        env.eval("policy = dist.make_python_packaging_policy()")?;
+        env.eval("policy.include_distribution_sources = False")?;
+        env.eval("exe = dist.to_python_executable('testapp', packaging_policy = policy)")?;
+
+        let resources = env.eval(&format!(
+            "exe.install_wheels(\"{}\", ['foo'])",
+            wheelhouse.display().to_string().replace('\\', "/")
+        ))?;
+        assert_eq!(resources.get_type(), "list");
+
+        let modules = resources
+            .iter()
+            .unwrap()
+            .iter()
+            .filter(|v| v.get_type() == PythonModuleSourceValue::TYPE)
+            .map(|v| {
+                let x = v.downcast_ref::<PythonModuleSourceValue>().unwrap();
+                x.inner("ignored").unwrap().m.name.clone()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(modules, vec!["bar".to_string(), "foo".to_string()]);
+
+        let distributions = resources
+            .iter()
+            .unwrap()
+            .iter()
+            .filter(|v| v.get_type() == PythonPackageDistributionResourceValue::TYPE)
+            .map(|v| {
+                let x = v
+                    .downcast_ref::<PythonPackageDistributionResourceValue>()
+                    .unwrap();
+                let inner = x.inner("ignored").unwrap();
+                format!("{}-{}", inner.r.package, inner.r.version)
+            })
+            .collect::<std::collections::BTreeSet<_>>();
+        assert!(distributions.contains("bar-2.0"));
+
+        assert!(env
+            .eval(&format!(
+                "exe.install_wheels(\"{}\", ['bar<1'])",
+                wheelhouse.display().to_string().replace('\\', "/")
+            ))
+            .is_err());
+
+        temp_dir.close()?;
+
+        Ok(())
+    }
+
     #[test]
     fn test_read_package_root_simple() -> Result<()> {
         let temp_dir = get_env()?.temporary_directory("pyoxidizer-test")?;
@@ -1385,6 +1796,27 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn sbom_filename() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+        add_exe(&mut env)?;
+
+        let v = env.eval("exe.sbom_filename")?;
+        assert_eq!(v.get_type(), "string");
+        assert_eq!(v.to_string(), "testapp.cdx.json");
+
+        env.eval("exe.sbom_filename = 'sbom.json'")?;
+        let v = env.eval("exe.sbom_filename")?;
+        assert_eq!(v.get_type(), "string");
+        assert_eq!(v.to_string(), "sbom.json");
+
+        env.eval("exe.sbom_filename = None")?;
+        let v = env.eval("exe.sbom_filename")?;
+        assert_eq!(v.get_type(), "NoneType");
+
+        Ok(())
+    }
+
     #[test]
     fn test_windows_runtime_dlls_mode() -> Result<()> {
         let mut env = test_evaluation_context_builder()?.into_context()?;
@@ -1412,6 +1844,89 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_packed_resources_compression() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+        add_exe(&mut env)?;
+
+        let value = env.eval("exe.packed_resources_compression")?;
+        assert_eq!(value.get_type(), "string");
+        assert_eq!(value.to_string(), "none");
+
+        let value = env
+            .eval("exe.packed_resources_compression = 'zstd'; exe.packed_resources_compression")?;
+        assert_eq!(value.get_type(), "string");
+        assert_eq!(value.to_string(), "zstd");
+
+        assert!(env
+            .eval("exe.packed_resources_compression = 'bogus'")
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_packed_resources_name_index() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+        add_exe(&mut env)?;
+
+        let value = env.eval("exe.packed_resources_name_index")?;
+        assert_eq!(value.get_type(), "bool");
+        assert!(!value.to_bool());
+
+        let value =
+            env.eval("exe.packed_resources_name_index = True; exe.packed_resources_name_index")?;
+        assert_eq!(value.get_type(), "bool");
+        assert!(value.to_bool());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_packed_resources_source_map() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+        add_exe(&mut env)?;
+
+        let value = env.eval("exe.packed_resources_source_map")?;
+        assert_eq!(value.get_type(), "bool");
+        assert!(!value.to_bool());
+
+        let value =
+            env.eval("exe.packed_resources_source_map = True; exe.packed_resources_source_map")?;
+        assert_eq!(value.get_type(), "bool");
+        assert!(value.to_bool());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_packed_resources_encryption_key() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+        add_exe(&mut env)?;
+
+        let value = env.eval("exe.packed_resources_encryption_key")?;
+        assert_eq!(value.get_type(), "NoneType");
+
+        let key = "42".repeat(32);
+        let value = env.eval(&format!(
+            "exe.packed_resources_encryption_key = '{}'; exe.packed_resources_encryption_key",
+            key
+        ))?;
+        assert_eq!(value.get_type(), "string");
+        assert_eq!(value.to_string(), key);
+
+        assert!(env
+            .eval("exe.packed_resources_encryption_key = 'deadbeef'")
+            .is_err());
+
+        let value = env.eval(
+            "exe.packed_resources_encryption_key = None; exe.packed_resources_encryption_key",
+        )?;
+        assert_eq!(value.get_type(), "NoneType");
+
+        Ok(())
+    }
+
     #[test]
     fn test_packed_resources_load_mode() -> Result<()> {
         let mut env = test_evaluation_context_builder()?.into_context()?;
@@ -1487,6 +2002,59 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_to_deb_builder() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+        add_exe(&mut env)?;
+        env.eval(
+            "deb = exe.to_deb_builder('myapp', '0.1', 'Some One <someone@example.com>', 'My app')",
+        )?;
+
+        let builder_value = env.eval("deb")?;
+        let builder = builder_value
+            .downcast_ref::<DebianPackageBuilderValue>()
+            .unwrap();
+        assert!(builder.files.has_path("usr/bin/testapp"));
+        assert!(builder.files.has_path("usr/lib/myapp/testapp"));
+
+        env.eval("deb.build('test_to_deb_builder')")?;
+
+        let deb_path = env
+            .target_build_path("test_to_deb_builder")
+            .unwrap()
+            .join(builder.deb_filename());
+
+        assert!(deb_path.exists());
+
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_to_rpm_builder() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+        add_exe(&mut env)?;
+        env.eval("rpm = exe.to_rpm_builder('myapp', '0.1', 'MPL-2.0', 'My app')")?;
+        env.eval("rpm.add_requires('glibc >= 2.17')")?;
+
+        let builder_value = env.eval("rpm")?;
+        let builder = builder_value.downcast_ref::<RpmBuilderValue>().unwrap();
+        assert!(builder.files.has_path("usr/bin/testapp"));
+        assert!(builder.files.has_path("usr/lib/myapp/testapp"));
+
+        env.eval("rpm.build('test_to_rpm_builder')")?;
+
+        let rpm_path = env
+            .target_build_path("test_to_rpm_builder")
+            .unwrap()
+            .join(builder.rpm_filename());
+
+        assert!(rpm_path.exists());
+
+        Ok(())
+    }
+
     #[cfg(windows)]
     #[test]
     fn test_to_wix_bundle_builder() -> Result<()> {
@@ -7,8 +7,9 @@ use {
     crate::py_packaging::config::PyembedPythonInterpreterConfig,
     python_packaging::{
         interpreter::{
-            Allocator, BytesWarning, CheckHashPycsMode, CoerceCLocale, MemoryAllocatorBackend,
-            MultiprocessingStartMethod, PythonInterpreterProfile, TerminfoResolution,
+            Allocator, BytesWarning, CheckHashPycsMode, CoerceCLocale, ImportProfileFormat,
+            MemoryAllocatorBackend, MultiprocessingStartMethod, PythonInterpreterProfile,
+            TerminfoResolution,
         },
         resource::BytecodeOptimizationLevel,
     },
@@ -217,6 +218,8 @@ impl TypedValue for PythonInterpreterConfigValue {
             "allocator_debug" => Value::from(inner.allocator_debug),
             "oxidized_importer" => Value::from(inner.oxidized_importer),
             "filesystem_importer" => Value::from(inner.filesystem_importer),
+            "packed_resources_verify" => Value::from(inner.packed_resources_verify),
+            "packed_resources_watch" => Value::from(inner.packed_resources_watch),
             "argvb" => Value::from(inner.argvb),
             "multiprocessing_auto_dispatch" => Value::from(inner.multiprocessing_auto_dispatch),
             "multiprocessing_start_method" => {
@@ -226,6 +229,17 @@ impl TypedValue for PythonInterpreterConfigValue {
             "sys_meipass" => Value::from(inner.sys_meipass),
             "terminfo_resolution" => inner.terminfo_resolution.to_value(),
             "write_modules_directory_env" => inner.write_modules_directory_env.to_value(),
+            "import_profile_path" => inner.import_profile_path.to_value(),
+            "import_profile_path_env" => inner.import_profile_path_env.to_value(),
+            "import_profile_format" => Value::from(inner.import_profile_format.to_string()),
+            "import_policy_allow" => inner.import_policy_allow.to_value(),
+            "import_policy_deny" => inner.import_policy_deny.to_value(),
+            "crash_handler" => Value::from(inner.crash_handler),
+            "crash_handler_fd" => match inner.crash_handler_fd {
+                Some(fd) => Value::from(i64::from(fd)),
+                None => Value::from(NoneType::None),
+            },
+            "crash_report_directory" => inner.crash_report_directory.to_value(),
             attr => {
                 return Err(ValueError::OperationNotSupported {
                     op: UnsupportedOperation::GetAttr(attr.to_string()),
@@ -304,6 +318,8 @@ impl TypedValue for PythonInterpreterConfigValue {
                 | "allocator_debug"
                 | "oxidized_importer"
                 | "filesystem_importer"
+                | "packed_resources_verify"
+                | "packed_resources_watch"
                 | "argvb"
                 | "multiprocessing_auto_dispatch"
                 | "multiprocessing_start_method"
@@ -311,6 +327,14 @@ impl TypedValue for PythonInterpreterConfigValue {
                 | "sys_meipass"
                 | "terminfo_resolution"
                 | "write_modules_directory_env"
+                | "import_profile_path"
+                | "import_profile_path_env"
+                | "import_profile_format"
+                | "import_policy_allow"
+                | "import_policy_deny"
+                | "crash_handler"
+                | "crash_handler_fd"
+                | "crash_report_directory"
         ))
     }
 
@@ -580,6 +604,12 @@ impl TypedValue for PythonInterpreterConfigValue {
             "filesystem_importer" => {
                 inner.filesystem_importer = value.to_bool();
             }
+            "packed_resources_verify" => {
+                inner.packed_resources_verify = value.to_bool();
+            }
+            "packed_resources_watch" => {
+                inner.packed_resources_watch = value.to_bool();
+            }
             "argvb" => {
                 inner.argvb = value.to_bool();
             }
@@ -617,6 +647,45 @@ impl TypedValue for PythonInterpreterConfigValue {
             "write_modules_directory_env" => {
                 inner.write_modules_directory_env = value.to_optional();
             }
+            "import_profile_path" => {
+                inner.import_profile_path = value.to_optional();
+            }
+            "import_profile_path_env" => {
+                inner.import_profile_path_env = value.to_optional();
+            }
+            "import_profile_format" => {
+                inner.import_profile_format =
+                    ImportProfileFormat::try_from(value.to_string().as_str()).map_err(|e| {
+                        ValueError::from(RuntimeError {
+                            code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                            message: e,
+                            label: format!("{}.{}", Self::TYPE, attribute),
+                        })
+                    })?;
+            }
+            "import_policy_allow" => {
+                inner.import_policy_allow = value.try_to_optional()?;
+            }
+            "import_policy_deny" => {
+                inner.import_policy_deny = value.try_to_optional()?;
+            }
+            "crash_handler" => {
+                inner.crash_handler = value.to_bool();
+            }
+            "crash_handler_fd" => {
+                let fd: Option<i64> = value.try_to_optional()?;
+
+                inner.crash_handler_fd = fd.map(i32::try_from).transpose().map_err(|_| {
+                    ValueError::from(RuntimeError {
+                        code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                        message: "file descriptor out of range".to_string(),
+                        label: format!("{}.{}", Self::TYPE, attribute),
+                    })
+                })?;
+            }
+            "crash_report_directory" => {
+                inner.crash_report_directory = value.to_optional();
+            }
             attr => {
                 return Err(ValueError::OperationNotSupported {
                     op: UnsupportedOperation::SetAttr(attr.to_string()),
@@ -1212,6 +1281,18 @@ mod tests {
         env.eval("config.allocator_backend = 'snmalloc'")?;
         eval_assert(&mut env, "config.allocator_backend == 'snmalloc'")?;
 
+        env.eval("config.allocator_backend = 'instrumented-jemalloc'")?;
+        eval_assert(
+            &mut env,
+            "config.allocator_backend == 'instrumented-jemalloc'",
+        )?;
+
+        env.eval("config.allocator_backend = 'instrumented-default'")?;
+        eval_assert(
+            &mut env,
+            "config.allocator_backend == 'instrumented-default'",
+        )?;
+
         env.eval("config.allocator_backend = 'default'")?;
         eval_assert(&mut env, "config.allocator_backend == 'default'")?;
 
@@ -1296,6 +1377,28 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_packed_resources_verify() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.packed_resources_verify == False")?;
+        env.eval("config.packed_resources_verify = True")?;
+        eval_assert(&mut env, "config.packed_resources_verify == True")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_packed_resources_watch() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.packed_resources_watch == False")?;
+        env.eval("config.packed_resources_watch = True")?;
+        eval_assert(&mut env, "config.packed_resources_watch == True")?;
+
+        Ok(())
+    }
+
     #[test]
     fn test_argvb() -> Result<()> {
         let mut env = get_env()?;
@@ -1385,4 +1488,83 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_import_profile() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.import_profile_path == None")?;
+        eval_assert(&mut env, "config.import_profile_path_env == None")?;
+        eval_assert(&mut env, "config.import_profile_format == 'json'")?;
+
+        env.eval("config.import_profile_path = '$ORIGIN/imports.json'")?;
+        eval_assert(
+            &mut env,
+            "config.import_profile_path == '$ORIGIN/imports.json'",
+        )?;
+
+        env.eval("config.import_profile_path_env = 'IMPORT_PROFILE'")?;
+        eval_assert(
+            &mut env,
+            "config.import_profile_path_env == 'IMPORT_PROFILE'",
+        )?;
+
+        env.eval("config.import_profile_format = 'chrome-trace'")?;
+        eval_assert(&mut env, "config.import_profile_format == 'chrome-trace'")?;
+
+        assert!(env.eval("config.import_profile_format = 'bad'").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_policy() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.import_policy_allow == None")?;
+        eval_assert(&mut env, "config.import_policy_deny == None")?;
+
+        env.eval("config.import_policy_allow = ['encodings', 'json']")?;
+        eval_assert(
+            &mut env,
+            "config.import_policy_allow == ['encodings', 'json']",
+        )?;
+
+        env.eval("config.import_policy_deny = ['ctypes', 'subprocess']")?;
+        eval_assert(
+            &mut env,
+            "config.import_policy_deny == ['ctypes', 'subprocess']",
+        )?;
+
+        env.eval("config.import_policy_deny = None")?;
+        eval_assert(&mut env, "config.import_policy_deny == None")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_crash_handler() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.crash_handler == False")?;
+        eval_assert(&mut env, "config.crash_handler_fd == None")?;
+        eval_assert(&mut env, "config.crash_report_directory == None")?;
+
+        env.eval("config.crash_handler = True")?;
+        eval_assert(&mut env, "config.crash_handler == True")?;
+
+        env.eval("config.crash_handler_fd = 3")?;
+        eval_assert(&mut env, "config.crash_handler_fd == 3")?;
+
+        assert!(env.eval("config.crash_handler_fd = 'bad'").is_err());
+        assert!(env.eval("config.crash_handler_fd = 4294967296").is_err());
+
+        env.eval("config.crash_report_directory = '$ORIGIN/crashes'")?;
+        eval_assert(
+            &mut env,
+            "config.crash_report_directory == '$ORIGIN/crashes'",
+        )?;
+
+        Ok(())
+    }
 }
@@ -3,9 +3,12 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use {
-    crate::{interpreter::run_python, yaml::run_yaml_path},
+    crate::{
+        interpreter::{run_module, run_python, run_zipapp, Config},
+        yaml::run_yaml_path,
+    },
     anyhow::{anyhow, Context, Result},
-    clap::{Arg, Command},
+    clap::{Arg, ArgMatches, Command},
     std::path::{Path, PathBuf},
 };
 
@@ -18,13 +21,44 @@ pub fn run() -> Result<i32> {
     // `pyoxy run-python -- <args>`.
     if let Some(stem) = exe.file_stem() {
         if stem.to_string_lossy().starts_with("python") {
-            return run_python(&exe, &std::env::args_os().skip(1).collect::<Vec<_>>());
+            return run_python(
+                &exe,
+                Config::default(),
+                &std::env::args_os().skip(1).collect::<Vec<_>>(),
+            );
         }
     }
 
     run_normal(&exe)
 }
 
+fn config_arg() -> Arg<'static> {
+    Arg::new("config")
+        .long("config")
+        .takes_value(true)
+        .value_name("FILE")
+        .allow_invalid_utf8(true)
+        .help("YAML or TOML file defining the Python interpreter configuration")
+}
+
+fn program_args_arg() -> Arg<'static> {
+    Arg::new("args")
+        .help("Arguments to Python interpreter")
+        .multiple_occurrences(true)
+        .multiple_values(true)
+        .allow_invalid_utf8(true)
+        .last(true)
+}
+
+/// Resolve the interpreter config from the `--config` argument.
+fn config_from_args(args: &ArgMatches) -> Result<Config<'static>> {
+    if let Some(path) = args.value_of_os("config") {
+        Config::from_path(Path::new(path))
+    } else {
+        Ok(Config::default())
+    }
+}
+
 fn run_normal(exe: &Path) -> Result<i32> {
     let app = Command::new("pyoxy")
         .version(PYOXY_VERSION)
@@ -34,14 +68,46 @@ fn run_normal(exe: &Path) -> Result<i32> {
     let app = app.subcommand(
         Command::new("run-python")
             .about("Make the executable behave like a `python` executable")
+            .arg(config_arg())
+            .arg(program_args_arg()),
+    );
+
+    let app = app.subcommand(
+        Command::new("run-module")
+            .about("Run a Python module as the main module, like `python -m`")
+            .arg_required_else_help(true)
+            .arg(
+                Arg::new("module")
+                    .value_name("MODULE")
+                    .required(true)
+                    .help("Name of module to run"),
+            )
+            .arg(config_arg())
             .arg(
-                Arg::new("args")
-                    .help("Arguments to Python interpreter")
+                Arg::new("packed_resources")
+                    .long("packed-resources")
+                    .takes_value(true)
                     .multiple_occurrences(true)
-                    .multiple_values(true)
+                    .value_name("FILE")
                     .allow_invalid_utf8(true)
-                    .last(true),
-            ),
+                    .help("Packed resources file to index before running the module"),
+            )
+            .arg(program_args_arg().help("Arguments to module")),
+    );
+
+    let app = app.subcommand(
+        Command::new("run-zipapp")
+            .about("Run a Python zipapp (e.g. a .pyz file)")
+            .arg_required_else_help(true)
+            .arg(
+                Arg::new("zipapp_path")
+                    .value_name("FILE")
+                    .required(true)
+                    .allow_invalid_utf8(true)
+                    .help("Path to zipapp to run"),
+            )
+            .arg(config_arg())
+            .arg(program_args_arg().help("Arguments to application")),
     );
 
     let app = app.subcommand(
@@ -54,14 +120,7 @@ fn run_normal(exe: &Path) -> Result<i32> {
                     .allow_invalid_utf8(true)
                     .help("Path to YAML file to evaluate"),
             )
-            .arg(
-                Arg::new("args")
-                    .help("Arguments to Python interpreter")
-                    .multiple_occurrences(true)
-                    .multiple_values(true)
-                    .allow_invalid_utf8(true)
-                    .last(true),
-            ),
+            .arg(program_args_arg()),
     );
 
     let matches = app.get_matches();
@@ -73,7 +132,39 @@ fn run_normal(exe: &Path) -> Result<i32> {
                 .unwrap_or_default()
                 .collect::<Vec<_>>();
 
-            run_python(exe, &program_args)
+            run_python(exe, config_from_args(args)?, &program_args)
+        }
+        Some(("run-module", args)) => {
+            let module = args.value_of("module").expect("module should be set");
+            let packed_resources = args
+                .values_of_os("packed_resources")
+                .unwrap_or_default()
+                .map(PathBuf::from)
+                .collect::<Vec<_>>();
+            let program_args = args
+                .values_of_os("args")
+                .unwrap_or_default()
+                .collect::<Vec<_>>();
+
+            run_module(
+                exe,
+                config_from_args(args)?,
+                module,
+                &packed_resources,
+                &program_args,
+            )
+        }
+        Some(("run-zipapp", args)) => {
+            let zipapp_path = PathBuf::from(
+                args.value_of_os("zipapp_path")
+                    .expect("zipapp_path should be set"),
+            );
+            let program_args = args
+                .values_of_os("args")
+                .unwrap_or_default()
+                .collect::<Vec<_>>();
+
+            run_zipapp(exe, config_from_args(args)?, &zipapp_path, &program_args)
         }
         Some(("run-yaml", args)) => {
             let yaml_path = PathBuf::from(
@@ -9,7 +9,7 @@ use {
     std::{
         ffi::{OsStr, OsString},
         ops::{Deref, DerefMut},
-        path::Path,
+        path::{Path, PathBuf},
     },
 };
 
@@ -48,6 +48,33 @@ impl<'a> From<Config<'a>> for OxidizedPythonInterpreterConfig<'a> {
 }
 
 impl<'a> Config<'a> {
+    /// Load a config from a YAML or TOML file.
+    ///
+    /// Files having a `.toml` extension are parsed as TOML. All other files are
+    /// parsed as YAML.
+    ///
+    /// Unless the config defines `origin`, it is set to the directory containing
+    /// the file. So `$ORIGIN` in paths refers to the config file's directory.
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let data = std::fs::read_to_string(path)
+            .with_context(|| format!("reading config file {}", path.display()))?;
+
+        let mut config: Config = if path.extension() == Some(OsStr::new("toml")) {
+            toml::from_str(&data).context("parsing TOML to data structure")?
+        } else {
+            serde_yaml::from_str(&data).context("parsing YAML to data structure")?
+        };
+
+        if config.origin.is_none() {
+            config.origin = std::fs::canonicalize(path)
+                .with_context(|| format!("resolving path to {}", path.display()))?
+                .parent()
+                .map(|p| p.to_path_buf());
+        }
+
+        Ok(config)
+    }
+
     /// Apply the current environment's settings to this config.
     pub fn apply_environment(&mut self) {
         if let Some(resources) = STDLIB_RESOURCES_DATA {
@@ -78,22 +105,106 @@ impl<'a> Config<'a> {
     }
 }
 
-/// Runs an embedded Python interpreter in `python` mode.
-pub fn run_python<T>(exe: &Path, args: &[T]) -> Result<i32>
+/// Python code run by `pyoxy run-zipapp`.
+///
+/// This emulates how `python` runs a zipapp: `sys.argv[0]` and `sys.path[0]` are
+/// the path to the archive and the `__main__` module is run as the main module.
+/// `__main__` is found via `OxidizedFinder`, which indexes the archive.
+const ZIPAPP_MAIN_COMMAND: &str = "import runpy, sys; sys.argv.pop(0); sys.path.insert(0, sys.argv[0]); runpy._run_module_as_main('__main__', False)";
+
+/// Construct `argv` from leading arguments and additional arguments.
+fn argv<T>(leading: &[&OsStr], args: &[T]) -> Vec<OsString>
 where
-    T: Into<OsString> + AsRef<OsStr>,
+    T: AsRef<OsStr>,
 {
-    let mut config = Config::default();
-    config.apply_environment();
-    config.exe = Some(exe.to_path_buf());
-    config.argv = Some(
-        vec![exe.as_os_str().to_os_string()]
-            .into_iter()
-            .chain(args.iter().map(|x| x.into()))
-            .collect::<Vec<_>>(),
-    );
+    leading
+        .iter()
+        .map(|x| x.to_os_string())
+        .chain(args.iter().map(|x| x.as_ref().to_os_string()))
+        .collect::<Vec<_>>()
+}
+
+/// Spawn an interpreter from a config and run it to completion.
+fn run_config(mut config: Config, exe: &Path) -> Result<i32> {
+    if config.exe.is_none() {
+        config.exe = Some(exe.to_path_buf());
+    }
 
     let interp =
         MainPythonInterpreter::new(config.into()).context("initializing Python interpreter")?;
     Ok(interp.run())
 }
+
+/// Runs an embedded Python interpreter in `python` mode.
+pub fn run_python<T>(exe: &Path, mut config: Config, args: &[T]) -> Result<i32>
+where
+    T: Into<OsString> + AsRef<OsStr>,
+{
+    config.apply_environment();
+    config.argv = Some(argv(&[exe.as_os_str()], args));
+
+    run_config(config, exe)
+}
+
+/// Runs a module as the main module, like `python -m`.
+///
+/// Packed resources files are indexed by `OxidizedFinder` before the module
+/// is imported. Arguments are passed to the module and not interpreted by
+/// Python.
+pub fn run_module<T>(
+    exe: &Path,
+    mut config: Config,
+    module: &str,
+    packed_resources: &[PathBuf],
+    args: &[T],
+) -> Result<i32>
+where
+    T: AsRef<OsStr>,
+{
+    config.apply_environment();
+
+    for path in packed_resources {
+        let path = std::fs::canonicalize(path)
+            .with_context(|| format!("resolving path to {}", path.display()))?;
+
+        config
+            .packed_resources
+            .push(PackedResourcesSource::MemoryMappedPath(path));
+        config.oxidized_importer = true;
+    }
+
+    config.interpreter_config.run_command = None;
+    config.interpreter_config.run_module = Some(module.to_string());
+    config.interpreter_config.parse_argv = Some(false);
+    // Python forces `sys.argv[0]` to `-m` when running a module. This also
+    // makes it put the current directory on `sys.path`.
+    config.argv = Some(argv(&[OsStr::new("-m")], args));
+
+    run_config(config, exe)
+}
+
+/// Runs a zipapp (e.g. a `.pyz` file), like `python <path>`.
+///
+/// The archive is indexed by `OxidizedFinder` and its `__main__` module is run.
+/// Arguments are passed to the application and not interpreted by Python.
+pub fn run_zipapp<T>(exe: &Path, mut config: Config, zipapp_path: &Path, args: &[T]) -> Result<i32>
+where
+    T: AsRef<OsStr>,
+{
+    let zipapp_path = std::fs::canonicalize(zipapp_path)
+        .with_context(|| format!("resolving path to {}", zipapp_path.display()))?;
+
+    config.apply_environment();
+
+    config
+        .packed_resources
+        .push(PackedResourcesSource::ZipPath(zipapp_path.clone()));
+    config.oxidized_importer = true;
+
+    config.interpreter_config.run_command = Some(ZIPAPP_MAIN_COMMAND.to_string());
+    config.interpreter_config.parse_argv = Some(false);
+    // ZIPAPP_MAIN_COMMAND removes the first argument.
+    config.argv = Some(argv(&[OsStr::new("-c"), zipapp_path.as_os_str()], args));
+
+    run_config(config, exe)
+}
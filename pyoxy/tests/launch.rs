@@ -0,0 +1,101 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    anyhow::Result,
+    assert_cmd::Command,
+    libtest_mimic::{run_tests, Arguments, Outcome, Test},
+    predicates::prelude::*,
+};
+
+const APP_MAIN: &str = "import sys\nprint('hello from', __name__, sys.argv[1:])\n";
+
+fn run() -> Result<()> {
+    let td = tempfile::Builder::new().prefix("pyoxy-test-").tempdir()?;
+
+    let app_dir = td.path().join("app");
+    std::fs::create_dir(&app_dir)?;
+    std::fs::write(app_dir.join("__main__.py"), APP_MAIN)?;
+    std::fs::write(td.path().join("hello.py"), APP_MAIN)?;
+
+    // Modules are found relative to the current directory, like `python -m`.
+    // Arguments go to the module.
+    Command::cargo_bin("pyoxy")?
+        .current_dir(td.path())
+        .arg("run-module")
+        .arg("hello")
+        .arg("--")
+        .arg("-c")
+        .arg("foo")
+        .assert()
+        .success()
+        .stdout(predicate::eq("hello from __main__ ['-c', 'foo']\n").normalize());
+
+    // Config files can be YAML or TOML.
+    std::fs::write(
+        td.path().join("config.yaml"),
+        "interpreter_config:\n  write_bytecode: false\n",
+    )?;
+    std::fs::write(
+        td.path().join("config.toml"),
+        "[interpreter_config]\nwrite_bytecode = false\n",
+    )?;
+
+    for config in ["config.yaml", "config.toml"] {
+        Command::cargo_bin("pyoxy")?
+            .current_dir(td.path())
+            .arg("run-python")
+            .arg("--config")
+            .arg(config)
+            .arg("--")
+            .arg("-c")
+            .arg("import sys; print(sys.flags.dont_write_bytecode)")
+            .assert()
+            .success()
+            .stdout(predicate::eq("1\n").normalize());
+    }
+
+    // Build a zipapp with the standard library's zipapp module and run it.
+    Command::cargo_bin("pyoxy")?
+        .current_dir(td.path())
+        .arg("run-module")
+        .arg("zipapp")
+        .arg("--")
+        .arg("app")
+        .arg("-o")
+        .arg("app.pyz")
+        .assert()
+        .success();
+
+    Command::cargo_bin("pyoxy")?
+        .current_dir(td.path())
+        .arg("run-zipapp")
+        .arg("app.pyz")
+        .arg("--")
+        .arg("bar")
+        .assert()
+        .success()
+        .stdout(predicate::eq("hello from __main__ ['bar']\n").normalize());
+
+    Ok(())
+}
+
+fn main() {
+    let args = Arguments::from_args();
+
+    // libtest_mimic doesn't properly handle `--list --ignored`.
+    let tests: Vec<Test<()>> = if args.ignored {
+        vec![]
+    } else {
+        vec![Test::test("main")]
+    };
+
+    run_tests(&args, tests, |_| match run() {
+        Ok(_) => Outcome::Passed,
+        Err(e) => Outcome::Failed {
+            msg: Some(format!("{:?}", e)),
+        },
+    })
+    .exit();
+}
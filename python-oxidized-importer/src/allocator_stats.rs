@@ -0,0 +1,240 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Statistics for instrumented Python memory allocators.
+
+The counters live in process-global storage so allocator functions installed
+before the interpreter is initialized can record into them and so they can be
+queried from Python via `oxidized_importer.allocator_stats()`.
+
+The raw domain allocator is called without the GIL held. So all counters are
+atomics.
+*/
+
+use {
+    pyo3::{prelude::*, types::PyDict},
+    std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+};
+
+/// Number of buckets in the allocation size histogram.
+///
+/// Bucket 0 holds 0 byte allocations. Bucket `n` holds allocations of at least
+/// `2^(n-1)` bytes and less than `2^n` bytes. The last bucket also holds all
+/// larger allocations.
+pub const ALLOCATION_SIZE_BUCKETS: usize = 24;
+
+/// A Python memory allocator domain.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AllocatorDomain {
+    /// `PYMEM_DOMAIN_RAW`.
+    Raw,
+    /// `PYMEM_DOMAIN_MEM`.
+    Mem,
+    /// `PYMEM_DOMAIN_OBJ`.
+    Obj,
+}
+
+impl AllocatorDomain {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Raw => "raw",
+            Self::Mem => "mem",
+            Self::Obj => "obj",
+        }
+    }
+
+    /// Obtain the counters for this domain.
+    pub fn counters(&self) -> &'static AllocatorDomainCounters {
+        match self {
+            Self::Raw => &RAW_COUNTERS,
+            Self::Mem => &MEM_COUNTERS,
+            Self::Obj => &OBJ_COUNTERS,
+        }
+    }
+}
+
+/// Records allocations performed in an allocator domain.
+pub struct AllocatorDomainCounters {
+    enabled: AtomicBool,
+    allocations: AtomicU64,
+    reallocations: AtomicU64,
+    deallocations: AtomicU64,
+    allocated_bytes: AtomicU64,
+    current_bytes: AtomicUsize,
+    peak_bytes: AtomicUsize,
+    size_histogram: [AtomicU64; ALLOCATION_SIZE_BUCKETS],
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO: AtomicU64 = AtomicU64::new(0);
+
+impl AllocatorDomainCounters {
+    const fn new() -> Self {
+        Self {
+            enabled: AtomicBool::new(false),
+            allocations: AtomicU64::new(0),
+            reallocations: AtomicU64::new(0),
+            deallocations: AtomicU64::new(0),
+            allocated_bytes: AtomicU64::new(0),
+            current_bytes: AtomicUsize::new(0),
+            peak_bytes: AtomicUsize::new(0),
+            size_histogram: [ZERO; ALLOCATION_SIZE_BUCKETS],
+        }
+    }
+
+    /// Mark the domain as instrumented.
+    pub fn enable(&self) {
+        self.enabled.store(true, Ordering::Relaxed);
+    }
+
+    fn record_size(&self, size: usize) {
+        let bucket =
+            ((usize::BITS - size.leading_zeros()) as usize).min(ALLOCATION_SIZE_BUCKETS - 1);
+
+        self.size_histogram[bucket].fetch_add(1, Ordering::Relaxed);
+        self.allocated_bytes
+            .fetch_add(size as u64, Ordering::Relaxed);
+    }
+
+    fn grow(&self, size: usize) {
+        let current = self.current_bytes.fetch_add(size, Ordering::Relaxed) + size;
+        self.peak_bytes.fetch_max(current, Ordering::Relaxed);
+    }
+
+    /// Record a new allocation of `size` bytes.
+    pub fn record_allocation(&self, size: usize) {
+        self.allocations.fetch_add(1, Ordering::Relaxed);
+        self.record_size(size);
+        self.grow(size);
+    }
+
+    /// Record the resizing of an allocation from `old_size` to `new_size` bytes.
+    pub fn record_reallocation(&self, old_size: usize, new_size: usize) {
+        self.reallocations.fetch_add(1, Ordering::Relaxed);
+        self.record_size(new_size);
+
+        if new_size > old_size {
+            self.grow(new_size - old_size);
+        } else {
+            self.current_bytes
+                .fetch_sub(old_size - new_size, Ordering::Relaxed);
+        }
+    }
+
+    /// Record the release of an allocation of `size` bytes.
+    pub fn record_deallocation(&self, size: usize) {
+        self.deallocations.fetch_add(1, Ordering::Relaxed);
+        self.current_bytes.fetch_sub(size, Ordering::Relaxed);
+    }
+
+    /// Obtain a snapshot of the counters.
+    ///
+    /// Returns `None` if the domain isn't instrumented.
+    pub fn stats(&self) -> Option<AllocatorDomainStats> {
+        if !self.enabled.load(Ordering::Relaxed) {
+            return None;
+        }
+
+        let mut size_histogram = [0; ALLOCATION_SIZE_BUCKETS];
+        for (dest, count) in size_histogram.iter_mut().zip(self.size_histogram.iter()) {
+            *dest = count.load(Ordering::Relaxed);
+        }
+
+        Some(AllocatorDomainStats {
+            allocations: self.allocations.load(Ordering::Relaxed),
+            reallocations: self.reallocations.load(Ordering::Relaxed),
+            deallocations: self.deallocations.load(Ordering::Relaxed),
+            allocated_bytes: self.allocated_bytes.load(Ordering::Relaxed),
+            current_bytes: self.current_bytes.load(Ordering::Relaxed),
+            peak_bytes: self.peak_bytes.load(Ordering::Relaxed),
+            size_histogram,
+        })
+    }
+}
+
+static RAW_COUNTERS: AllocatorDomainCounters = AllocatorDomainCounters::new();
+static MEM_COUNTERS: AllocatorDomainCounters = AllocatorDomainCounters::new();
+static OBJ_COUNTERS: AllocatorDomainCounters = AllocatorDomainCounters::new();
+
+/// Statistics about allocations performed in an allocator domain.
+///
+/// Counters are updated independently of each other. So values in a snapshot
+/// taken while other threads are allocating may be slightly inconsistent.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AllocatorDomainStats {
+    /// Number of `malloc()` and `calloc()` calls.
+    pub allocations: u64,
+    /// Number of `realloc()` calls on existing allocations.
+    pub reallocations: u64,
+    /// Number of `free()` calls on existing allocations.
+    pub deallocations: u64,
+    /// Total bytes requested by allocations and reallocations.
+    pub allocated_bytes: u64,
+    /// Bytes currently allocated.
+    pub current_bytes: usize,
+    /// Highest value of `current_bytes` seen.
+    pub peak_bytes: usize,
+    /// Counts of allocations and reallocations by requested size.
+    ///
+    /// See [ALLOCATION_SIZE_BUCKETS] for the meaning of each bucket.
+    pub size_histogram: [u64; ALLOCATION_SIZE_BUCKETS],
+}
+
+impl AllocatorDomainStats {
+    /// Iterate over `(minimum size, count)` pairs of the size histogram.
+    pub fn size_histogram_buckets(&self) -> impl Iterator<Item = (usize, u64)> + '_ {
+        self.size_histogram
+            .iter()
+            .enumerate()
+            .map(|(i, count)| (if i == 0 { 0 } else { 1 << (i - 1) }, *count))
+    }
+}
+
+/// Obtain statistics for an instrumented allocator domain.
+///
+/// Returns `None` if the domain isn't instrumented.
+pub fn allocator_stats(domain: AllocatorDomain) -> Option<AllocatorDomainStats> {
+    domain.counters().stats()
+}
+
+/// Obtain statistics for instrumented allocator domains.
+///
+/// Returns a dict keyed by domain name (`raw`, `mem`, `obj`). Domains that
+/// aren't instrumented are absent.
+#[pyfunction]
+#[pyo3(name = "allocator_stats")]
+fn py_allocator_stats(py: Python) -> PyResult<&PyDict> {
+    let result = PyDict::new(py);
+
+    for domain in [
+        AllocatorDomain::Raw,
+        AllocatorDomain::Mem,
+        AllocatorDomain::Obj,
+    ] {
+        if let Some(stats) = allocator_stats(domain) {
+            let d = PyDict::new(py);
+            d.set_item("allocations", stats.allocations)?;
+            d.set_item("reallocations", stats.reallocations)?;
+            d.set_item("deallocations", stats.deallocations)?;
+            d.set_item("allocated_bytes", stats.allocated_bytes)?;
+            d.set_item("current_bytes", stats.current_bytes)?;
+            d.set_item("peak_bytes", stats.peak_bytes)?;
+            d.set_item(
+                "size_histogram",
+                stats.size_histogram_buckets().collect::<Vec<_>>(),
+            )?;
+
+            result.set_item(domain.as_str(), d)?;
+        }
+    }
+
+    Ok(result)
+}
+
+pub(crate) fn init_module(m: &PyModule) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(py_allocator_stats, m)?)?;
+
+    Ok(())
+}
@@ -0,0 +1,58 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Restricting which modules `OxidizedFinder` is allowed to import.
+*/
+
+/// Name of the `sys.audit()` event raised when an import is denied.
+pub const IMPORT_DENIED_AUDIT_EVENT: &str = "oxidized_importer.import_denied";
+
+/// Whether a module name is matched by a policy pattern.
+///
+/// A pattern matches the module having that name and all of its submodules.
+fn pattern_matches(pattern: &str, name: &str) -> bool {
+    match name.strip_prefix(pattern) {
+        Some(rest) => rest.is_empty() || rest.starts_with('.'),
+        None => false,
+    }
+}
+
+/// Defines which modules may be imported.
+///
+/// Deny patterns take precedence over allow patterns. If no allow patterns are
+/// defined, all modules not matching a deny pattern may be imported.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ImportPolicy {
+    allow: Option<Vec<String>>,
+    deny: Vec<String>,
+}
+
+impl ImportPolicy {
+    /// Construct a new instance from allow and deny patterns.
+    pub fn new(allow: Option<Vec<String>>, deny: Vec<String>) -> Self {
+        Self { allow, deny }
+    }
+
+    /// Determine why the import of a module is denied.
+    ///
+    /// Returns `None` if the module may be imported.
+    pub fn denial_reason(&self, name: &str) -> Option<String> {
+        if let Some(pattern) = self.deny.iter().find(|p| pattern_matches(p, name)) {
+            return Some(format!("matches deny pattern {}", pattern));
+        }
+
+        match &self.allow {
+            Some(allow) if !allow.iter().any(|p| pattern_matches(p, name)) => {
+                Some("does not match any allow pattern".to_string())
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the import of a module is allowed.
+    pub fn is_allowed(&self, name: &str) -> bool {
+        self.denial_reason(name).is_none()
+    }
+}
@@ -0,0 +1,227 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Recording of timing information for imports serviced by `OxidizedFinder`.
+*/
+
+use {
+    crate::python_resources::{ImportablePythonModule, ModuleFlavor},
+    python_packaging::interpreter::ImportProfileFormat,
+    std::{
+        fmt::Write as FmtWrite,
+        io::Write,
+        path::Path,
+        sync::Mutex,
+        time::{Duration, Instant},
+    },
+};
+
+/// The phase of an import an event was recorded for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImportPhase {
+    /// `find_spec()`.
+    Find,
+    /// `create_module()`.
+    Load,
+    /// `exec_module()`.
+    Exec,
+}
+
+impl ImportPhase {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Find => "find",
+            Self::Load => "load",
+            Self::Exec => "exec",
+        }
+    }
+}
+
+/// Where the code of an imported module came from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImportSource {
+    /// Module data is held in memory.
+    Memory,
+    /// Module data is read from the filesystem.
+    Filesystem,
+    /// A built-in extension module.
+    Builtin,
+    /// A frozen module.
+    Frozen,
+}
+
+impl ImportSource {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Memory => "memory",
+            Self::Filesystem => "filesystem",
+            Self::Builtin => "builtin",
+            Self::Frozen => "frozen",
+        }
+    }
+}
+
+impl<'a> From<&ImportablePythonModule<'a, u8>> for ImportSource {
+    fn from(module: &ImportablePythonModule<'a, u8>) -> Self {
+        match module.flavor {
+            ModuleFlavor::Builtin => Self::Builtin,
+            ModuleFlavor::Frozen => Self::Frozen,
+            ModuleFlavor::Extension | ModuleFlavor::SourceBytecode => {
+                if module.is_in_memory() {
+                    Self::Memory
+                } else {
+                    Self::Filesystem
+                }
+            }
+        }
+    }
+}
+
+/// A timed phase of the import of a module.
+#[derive(Clone, Debug)]
+pub struct ImportEvent {
+    /// Name of the module being imported.
+    pub name: String,
+    /// The phase of the import.
+    pub phase: ImportPhase,
+    /// Where the module came from.
+    pub source: ImportSource,
+    /// When the phase started, relative to the creation of the profiler.
+    pub start: Duration,
+    /// How long the phase took.
+    ///
+    /// Durations of the `exec` phase include the time spent importing
+    /// modules imported by the module.
+    pub duration: Duration,
+}
+
+/// Records timing of imports serviced by `OxidizedFinder`.
+#[derive(Debug)]
+pub struct ImportProfiler {
+    epoch: Instant,
+    events: Mutex<Vec<ImportEvent>>,
+}
+
+impl Default for ImportProfiler {
+    fn default() -> Self {
+        Self {
+            epoch: Instant::now(),
+            events: Mutex::new(vec![]),
+        }
+    }
+}
+
+impl ImportProfiler {
+    /// Record an import phase that started at `start` and ended now.
+    pub fn record(&self, name: &str, phase: ImportPhase, source: ImportSource, start: Instant) {
+        let event = ImportEvent {
+            name: name.to_string(),
+            phase,
+            source,
+            start: start.saturating_duration_since(self.epoch),
+            duration: start.elapsed(),
+        };
+
+        if let Ok(mut events) = self.events.lock() {
+            events.push(event);
+        }
+    }
+
+    /// Obtain a copy of recorded events, in the order they completed.
+    pub fn events(&self) -> Vec<ImportEvent> {
+        match self.events.lock() {
+            Ok(events) => events.clone(),
+            Err(_) => vec![],
+        }
+    }
+
+    /// Serialize recorded events to a JSON document.
+    ///
+    /// The document is an object with an `imports` key holding an array of
+    /// objects having `name`, `phase`, `source`, `start_us`, and `duration_us`
+    /// keys. Times are in microseconds.
+    pub fn to_json(&self) -> String {
+        let mut s = String::from("{\n  \"imports\": [");
+
+        for (i, event) in self.events().iter().enumerate() {
+            let _ = write!(
+                s,
+                "{}\n    {{\"name\": {}, \"phase\": \"{}\", \"source\": \"{}\", \"start_us\": {}, \"duration_us\": {}}}",
+                if i == 0 { "" } else { "," },
+                json_string(&event.name),
+                event.phase.as_str(),
+                event.source.as_str(),
+                event.start.as_micros(),
+                event.duration.as_micros(),
+            );
+        }
+
+        s.push_str("\n  ]\n}\n");
+
+        s
+    }
+
+    /// Serialize recorded events to a Chrome trace event format JSON document.
+    ///
+    /// Each event is a complete (`X`) event whose category is the import phase.
+    pub fn to_chrome_trace(&self) -> String {
+        let pid = std::process::id();
+        let mut s = String::from("{\"traceEvents\": [");
+
+        for (i, event) in self.events().iter().enumerate() {
+            let _ = write!(
+                s,
+                "{}\n  {{\"name\": {}, \"cat\": \"{}\", \"ph\": \"X\", \"ts\": {}, \"dur\": {}, \"pid\": {}, \"tid\": 1, \"args\": {{\"source\": \"{}\"}}}}",
+                if i == 0 { "" } else { "," },
+                json_string(&event.name),
+                event.phase.as_str(),
+                event.start.as_micros(),
+                event.duration.as_micros(),
+                pid,
+                event.source.as_str(),
+            );
+        }
+
+        s.push_str("\n], \"displayTimeUnit\": \"ms\"}\n");
+
+        s
+    }
+
+    /// Write recorded events to a file in the given format.
+    pub fn write_to_path(
+        &self,
+        path: impl AsRef<Path>,
+        format: ImportProfileFormat,
+    ) -> std::io::Result<()> {
+        let data = match format {
+            ImportProfileFormat::Json => self.to_json(),
+            ImportProfileFormat::ChromeTrace => self.to_chrome_trace(),
+        };
+
+        let mut fh = std::fs::File::create(path.as_ref())?;
+        fh.write_all(data.as_bytes())
+    }
+}
+
+/// Quote a string for use in a JSON document.
+fn json_string(value: &str) -> String {
+    let mut s = String::with_capacity(value.len() + 2);
+    s.push('"');
+
+    for c in value.chars() {
+        match c {
+            '"' => s.push_str("\\\""),
+            '\\' => s.push_str("\\\\"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(s, "\\u{:04x}", c as u32);
+            }
+            c => s.push(c),
+        }
+    }
+
+    s.push('"');
+
+    s
+}
@@ -19,6 +19,8 @@ use {
     crate::{
         conversion::pyobject_to_pathbuf,
         get_module_state,
+        import_policy::{ImportPolicy, IMPORT_DENIED_AUDIT_EVENT},
+        import_profiler::{ImportPhase, ImportProfiler, ImportSource},
         path_entry_finder::OxidizedPathEntryFinder,
         pkg_resources::register_pkg_resources_with_module,
         python_resources::{
@@ -35,7 +37,7 @@ use {
         AsPyPointer, FromPyPointer, PyNativeType, PyTraverseError, PyVisit,
     },
     python_packaging::resource::BytecodeOptimizationLevel,
-    std::sync::Arc,
+    std::{sync::Arc, time::Instant},
 };
 
 #[cfg(windows)]
@@ -271,6 +273,10 @@ pub struct ImporterState {
     pub(crate) multiprocessing_set_start_method: Option<String>,
     /// Whether to automatically register ourself with `pkg_resources` when it is imported.
     pub(crate) pkg_resources_import_auto_register: bool,
+    /// Records timing of imports, if enabled.
+    pub(crate) import_profiler: Option<Arc<ImportProfiler>>,
+    /// Restricts which modules may be imported, if set.
+    pub(crate) import_policy: Option<ImportPolicy>,
     /// Holds state about importable resources.
     ///
     /// This field is a PyCapsule and is a glorified wrapper around
@@ -389,6 +395,8 @@ impl ImporterState {
             multiprocessing_set_start_method: None,
             // TODO value should come from config.
             pkg_resources_import_auto_register: true,
+            import_profiler: None,
+            import_policy: None,
             resources_state: capsule,
         })
     }
@@ -448,6 +456,18 @@ impl ImporterState {
     pub fn set_multiprocessing_set_start_method(&mut self, value: Option<String>) {
         self.multiprocessing_set_start_method = value;
     }
+
+    /// Set the profiler recording timing of imports performed by this importer.
+    #[allow(unused)]
+    pub fn set_import_profiler(&mut self, value: Option<Arc<ImportProfiler>>) {
+        self.import_profiler = value;
+    }
+
+    /// Set the policy restricting which modules may be imported by this importer.
+    #[allow(unused)]
+    pub fn set_import_policy(&mut self, value: Option<ImportPolicy>) {
+        self.import_policy = value;
+    }
 }
 
 impl Drop for ImporterState {
@@ -523,6 +543,39 @@ impl OxidizedFinder {
     ) -> PyResult<&'p PyAny> {
         let py = slf.py();
         let finder = slf.borrow();
+        let start = Instant::now();
+
+        // Denied imports raise instead of returning None so meta path finders
+        // after us can't service them.
+        if let Some(policy) = &finder.state.import_policy {
+            if let Some(reason) = policy.denial_reason(&fullname) {
+                finder
+                    .state
+                    .sys_module
+                    .getattr(py, "audit")?
+                    .call1(py, (IMPORT_DENIED_AUDIT_EVENT, &fullname, &reason))?;
+
+                return Err(PyImportError::new_err(format!(
+                    "import of {} denied by import policy: {}",
+                    fullname, reason
+                )));
+            }
+        }
+
+        if finder
+            .state
+            .get_resources_state()
+            .watch_memory_mapped_files()
+        {
+            // Errors are ignored so a file being rewritten doesn't break importing.
+            // Resources from a source that fails to index remain unavailable
+            // until a later call successfully indexes it. Other sources are
+            // unaffected.
+            let _ = finder
+                .state
+                .get_resources_state_mut()
+                .reload_changed_memory_mapped_files();
+        }
 
         let module = match finder
             .state
@@ -533,7 +586,7 @@ impl OxidizedFinder {
             None => return Ok(py.None().into_ref(py)),
         };
 
-        match module.flavor {
+        let spec = match module.flavor {
             ModuleFlavor::Extension | ModuleFlavor::SourceBytecode => module.resolve_module_spec(
                 py,
                 finder.state.module_spec_type.clone_ref(py).into_ref(py),
@@ -546,15 +599,26 @@ impl OxidizedFinder {
                 Ok(finder
                     .state
                     .builtin_importer
-                    .call_method(py, "find_spec", (fullname,), None)?
+                    .call_method(py, "find_spec", (&fullname,), None)?
                     .into_ref(py))
             }
             ModuleFlavor::Frozen => Ok(finder
                 .state
                 .frozen_importer
-                .call_method(py, "find_spec", (fullname, path, target), None)?
+                .call_method(py, "find_spec", (&fullname, path, target), None)?
                 .into_ref(py)),
+        };
+
+        if let Some(profiler) = &finder.state.import_profiler {
+            profiler.record(
+                &fullname,
+                ImportPhase::Find,
+                ImportSource::from(&module),
+                start,
+            );
         }
+
+        spec
     }
 
     fn find_module<'p>(
@@ -573,6 +637,11 @@ impl OxidizedFinder {
     }
 
     fn invalidate_caches(&self) -> PyResult<()> {
+        self.state
+            .get_resources_state_mut()
+            .reload_changed_memory_mapped_files()
+            .map_err(PyValueError::new_err)?;
+
         Ok(())
     }
 
@@ -584,6 +653,7 @@ impl OxidizedFinder {
         let py = slf.py();
         let finder = slf.borrow();
         let state = &finder.state;
+        let start = Instant::now();
 
         let name = spec.getattr("name")?;
         let key = name.extract::<String>()?;
@@ -597,7 +667,7 @@ impl OxidizedFinder {
         };
 
         // Extension modules need special module creation logic.
-        if module.flavor == ModuleFlavor::Extension {
+        let res = if module.flavor == ModuleFlavor::Extension {
             // We need a custom implementation of create_module() for in-memory shared
             // library extensions because if we wait until `exec_module()` to
             // initialize the module object, this can confuse some CPython
@@ -628,13 +698,20 @@ impl OxidizedFinder {
             }
         } else {
             Ok(py.None())
+        };
+
+        if let Some(profiler) = &state.import_profiler {
+            profiler.record(&key, ImportPhase::Load, ImportSource::from(&module), start);
         }
+
+        res
     }
 
     fn exec_module(slf: &PyCell<Self>, module: &PyAny) -> PyResult<Py<PyAny>> {
         let py = slf.py();
         let finder = slf.borrow();
         let state = &finder.state;
+        let start = Instant::now();
 
         let name = module.getattr("__name__")?;
         let key = name.extract::<String>()?;
@@ -651,7 +728,7 @@ impl OxidizedFinder {
             }
         };
 
-        if let Some(bytecode) = entry.resolve_bytecode(
+        let res = if let Some(bytecode) = entry.resolve_bytecode(
             py,
             state.optimize_level,
             state.decode_source.as_ref(py),
@@ -680,7 +757,13 @@ impl OxidizedFinder {
                 .call(py, (&exec_dynamic, module), None)
         } else {
             Ok(py.None())
-        }?;
+        };
+
+        if let Some(profiler) = &state.import_profiler {
+            profiler.record(&key, ImportPhase::Exec, ImportSource::from(&entry), start);
+        }
+
+        res?;
 
         // Perform import time side-effects for special modules.
         match key.as_str() {
@@ -791,6 +874,9 @@ impl OxidizedFinder {
     ///
     /// If source code is available, then the method should return the path to the
     /// source file, regardless of whether a bytecode was used to load the module.
+    ///
+    /// If the module isn't backed by the filesystem but its original source path
+    /// was recorded in a source map, that path is returned.
     fn get_filename<'p>(slf: &'p PyCell<Self>, fullname: &str) -> PyResult<&'p PyAny> {
         let finder = slf.borrow();
         let state = &finder.state;
@@ -804,9 +890,16 @@ impl OxidizedFinder {
             .resolve_importable_module(&key, state.optimize_level)
             .ok_or_else(|| make_error("unknown module"))?;
 
-        module
+        if let Some(origin) = module
             .resolve_origin(slf.py())
             .map_err(|_| make_error("unable to resolve origin"))?
+        {
+            return Ok(origin);
+        }
+
+        module
+            .original_source_path()
+            .map(|path| path.into_py(slf.py()).into_ref(slf.py()))
             .ok_or_else(|| make_error("no origin"))
     }
 
@@ -963,6 +1056,20 @@ impl OxidizedFinder {
         Ok(self.state.pkg_resources_import_auto_register)
     }
 
+    #[getter]
+    fn get_watch_memory_mapped_files(&self) -> PyResult<bool> {
+        Ok(self.state.get_resources_state().watch_memory_mapped_files())
+    }
+
+    #[setter]
+    fn set_watch_memory_mapped_files(&self, value: bool) -> PyResult<()> {
+        self.state
+            .get_resources_state_mut()
+            .set_watch_memory_mapped_files(value);
+
+        Ok(())
+    }
+
     fn path_hook(slf: &PyCell<Self>, path: &PyAny) -> PyResult<OxidizedPathEntryFinder> {
         Self::path_hook_inner(slf, path).map_err(|inner| {
             let err = PyImportError::new_err("error running OxidizedFinder.path_hook");
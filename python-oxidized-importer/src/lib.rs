@@ -4,7 +4,10 @@
 
 //! oxidized_importer Python extension.
 
+mod allocator_stats;
 mod conversion;
+mod import_policy;
+mod import_profiler;
 #[allow(clippy::needless_option_as_deref)]
 mod importer;
 #[cfg(windows)]
@@ -24,6 +27,12 @@ mod resource_scanning;
 mod zip_import;
 
 pub use crate::{
+    allocator_stats::{
+        allocator_stats, AllocatorDomain, AllocatorDomainCounters, AllocatorDomainStats,
+        ALLOCATION_SIZE_BUCKETS,
+    },
+    import_policy::{ImportPolicy, IMPORT_DENIED_AUDIT_EVENT},
+    import_profiler::{ImportEvent, ImportPhase, ImportProfiler, ImportSource},
     importer::{
         install_path_hook, remove_external_importers, replace_meta_path_importers, ImporterState,
         OxidizedFinder,
@@ -184,6 +193,7 @@ fn module_init(py: Python, m: &PyModule) -> PyResult<()> {
 
     state.initialized = false;
 
+    crate::allocator_stats::init_module(m)?;
     crate::pkg_resources::init_module(m)?;
     crate::resource_scanning::init_module(m)?;
 
@@ -291,7 +291,13 @@ impl OxidizedResourceCollector {
         let mut resources = Vec::new();
 
         for resource in prepared.resources.values() {
-            resources.push(resource_to_pyobject(py, resource)?);
+            // Source maps are opt-in. Don't leak paths on the local filesystem
+            // into resources by default.
+            let mut resource = resource.clone();
+            resource.original_source_path = None;
+            resource.original_source_digest_sha256 = None;
+
+            resources.push(resource_to_pyobject(py, &resource)?);
         }
 
         let mut file_installs = Vec::new();
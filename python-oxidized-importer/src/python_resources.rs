@@ -13,6 +13,7 @@ use {
         pyobject_to_pathbuf_optional,
     },
     anyhow::Result,
+    once_cell::sync::OnceCell,
     pyo3::{
         buffer::PyBuffer,
         exceptions::{PyImportError, PyOSError, PyValueError},
@@ -22,7 +23,8 @@ use {
         types::{PyBytes, PyDict, PyList, PyString, PyTuple},
     },
     python_packaging::resource::BytecodeOptimizationLevel,
-    python_packed_resources::Resource,
+    python_packed_resources::{Resource, ResourcesIndex},
+    sha2::{Digest, Sha256},
     std::{
         borrow::Cow,
         cell::RefCell,
@@ -30,6 +32,7 @@ use {
         ffi::CStr,
         os::raw::c_int,
         path::{Path, PathBuf},
+        time::SystemTime,
     },
 };
 
@@ -155,7 +158,8 @@ impl<'a> ImportablePythonModule<'a, u8> {
 
             Some(PyBytes::new(py, &source))
         } else {
-            None
+            self.read_original_source()
+                .map(|source| PyBytes::new(py, &source))
         };
 
         if let Some(bytes) = bytes {
@@ -350,6 +354,36 @@ impl<'a> ImportablePythonModule<'a, u8> {
         })
     }
 
+    /// Obtain the path to the original source file this module was derived from.
+    ///
+    /// This is only defined if the packed resources data has source maps. The
+    /// path likely refers to a file on the machine that produced the resources.
+    pub fn original_source_path(&self) -> Option<&Path> {
+        self.resource
+            .original_source_path
+            .as_ref()
+            .map(|path| Path::new(path.as_ref()))
+    }
+
+    /// Read module source from the original source file this module was derived from.
+    ///
+    /// Returns `None` if the file can't be read or if its content doesn't
+    /// match the recorded digest, as source from a different revision would
+    /// not line up with the embedded bytecode.
+    fn read_original_source(&self) -> Option<Vec<u8>> {
+        let source = std::fs::read(self.original_source_path()?).ok()?;
+
+        if let Some(expected) = &self.resource.original_source_digest_sha256 {
+            let digest: [u8; 32] = Sha256::digest(&source).into();
+
+            if &digest != expected {
+                return None;
+            }
+        }
+
+        Some(source)
+    }
+
     /// Obtain the filesystem path to this resource to be used for `ModuleSpec.origin`.
     fn origin_path(&self) -> Option<PathBuf> {
         match self.flavor {
@@ -383,6 +417,18 @@ impl<'a> ImportablePythonModule<'a, u8> {
     pub fn in_memory_extension_module_shared_library(&self) -> &'a Option<Cow<'a, [u8]>> {
         &self.resource.in_memory_extension_module_shared_library
     }
+
+    /// Whether code for this module is held in memory instead of the filesystem.
+    pub fn is_in_memory(&self) -> bool {
+        self.resource.in_memory_source.is_some()
+            || self.resource.in_memory_bytecode.is_some()
+            || self.resource.in_memory_bytecode_opt1.is_some()
+            || self.resource.in_memory_bytecode_opt2.is_some()
+            || self
+                .resource
+                .in_memory_extension_module_shared_library
+                .is_some()
+    }
 }
 
 /// A source for packed resources data.
@@ -394,6 +440,18 @@ pub enum PackedResourcesSource<'a> {
     /// Load resources data from a filesystem path using memory mapped I/O.
     #[allow(unused)]
     MemoryMappedPath(PathBuf),
+
+    /// Load resources from a zip archive at a filesystem path.
+    ///
+    /// Resources are derived from the layout of files in the archive.
+    /// Requires the `zipimport` feature.
+    ZipPath(PathBuf),
+
+    /// Load resources from a wheel archive at a filesystem path.
+    ///
+    /// Like [Self::ZipPath] except the archive is treated as a wheel and
+    /// content is resolved to where it would be installed.
+    WheelPath(PathBuf),
 }
 
 impl<'a> From<&'a [u8]> for PackedResourcesSource<'a> {
@@ -402,6 +460,100 @@ impl<'a> From<&'a [u8]> for PackedResourcesSource<'a> {
     }
 }
 
+/// Identifies a version of a file's content.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct FileStamp {
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+impl FileStamp {
+    /// Obtain the stamp of a filesystem path, if it exists.
+    fn from_path(path: &Path) -> Option<Self> {
+        let metadata = std::fs::metadata(path).ok()?;
+
+        Some(Self {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+        })
+    }
+}
+
+/// A source of resources indexed into a [PythonResourcesState].
+///
+/// Sources are recorded so resources can be re-indexed when memory mapped
+/// files change.
+#[derive(Debug)]
+enum IndexedSource<'a, X>
+where
+    [X]: ToOwned<Owned = Vec<X>>,
+{
+    /// Packed resources data in memory.
+    Data(&'a [u8]),
+
+    /// Packed resources data in a memory mapped file.
+    MemoryMappedPath {
+        path: PathBuf,
+        stamp: Option<FileStamp>,
+    },
+
+    /// A zip archive.
+    ZipPath { path: PathBuf, wheel: bool },
+
+    /// A resource added individually.
+    Resource(Resource<'a, X>),
+
+    /// Built-in extension modules of the interpreter.
+    InterpreterBuiltinExtensionModules,
+
+    /// Frozen modules of the interpreter.
+    InterpreterFrozenModules,
+}
+
+/// Resources from packed resources data that are deserialized on demand.
+///
+/// Deserialized resources are cached. So compressed data, such as module
+/// bytecode, is decompressed at most once.
+#[derive(Debug)]
+struct LazyResources<'a> {
+    index: ResourcesIndex<'a>,
+
+    /// Deserialized resources, by position in `index`.
+    ///
+    /// Resources that fail to deserialize are recorded as `None`.
+    resources: Vec<OnceCell<Option<Box<Resource<'a, u8>>>>>,
+}
+
+impl<'a> LazyResources<'a> {
+    fn new(index: ResourcesIndex<'a>) -> Self {
+        let resources = std::iter::repeat_with(OnceCell::new)
+            .take(index.len())
+            .collect();
+
+        Self { index, resources }
+    }
+
+    /// Whether a named resource exists.
+    fn contains(&self, name: &str) -> bool {
+        matches!(self.index.find(name), Ok(Some(_)))
+    }
+
+    /// Obtain the resource at a given position in the index, deserializing it if needed.
+    fn resolve(&self, position: usize) -> Option<&Resource<'a, u8>> {
+        self.resources[position]
+            .get_or_init(|| self.index.resolve(position).ok().map(Box::new))
+            .as_deref()
+    }
+
+    /// Obtain a named resource, deserializing it if needed.
+    fn get(&self, name: &str) -> Option<&Resource<'a, u8>> {
+        match self.index.find(name) {
+            Ok(Some(position)) => self.resolve(position),
+            _ => None,
+        }
+    }
+}
+
 /// Defines Python resources available for import.
 #[derive(Debug)]
 pub struct PythonResourcesState<'a, X>
@@ -419,6 +571,11 @@ where
     /// Named resources available for loading.
     resources: HashMap<Cow<'a, str>, Resource<'a, X>>,
 
+    /// Resources from packed resources data having a name index.
+    ///
+    /// Entries in `resources` take precedence over these.
+    lazy_resources: Vec<LazyResources<'a>>,
+
     /// List of `PyObject` that back indexed data.
     ///
     /// Holding a reference to these prevents them from being gc'd and for
@@ -427,6 +584,21 @@ where
 
     /// Holds memory mapped file instances that resources data came from.
     backing_mmaps: Vec<memmap2::Mmap>,
+
+    /// Whether to verify the integrity of packed resources data when indexing it.
+    packed_resources_verify: bool,
+
+    /// Key used to decrypt encrypted packed resources data.
+    packed_resources_encryption_key: Option<[u8; 32]>,
+
+    /// Sources that resources were indexed from, in order.
+    indexed_sources: Vec<IndexedSource<'a, X>>,
+
+    /// Whether to re-index resources when memory mapped files change.
+    watch_memory_mapped_files: bool,
+
+    /// Whether a source failed to be re-indexed and should be retried.
+    reindex_pending: bool,
 }
 
 impl<'a> Default for PythonResourcesState<'a, u8> {
@@ -435,8 +607,14 @@ impl<'a> Default for PythonResourcesState<'a, u8> {
             current_exe: PathBuf::new(),
             origin: PathBuf::new(),
             resources: HashMap::new(),
+            lazy_resources: vec![],
             backing_py_objects: vec![],
             backing_mmaps: vec![],
+            packed_resources_verify: false,
+            packed_resources_encryption_key: None,
+            indexed_sources: vec![],
+            watch_memory_mapped_files: false,
+            reindex_pending: false,
         }
     }
 }
@@ -477,50 +655,315 @@ impl<'a> PythonResourcesState<'a, u8> {
         self.origin = path;
     }
 
+    /// Whether packed resources data is verified when it is indexed.
+    pub fn packed_resources_verify(&self) -> bool {
+        self.packed_resources_verify
+    }
+
+    /// Set whether packed resources data is verified when it is indexed.
+    ///
+    /// When enabled, indexing data fails unless it contains content digests
+    /// and all digests match.
+    pub fn set_packed_resources_verify(&mut self, value: bool) {
+        self.packed_resources_verify = value;
+    }
+
+    /// Set the key used to decrypt encrypted packed resources data.
+    ///
+    /// The key applies to data indexed after this is called. Indexing data
+    /// having encrypted blob sections fails if the key isn't set or isn't the
+    /// key the data was encrypted with.
+    pub fn set_packed_resources_encryption_key(&mut self, key: Option<[u8; 32]>) {
+        self.packed_resources_encryption_key = key;
+    }
+
+    /// Whether memory mapped files are checked for changes when resolving modules.
+    pub fn watch_memory_mapped_files(&self) -> bool {
+        self.watch_memory_mapped_files
+    }
+
+    /// Set whether memory mapped files are checked for changes when resolving modules.
+    ///
+    /// See [Self::reload_changed_memory_mapped_files()].
+    pub fn set_watch_memory_mapped_files(&mut self, value: bool) {
+        self.watch_memory_mapped_files = value;
+    }
+
     /// Load resources by parsing a blob.
     ///
     /// If an existing entry exists, the new entry will be merged into it. Set fields
     /// on the incoming entry will overwrite fields on the existing entry.
     ///
     /// If an entry doesn't exist, the resource will be inserted as-is.
+    ///
+    /// If the data has a name index or compressed blob sections, resources not
+    /// already known are deserialized the first time they are requested instead
+    /// of when this is called. Compressed data is decompressed at that time and
+    /// retained for subsequent requests. Lazily deserialized resources that fail
+    /// to parse are treated as missing.
+    ///
+    /// If [Self::packed_resources_verify()] is true, the integrity of the data is
+    /// verified and an error is returned on mismatch. All resources are
+    /// deserialized up front in this mode.
     pub fn index_data(&mut self, data: &'a [u8]) -> Result<(), &'static str> {
-        let resources = python_packed_resources::load_resources(data)?;
+        self.index_data_inner(data)?;
+        self.indexed_sources.push(IndexedSource::Data(data));
+
+        Ok(())
+    }
+
+    fn index_data_inner(&mut self, data: &'a [u8]) -> Result<(), &'static str> {
+        let mut resources = if self.packed_resources_verify {
+            python_packed_resources::load_resources_verified(data)?
+        } else {
+            python_packed_resources::load_resources(data)?
+        };
+
+        if let Some(key) = self.packed_resources_encryption_key {
+            resources.set_encryption_key(key);
+        }
+
+        if !self.packed_resources_verify
+            && (resources.name_index().is_some() || resources.has_compressed_blob_sections())
+        {
+            return self.index_lazy(resources.into_index()?);
+        }
 
         // Reserve space for expected number of incoming items so we can avoid extra
         // allocations.
         self.resources.reserve(resources.expected_resources_count());
 
         for resource in resources {
-            let resource = resource?;
+            self.merge_resource(resource?)?;
+        }
+
+        Ok(())
+    }
+
+    /// Merge a resource into an existing entry or insert it if one doesn't exist.
+    fn merge_resource(&mut self, resource: Resource<'a, u8>) -> Result<(), &'static str> {
+        self.promote_lazy_resource(&resource.name);
+
+        match self.resources.entry(resource.name.clone()) {
+            Entry::Occupied(existing) => {
+                existing.into_mut().merge_from(resource)?;
+            }
+            Entry::Vacant(vacant) => {
+                vacant.insert(resource);
+            }
+        }
 
-            match self.resources.entry(resource.name.clone()) {
-                Entry::Occupied(existing) => {
-                    existing.into_mut().merge_from(resource)?;
+        Ok(())
+    }
+
+    /// Index resources from packed resources data that are deserialized on demand.
+    fn index_lazy(&mut self, index: ResourcesIndex<'a>) -> Result<(), &'static str> {
+        // Resources that are already known need to be merged. So deserialize them now.
+        if !self.resources.is_empty() || !self.lazy_resources.is_empty() {
+            for position in 0..index.len() {
+                let name = index.name(position)?;
+
+                if !self.has_resource(name) {
+                    continue;
                 }
-                Entry::Vacant(vacant) => {
-                    vacant.insert(resource);
+
+                self.promote_lazy_resource(name);
+                let resource = index.resolve(position)?;
+
+                match self.resources.entry(resource.name.clone()) {
+                    Entry::Occupied(existing) => {
+                        existing.into_mut().merge_from(resource)?;
+                    }
+                    Entry::Vacant(vacant) => {
+                        vacant.insert(resource);
+                    }
                 }
             }
         }
 
+        self.lazy_resources.push(LazyResources::new(index));
+
         Ok(())
     }
 
+    /// Ensure a lazily indexed resource is present in `self.resources`.
+    ///
+    /// This must be called before modifying an entry in `self.resources` so
+    /// modifications are applied to the complete resource.
+    fn promote_lazy_resource(&mut self, name: &str) {
+        if self.lazy_resources.is_empty() || self.resources.contains_key(name) {
+            return;
+        }
+
+        if let Some(resource) = self.lazy_resources.iter().find_map(|lazy| lazy.get(name)) {
+            self.resources
+                .insert(resource.name.clone(), resource.clone());
+        }
+    }
+
+    /// Obtain a named resource.
+    fn get_resource(&self, name: &str) -> Option<&Resource<'a, u8>> {
+        match self.resources.get(name) {
+            Some(resource) => Some(resource),
+            None => self.lazy_resources.iter().find_map(|lazy| lazy.get(name)),
+        }
+    }
+
+    /// Obtain an iterator over all resources.
+    ///
+    /// This deserializes all lazily indexed resources.
+    fn iter_resources(&self) -> impl Iterator<Item = &Resource<'a, u8>> + '_ {
+        self.resources
+            .values()
+            .chain(self.lazy_resources.iter().flat_map(move |lazy| {
+                (0..lazy.index.len()).filter_map(move |position| match lazy.index.name(position) {
+                    // Entries in self.resources shadow lazily indexed resources.
+                    Ok(name) if !self.resources.contains_key(name) => lazy.resolve(position),
+                    _ => None,
+                })
+            }))
+    }
+
     /// Load resources data from a filesystem path using memory mapped I/O.
     pub fn index_path_memory_mapped(&mut self, path: impl AsRef<Path>) -> Result<(), String> {
         let path = path.as_ref();
         let f = std::fs::File::open(path).map_err(|e| e.to_string())?;
 
+        let stamp = FileStamp::from_path(path);
+
         let mapped = unsafe { memmap2::Mmap::map(&f) }.map_err(|e| e.to_string())?;
 
         let data = unsafe { std::slice::from_raw_parts::<u8>(mapped.as_ptr(), mapped.len()) };
 
-        self.index_data(data)?;
+        self.index_data_inner(data)?;
         self.backing_mmaps.push(mapped);
+        self.indexed_sources.push(IndexedSource::MemoryMappedPath {
+            path: path.to_path_buf(),
+            stamp,
+        });
 
         Ok(())
     }
 
+    /// Re-index all resources if a memory mapped file they came from has changed.
+    ///
+    /// All indexed sources are replayed in their original order so stale
+    /// entries are evicted and resources are merged the same way they
+    /// originally were. Memory mapped files are mapped again.
+    ///
+    /// Previous memory maps are retained because Python objects may still
+    /// reference their memory. Files should be replaced (e.g. via a rename)
+    /// rather than rewritten in place, as modifying a mapped file can crash
+    /// the process.
+    ///
+    /// If a source fails to index (e.g. a changed file is invalid or a zip
+    /// archive was deleted), its resources are missing until a subsequent call
+    /// succeeds in indexing it. An error is returned after all other sources
+    /// are replayed.
+    ///
+    /// Returns whether resources were re-indexed.
+    pub fn reload_changed_memory_mapped_files(&mut self) -> Result<bool, String> {
+        let changed = self.reindex_pending
+            || self.indexed_sources.iter().any(|source| match source {
+                IndexedSource::MemoryMappedPath { path, stamp } => {
+                    FileStamp::from_path(path) != *stamp
+                }
+                _ => false,
+            });
+
+        if !changed {
+            return Ok(false);
+        }
+
+        let sources = std::mem::take(&mut self.indexed_sources);
+        self.resources.clear();
+        self.lazy_resources.clear();
+
+        let mut errors = vec![];
+
+        for source in sources {
+            let result = match &source {
+                IndexedSource::Data(data) => self
+                    .index_data(*data)
+                    .map_err(|e| format!("error re-indexing packed resources data: {}", e)),
+                IndexedSource::MemoryMappedPath { path, .. } => self
+                    .index_path_memory_mapped(path)
+                    .map_err(|e| format!("error re-indexing {}: {}", path.display(), e)),
+                IndexedSource::ZipPath { path, wheel } => self
+                    .index_zip_path(path, *wheel)
+                    .map_err(|e| format!("error re-indexing {}: {}", path.display(), e)),
+                IndexedSource::Resource(resource) => self
+                    .add_resource(resource.clone())
+                    .map_err(|e| format!("error re-adding {}: {}", resource.name, e)),
+                IndexedSource::InterpreterBuiltinExtensionModules => self
+                    .index_interpreter_builtin_extension_modules()
+                    .map_err(|e| format!("error re-indexing builtin modules: {}", e)),
+                IndexedSource::InterpreterFrozenModules => self
+                    .index_interpreter_frozen_modules()
+                    .map_err(|e| format!("error re-indexing frozen modules: {}", e)),
+            };
+
+            if let Err(e) = result {
+                errors.push(e);
+
+                // Retain the source so it is indexed again by a later call.
+                self.indexed_sources.push(source);
+            }
+        }
+
+        self.reindex_pending = !errors.is_empty();
+
+        if errors.is_empty() {
+            Ok(true)
+        } else {
+            Err(errors.join("; "))
+        }
+    }
+
+    /// Load resources from a zip archive at a filesystem path.
+    ///
+    /// Python modules, package resources, and package distribution resources
+    /// are derived from the layout of files in the archive. If `wheel` is true,
+    /// the archive is treated as a wheel and content in its `.data` directory
+    /// is remapped to where it would be installed.
+    ///
+    /// Resources are merged into existing entries the same way as
+    /// [Self::index_data()].
+    #[cfg(feature = "zipimport")]
+    pub fn index_zip_path(&mut self, path: impl AsRef<Path>, wheel: bool) -> Result<(), String> {
+        let path = path.as_ref();
+        let f = std::fs::File::open(path).map_err(|e| e.to_string())?;
+
+        let mut index = crate::zip_import::ZipIndex::new(std::io::BufReader::new(f), None)
+            .map_err(|e| format!("error reading zip archive {}: {}", path.display(), e))?;
+
+        let resources = index
+            .python_resources(wheel)
+            .map_err(|e| format!("error reading zip archive {}: {}", path.display(), e))?;
+
+        for resource in resources {
+            self.merge_resource(resource)?;
+        }
+
+        self.indexed_sources.push(IndexedSource::ZipPath {
+            path: path.to_path_buf(),
+            wheel,
+        });
+
+        Ok(())
+    }
+
+    /// Load resources from a zip archive at a filesystem path.
+    ///
+    /// Always errors because zip archive support is not enabled.
+    #[cfg(not(feature = "zipimport"))]
+    pub fn index_zip_path(&mut self, path: impl AsRef<Path>, _wheel: bool) -> Result<(), String> {
+        Err(format!(
+            "unable to load {}: zip archive support not enabled",
+            path.as_ref().display()
+        ))
+    }
+
     /// Load resources from packed data stored in a PyObject.
     ///
     /// The `PyObject` must conform to the buffer protocol.
@@ -554,6 +997,8 @@ impl<'a> PythonResourcesState<'a, u8> {
                 }
             };
 
+            self.promote_lazy_resource(name_str);
+
             self.resources
                 .entry(name_str.into())
                 .and_modify(|r| {
@@ -566,6 +1011,9 @@ impl<'a> PythonResourcesState<'a, u8> {
                 });
         }
 
+        self.indexed_sources
+            .push(IndexedSource::InterpreterBuiltinExtensionModules);
+
         Ok(())
     }
 
@@ -586,6 +1034,8 @@ impl<'a> PythonResourcesState<'a, u8> {
                 }
             };
 
+            self.promote_lazy_resource(name_str);
+
             self.resources
                 .entry(name_str.into())
                 .and_modify(|r| {
@@ -598,6 +1048,9 @@ impl<'a> PythonResourcesState<'a, u8> {
                 });
         }
 
+        self.indexed_sources
+            .push(IndexedSource::InterpreterFrozenModules);
+
         Ok(())
     }
 
@@ -616,6 +1069,7 @@ impl<'a> PythonResourcesState<'a, u8> {
     /// Says whether a named resource exists.
     pub fn has_resource(&self, name: &str) -> bool {
         self.resources.contains_key(name)
+            || self.lazy_resources.iter().any(|lazy| lazy.contains(name))
     }
 
     /// Add a resource to the instance.
@@ -626,7 +1080,9 @@ impl<'a> PythonResourcesState<'a, u8> {
         &mut self,
         resource: Resource<'resource, u8>,
     ) -> Result<(), &'static str> {
-        self.resources.insert(resource.name.clone(), resource);
+        self.resources
+            .insert(resource.name.clone(), resource.clone());
+        self.indexed_sources.push(IndexedSource::Resource(resource));
 
         Ok(())
     }
@@ -662,7 +1118,7 @@ impl<'a> PythonResourcesState<'a, u8> {
         // for recognizing `__init__` because Python code in the wild relies on it.
         let name = name.strip_suffix(".__init__").unwrap_or(name);
 
-        let resource = match self.resources.get(name) {
+        let resource = match self.get_resource(name) {
             Some(entry) => entry,
             None => return None,
         };
@@ -745,7 +1201,7 @@ impl<'a> PythonResourcesState<'a, u8> {
         package: &str,
         resource_name: &str,
     ) -> PyResult<Option<&'p PyAny>> {
-        let entry = match self.resources.get(package) {
+        let entry = match self.get_resource(package) {
             Some(entry) => entry,
             None => return Ok(None),
         };
@@ -778,7 +1234,7 @@ impl<'a> PythonResourcesState<'a, u8> {
 
     /// Determines whether a specific package + name pair is a known Python package resource.
     pub fn is_package_resource(&self, package: &str, resource_name: &str) -> bool {
-        if let Some(entry) = self.resources.get(package) {
+        if let Some(entry) = self.get_resource(package) {
             if let Some(resources) = &entry.in_memory_package_resources {
                 if resources.contains_key(resource_name) {
                     return true;
@@ -799,7 +1255,7 @@ impl<'a> PythonResourcesState<'a, u8> {
     ///
     /// The names are returned in sorted order.
     pub fn package_resource_names<'p>(&self, py: Python<'p>, package: &str) -> PyResult<&'p PyAny> {
-        let entry = match self.resources.get(package) {
+        let entry = match self.get_resource(package) {
             Some(entry) => entry,
             None => return Ok(PyList::empty(py).into()),
         };
@@ -833,7 +1289,7 @@ impl<'a> PythonResourcesState<'a, u8> {
             format!("{}/", name)
         };
 
-        if let Some(entry) = self.resources.get(package) {
+        if let Some(entry) = self.get_resource(package) {
             if let Some(resources) = &entry.in_memory_package_resources {
                 if resources.keys().any(|path| path.starts_with(&prefix)) {
                     return true;
@@ -890,7 +1346,7 @@ impl<'a> PythonResourcesState<'a, u8> {
 
         let mut entries = BTreeSet::new();
 
-        if let Some(entry) = self.resources.get(package) {
+        if let Some(entry) = self.get_resource(package) {
             if let Some(resources) = &entry.in_memory_package_resources {
                 entries.extend(resources.keys().filter_map(filter_map_resource));
             }
@@ -1008,7 +1464,7 @@ impl<'a> PythonResourcesState<'a, u8> {
             let resource_name = name_parts.join("/");
             let resource_name_ref: &str = &resource_name;
 
-            if let Some(entry) = self.resources.get(package_name_ref) {
+            if let Some(entry) = self.get_resource(package_name_ref) {
                 if check_in_memory {
                     if let Some(resources) = &entry.in_memory_package_resources {
                         if let Some(data) = resources.get(resource_name_ref) {
@@ -1063,8 +1519,7 @@ impl<'a> PythonResourcesState<'a, u8> {
         optimize_level: BytecodeOptimizationLevel,
     ) -> PyResult<&'p PyList> {
         let infos: PyResult<Vec<_>> = self
-            .resources
-            .values()
+            .iter_resources()
             .filter(|r| {
                 r.is_python_extension_module
                     || (r.is_python_module && is_module_importable(r, optimize_level))
@@ -1094,8 +1549,7 @@ impl<'a> PythonResourcesState<'a, u8> {
 
     /// Resolve the names of package distributions matching a name filter.
     pub fn package_distribution_names(&self, filter: impl Fn(&str) -> bool) -> Vec<&'_ str> {
-        self.resources
-            .values()
+        self.iter_resources()
             .filter(|r| {
                 r.is_python_package
                     && (r.in_memory_distribution_resources.is_some()
@@ -1112,7 +1566,7 @@ impl<'a> PythonResourcesState<'a, u8> {
         package: &str,
         name: &str,
     ) -> Result<Option<Cow<'_, [u8]>>> {
-        if let Some(entry) = self.resources.get(package) {
+        if let Some(entry) = self.get_resource(package) {
             if let Some(resources) = &entry.in_memory_distribution_resources {
                 if let Some(data) = resources.get(name) {
                     return Ok(Some(Cow::Borrowed(data.as_ref())));
@@ -1148,7 +1602,7 @@ impl<'a> PythonResourcesState<'a, u8> {
             format!("{}/", name)
         };
 
-        if let Some(entry) = &self.resources.get(package) {
+        if let Some(entry) = self.get_resource(package) {
             if let Some(resources) = &entry.in_memory_distribution_resources {
                 if resources.keys().any(|path| path.starts_with(&prefix)) {
                     return true;
@@ -1209,7 +1663,7 @@ impl<'a> PythonResourcesState<'a, u8> {
 
         let mut entries = BTreeSet::new();
 
-        if let Some(entry) = self.resources.get(package) {
+        if let Some(entry) = self.get_resource(package) {
             if let Some(resources) = &entry.in_memory_distribution_resources {
                 entries.extend(resources.keys().filter_map(filter_map_resource));
             }
@@ -1224,7 +1678,7 @@ impl<'a> PythonResourcesState<'a, u8> {
 
     /// Resolve content of a shared library to load from memory.
     pub fn resolve_in_memory_shared_library_data(&self, name: &str) -> Option<&[u8]> {
-        if let Some(entry) = &self.resources.get(name) {
+        if let Some(entry) = self.get_resource(name) {
             if let Some(library_data) = &entry.in_memory_shared_library {
                 Some(library_data.as_ref())
             } else {
@@ -1237,7 +1691,7 @@ impl<'a> PythonResourcesState<'a, u8> {
 
     /// Convert indexed resources to a [PyList].
     pub fn resources_as_py_list<'p>(&self, py: Python<'p>) -> PyResult<&'p PyList> {
-        let mut resources = self.resources.values().collect::<Vec<_>>();
+        let mut resources = self.iter_resources().collect::<Vec<_>>();
         resources.sort_by_key(|r| &r.name);
 
         let objects = resources
@@ -1258,8 +1712,7 @@ impl<'a> PythonResourcesState<'a, u8> {
         ignore_frozen: bool,
     ) -> Result<Vec<u8>> {
         let mut resources = self
-            .resources
-            .values()
+            .iter_resources()
             .filter(|resource| {
                 // This assumes builtins and frozen are mutually exclusive with other types.
                 !((resource.is_python_builtin_extension_module && ignore_builtin)
@@ -1272,7 +1725,11 @@ impl<'a> PythonResourcesState<'a, u8> {
 
         let mut buffer = Vec::new();
 
-        python_packed_resources::write_packed_resources_v3(&resources, &mut buffer, None)?;
+        python_packed_resources::write_packed_resources_v3(
+            &resources,
+            &mut buffer,
+            &python_packed_resources::PackedResourcesWriteOptions::default(),
+        )?;
 
         Ok(buffer)
     }
@@ -1755,6 +2212,44 @@ impl OxidizedResource {
 
         Ok(())
     }
+
+    #[getter]
+    fn get_original_source_path(&self) -> Option<String> {
+        self.resource
+            .borrow()
+            .original_source_path
+            .as_ref()
+            .map(|x| x.to_string())
+    }
+
+    #[setter]
+    fn set_original_source_path(&self, value: Option<String>) -> PyResult<()> {
+        self.resource.borrow_mut().original_source_path = value.map(Cow::Owned);
+
+        Ok(())
+    }
+
+    #[getter]
+    fn get_original_source_digest_sha256<'p>(&self, py: Python<'p>) -> Option<&'p PyBytes> {
+        self.resource
+            .borrow()
+            .original_source_digest_sha256
+            .as_ref()
+            .map(|x| PyBytes::new(py, x))
+    }
+
+    #[setter]
+    fn set_original_source_digest_sha256(&self, value: &PyAny) -> PyResult<()> {
+        self.resource.borrow_mut().original_source_digest_sha256 =
+            match pyobject_to_owned_bytes_optional(value)? {
+                Some(data) => Some(data.try_into().map_err(|_| {
+                    PyValueError::new_err("original source digest must be 32 bytes")
+                })?),
+                None => None,
+            };
+
+        Ok(())
+    }
 }
 
 /// Convert a Resource to an OxidizedResource.
@@ -15,10 +15,12 @@ use {
         types::{PyBytes, PyDict, PyType},
         PyNativeType, PyTraverseError, PyVisit,
     },
+    python_packed_resources::Resource,
     std::{
-        collections::HashMap,
+        borrow::Cow,
+        collections::{BTreeMap, BTreeSet, HashMap},
         io::{BufReader, Cursor, Read, Seek},
-        path::{Path, PathBuf},
+        path::{Component, Path, PathBuf},
     },
     zip::read::ZipArchive,
 };
@@ -158,6 +160,187 @@ impl<R: Read + Seek> ZipIndex<R> {
     }
 }
 
+/// Whether a string is a valid Python identifier for module name purposes.
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+
+    match chars.next() {
+        Some(c) if c.is_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+
+    chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Resolve the UTF-8 components of a relative path.
+fn path_components(path: &Path) -> Option<Vec<String>> {
+    let components = path
+        .components()
+        .map(|c| match c {
+            Component::Normal(s) => s.to_str().map(|s| s.to_string()),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    if components.is_empty() {
+        None
+    } else {
+        Some(components)
+    }
+}
+
+/// Resolve the path a wheel member would be installed to, relative to `site-packages`.
+///
+/// Returns `None` for members that aren't installed to `site-packages`.
+fn wheel_install_path(path: &Path) -> Option<PathBuf> {
+    let mut components = path.components();
+
+    let first = components.next()?.as_os_str().to_str()?;
+
+    if first.ends_with(".data") {
+        match components.next()?.as_os_str().to_str()? {
+            "purelib" | "platlib" => Some(components.as_path().to_path_buf()),
+            _ => None,
+        }
+    } else {
+        Some(path.to_path_buf())
+    }
+}
+
+fn entry<'r>(
+    resources: &'r mut BTreeMap<String, Resource<'static, u8>>,
+    name: &str,
+) -> &'r mut Resource<'static, u8> {
+    resources
+        .entry(name.to_string())
+        .or_insert_with(|| Resource {
+            name: Cow::Owned(name.to_string()),
+            ..Resource::default()
+        })
+}
+
+impl<R: Read + Seek> ZipIndex<R> {
+    /// Convert the content of the zip archive to resources.
+    ///
+    /// `.py` files become Python modules. Directories containing modules but
+    /// no `__init__.py` become namespace packages. Other files become resources
+    /// of the nearest enclosing package and files in top-level `.dist-info`
+    /// directories become package distribution resources. Bytecode files are
+    /// ignored: bytecode is compiled from source when modules are loaded.
+    ///
+    /// If `wheel` is true, the archive is treated as a wheel: content of the
+    /// `purelib` and `platlib` directories in the `.data` directory is
+    /// remapped to the archive root and other `.data` content is ignored.
+    ///
+    /// All resource data is read into memory.
+    pub fn python_resources(&mut self, wheel: bool) -> Result<Vec<Resource<'static, u8>>> {
+        // Map of install path to path in the archive.
+        let paths = self
+            .members
+            .keys()
+            .filter_map(|path| {
+                let install_path = if wheel {
+                    wheel_install_path(path)?
+                } else {
+                    path.clone()
+                };
+
+                Some((install_path, path.clone()))
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        let packages = paths
+            .keys()
+            .filter(|path| path.file_name() == Some("__init__.py".as_ref()))
+            .filter_map(|path| path.parent().map(|p| p.to_path_buf()))
+            .collect::<BTreeSet<_>>();
+
+        let mut resources = BTreeMap::new();
+
+        for (install_path, path) in paths {
+            let components = if let Some(components) = path_components(&install_path) {
+                components
+            } else {
+                continue;
+            };
+
+            let (filename, dirs) = components.split_last().expect("components are non-empty");
+
+            if let Some(distribution) = dirs.first().and_then(|d| d.strip_suffix(".dist-info")) {
+                // The distribution directory is named `<name>-<version>.dist-info`.
+                let package = distribution.split('-').next().unwrap_or(distribution);
+
+                let data = self.resolve_path_content(&path)?;
+                let resource = entry(&mut resources, package);
+
+                // A distribution resource makes the entity a package.
+                resource.is_python_module = true;
+                resource.is_python_package = true;
+                resource
+                    .in_memory_distribution_resources
+                    .get_or_insert_with(HashMap::new)
+                    .insert(Cow::Owned(components[1..].join("/")), Cow::Owned(data));
+
+                continue;
+            }
+
+            if dirs.iter().any(|d| d == "__pycache__") || filename.ends_with(".pyc") {
+                continue;
+            }
+
+            if let Some(stem) = filename.strip_suffix(".py") {
+                if !is_identifier(stem) || !dirs.iter().all(|d| is_identifier(d)) {
+                    continue;
+                }
+
+                let (name, is_package) = if stem == "__init__" {
+                    if dirs.is_empty() {
+                        continue;
+                    }
+
+                    (dirs.join("."), true)
+                } else {
+                    let mut parts = dirs.to_vec();
+                    parts.push(stem.to_string());
+
+                    (parts.join("."), false)
+                };
+
+                for depth in 1..=dirs.len() {
+                    if !packages.contains(&dirs[0..depth].iter().collect::<PathBuf>()) {
+                        let resource = entry(&mut resources, &dirs[0..depth].join("."));
+                        resource.is_python_module = true;
+                        resource.is_python_package = true;
+                        resource.is_python_namespace_package = true;
+                    }
+                }
+
+                let data = self.resolve_path_content(&path)?;
+                let resource = entry(&mut resources, &name);
+                resource.is_python_module = true;
+                resource.is_python_package |= is_package;
+                resource.in_memory_source = Some(Cow::Owned(data));
+            } else if let Some(depth) = (1..=dirs.len())
+                .rev()
+                .find(|depth| packages.contains(&dirs[0..*depth].iter().collect::<PathBuf>()))
+            {
+                if !dirs[0..depth].iter().all(|d| is_identifier(d)) {
+                    continue;
+                }
+
+                let data = self.resolve_path_content(&path)?;
+                let resource = entry(&mut resources, &dirs[0..depth].join("."));
+                resource
+                    .in_memory_package_resources
+                    .get_or_insert_with(HashMap::new)
+                    .insert(Cow::Owned(components[depth..].join("/")), Cow::Owned(data));
+            }
+        }
+
+        Ok(resources.into_values().collect())
+    }
+}
+
 pub trait SeekableReader: Read + Seek + Send {}
 
 impl SeekableReader for Cursor<Vec<u8>> {}
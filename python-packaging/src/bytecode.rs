@@ -12,9 +12,10 @@ use {
     super::resource::BytecodeOptimizationLevel,
     anyhow::{anyhow, Context, Result},
     byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt},
+    sha2::{Digest, Sha256},
     std::{
         io::{BufRead, BufReader, Read, Write},
-        path::Path,
+        path::{Path, PathBuf},
         process,
     },
 };
@@ -225,6 +226,110 @@ impl Drop for BytecodeCompiler {
     }
 }
 
+/// A [PythonBytecodeCompiler] that caches compiled bytecode on the filesystem.
+///
+/// Results are stored in files named by the SHA-256 digest of everything
+/// influencing compilation: the bytecode magic number, the optimization
+/// level, the output mode, the filename, and the source code. The cache is
+/// therefore content addressed and can be shared across builds and processes.
+pub struct CachingBytecodeCompiler<C: PythonBytecodeCompiler> {
+    inner: C,
+    cache_dir: PathBuf,
+    hits: usize,
+    compiled: Vec<String>,
+}
+
+impl<C: PythonBytecodeCompiler> CachingBytecodeCompiler<C> {
+    /// Construct a new instance wrapping a compiler and storing results in a directory.
+    pub fn new(inner: C, cache_dir: impl AsRef<Path>) -> Self {
+        Self {
+            inner,
+            cache_dir: cache_dir.as_ref().to_path_buf(),
+            hits: 0,
+            compiled: vec![],
+        }
+    }
+
+    /// The number of compilations that were satisfied by the cache.
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    /// Filenames of sources that weren't in the cache and had to be compiled.
+    pub fn compiled(&self) -> &[String] {
+        &self.compiled
+    }
+
+    /// Resolve the path to the cache entry for a compilation request.
+    fn cache_path(
+        &self,
+        source: &[u8],
+        filename: &str,
+        optimize: BytecodeOptimizationLevel,
+        output_mode: &CompileMode,
+    ) -> PathBuf {
+        let mut hasher = Sha256::new();
+        hasher.update(self.inner.get_magic_number().to_le_bytes());
+        hasher.update(i32::from(optimize).to_le_bytes());
+        hasher.update(match output_mode {
+            CompileMode::Bytecode => b"bytecode\0".as_ref(),
+            CompileMode::PycCheckedHash => b"pyc-checked-hash\0".as_ref(),
+            CompileMode::PycUncheckedHash => b"pyc-unchecked-hash\0".as_ref(),
+        });
+        hasher.update((filename.len() as u64).to_le_bytes());
+        hasher.update(filename.as_bytes());
+        hasher.update(source);
+
+        let digest = hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>();
+
+        self.cache_dir.join(&digest[0..2]).join(digest)
+    }
+}
+
+impl<C: PythonBytecodeCompiler> PythonBytecodeCompiler for CachingBytecodeCompiler<C> {
+    fn get_magic_number(&self) -> u32 {
+        self.inner.get_magic_number()
+    }
+
+    fn compile(
+        &mut self,
+        source: &[u8],
+        filename: &str,
+        optimize: BytecodeOptimizationLevel,
+        output_mode: CompileMode,
+    ) -> Result<Vec<u8>> {
+        let path = self.cache_path(source, filename, optimize, &output_mode);
+
+        if let Ok(data) = std::fs::read(&path) {
+            self.hits += 1;
+            return Ok(data);
+        }
+
+        let data = self
+            .inner
+            .compile(source, filename, optimize, output_mode)?;
+
+        // Write to a temporary file and rename so concurrent readers never
+        // see a partially written entry.
+        let parent = path.parent().expect("cache path should have parent");
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("creating {}", parent.display()))?;
+        let temp_path = path.with_extension(format!("tmp{}", process::id()));
+        std::fs::write(&temp_path, &data)
+            .with_context(|| format!("writing {}", temp_path.display()))?;
+        std::fs::rename(&temp_path, &path)
+            .with_context(|| format!("renaming {} to {}", temp_path.display(), path.display()))?;
+
+        self.compiled.push(filename.to_string());
+
+        Ok(data)
+    }
+}
+
 /// How to write out a .pyc bytecode header.
 #[derive(Debug, Clone, Copy)]
 pub enum BytecodeHeaderMode {
@@ -265,7 +370,7 @@ pub fn compute_bytecode_header(magic_number: u32, mode: BytecodeHeaderMode) -> R
 
 #[cfg(test)]
 mod tests {
-    use super::*;
+    use {super::*, crate::testutil::FakeBytecodeCompiler};
 
     #[test]
     fn test_header() -> Result<()> {
@@ -288,4 +393,85 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn caching_compiler() -> Result<()> {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("python-packaging-test")
+            .tempdir()?;
+
+        let mut compiler = CachingBytecodeCompiler::new(
+            FakeBytecodeCompiler { magic_number: 42 },
+            temp_dir.path(),
+        );
+
+        let a = compiler.compile(
+            b"foo",
+            "foo.py",
+            BytecodeOptimizationLevel::Zero,
+            CompileMode::Bytecode,
+        )?;
+        assert_eq!(a, b"bc0foo");
+        assert_eq!(compiler.hits(), 0);
+        assert_eq!(compiler.compiled(), &["foo.py".to_string()]);
+
+        let b = compiler.compile(
+            b"foo",
+            "foo.py",
+            BytecodeOptimizationLevel::Zero,
+            CompileMode::Bytecode,
+        )?;
+        assert_eq!(a, b);
+        assert_eq!(compiler.hits(), 1);
+
+        // Any input change results in a new compilation.
+        compiler.compile(
+            b"foo",
+            "foo.py",
+            BytecodeOptimizationLevel::One,
+            CompileMode::Bytecode,
+        )?;
+        compiler.compile(
+            b"foo",
+            "bar.py",
+            BytecodeOptimizationLevel::Zero,
+            CompileMode::Bytecode,
+        )?;
+        compiler.compile(
+            b"foo",
+            "foo.py",
+            BytecodeOptimizationLevel::Zero,
+            CompileMode::PycCheckedHash,
+        )?;
+        assert_eq!(compiler.hits(), 1);
+        assert_eq!(compiler.compiled().len(), 4);
+
+        // The cache is shared with other instances using the same directory.
+        let mut compiler = CachingBytecodeCompiler::new(
+            FakeBytecodeCompiler { magic_number: 42 },
+            temp_dir.path(),
+        );
+        compiler.compile(
+            b"foo",
+            "bar.py",
+            BytecodeOptimizationLevel::Zero,
+            CompileMode::Bytecode,
+        )?;
+        assert_eq!(compiler.hits(), 1);
+
+        // But not with compilers having a different magic number.
+        let mut compiler = CachingBytecodeCompiler::new(
+            FakeBytecodeCompiler { magic_number: 43 },
+            temp_dir.path(),
+        );
+        compiler.compile(
+            b"foo",
+            "bar.py",
+            BytecodeOptimizationLevel::Zero,
+            CompileMode::Bytecode,
+        )?;
+        assert_eq!(compiler.hits(), 0);
+
+        Ok(())
+    }
 }
@@ -231,6 +231,28 @@ pub enum MemoryAllocatorBackend {
     ///
     /// Serialized value: `rust`
     Rust,
+
+    /// Use another backend and record statistics about its allocations.
+    ///
+    /// Allocation counts, bytes, peak usage, and a histogram of allocation
+    /// sizes are recorded for each allocator domain the backend is installed
+    /// for. Statistics can be queried from Rust and from Python via
+    /// `oxidized_importer.allocator_stats()`.
+    ///
+    /// Recording statistics adds overhead to every allocation.
+    ///
+    /// Serialized value: `instrumented-<backend>` (e.g. `instrumented-jemalloc`)
+    Instrumented(BaseMemoryAllocatorBackend),
+}
+
+impl MemoryAllocatorBackend {
+    /// Obtain the backend performing allocations, ignoring instrumentation.
+    pub fn uninstrumented(&self) -> Self {
+        match self {
+            Self::Instrumented(base) => (*base).into(),
+            backend => *backend,
+        }
+    }
 }
 
 impl Default for MemoryAllocatorBackend {
@@ -251,6 +273,9 @@ impl ToString for MemoryAllocatorBackend {
             Self::Mimalloc => "mimalloc",
             Self::Snmalloc => "snmalloc",
             Self::Rust => "rust",
+            Self::Instrumented(base) => {
+                return format!("instrumented-{}", Self::from(*base).to_string());
+            }
         }
         .to_string()
     }
@@ -272,7 +297,12 @@ impl TryFrom<&str> for MemoryAllocatorBackend {
             "mimalloc" => Ok(Self::Mimalloc),
             "snmalloc" => Ok(Self::Snmalloc),
             "rust" => Ok(Self::Rust),
-            _ => Err(format!("{} is not a valid memory allocator backend", value)),
+            _ => value
+                .strip_prefix("instrumented-")
+                .and_then(|base| Self::try_from(base).ok())
+                .and_then(|base| BaseMemoryAllocatorBackend::try_from(base).ok())
+                .map(Self::Instrumented)
+                .ok_or_else(|| format!("{} is not a valid memory allocator backend", value)),
         }
     }
 }
@@ -285,6 +315,52 @@ impl TryFrom<String> for MemoryAllocatorBackend {
     }
 }
 
+/// A memory allocator backend that can be instrumented.
+///
+/// See [MemoryAllocatorBackend::Instrumented].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BaseMemoryAllocatorBackend {
+    /// See [MemoryAllocatorBackend::Default].
+    Default,
+    /// See [MemoryAllocatorBackend::Jemalloc].
+    Jemalloc,
+    /// See [MemoryAllocatorBackend::Mimalloc].
+    Mimalloc,
+    /// See [MemoryAllocatorBackend::Snmalloc].
+    Snmalloc,
+    /// See [MemoryAllocatorBackend::Rust].
+    Rust,
+}
+
+impl From<BaseMemoryAllocatorBackend> for MemoryAllocatorBackend {
+    fn from(v: BaseMemoryAllocatorBackend) -> Self {
+        match v {
+            BaseMemoryAllocatorBackend::Default => Self::Default,
+            BaseMemoryAllocatorBackend::Jemalloc => Self::Jemalloc,
+            BaseMemoryAllocatorBackend::Mimalloc => Self::Mimalloc,
+            BaseMemoryAllocatorBackend::Snmalloc => Self::Snmalloc,
+            BaseMemoryAllocatorBackend::Rust => Self::Rust,
+        }
+    }
+}
+
+impl TryFrom<MemoryAllocatorBackend> for BaseMemoryAllocatorBackend {
+    type Error = String;
+
+    fn try_from(value: MemoryAllocatorBackend) -> Result<Self, Self::Error> {
+        match value {
+            MemoryAllocatorBackend::Default => Ok(Self::Default),
+            MemoryAllocatorBackend::Jemalloc => Ok(Self::Jemalloc),
+            MemoryAllocatorBackend::Mimalloc => Ok(Self::Mimalloc),
+            MemoryAllocatorBackend::Snmalloc => Ok(Self::Snmalloc),
+            MemoryAllocatorBackend::Rust => Ok(Self::Rust),
+            MemoryAllocatorBackend::Instrumented(_) => {
+                Err("instrumented allocator backends can't be instrumented".to_string())
+            }
+        }
+    }
+}
+
 /// Holds values for `coerce_c_locale`.
 ///
 /// See <https://docs.python.org/3/c-api/init_config.html#c.PyPreConfig.coerce_c_locale>.
@@ -666,6 +742,71 @@ impl TryFrom<String> for MultiprocessingStartMethod {
     }
 }
 
+/// Defines the format of import profiling data.
+///
+/// Serialization type: `string`
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serialization", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialization", serde(try_from = "String", into = "String"))]
+pub enum ImportProfileFormat {
+    /// A JSON document listing each recorded import event.
+    ///
+    /// Serialized value: `json`
+    Json,
+
+    /// A JSON document in the Chrome trace event format.
+    ///
+    /// The file can be loaded in `chrome://tracing`, Perfetto, and other
+    /// tools supporting this format.
+    ///
+    /// Serialized value: `chrome-trace`
+    ChromeTrace,
+}
+
+impl ToString for ImportProfileFormat {
+    fn to_string(&self) -> String {
+        match self {
+            Self::Json => "json",
+            Self::ChromeTrace => "chrome-trace",
+        }
+        .to_string()
+    }
+}
+
+impl From<ImportProfileFormat> for String {
+    fn from(v: ImportProfileFormat) -> Self {
+        v.to_string()
+    }
+}
+
+impl FromStr for ImportProfileFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Self::Json),
+            "chrome-trace" => Ok(Self::ChromeTrace),
+            _ => Err(format!("{} is not a valid import profile format", s)),
+        }
+    }
+}
+
+impl TryFrom<&str> for ImportProfileFormat {
+    type Error = String;
+
+    fn try_from(v: &str) -> Result<Self, Self::Error> {
+        Self::from_str(v)
+    }
+}
+
+impl TryFrom<String> for ImportProfileFormat {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::try_from(value.as_str())
+    }
+}
+
 /// Holds configuration of a Python interpreter.
 ///
 /// This struct holds fields that are exposed by `PyPreConfig` and
@@ -22,6 +22,7 @@ pub mod module_util;
 pub mod package_metadata;
 pub mod policy;
 pub mod python_source;
+pub mod requirement;
 pub mod resource;
 pub mod resource_collection;
 #[cfg(test)]
@@ -30,5 +31,7 @@ mod testutil;
 pub mod wheel;
 #[cfg(feature = "wheel")]
 pub mod wheel_builder;
+#[cfg(feature = "wheel")]
+pub mod wheel_resolver;
 #[cfg(feature = "zip")]
 pub mod zip_app_builder;
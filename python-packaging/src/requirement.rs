@@ -0,0 +1,1096 @@
+// Copyright 2022 Gregory Szorc.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*! Python package versions and requirements.
+
+This module implements the subset of
+[PEP 440](https://peps.python.org/pep-0440/) (versions and version specifiers)
+and [PEP 508](https://peps.python.org/pep-0508/) (dependency specifiers) needed
+to resolve requirements against a set of available distributions.
+*/
+
+use {
+    anyhow::{anyhow, Result},
+    once_cell::sync::Lazy,
+    std::{
+        cmp::Ordering,
+        collections::BTreeSet,
+        fmt::{Display, Formatter},
+        str::FromStr,
+    },
+};
+
+/// Regular expression matching a PEP 440 version.
+///
+/// This is derived from `packaging.version.VERSION_PATTERN`.
+static RE_VERSION: Lazy<regex::Regex> = Lazy::new(|| {
+    regex::Regex::new(
+        r"(?ix)
+        ^\s*v?
+        (?:(?P<epoch>[0-9]+)!)?
+        (?P<release>[0-9]+(?:\.[0-9]+)*)
+        (?P<pre>[-_.]?(?P<pre_l>alpha|a|beta|b|preview|pre|c|rc)[-_.]?(?P<pre_n>[0-9]+)?)?
+        (?P<post>(?:-(?P<post_n1>[0-9]+))|(?:[-_.]?(?P<post_l>post|rev|r)[-_.]?(?P<post_n2>[0-9]+)?))?
+        (?P<dev>[-_.]?(?P<dev_l>dev)[-_.]?(?P<dev_n>[0-9]+)?)?
+        (?:\+(?P<local>[a-z0-9]+(?:[-_.][a-z0-9]+)*))?
+        \s*$",
+    )
+    .unwrap()
+});
+
+/// Regular expression matching a project name.
+static RE_NAME: Lazy<regex::Regex> =
+    Lazy::new(|| regex::Regex::new(r"^[A-Za-z0-9](?:[A-Za-z0-9._-]*[A-Za-z0-9])?").unwrap());
+
+/// Normalize a project name per PEP 503.
+///
+/// Names are compared case insensitively and runs of `-`, `_`, and `.` are
+/// equivalent.
+pub fn normalize_project_name(name: &str) -> String {
+    let mut res = String::with_capacity(name.len());
+    let mut in_separator = false;
+
+    for c in name.chars() {
+        if matches!(c, '-' | '_' | '.') {
+            if !in_separator {
+                res.push('-');
+            }
+            in_separator = true;
+        } else {
+            res.push(c.to_ascii_lowercase());
+            in_separator = false;
+        }
+    }
+
+    res
+}
+
+/// The kind of a pre-release.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum PreReleaseKind {
+    Alpha,
+    Beta,
+    ReleaseCandidate,
+}
+
+/// A segment of a local version label.
+///
+/// Numeric segments sort after alphanumeric segments.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+enum LocalSegment {
+    String(String),
+    Number(u64),
+}
+
+/// A PEP 440 version.
+#[derive(Clone, Debug)]
+pub struct Version {
+    epoch: u64,
+    release: Vec<u64>,
+    pre: Option<(PreReleaseKind, u64)>,
+    post: Option<u64>,
+    dev: Option<u64>,
+    local: Vec<LocalSegment>,
+}
+
+fn parse_number(s: Option<regex::Match>) -> Result<u64> {
+    match s {
+        Some(m) => m
+            .as_str()
+            .parse::<u64>()
+            .map_err(|e| anyhow!("invalid version component {}: {}", m.as_str(), e)),
+        None => Ok(0),
+    }
+}
+
+impl FromStr for Version {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let captures = RE_VERSION
+            .captures(s)
+            .ok_or_else(|| anyhow!("invalid version: {}", s))?;
+
+        let epoch = parse_number(captures.name("epoch"))?;
+
+        let release = captures
+            .name("release")
+            .expect("release is required by regex")
+            .as_str()
+            .split('.')
+            .map(|x| {
+                x.parse::<u64>()
+                    .map_err(|e| anyhow!("invalid version component {}: {}", x, e))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let pre = if let Some(label) = captures.name("pre_l") {
+            let kind = match label.as_str().to_ascii_lowercase().as_str() {
+                "a" | "alpha" => PreReleaseKind::Alpha,
+                "b" | "beta" => PreReleaseKind::Beta,
+                _ => PreReleaseKind::ReleaseCandidate,
+            };
+
+            Some((kind, parse_number(captures.name("pre_n"))?))
+        } else {
+            None
+        };
+
+        let post = if captures.name("post").is_some() {
+            Some(parse_number(
+                captures
+                    .name("post_n1")
+                    .or_else(|| captures.name("post_n2")),
+            )?)
+        } else {
+            None
+        };
+
+        let dev = if captures.name("dev").is_some() {
+            Some(parse_number(captures.name("dev_n"))?)
+        } else {
+            None
+        };
+
+        let local = if let Some(local) = captures.name("local") {
+            local
+                .as_str()
+                .split(['-', '_', '.'])
+                .map(|s| match s.parse::<u64>() {
+                    Ok(v) => LocalSegment::Number(v),
+                    Err(_) => LocalSegment::String(s.to_ascii_lowercase()),
+                })
+                .collect::<Vec<_>>()
+        } else {
+            vec![]
+        };
+
+        Ok(Self {
+            epoch,
+            release,
+            pre,
+            post,
+            dev,
+            local,
+        })
+    }
+}
+
+impl Display for Version {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.epoch != 0 {
+            write!(f, "{}!", self.epoch)?;
+        }
+
+        write!(
+            f,
+            "{}",
+            self.release
+                .iter()
+                .map(|x| x.to_string())
+                .collect::<Vec<_>>()
+                .join(".")
+        )?;
+
+        if let Some((kind, n)) = &self.pre {
+            let label = match kind {
+                PreReleaseKind::Alpha => "a",
+                PreReleaseKind::Beta => "b",
+                PreReleaseKind::ReleaseCandidate => "rc",
+            };
+            write!(f, "{}{}", label, n)?;
+        }
+
+        if let Some(n) = &self.post {
+            write!(f, ".post{}", n)?;
+        }
+
+        if let Some(n) = &self.dev {
+            write!(f, ".dev{}", n)?;
+        }
+
+        if !self.local.is_empty() {
+            let local = self
+                .local
+                .iter()
+                .map(|s| match s {
+                    LocalSegment::String(s) => s.clone(),
+                    LocalSegment::Number(n) => n.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(".");
+            write!(f, "+{}", local)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Sort key for the pre-release component of a version.
+#[derive(Eq, Ord, PartialEq, PartialOrd)]
+enum PreKey {
+    /// A development release without a pre-release sorts before pre-releases.
+    DevOnly,
+    Pre(PreReleaseKind, u64),
+    /// Releases without a pre-release sort after pre-releases.
+    None,
+}
+
+impl Version {
+    /// The release segment with trailing zeros removed.
+    fn trimmed_release(&self) -> &[u64] {
+        let mut end = self.release.len();
+        while end > 1 && self.release[end - 1] == 0 {
+            end -= 1;
+        }
+
+        &self.release[0..end]
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn sort_key(
+        &self,
+    ) -> (
+        u64,
+        &[u64],
+        PreKey,
+        Option<u64>,
+        (bool, u64),
+        &[LocalSegment],
+    ) {
+        let pre = match (&self.pre, &self.post, &self.dev) {
+            (None, None, Some(_)) => PreKey::DevOnly,
+            (Some((kind, n)), _, _) => PreKey::Pre(*kind, *n),
+            (None, _, _) => PreKey::None,
+        };
+
+        (
+            self.epoch,
+            self.trimmed_release(),
+            pre,
+            self.post,
+            (self.dev.is_none(), self.dev.unwrap_or(0)),
+            &self.local,
+        )
+    }
+
+    /// The release segment of the version. e.g. `[1, 2, 3]` for `1.2.3`.
+    pub fn release(&self) -> &[u64] {
+        &self.release
+    }
+
+    /// Whether this is a pre-release or development release.
+    pub fn is_prerelease(&self) -> bool {
+        self.pre.is_some() || self.dev.is_some()
+    }
+
+    /// Whether this is a post-release.
+    pub fn is_postrelease(&self) -> bool {
+        self.post.is_some()
+    }
+
+    /// Whether the version has a local version label.
+    pub fn is_local(&self) -> bool {
+        !self.local.is_empty()
+    }
+
+    /// Obtain the version without its local version label.
+    pub fn public(&self) -> Self {
+        Self {
+            local: vec![],
+            ..self.clone()
+        }
+    }
+
+    /// Obtain the epoch and release segments only.
+    fn base(&self) -> Self {
+        Self {
+            epoch: self.epoch,
+            release: self.release.clone(),
+            pre: None,
+            post: None,
+            dev: None,
+            local: vec![],
+        }
+    }
+}
+
+impl PartialEq for Version {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Version {}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.sort_key().cmp(&other.sort_key())
+    }
+}
+
+/// A comparison operator in a version specifier.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum VersionOperator {
+    Compatible,
+    Equal,
+    EqualPrefix,
+    NotEqual,
+    NotEqualPrefix,
+    LessThanEqual,
+    GreaterThanEqual,
+    LessThan,
+    GreaterThan,
+    ArbitraryEqual,
+}
+
+impl VersionOperator {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Compatible => "~=",
+            Self::Equal | Self::EqualPrefix => "==",
+            Self::NotEqual | Self::NotEqualPrefix => "!=",
+            Self::LessThanEqual => "<=",
+            Self::GreaterThanEqual => ">=",
+            Self::LessThan => "<",
+            Self::GreaterThan => ">",
+            Self::ArbitraryEqual => "===",
+        }
+    }
+}
+
+/// A single version specifier. e.g. `>=1.0`.
+#[derive(Clone, Debug)]
+pub struct VersionSpecifier {
+    operator: VersionOperator,
+    version: Version,
+    /// The version string as written. Used for `===` and display.
+    raw: String,
+}
+
+impl FromStr for VersionSpecifier {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+
+        let (operator, rest) = ["===", "~=", "==", "!=", "<=", ">=", "<", ">"]
+            .iter()
+            .find_map(|op| s.strip_prefix(op).map(|rest| (*op, rest.trim())))
+            .ok_or_else(|| anyhow!("invalid version specifier: {}", s))?;
+
+        let (operator, version_str) = match (operator, rest.strip_suffix(".*")) {
+            ("==", Some(prefix)) => (VersionOperator::EqualPrefix, prefix),
+            ("!=", Some(prefix)) => (VersionOperator::NotEqualPrefix, prefix),
+            (_, Some(_)) => {
+                return Err(anyhow!(
+                    "prefix matching not allowed with operator {}: {}",
+                    operator,
+                    s
+                ));
+            }
+            ("===", None) => (VersionOperator::ArbitraryEqual, rest),
+            ("~=", None) => (VersionOperator::Compatible, rest),
+            ("==", None) => (VersionOperator::Equal, rest),
+            ("!=", None) => (VersionOperator::NotEqual, rest),
+            ("<=", None) => (VersionOperator::LessThanEqual, rest),
+            (">=", None) => (VersionOperator::GreaterThanEqual, rest),
+            ("<", None) => (VersionOperator::LessThan, rest),
+            (">", None) => (VersionOperator::GreaterThan, rest),
+            _ => unreachable!(),
+        };
+
+        let version = if operator == VersionOperator::ArbitraryEqual {
+            // Arbitrary equality doesn't require a valid version. Fall back to
+            // something that will never compare equal.
+            Version::from_str(version_str).unwrap_or_else(|_| Version::from_str("0").unwrap())
+        } else {
+            Version::from_str(version_str)?
+        };
+
+        if operator == VersionOperator::Compatible && version.release.len() < 2 {
+            return Err(anyhow!(
+                "~= requires a version with at least 2 release components: {}",
+                s
+            ));
+        }
+
+        Ok(Self {
+            operator,
+            version,
+            raw: version_str.to_string(),
+        })
+    }
+}
+
+impl Display for VersionSpecifier {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", self.operator.as_str(), self.raw)?;
+
+        if matches!(
+            self.operator,
+            VersionOperator::EqualPrefix | VersionOperator::NotEqualPrefix
+        ) {
+            write!(f, ".*")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether the release of `candidate` begins with the release of `prefix`.
+fn release_has_prefix(candidate: &Version, prefix: &Version) -> bool {
+    candidate.epoch == prefix.epoch
+        && prefix
+            .release
+            .iter()
+            .enumerate()
+            .all(|(i, v)| candidate.release.get(i).copied().unwrap_or(0) == *v)
+}
+
+impl VersionSpecifier {
+    /// Whether this specifier explicitly references a pre-release.
+    pub fn is_prerelease(&self) -> bool {
+        self.version.is_prerelease()
+    }
+
+    /// Whether a version satisfies this specifier.
+    pub fn contains(&self, candidate: &Version) -> bool {
+        match self.operator {
+            VersionOperator::Compatible => {
+                let prefix = Version {
+                    release: self.version.release[0..self.version.release.len() - 1].to_vec(),
+                    ..self.version.base()
+                };
+
+                candidate.public() >= self.version && release_has_prefix(candidate, &prefix)
+            }
+            VersionOperator::Equal => {
+                if self.version.is_local() {
+                    candidate == &self.version
+                } else {
+                    candidate.public() == self.version
+                }
+            }
+            VersionOperator::EqualPrefix => release_has_prefix(candidate, &self.version),
+            VersionOperator::NotEqual => {
+                if self.version.is_local() {
+                    candidate != &self.version
+                } else {
+                    candidate.public() != self.version
+                }
+            }
+            VersionOperator::NotEqualPrefix => !release_has_prefix(candidate, &self.version),
+            VersionOperator::LessThanEqual => candidate.public() <= self.version,
+            VersionOperator::GreaterThanEqual => candidate.public() >= self.version,
+            VersionOperator::LessThan => {
+                // <V excludes pre-releases of V unless V is itself a pre-release.
+                candidate.public() < self.version
+                    && !(!self.version.is_prerelease()
+                        && candidate.is_prerelease()
+                        && candidate.base() == self.version.base())
+            }
+            VersionOperator::GreaterThan => {
+                // >V excludes post-releases and local versions of V unless V is
+                // itself a post-release.
+                candidate.public() > self.version
+                    && !(!self.version.is_postrelease()
+                        && candidate.is_postrelease()
+                        && candidate.base() == self.version.base())
+                    && !(candidate.is_local() && candidate.public() == self.version)
+            }
+            VersionOperator::ArbitraryEqual => {
+                candidate.to_string().eq_ignore_ascii_case(&self.raw)
+            }
+        }
+    }
+}
+
+/// A set of version specifiers that must all be satisfied.
+#[derive(Clone, Debug, Default)]
+pub struct VersionSpecifiers(Vec<VersionSpecifier>);
+
+impl FromStr for VersionSpecifiers {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.trim().is_empty() {
+            return Ok(Self::default());
+        }
+
+        Ok(Self(
+            s.split(',')
+                .map(VersionSpecifier::from_str)
+                .collect::<Result<Vec<_>>>()?,
+        ))
+    }
+}
+
+impl Display for VersionSpecifiers {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            self.0
+                .iter()
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        )
+    }
+}
+
+impl VersionSpecifiers {
+    /// Whether there are no specifiers.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Whether any specifier explicitly references a pre-release.
+    ///
+    /// Pre-releases are only considered if this is true.
+    pub fn allows_prereleases(&self) -> bool {
+        self.0.iter().any(|s| s.is_prerelease())
+    }
+
+    /// Whether a version satisfies all specifiers.
+    pub fn contains(&self, version: &Version) -> bool {
+        self.0.iter().all(|s| s.contains(version))
+    }
+}
+
+/// Values of PEP 508 environment markers for a Python interpreter.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct MarkerEnvironment {
+    pub implementation_name: String,
+    pub implementation_version: String,
+    pub os_name: String,
+    pub platform_machine: String,
+    pub platform_python_implementation: String,
+    pub platform_release: String,
+    pub platform_system: String,
+    pub platform_version: String,
+    pub python_full_version: String,
+    pub python_version: String,
+    pub sys_platform: String,
+}
+
+impl MarkerEnvironment {
+    fn get(&self, name: &str) -> Option<&str> {
+        Some(match name {
+            "implementation_name" => &self.implementation_name,
+            "implementation_version" => &self.implementation_version,
+            "os_name" => &self.os_name,
+            "platform_machine" => &self.platform_machine,
+            "platform_python_implementation" | "python_implementation" => {
+                &self.platform_python_implementation
+            }
+            "platform_release" => &self.platform_release,
+            "platform_system" => &self.platform_system,
+            "platform_version" => &self.platform_version,
+            "python_full_version" => &self.python_full_version,
+            "python_version" => &self.python_version,
+            "sys_platform" => &self.sys_platform,
+            _ => return None,
+        })
+    }
+}
+
+/// A value in a marker expression.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MarkerValue {
+    /// An environment marker variable.
+    Variable(String),
+    /// A quoted string literal.
+    Literal(String),
+}
+
+/// A parsed PEP 508 environment marker expression.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Marker {
+    Compare(MarkerValue, String, MarkerValue),
+    And(Box<Marker>, Box<Marker>),
+    Or(Box<Marker>, Box<Marker>),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum MarkerToken {
+    OpenParen,
+    CloseParen,
+    Identifier(String),
+    Literal(String),
+    Operator(String),
+}
+
+fn tokenize_marker(s: &str) -> Result<Vec<MarkerToken>> {
+    let chars = s.chars().collect::<Vec<_>>();
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '(' {
+            tokens.push(MarkerToken::OpenParen);
+            i += 1;
+        } else if c == ')' {
+            tokens.push(MarkerToken::CloseParen);
+            i += 1;
+        } else if c == '"' || c == '\'' {
+            let end = chars[i + 1..]
+                .iter()
+                .position(|x| *x == c)
+                .ok_or_else(|| anyhow!("unterminated string in marker: {}", s))?;
+            tokens.push(MarkerToken::Literal(
+                chars[i + 1..i + 1 + end].iter().collect(),
+            ));
+            i += end + 2;
+        } else if "<>=!~".contains(c) {
+            let start = i;
+            while i < chars.len() && "<>=!~".contains(chars[i]) {
+                i += 1;
+            }
+            tokens.push(MarkerToken::Operator(chars[start..i].iter().collect()));
+        } else if c.is_ascii_alphanumeric() || c == '_' || c == '.' {
+            let start = i;
+            while i < chars.len()
+                && (chars[i].is_ascii_alphanumeric() || chars[i] == '_' || chars[i] == '.')
+            {
+                i += 1;
+            }
+            tokens.push(MarkerToken::Identifier(chars[start..i].iter().collect()));
+        } else {
+            return Err(anyhow!("unexpected character {} in marker: {}", c, s));
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct MarkerParser {
+    tokens: Vec<MarkerToken>,
+    position: usize,
+}
+
+impl MarkerParser {
+    fn peek(&self) -> Option<&MarkerToken> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<MarkerToken> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(MarkerToken::Identifier(s)) if s == keyword)
+    }
+
+    fn parse_or(&mut self) -> Result<Marker> {
+        let mut marker = self.parse_and()?;
+
+        while self.peek_keyword("or") {
+            self.next();
+            marker = Marker::Or(Box::new(marker), Box::new(self.parse_and()?));
+        }
+
+        Ok(marker)
+    }
+
+    fn parse_and(&mut self) -> Result<Marker> {
+        let mut marker = self.parse_atom()?;
+
+        while self.peek_keyword("and") {
+            self.next();
+            marker = Marker::And(Box::new(marker), Box::new(self.parse_atom()?));
+        }
+
+        Ok(marker)
+    }
+
+    fn parse_atom(&mut self) -> Result<Marker> {
+        if self.peek() == Some(&MarkerToken::OpenParen) {
+            self.next();
+            let marker = self.parse_or()?;
+
+            if self.next() != Some(MarkerToken::CloseParen) {
+                return Err(anyhow!("expected ) in marker"));
+            }
+
+            return Ok(marker);
+        }
+
+        let left = self.parse_value()?;
+
+        let op = match self.next() {
+            Some(MarkerToken::Operator(op)) => op,
+            Some(MarkerToken::Identifier(s)) if s == "in" => s,
+            Some(MarkerToken::Identifier(s)) if s == "not" => match self.next() {
+                Some(MarkerToken::Identifier(s)) if s == "in" => "not in".to_string(),
+                _ => return Err(anyhow!("expected in after not in marker")),
+            },
+            token => return Err(anyhow!("expected marker operator; got {:?}", token)),
+        };
+
+        if !matches!(
+            op.as_str(),
+            "<" | "<=" | "==" | "!=" | ">=" | ">" | "~=" | "===" | "in" | "not in"
+        ) {
+            return Err(anyhow!("invalid marker operator: {}", op));
+        }
+
+        let right = self.parse_value()?;
+
+        Ok(Marker::Compare(left, op, right))
+    }
+
+    fn parse_value(&mut self) -> Result<MarkerValue> {
+        match self.next() {
+            Some(MarkerToken::Literal(s)) => Ok(MarkerValue::Literal(s)),
+            Some(MarkerToken::Identifier(s)) => {
+                if s == "extra" || MarkerEnvironment::default().get(&s).is_some() {
+                    Ok(MarkerValue::Variable(s))
+                } else {
+                    Err(anyhow!("unknown marker variable: {}", s))
+                }
+            }
+            token => Err(anyhow!("expected marker value; got {:?}", token)),
+        }
+    }
+}
+
+impl FromStr for Marker {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parser = MarkerParser {
+            tokens: tokenize_marker(s)?,
+            position: 0,
+        };
+
+        let marker = parser.parse_or()?;
+
+        if parser.position != parser.tokens.len() {
+            return Err(anyhow!("unexpected trailing content in marker: {}", s));
+        }
+
+        Ok(marker)
+    }
+}
+
+impl Marker {
+    /// Evaluate the marker against an environment.
+    ///
+    /// `extras` holds the normalized names of extras being requested. The
+    /// `extra` marker variable matches any of them.
+    pub fn evaluate(&self, env: &MarkerEnvironment, extras: &BTreeSet<String>) -> bool {
+        match self {
+            Self::And(a, b) => a.evaluate(env, extras) && b.evaluate(env, extras),
+            Self::Or(a, b) => a.evaluate(env, extras) || b.evaluate(env, extras),
+            Self::Compare(left, op, right) => match (left, right) {
+                (MarkerValue::Variable(v), MarkerValue::Literal(l))
+                | (MarkerValue::Literal(l), MarkerValue::Variable(v))
+                    if v == "extra" =>
+                {
+                    let contains = extras.contains(&normalize_project_name(l));
+
+                    match op.as_str() {
+                        "==" => contains,
+                        "!=" => !contains,
+                        _ => false,
+                    }
+                }
+                _ => {
+                    let resolve = |value: &MarkerValue| match value {
+                        MarkerValue::Variable(v) => env.get(v).unwrap_or_default().to_string(),
+                        MarkerValue::Literal(l) => l.clone(),
+                    };
+
+                    compare_marker_values(&resolve(left), op, &resolve(right))
+                }
+            },
+        }
+    }
+}
+
+fn compare_marker_values(left: &str, op: &str, right: &str) -> bool {
+    match op {
+        "in" => return right.contains(left),
+        "not in" => return !right.contains(left),
+        _ => {}
+    }
+
+    // Comparisons are performed as version comparisons if the right side is a
+    // valid version specifier. Otherwise they fall back to string comparisons.
+    if let (Ok(spec), Ok(version)) = (
+        VersionSpecifier::from_str(&format!("{}{}", op, right)),
+        Version::from_str(left),
+    ) {
+        return spec.contains(&version);
+    }
+
+    match op {
+        "==" | "===" => left == right,
+        "!=" => left != right,
+        "<" => left < right,
+        "<=" => left <= right,
+        ">" => left > right,
+        ">=" => left >= right,
+        _ => false,
+    }
+}
+
+/// A PEP 508 dependency specifier. e.g. `requests[socks]>=2.0; python_version >= "3.7"`.
+#[derive(Clone, Debug)]
+pub struct Requirement {
+    /// The project name as written.
+    pub name: String,
+    /// Normalized names of extras requested.
+    pub extras: BTreeSet<String>,
+    /// Constraints on the version.
+    pub specifiers: VersionSpecifiers,
+    /// Marker controlling whether the requirement applies.
+    pub marker: Option<Marker>,
+    raw: String,
+}
+
+impl FromStr for Requirement {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let raw = s.trim().to_string();
+
+        let (requirement, marker) = match raw.split_once(';') {
+            Some((requirement, marker)) => (requirement.trim(), Some(Marker::from_str(marker)?)),
+            None => (raw.as_str(), None),
+        };
+
+        let name = RE_NAME
+            .find(requirement)
+            .ok_or_else(|| anyhow!("invalid requirement: {}", s))?
+            .as_str()
+            .to_string();
+
+        let mut rest = requirement[name.len()..].trim_start();
+
+        let mut extras = BTreeSet::new();
+        if let Some(remaining) = rest.strip_prefix('[') {
+            let end = remaining
+                .find(']')
+                .ok_or_else(|| anyhow!("unterminated extras in requirement: {}", s))?;
+
+            extras.extend(
+                remaining[0..end]
+                    .split(',')
+                    .map(|x| x.trim())
+                    .filter(|x| !x.is_empty())
+                    .map(normalize_project_name),
+            );
+
+            rest = remaining[end + 1..].trim_start();
+        }
+
+        if rest.starts_with('@') {
+            return Err(anyhow!("URL requirements are not supported: {}", s));
+        }
+
+        let rest = match rest.strip_prefix('(') {
+            Some(inner) => inner
+                .strip_suffix(')')
+                .ok_or_else(|| anyhow!("unterminated version specifiers in requirement: {}", s))?,
+            None => rest,
+        };
+
+        let specifiers = VersionSpecifiers::from_str(rest)?;
+
+        Ok(Self {
+            name,
+            extras,
+            specifiers,
+            marker,
+            raw,
+        })
+    }
+}
+
+impl Display for Requirement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+
+impl Requirement {
+    /// The normalized project name.
+    pub fn project_name(&self) -> String {
+        normalize_project_name(&self.name)
+    }
+
+    /// Whether the requirement applies given an environment and requested extras.
+    pub fn applies(&self, env: &MarkerEnvironment, extras: &BTreeSet<String>) -> bool {
+        match &self.marker {
+            Some(marker) => marker.evaluate(env, extras),
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(s: &str) -> Version {
+        Version::from_str(s).unwrap()
+    }
+
+    fn contains(spec: &str, version: &str) -> bool {
+        VersionSpecifiers::from_str(spec)
+            .unwrap()
+            .contains(&v(version))
+    }
+
+    #[test]
+    fn normalize_names() {
+        assert_eq!(normalize_project_name("Foo.Bar"), "foo-bar");
+        assert_eq!(normalize_project_name("foo__bar"), "foo-bar");
+        assert_eq!(normalize_project_name("foo-_.bar"), "foo-bar");
+    }
+
+    #[test]
+    fn version_ordering() -> Result<()> {
+        let ordered = [
+            "1.0.dev0",
+            "1.0a1.dev1",
+            "1.0a1",
+            "1.0b2",
+            "1.0rc1",
+            "1.0",
+            "1.0+local",
+            "1.0.post1.dev1",
+            "1.0.post1",
+            "1.1",
+            "1!0.1",
+        ];
+
+        for pair in ordered.windows(2) {
+            assert!(v(pair[0]) < v(pair[1]), "{} < {}", pair[0], pair[1]);
+        }
+
+        assert_eq!(v("1.0"), v("1.0.0"));
+        assert_eq!(v("1.0alpha1"), v("1.0a1"));
+        assert_eq!(v("1.0-1"), v("1.0.post1"));
+        assert_eq!(v("v1.0.0-RC1").to_string(), "1.0.0rc1");
+        assert!(Version::from_str("not a version").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn specifiers() {
+        assert!(contains("", "1.0"));
+        assert!(contains("==1.0", "1.0.0"));
+        assert!(contains("==1.0", "1.0+local"));
+        assert!(!contains("==1.0+local", "1.0"));
+        assert!(contains("==1.*", "1.9"));
+        assert!(!contains("==1.*", "2.0"));
+        assert!(contains("!=1.5", "1.6"));
+        assert!(!contains("!=1.5.*", "1.5.3"));
+        assert!(contains(">=1.0,<2", "1.5"));
+        assert!(!contains(">=1.0,<2", "2.0"));
+        assert!(!contains("<2.0", "2.0rc1"));
+        assert!(contains("<2.0rc2", "2.0rc1"));
+        assert!(!contains(">1.0", "1.0.post1"));
+        assert!(contains(">1.0.post1", "1.0.post2"));
+        assert!(contains("~=2.2", "2.9"));
+        assert!(!contains("~=2.2", "3.0"));
+        assert!(contains("~=1.4.5", "1.4.9"));
+        assert!(!contains("~=1.4.5", "1.5.0"));
+        assert!(contains("===1.0", "1.0"));
+        assert!(VersionSpecifiers::from_str("~=1").is_err());
+        assert!(VersionSpecifiers::from_str(">=1.*").is_err());
+        assert!(VersionSpecifiers::from_str(">=1.0a1")
+            .unwrap()
+            .allows_prereleases());
+    }
+
+    #[test]
+    fn markers() -> Result<()> {
+        let env = MarkerEnvironment {
+            implementation_name: "cpython".to_string(),
+            os_name: "posix".to_string(),
+            platform_machine: "x86_64".to_string(),
+            platform_python_implementation: "CPython".to_string(),
+            platform_system: "Linux".to_string(),
+            python_full_version: "3.10.4".to_string(),
+            python_version: "3.10".to_string(),
+            sys_platform: "linux".to_string(),
+            ..MarkerEnvironment::default()
+        };
+        let no_extras = BTreeSet::new();
+        let extras = BTreeSet::from(["socks".to_string()]);
+
+        let eval = |s: &str, extras: &BTreeSet<String>| -> Result<bool> {
+            Ok(Marker::from_str(s)?.evaluate(&env, extras))
+        };
+
+        assert!(eval("python_version >= '3.8'", &no_extras)?);
+        // Version comparison, not string comparison.
+        assert!(!eval("python_version < '3.9'", &no_extras)?);
+        assert!(eval("sys_platform == 'linux'", &no_extras)?);
+        assert!(!eval("sys_platform == \"win32\"", &no_extras)?);
+        assert!(eval("'linux' in sys_platform", &no_extras)?);
+        assert!(eval("platform_machine not in 'arm64 aarch64'", &no_extras)?);
+        assert!(eval(
+            "(os_name == 'nt' or sys_platform == 'linux') and implementation_name == 'cpython'",
+            &no_extras
+        )?);
+        assert!(!eval("extra == 'socks'", &no_extras)?);
+        assert!(eval("extra == 'Socks'", &extras)?);
+        assert!(eval("python_version < '3' or extra == \"socks\"", &extras)?);
+        assert!(Marker::from_str("bogus == 'x'").is_err());
+        assert!(Marker::from_str("python_version >= '3.8' and").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn requirements() -> Result<()> {
+        let r = Requirement::from_str("requests")?;
+        assert_eq!(r.project_name(), "requests");
+        assert!(r.specifiers.is_empty());
+        assert!(r.marker.is_none());
+
+        let r = Requirement::from_str(
+            "Requests[Socks, security] (>=2.0,<3) ; python_version >= \"3.6\"",
+        )?;
+        assert_eq!(r.name, "Requests");
+        assert_eq!(
+            r.extras,
+            BTreeSet::from(["security".to_string(), "socks".to_string()])
+        );
+        assert!(r.specifiers.contains(&v("2.28.1")));
+        assert!(!r.specifiers.contains(&v("3.0")));
+        assert!(r.marker.is_some());
+
+        let r = Requirement::from_str("zope.interface>=5")?;
+        assert_eq!(r.project_name(), "zope-interface");
+
+        assert!(Requirement::from_str("foo @ https://example.com/foo.whl").is_err());
+        assert!(Requirement::from_str("foo >= bar").is_err());
+
+        Ok(())
+    }
+}
@@ -25,11 +25,12 @@ use {
         },
     },
     anyhow::{anyhow, Context, Result},
-    python_packed_resources::Resource,
+    python_packed_resources::{PackedResourcesWriteOptions, Resource},
+    sha2::{Digest, Sha256},
     std::{
         borrow::Cow,
         collections::{BTreeMap, BTreeSet, HashMap},
-        path::PathBuf,
+        path::{Path, PathBuf},
     },
     tugger_file_manifest::{File, FileData, FileEntry, FileManifest},
 };
@@ -100,6 +101,46 @@ impl PrePackagedResource {
             || self.is_extension_module
     }
 
+    /// Obtain the filesystem path of the source code this resource was derived from.
+    ///
+    /// Only Python module source backed by a file is considered. Source
+    /// used to compile bytecode counts as well, so the path is available
+    /// even if the source itself isn't packaged.
+    pub fn original_source_path(&self) -> Option<&Path> {
+        let bytecode_providers = [
+            self.in_memory_bytecode.as_ref(),
+            self.in_memory_bytecode_opt1.as_ref(),
+            self.in_memory_bytecode_opt2.as_ref(),
+            self.relative_path_bytecode
+                .as_ref()
+                .map(|(_, _, provider)| provider),
+            self.relative_path_bytecode_opt1
+                .as_ref()
+                .map(|(_, _, provider)| provider),
+            self.relative_path_bytecode_opt2
+                .as_ref()
+                .map(|(_, _, provider)| provider),
+        ];
+
+        [
+            self.in_memory_source.as_ref(),
+            self.relative_path_module_source
+                .as_ref()
+                .map(|(_, location)| location),
+        ]
+        .into_iter()
+        .chain(
+            bytecode_providers
+                .into_iter()
+                .map(|provider| match provider {
+                    Some(PythonModuleBytecodeProvider::FromSource(location)) => Some(location),
+                    _ => None,
+                }),
+        )
+        .flatten()
+        .find_map(|location| location.backing_path())
+    }
+
     /// Convert the instance to a `Resource`.
     ///
     /// This will compile bytecode from source code using the specified compiler.
@@ -111,6 +152,16 @@ impl PrePackagedResource {
     ) -> Result<(Resource<'a, u8>, Vec<FileInstall>)> {
         let mut installs = Vec::new();
 
+        let original_source = if let Some(path) = self.original_source_path() {
+            let data = std::fs::read(path)
+                .with_context(|| format!("reading original source from {}", path.display()))?;
+            let digest: [u8; 32] = Sha256::digest(&data).into();
+
+            Some((path.to_string_lossy().to_string(), digest))
+        } else {
+            None
+        };
+
         let resource = Resource {
             name: Cow::Owned(self.name.clone()),
             is_python_package: self.is_package,
@@ -402,6 +453,9 @@ impl PrePackagedResource {
             } else {
                 None
             },
+            content_digest_sha256: None,
+            original_source_digest_sha256: original_source.as_ref().map(|(_, digest)| *digest),
+            original_source_path: original_source.map(|(path, _)| Cow::Owned(path)),
         };
 
         if let Some((prefix, filename, location)) = &self.relative_path_shared_library {
@@ -646,15 +700,36 @@ pub struct CompiledResourcesCollection<'a> {
 
 impl<'a> CompiledResourcesCollection<'a> {
     /// Write resources to packed resources data, version 1.
-    pub fn write_packed_resources<W: std::io::Write>(&self, writer: &mut W) -> Result<()> {
+    ///
+    /// `options` controls how the data is written, including compression,
+    /// content digests, the name index, and encryption.
+    ///
+    /// `source_map` defines whether to retain the paths and digests of the
+    /// original source files resources were derived from. If false, these
+    /// fields are stripped from the written data.
+    pub fn write_packed_resources<W: std::io::Write>(
+        &self,
+        writer: &mut W,
+        options: &PackedResourcesWriteOptions,
+        source_map: bool,
+    ) -> Result<()> {
         python_packed_resources::write_packed_resources_v3(
             &self
                 .resources
                 .values()
-                .cloned()
+                .map(|resource| {
+                    let mut resource = resource.clone();
+
+                    if !source_map {
+                        resource.original_source_path = None;
+                        resource.original_source_digest_sha256 = None;
+                    }
+
+                    resource
+                })
                 .collect::<Vec<Resource<'a, u8>>>(),
             writer,
-            None,
+            options,
         )
     }
 
@@ -1998,6 +2073,45 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_resource_conversion_original_source() -> Result<()> {
+        let mut compiler = FakeBytecodeCompiler { magic_number: 42 };
+
+        let td = tempfile::Builder::new()
+            .prefix("python-packaging-test")
+            .tempdir()?;
+        let source_path = td.path().join("module.py");
+        std::fs::write(&source_path, b"source")?;
+
+        let pre = PrePackagedResource {
+            is_module: true,
+            name: "module".to_string(),
+            in_memory_bytecode: Some(PythonModuleBytecodeProvider::FromSource(FileData::Path(
+                source_path.clone(),
+            ))),
+            ..PrePackagedResource::default()
+        };
+
+        assert_eq!(pre.original_source_path(), Some(source_path.as_path()));
+
+        let (resource, installs) = pre.to_resource(&mut compiler)?;
+
+        assert_eq!(
+            resource,
+            Resource {
+                is_python_module: true,
+                name: Cow::Owned("module".to_string()),
+                in_memory_bytecode: Some(Cow::Owned(b"bc0source".to_vec())),
+                original_source_path: Some(Cow::Owned(source_path.to_string_lossy().to_string())),
+                original_source_digest_sha256: Some(Sha256::digest(b"source").into()),
+                ..Resource::default()
+            }
+        );
+        assert!(installs.is_empty());
+
+        Ok(())
+    }
+
     #[test]
     fn test_resource_conversion_in_memory_bytecode_opt1_provided() -> Result<()> {
         let mut compiler = FakeBytecodeCompiler { magic_number: 42 };
@@ -0,0 +1,672 @@
+// Copyright 2022 Gregory Szorc.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*! Resolve requirements against a directory of wheels.
+
+This module provides a minimal alternative to `pip download` / `pip install`
+for when all distributions are available as wheels in a local directory
+(a *wheelhouse*). It performs no network I/O and never executes Python.
+
+Resolution is greedy: the first time a project is encountered, the highest
+compatible version satisfying the requirement being processed is selected.
+There is no backtracking. If a later requirement conflicts with an
+already-selected version, resolution fails.
+*/
+
+use {
+    crate::{
+        requirement::{normalize_project_name, MarkerEnvironment, Requirement, Version},
+        wheel::WheelArchive,
+    },
+    anyhow::{anyhow, Context, Result},
+    once_cell::sync::Lazy,
+    std::{
+        collections::{BTreeMap, BTreeSet, VecDeque},
+        path::{Path, PathBuf},
+        str::FromStr,
+    },
+};
+
+/// Regular expression for parsing wheel filenames.
+///
+/// See <https://peps.python.org/pep-0427/#file-name-convention>.
+static RE_WHEEL_FILENAME: Lazy<regex::Regex> = Lazy::new(|| {
+    regex::Regex::new(
+        r"^(?P<name>[^-]+)-(?P<version>[^-]+)(-(?P<build>\d[^-]*))?-(?P<python>[^-]+)-(?P<abi>[^-]+)-(?P<platform>[^-]+)\.whl$",
+    )
+    .unwrap()
+});
+
+/// Represents the parsed filename of a wheel.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct WheelFilename {
+    /// Distribution name, as it appears in the filename.
+    pub name: String,
+    /// Version string.
+    pub version: String,
+    /// Optional build tag.
+    pub build: Option<String>,
+    /// Python tags. e.g. `py3` or `cp39`.
+    pub python_tags: Vec<String>,
+    /// ABI tags. e.g. `none` or `cp39`.
+    pub abi_tags: Vec<String>,
+    /// Platform tags. e.g. `any` or `manylinux2014_x86_64`.
+    pub platform_tags: Vec<String>,
+}
+
+impl WheelFilename {
+    /// Parse a wheel filename.
+    pub fn parse(filename: &str) -> Result<Self> {
+        let captures = RE_WHEEL_FILENAME
+            .captures(filename)
+            .ok_or_else(|| anyhow!("invalid wheel filename: {}", filename))?;
+
+        let split = |name: &str| {
+            captures[name]
+                .split('.')
+                .map(|x| x.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        Ok(Self {
+            name: captures["name"].to_string(),
+            version: captures["version"].to_string(),
+            build: captures.name("build").map(|m| m.as_str().to_string()),
+            python_tags: split("python"),
+            abi_tags: split("abi"),
+            platform_tags: split("platform"),
+        })
+    }
+
+    /// The normalized project name.
+    pub fn project_name(&self) -> String {
+        normalize_project_name(&self.name)
+    }
+
+    /// Obtain all `(python, abi, platform)` tag triples this wheel supports.
+    ///
+    /// Compressed tag sets (e.g. `py2.py3`) are expanded.
+    pub fn tags(&self) -> Vec<(String, String, String)> {
+        let mut res = vec![];
+
+        for python in &self.python_tags {
+            for abi in &self.abi_tags {
+                for platform in &self.platform_tags {
+                    res.push((python.clone(), abi.clone(), platform.clone()));
+                }
+            }
+        }
+
+        res
+    }
+}
+
+/// Obtain platform tags compatible with a given platform tag.
+///
+/// Tags are ordered from most to least preferred. A tag of `none` (signifying
+/// an unknown platform) yields no tags.
+pub fn compatible_platform_tags(tag: &str) -> Vec<String> {
+    if tag == "none" {
+        return vec![];
+    }
+
+    if let Some(arch) = tag.strip_prefix("manylinux2014_") {
+        // manylinux2014 is glibc 2.17. manylinux2010 (2.12) and manylinux1 (2.5)
+        // were only defined for x86 architectures.
+        let min_minor = if matches!(arch, "x86_64" | "i686") {
+            5
+        } else {
+            17
+        };
+
+        let mut res = vec![];
+        for minor in (min_minor..=17).rev() {
+            res.push(format!("manylinux_2_{}_{}", minor, arch));
+
+            match minor {
+                17 => res.push(format!("manylinux2014_{}", arch)),
+                12 => res.push(format!("manylinux2010_{}", arch)),
+                5 => res.push(format!("manylinux1_{}", arch)),
+                _ => {}
+            }
+        }
+        res.push(format!("linux_{}", arch));
+
+        return res;
+    }
+
+    if let Some(rest) = tag.strip_prefix("macosx_") {
+        let parts = rest.splitn(3, '_').collect::<Vec<_>>();
+
+        if let [major, minor, arch] = parts[..] {
+            if let (Ok(major), Ok(minor)) = (major.parse::<u32>(), minor.parse::<u32>()) {
+                let formats: &[&str] = match arch {
+                    "x86_64" => &[
+                        "x86_64",
+                        "intel",
+                        "fat64",
+                        "fat32",
+                        "universal2",
+                        "universal",
+                    ],
+                    "arm64" => &["arm64", "universal2"],
+                    _ => &[],
+                };
+
+                let mut versions = vec![];
+                if major >= 11 {
+                    versions.extend((11..=major).rev().map(|major| (major, 0)));
+                    versions.extend((4..=16).rev().map(|minor| (10, minor)));
+                } else {
+                    versions.extend((4..=minor).rev().map(|minor| (10, minor)));
+                }
+
+                let mut res = vec![tag.to_string()];
+                for (major, minor) in versions {
+                    for format in formats {
+                        // arm64 binaries only exist for macOS 11+. But universal2
+                        // binaries can target older versions.
+                        if major == 10 && arch == "arm64" && *format != "universal2" {
+                            continue;
+                        }
+
+                        let candidate = format!("macosx_{}_{}_{}", major, minor, format);
+                        if !res.contains(&candidate) {
+                            res.push(candidate);
+                        }
+                    }
+                }
+
+                return res;
+            }
+        }
+    }
+
+    vec![tag.to_string()]
+}
+
+/// Defines the set of wheel tags supported by a Python interpreter.
+#[derive(Clone, Debug, Default)]
+pub struct WheelTagCompatibility {
+    /// Supported `(python, abi, platform)` tags, from most to least preferred.
+    tags: Vec<(String, String, String)>,
+}
+
+impl WheelTagCompatibility {
+    /// Construct an instance from an explicit, ordered list of tags.
+    pub fn new(tags: Vec<(String, String, String)>) -> Self {
+        Self { tags }
+    }
+
+    /// Construct an instance describing a CPython interpreter.
+    ///
+    /// `abi` is the interpreter's ABI tag (e.g. `cp39`). `platform` is its
+    /// platform tag (e.g. `manylinux2014_x86_64`). Tag ordering mirrors
+    /// `packaging.tags.sys_tags()`.
+    pub fn cpython(major: u32, minor: u32, abi: &str, platform: &str) -> Self {
+        let platforms = compatible_platform_tags(platform);
+        let interpreter = format!("cp{}{}", major, minor);
+        let mut tags = vec![];
+
+        let mut push = |python: &str, abi: &str, platform: &str| {
+            tags.push((python.to_string(), abi.to_string(), platform.to_string()));
+        };
+
+        for abi in [abi, "abi3", "none"] {
+            for platform in &platforms {
+                push(&interpreter, abi, platform);
+            }
+        }
+
+        for older in (2..minor).rev() {
+            for platform in &platforms {
+                push(&format!("cp{}{}", major, older), "abi3", platform);
+            }
+        }
+
+        let mut generic = vec![format!("py{}{}", major, minor), format!("py{}", major)];
+        generic.extend(
+            (0..minor)
+                .rev()
+                .map(|minor| format!("py{}{}", major, minor)),
+        );
+
+        for python in &generic {
+            for platform in &platforms {
+                push(python, "none", platform);
+            }
+        }
+
+        push(&interpreter, "none", "any");
+
+        for python in &generic {
+            push(python, "none", "any");
+        }
+
+        Self { tags }
+    }
+
+    /// Obtain the supported tags, from most to least preferred.
+    pub fn tags(&self) -> &[(String, String, String)] {
+        &self.tags
+    }
+
+    /// Obtain the preference of a wheel.
+    ///
+    /// Lower values are more preferred. Returns `None` if the wheel is not
+    /// compatible.
+    pub fn priority(&self, wheel: &WheelFilename) -> Option<usize> {
+        let wheel_tags = wheel.tags();
+
+        self.tags.iter().position(|tag| wheel_tags.contains(tag))
+    }
+
+    /// Whether a wheel is compatible.
+    pub fn is_compatible(&self, wheel: &WheelFilename) -> bool {
+        self.priority(wheel).is_some()
+    }
+}
+
+/// A wheel file available to a resolver.
+#[derive(Clone, Debug)]
+struct WheelCandidate {
+    path: PathBuf,
+    filename: WheelFilename,
+    version: Version,
+}
+
+/// A wheel selected by resolution.
+#[derive(Clone, Debug)]
+pub struct ResolvedWheel {
+    /// Normalized project name.
+    pub name: String,
+    /// Selected version.
+    pub version: Version,
+    /// Filesystem path of the wheel.
+    pub path: PathBuf,
+}
+
+/// State for a project selected during resolution.
+struct Selection {
+    wheel: ResolvedWheel,
+    requires: Vec<Requirement>,
+    extras: BTreeSet<String>,
+}
+
+/// Resolves requirements to wheels in local directories.
+#[derive(Clone, Debug)]
+pub struct WheelResolver {
+    tags: WheelTagCompatibility,
+    environment: MarkerEnvironment,
+    /// Available wheels, keyed by normalized project name.
+    candidates: BTreeMap<String, Vec<WheelCandidate>>,
+}
+
+impl WheelResolver {
+    /// Construct a new instance targeting an interpreter.
+    pub fn new(tags: WheelTagCompatibility, environment: MarkerEnvironment) -> Self {
+        Self {
+            tags,
+            environment,
+            candidates: BTreeMap::new(),
+        }
+    }
+
+    /// Register a wheel file as available for resolution.
+    pub fn add_wheel(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+
+        let filename = path
+            .file_name()
+            .ok_or_else(|| anyhow!("could not derive file name of {}", path.display()))?
+            .to_string_lossy();
+
+        let filename = WheelFilename::parse(&filename)?;
+        let version = Version::from_str(&filename.version)
+            .with_context(|| format!("parsing version of {}", path.display()))?;
+
+        self.candidates
+            .entry(filename.project_name())
+            .or_default()
+            .push(WheelCandidate {
+                path: path.to_path_buf(),
+                filename,
+                version,
+            });
+
+        Ok(())
+    }
+
+    /// Register all `.whl` files in a directory as available for resolution.
+    ///
+    /// Subdirectories one level deep are also scanned, which allows the use
+    /// of per-project directories as found in simple repository layouts.
+    pub fn add_directory(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+
+        if !path.is_dir() {
+            return Err(anyhow!("{} is not a directory", path.display()));
+        }
+
+        for entry in walkdir::WalkDir::new(path).max_depth(2).sort_by_file_name() {
+            let entry = entry?;
+
+            if entry.file_type().is_file()
+                && entry.path().extension().and_then(|x| x.to_str()) == Some("whl")
+            {
+                self.add_wheel(entry.path())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Select the best wheel for a requirement.
+    fn select(&self, requirement: &Requirement) -> Result<&WheelCandidate> {
+        let name = requirement.project_name();
+
+        let candidates = self
+            .candidates
+            .get(&name)
+            .ok_or_else(|| anyhow!("no wheels available for {}", name))?;
+
+        let allow_prereleases = requirement.specifiers.allows_prereleases();
+
+        candidates
+            .iter()
+            .filter(|c| allow_prereleases || !c.version.is_prerelease())
+            .filter(|c| requirement.specifiers.contains(&c.version))
+            .filter_map(|c| self.tags.priority(&c.filename).map(|p| (c, p)))
+            // Highest version wins. Ties are broken by tag preference.
+            .max_by(|(a, a_priority), (b, b_priority)| {
+                a.version
+                    .cmp(&b.version)
+                    .then_with(|| b_priority.cmp(a_priority))
+            })
+            .map(|(c, _)| c)
+            .ok_or_else(|| {
+                anyhow!(
+                    "no compatible wheel for {} satisfies {} (available: {})",
+                    name,
+                    requirement,
+                    candidates
+                        .iter()
+                        .map(|c| c.path.file_name().unwrap_or_default().to_string_lossy())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })
+    }
+
+    /// Resolve requirements to a set of wheels.
+    ///
+    /// Dependencies declared via `Requires-Dist` metadata are followed.
+    /// Returned wheels are ordered by normalized project name.
+    pub fn resolve(&self, requirements: &[Requirement]) -> Result<Vec<ResolvedWheel>> {
+        let mut selections: BTreeMap<String, Selection> = BTreeMap::new();
+
+        // Requirements to process, along with the extras requested of the
+        // project declaring them.
+        let mut queue = requirements
+            .iter()
+            .map(|r| (r.clone(), BTreeSet::new()))
+            .collect::<VecDeque<_>>();
+
+        while let Some((requirement, parent_extras)) = queue.pop_front() {
+            if !requirement.applies(&self.environment, &parent_extras) {
+                continue;
+            }
+
+            let name = requirement.project_name();
+
+            if let Some(selection) = selections.get_mut(&name) {
+                if !requirement.specifiers.contains(&selection.wheel.version) {
+                    return Err(anyhow!(
+                        "{} {} was selected but {} is also required",
+                        name,
+                        selection.wheel.version,
+                        requirement
+                    ));
+                }
+
+                // Newly requested extras may pull in additional dependencies.
+                if !requirement.extras.is_subset(&selection.extras) {
+                    selection.extras.extend(requirement.extras.iter().cloned());
+
+                    for r in &selection.requires {
+                        queue.push_back((r.clone(), selection.extras.clone()));
+                    }
+                }
+
+                continue;
+            }
+
+            let candidate = self.select(&requirement)?;
+
+            let metadata = WheelArchive::from_path(&candidate.path)?
+                .metadata()
+                .with_context(|| format!("reading metadata from {}", candidate.path.display()))?;
+
+            let requires = metadata
+                .find_all_headers("Requires-Dist")
+                .into_iter()
+                .map(|s| {
+                    Requirement::from_str(s).with_context(|| {
+                        format!("parsing Requires-Dist of {}", candidate.path.display())
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            for r in &requires {
+                queue.push_back((r.clone(), requirement.extras.clone()));
+            }
+
+            selections.insert(
+                name.clone(),
+                Selection {
+                    wheel: ResolvedWheel {
+                        name,
+                        version: candidate.version.clone(),
+                        path: candidate.path.clone(),
+                    },
+                    requires,
+                    extras: requirement.extras.clone(),
+                },
+            );
+        }
+
+        Ok(selections.into_values().map(|s| s.wheel).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::wheel_builder::WheelBuilder};
+
+    fn linux_environment() -> MarkerEnvironment {
+        MarkerEnvironment {
+            implementation_name: "cpython".to_string(),
+            os_name: "posix".to_string(),
+            platform_machine: "x86_64".to_string(),
+            platform_python_implementation: "CPython".to_string(),
+            platform_system: "Linux".to_string(),
+            python_full_version: "3.9.13".to_string(),
+            python_version: "3.9".to_string(),
+            sys_platform: "linux".to_string(),
+            ..MarkerEnvironment::default()
+        }
+    }
+
+    fn linux_resolver() -> WheelResolver {
+        WheelResolver::new(
+            WheelTagCompatibility::cpython(3, 9, "cp39", "manylinux2014_x86_64"),
+            linux_environment(),
+        )
+    }
+
+    fn write_wheel(
+        dir: &Path,
+        name: &str,
+        version: &str,
+        tag: &str,
+        requires: &[&str],
+    ) -> Result<PathBuf> {
+        let mut builder = WheelBuilder::new(name, version);
+        builder.set_tag(tag)?;
+
+        let mut metadata = format!(
+            "Metadata-Version: 2.1\nName: {}\nVersion: {}\n",
+            name, version
+        );
+        for r in requires {
+            metadata.push_str(&format!("Requires-Dist: {}\n", r));
+        }
+        builder.add_file_dist_info("METADATA", metadata.as_bytes())?;
+        builder.add_file(
+            format!("{}/__init__.py", name.replace('-', "_")),
+            b"".as_ref(),
+        )?;
+
+        builder.write_wheel_into_directory(dir)
+    }
+
+    fn resolved_names(resolved: &[ResolvedWheel]) -> Vec<String> {
+        resolved
+            .iter()
+            .map(|w| format!("{}=={}", w.name, w.version))
+            .collect()
+    }
+
+    #[test]
+    fn parse_filename() -> Result<()> {
+        let f = WheelFilename::parse("foo_bar-1.0-1-py2.py3-none-any.whl")?;
+        assert_eq!(f.project_name(), "foo-bar");
+        assert_eq!(f.version, "1.0");
+        assert_eq!(f.build, Some("1".to_string()));
+        assert_eq!(f.python_tags, vec!["py2", "py3"]);
+        assert_eq!(f.tags().len(), 2);
+
+        assert!(WheelFilename::parse("foo-1.0.tar.gz").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn platform_tags() {
+        let tags = compatible_platform_tags("manylinux2014_x86_64");
+        assert_eq!(tags[0], "manylinux_2_17_x86_64");
+        assert_eq!(tags[1], "manylinux2014_x86_64");
+        assert!(tags.contains(&"manylinux1_x86_64".to_string()));
+        assert_eq!(tags.last().unwrap(), "linux_x86_64");
+
+        let tags = compatible_platform_tags("manylinux2014_aarch64");
+        assert!(!tags.contains(&"manylinux1_aarch64".to_string()));
+
+        let tags = compatible_platform_tags("macosx_11_0_arm64");
+        assert_eq!(tags[0], "macosx_11_0_arm64");
+        assert!(tags.contains(&"macosx_10_9_universal2".to_string()));
+        assert!(!tags.contains(&"macosx_10_9_arm64".to_string()));
+
+        let tags = compatible_platform_tags("macosx_10_9_x86_64");
+        assert!(tags.contains(&"macosx_10_6_intel".to_string()));
+        assert!(!tags.contains(&"macosx_10_10_x86_64".to_string()));
+
+        assert_eq!(compatible_platform_tags("win_amd64"), vec!["win_amd64"]);
+        assert!(compatible_platform_tags("none").is_empty());
+    }
+
+    #[test]
+    fn tag_compatibility() -> Result<()> {
+        let tags = WheelTagCompatibility::cpython(3, 9, "cp39", "win_amd64");
+
+        let priority = |filename: &str| -> Result<Option<usize>> {
+            Ok(tags.priority(&WheelFilename::parse(filename)?))
+        };
+
+        let native = priority("foo-1.0-cp39-cp39-win_amd64.whl")?.unwrap();
+        let abi3 = priority("foo-1.0-cp36-abi3-win_amd64.whl")?.unwrap();
+        let pure = priority("foo-1.0-py3-none-any.whl")?.unwrap();
+        assert!(native < abi3);
+        assert!(abi3 < pure);
+
+        assert!(priority("foo-1.0-cp310-cp310-win_amd64.whl")?.is_none());
+        assert!(priority("foo-1.0-cp39-cp39-win32.whl")?.is_none());
+        assert!(priority("foo-1.0-py2-none-any.whl")?.is_none());
+
+        // Unknown platforms can only use pure Python wheels.
+        let tags = WheelTagCompatibility::cpython(3, 9, "cp39", "none");
+        assert!(tags.is_compatible(&WheelFilename::parse("foo-1.0-py3-none-any.whl")?));
+        assert!(!tags.is_compatible(&WheelFilename::parse("foo-1.0-cp39-cp39-linux_x86_64.whl")?));
+
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_dependencies() -> Result<()> {
+        let td = tempfile::Builder::new()
+            .prefix("python-packaging-test")
+            .tempdir()?;
+
+        write_wheel(
+            td.path(),
+            "app",
+            "1.0",
+            "py3-none-any",
+            &["lib>=1.0", "extra-dep; extra == 'fancy'"],
+        )?;
+        write_wheel(td.path(), "lib", "1.0", "py3-none-any", &[])?;
+        write_wheel(
+            td.path(),
+            "lib",
+            "1.1",
+            "py3-none-any",
+            &["win-only; sys_platform == 'win32'"],
+        )?;
+        write_wheel(td.path(), "lib", "2.0rc1", "py3-none-any", &[])?;
+        write_wheel(td.path(), "lib", "3.0", "cp39-cp39-win_amd64", &[])?;
+        write_wheel(
+            td.path(),
+            "extra_dep",
+            "0.1",
+            "cp39-cp39-manylinux2014_x86_64",
+            &[],
+        )?;
+
+        let mut resolver = linux_resolver();
+        resolver.add_directory(td.path())?;
+
+        let resolved = resolver.resolve(&[Requirement::from_str("app")?])?;
+        assert_eq!(resolved_names(&resolved), vec!["app==1.0", "lib==1.1"]);
+
+        let resolved = resolver.resolve(&[Requirement::from_str("App[Fancy]")?])?;
+        assert_eq!(
+            resolved_names(&resolved),
+            vec!["app==1.0", "extra-dep==0.1", "lib==1.1"]
+        );
+
+        let resolved = resolver.resolve(&[Requirement::from_str("lib>=2.0a1")?])?;
+        assert_eq!(resolved_names(&resolved), vec!["lib==2.0rc1"]);
+
+        // Greedy resolution doesn't backtrack.
+        assert!(resolver
+            .resolve(&[
+                Requirement::from_str("lib")?,
+                Requirement::from_str("lib<1.1")?
+            ])
+            .is_err());
+
+        assert!(resolver
+            .resolve(&[Requirement::from_str("missing")?])
+            .is_err());
+        assert!(resolver
+            .resolve(&[Requirement::from_str("lib>=3")?])
+            .is_err());
+
+        Ok(())
+    }
+}
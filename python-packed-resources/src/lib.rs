@@ -35,8 +35,8 @@ mod serialization;
 mod writer;
 
 pub use crate::{
-    parser::{load_resources, ResourceParserIterator},
+    parser::{load_resources, load_resources_verified, ResourceParserIterator, ResourcesIndex},
     resource::Resource,
-    serialization::HEADER_V3,
-    writer::write_packed_resources_v3,
+    serialization::{BlobCompression, BlobInteriorPadding, HEADER_V3},
+    writer::{write_packed_resources_v3, PackedResourcesWriteOptions},
 };
@@ -11,10 +11,19 @@
 use {
     crate::{
         resource::Resource,
-        serialization::{BlobInteriorPadding, BlobSectionField, ResourceField, HEADER_V3},
+        serialization::{
+            encryption_key_check, BlobCompression, BlobEncryption, BlobInteriorPadding,
+            BlobSectionField, ResourceField, COMPRESSIBLE_FIELDS, HEADER_V3,
+        },
     },
     byteorder::{LittleEndian, ReadBytesExt},
-    std::{borrow::Cow, collections::HashMap, io::Cursor, path::Path},
+    sha2::{Digest, Sha256},
+    std::{
+        borrow::Cow,
+        collections::HashMap,
+        io::{Cursor, Read},
+        path::Path,
+    },
 };
 
 #[cfg(unix)]
@@ -28,6 +37,10 @@ struct BlobSection {
     resource_field: u8,
     raw_payload_length: usize,
     interior_padding: Option<BlobInteriorPadding>,
+    compression: Option<BlobCompression>,
+    sha256_digest: Option<[u8; 32]>,
+    encryption_nonce: Option<[u8; 12]>,
+    encryption_key_check: Option<[u8; 16]>,
 }
 
 /// Holds state used to read an individual blob section.
@@ -35,19 +48,34 @@ struct BlobSection {
 struct BlobSectionReadState {
     offset: usize,
     interior_padding: BlobInteriorPadding,
+    compression: BlobCompression,
+    /// Offset of the start of the section.
+    ///
+    /// Used to seek the cipher of encrypted sections.
+    section_offset: usize,
+    encryption_nonce: Option<[u8; 12]>,
+    encryption_key_check: Option<[u8; 16]>,
 }
 
 /// An iterator over an actively parsed packed resources data structure.
 ///
 /// The iterator emits [Resource] instances. The index data for a given resource is
 /// not read or validated until the iterator attempts to deserialize it.
+///
+/// If the iterator was obtained via [load_resources_verified()], the content
+/// digest of each resource is verified as it is deserialized.
 pub struct ResourceParserIterator<'a> {
     done: bool,
+    verify: bool,
     data: &'a [u8],
     reader: Cursor<&'a [u8]>,
     blob_sections: [Option<BlobSectionReadState>; 256],
     claimed_resources_count: usize,
     read_resources_count: usize,
+    name_index: Option<ResourcesIndex<'a>>,
+    encryption_key: Option<[u8; 32]>,
+    /// Whether to advance past blobs in blob sections without resolving their data.
+    skip_blob_data: bool,
 }
 
 impl<'a> ResourceParserIterator<'a> {
@@ -56,6 +84,154 @@ impl<'a> ResourceParserIterator<'a> {
         self.claimed_resources_count
     }
 
+    /// Obtain the name index of the parsed data.
+    ///
+    /// Returns `None` if the data was written without a name index.
+    pub fn name_index(&self) -> Option<&ResourcesIndex<'a>> {
+        self.name_index.as_ref()
+    }
+
+    /// Whether the data has compressed blob sections.
+    ///
+    /// Iterating decompresses every compressed blob. Use [Self::into_index()]
+    /// to defer decompression until individual resources are resolved.
+    pub fn has_compressed_blob_sections(&self) -> bool {
+        self.blob_sections.iter().any(|state| {
+            matches!(
+                state,
+                Some(BlobSectionReadState {
+                    compression: BlobCompression::Zstd,
+                    ..
+                })
+            )
+        })
+    }
+
+    /// Obtain a [ResourcesIndex] providing random access to resources.
+    ///
+    /// If the data has a name index, it is returned. Otherwise the resources
+    /// index is scanned to build one. Scanning doesn't copy, decrypt, or
+    /// decompress blob data, so blobs remain borrowed from the original data
+    /// until a resource is resolved. (Scanning encrypted compressed blob
+    /// sections requires the encryption key.)
+    ///
+    /// This must be called before any resources are read from the iterator.
+    ///
+    /// Like reading resources, this fails if the data has encrypted blob
+    /// sections and the encryption key is missing or incorrect.
+    pub fn into_index(mut self) -> Result<ResourcesIndex<'a>, &'static str> {
+        self.check_encryption_key()?;
+
+        if let Some(index) = self.name_index.take() {
+            return Ok(index);
+        }
+
+        if self.read_resources_count != 0 {
+            return Err("resources index can only be built before reading resources");
+        }
+
+        let resources_index_offset = self.reader.position() as usize;
+
+        let sections = self
+            .blob_sections
+            .iter()
+            .enumerate()
+            .filter(|(field, _)| *field != u8::from(ResourceField::NameIndex) as usize)
+            .filter_map(|(field, state)| state.map(|state| (field as u8, state)))
+            .collect::<Vec<_>>();
+
+        let name_section_position = sections
+            .iter()
+            .position(|(field, _)| *field == u8::from(ResourceField::Name));
+
+        let (name_section_offset, name_section_position) = match name_section_position {
+            Some(position) => (sections[position].1.offset, position),
+            None if self.claimed_resources_count == 0 => (0, 0),
+            None => return Err("resources index requires a resource names blob section"),
+        };
+
+        // Content is verified when resources are resolved from the index.
+        let verify = self.verify;
+        self.verify = false;
+        self.skip_blob_data = true;
+
+        let mut entries = Vec::with_capacity(self.claimed_resources_count);
+
+        while !self.done {
+            let mut record = Vec::with_capacity(4 + 4 * sections.len());
+
+            let entry_offset = self.reader.position() as usize - resources_index_offset;
+            record.extend_from_slice(
+                &u32::try_from(entry_offset)
+                    .map_err(|_| "resources index too large")?
+                    .to_le_bytes(),
+            );
+
+            for (field, state) in &sections {
+                let offset = self.blob_sections[*field as usize]
+                    .ok_or("blob state not found")?
+                    .offset
+                    - state.offset;
+
+                record.extend_from_slice(
+                    &u32::try_from(offset)
+                        .map_err(|_| "blob section too large")?
+                        .to_le_bytes(),
+                );
+            }
+
+            if let Some(resource) = self.parse_next()? {
+                entries.push((resource.name, record));
+            }
+        }
+
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+        Ok(ResourcesIndex {
+            verify,
+            data: self.data,
+            resources_index_offset,
+            sections,
+            name_section_offset,
+            name_section_position,
+            records: Cow::Owned(entries.into_iter().flat_map(|(_, record)| record).collect()),
+            encryption_key: self.encryption_key,
+        })
+    }
+
+    /// Set the key used to decrypt encrypted blob sections.
+    ///
+    /// The key also applies to the name index. If the data has encrypted blob
+    /// sections, reading resources fails if a key isn't set or if the key
+    /// isn't the one the data was encrypted with.
+    pub fn set_encryption_key(&mut self, key: [u8; 32]) {
+        self.encryption_key = Some(key);
+
+        if let Some(index) = self.name_index.as_mut() {
+            index.encryption_key = Some(key);
+        }
+    }
+
+    /// Ensure the encryption key can decrypt all encrypted blob sections.
+    fn check_encryption_key(&self) -> Result<(), &'static str> {
+        for state in self.blob_sections.iter().flatten() {
+            if let (Some(nonce), Some(key_check)) =
+                (&state.encryption_nonce, &state.encryption_key_check)
+            {
+                let key = self
+                    .encryption_key
+                    .as_ref()
+                    .ok_or("encrypted blob section requires an encryption key")?;
+
+                if encryption_key_check(key, nonce) != *key_check {
+                    return Err("incorrect encryption key for encrypted blob section");
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     /// Resolve a slice to an individual blob's data.
     ///
     /// This accepts a reference to the original blobs payload, an array of
@@ -78,6 +254,121 @@ impl<'a> ResourceParserIterator<'a> {
         blob
     }
 
+    /// Resolve a range of data within a blob section, decrypting it if necessary.
+    fn read_blob_section_range(
+        &self,
+        state: &BlobSectionReadState,
+        start: usize,
+        end: usize,
+    ) -> Result<Cow<'a, [u8]>, &'static str> {
+        let data = self
+            .data
+            .get(start..end)
+            .ok_or("blob extends past end of data")?;
+
+        if let Some(nonce) = &state.encryption_nonce {
+            let key = self
+                .encryption_key
+                .as_ref()
+                .ok_or("encrypted blob section requires an encryption key")?;
+
+            Ok(Cow::Owned(decrypt_blob(
+                key,
+                nonce,
+                start - state.section_offset,
+                data,
+            )?))
+        } else {
+            Ok(Cow::Borrowed(data))
+        }
+    }
+
+    /// Resolve an individual blob's data, decompressing and decrypting it if necessary.
+    ///
+    /// Uncompressed and unencrypted blobs are returned as borrowed slices of the
+    /// original payload. Other blobs are decoded into owned memory.
+    ///
+    /// If blob data is being skipped, the blob is advanced past and empty data
+    /// is returned.
+    fn resolve_blob_data_maybe_compressed(
+        &mut self,
+        resource_field: ResourceField,
+        length: usize,
+    ) -> Result<Cow<'a, [u8]>, &'static str> {
+        let state = self.blob_sections[resource_field as usize].ok_or("blob state not found")?;
+
+        let padding = match &state.interior_padding {
+            BlobInteriorPadding::None => 0,
+            BlobInteriorPadding::Null => 1,
+        };
+
+        let (data, end) = match state.compression {
+            BlobCompression::None => {
+                if state.encryption_nonce.is_none() || self.skip_blob_data {
+                    return Ok(Cow::Borrowed(
+                        self.resolve_blob_data(resource_field, length),
+                    ));
+                }
+
+                let end = state
+                    .offset
+                    .checked_add(length)
+                    .ok_or("blob length overflow")?;
+
+                (
+                    self.read_blob_section_range(&state, state.offset, end)?,
+                    end,
+                )
+            }
+            BlobCompression::Zstd => {
+                let frame_start = state.offset + 8;
+
+                let frame_length = u64::from_le_bytes(
+                    self.read_blob_section_range(&state, state.offset, frame_start)?
+                        .as_ref()
+                        .try_into()
+                        .map_err(|_| "failed reading compressed blob length")?,
+                ) as usize;
+
+                let frame_end = frame_start
+                    .checked_add(frame_length)
+                    .ok_or("compressed blob length overflow")?;
+
+                if self.skip_blob_data {
+                    (Cow::Borrowed(&[][..]), frame_end)
+                } else {
+                    let frame = self.read_blob_section_range(&state, frame_start, frame_end)?;
+
+                    (Cow::Owned(decompress_zstd(&frame, length)?), frame_end)
+                }
+            }
+        };
+
+        if let Some(state) = self.blob_sections[resource_field as usize].as_mut() {
+            state.offset = end + padding;
+        }
+
+        Ok(data)
+    }
+
+    /// Resolve a UTF-8 string from a potentially compressed blob.
+    fn resolve_blob_str_maybe_compressed(
+        &mut self,
+        resource_field: ResourceField,
+        length: usize,
+    ) -> Result<Cow<'a, str>, &'static str> {
+        Ok(
+            match self.resolve_blob_data_maybe_compressed(resource_field, length)? {
+                Cow::Borrowed(data) => {
+                    Cow::Borrowed(std::str::from_utf8(data).map_err(|_| "invalid UTF-8 in blob")?)
+                }
+                Cow::Owned(data) => {
+                    Cow::Owned(String::from_utf8(data).map_err(|_| "invalid UTF-8 in blob")?)
+                }
+            },
+        )
+    }
+
     #[cfg(unix)]
     fn resolve_path(&mut self, resource_field: ResourceField, length: usize) -> Cow<'a, Path> {
         let path_str = OsStr::from_bytes(self.resolve_blob_data(resource_field, length));
@@ -124,13 +415,24 @@ impl<'a> ResourceParserIterator<'a> {
                     current_resource_name = None;
                 }
                 ResourceField::EndOfEntry => {
-                    let res = if current_resource_name.is_some() {
-                        Ok(Some(current_resource))
-                    } else {
-                        Err("resource name field is required")
-                    };
+                    if current_resource_name.is_none() {
+                        return Err("resource name field is required");
+                    }
+
+                    if self.verify {
+                        match &current_resource.content_digest_sha256 {
+                            Some(digest) => {
+                                if current_resource.compute_content_digest_sha256() != *digest {
+                                    return Err("resource content digest mismatch");
+                                }
+                            }
+                            None => {
+                                return Err("resource content digest is required for verification");
+                            }
+                        }
+                    }
 
-                    return res;
+                    return Ok(Some(current_resource));
                 }
                 ResourceField::Name => {
                     let l = self
@@ -160,7 +462,7 @@ impl<'a> ResourceParserIterator<'a> {
                         as usize;
 
                     current_resource.in_memory_source =
-                        Some(Cow::Borrowed(self.resolve_blob_data(field_type, l)));
+                        Some(self.resolve_blob_data_maybe_compressed(field_type, l)?);
                 }
                 ResourceField::InMemoryBytecode => {
                     let l = self
@@ -170,7 +472,7 @@ impl<'a> ResourceParserIterator<'a> {
                         as usize;
 
                     current_resource.in_memory_bytecode =
-                        Some(Cow::Borrowed(self.resolve_blob_data(field_type, l)));
+                        Some(self.resolve_blob_data_maybe_compressed(field_type, l)?);
                 }
                 ResourceField::InMemoryBytecodeOpt1 => {
                     let l = self
@@ -180,7 +482,7 @@ impl<'a> ResourceParserIterator<'a> {
                         as usize;
 
                     current_resource.in_memory_bytecode_opt1 =
-                        Some(Cow::Borrowed(self.resolve_blob_data(field_type, l)));
+                        Some(self.resolve_blob_data_maybe_compressed(field_type, l)?);
                 }
                 ResourceField::InMemoryBytecodeOpt2 => {
                     let l = self
@@ -190,7 +492,7 @@ impl<'a> ResourceParserIterator<'a> {
                         as usize;
 
                     current_resource.in_memory_bytecode_opt2 =
-                        Some(Cow::Borrowed(self.resolve_blob_data(field_type, l)));
+                        Some(self.resolve_blob_data_maybe_compressed(field_type, l)?);
                 }
                 ResourceField::InMemoryExtensionModuleSharedLibrary => {
                     let l = self
@@ -200,7 +502,7 @@ impl<'a> ResourceParserIterator<'a> {
                         as usize;
 
                     current_resource.in_memory_extension_module_shared_library =
-                        Some(Cow::Borrowed(self.resolve_blob_data(field_type, l)));
+                        Some(self.resolve_blob_data_maybe_compressed(field_type, l)?);
                 }
 
                 ResourceField::InMemoryResourcesData => {
@@ -219,11 +521,8 @@ impl<'a> ResourceParserIterator<'a> {
                             .map_err(|_| "failed reading resource name")?
                             as usize;
 
-                        let resource_name = unsafe {
-                            std::str::from_utf8_unchecked(
-                                self.resolve_blob_data(field_type, resource_name_length),
-                            )
-                        };
+                        let resource_name = self
+                            .resolve_blob_str_maybe_compressed(field_type, resource_name_length)?;
 
                         let resource_length = self
                             .reader
@@ -231,10 +530,10 @@ impl<'a> ResourceParserIterator<'a> {
                             .map_err(|_| "failed reading resource length")?
                             as usize;
 
-                        let resource_data = self.resolve_blob_data(field_type, resource_length);
+                        let resource_data =
+                            self.resolve_blob_data_maybe_compressed(field_type, resource_length)?;
 
-                        resources
-                            .insert(Cow::Borrowed(resource_name), Cow::Borrowed(resource_data));
+                        resources.insert(resource_name, resource_data);
                     }
 
                     current_resource.in_memory_package_resources = Some(resources);
@@ -256,20 +555,18 @@ impl<'a> ResourceParserIterator<'a> {
                             .map_err(|_| "failed reading distribution metadata name")?
                             as usize;
 
-                        let name = unsafe {
-                            std::str::from_utf8_unchecked(
-                                self.resolve_blob_data(field_type, name_length),
-                            )
-                        };
+                        let name =
+                            self.resolve_blob_str_maybe_compressed(field_type, name_length)?;
 
                         let resource_length =
                             self.reader.read_u64::<LittleEndian>().map_err(|_| {
                                 "failed reading package distribution resource length"
                             })? as usize;
 
-                        let resource_data = self.resolve_blob_data(field_type, resource_length);
+                        let resource_data =
+                            self.resolve_blob_data_maybe_compressed(field_type, resource_length)?;
 
-                        resources.insert(Cow::Borrowed(name), Cow::Borrowed(resource_data));
+                        resources.insert(name, resource_data);
                     }
 
                     current_resource.in_memory_distribution_resources = Some(resources);
@@ -283,7 +580,7 @@ impl<'a> ResourceParserIterator<'a> {
                         as usize;
 
                     current_resource.in_memory_shared_library =
-                        Some(Cow::Borrowed(self.resolve_blob_data(field_type, l)));
+                        Some(self.resolve_blob_data_maybe_compressed(field_type, l)?);
                 }
 
                 ResourceField::SharedLibraryDependencyNames => {
@@ -471,7 +768,7 @@ impl<'a> ResourceParserIterator<'a> {
                         as usize;
 
                     current_resource.file_data_embedded =
-                        Some(Cow::Borrowed(self.resolve_blob_data(field_type, l)));
+                        Some(self.resolve_blob_data_maybe_compressed(field_type, l)?);
                 }
 
                 ResourceField::FileDataUtf8RelativePath => {
@@ -485,6 +782,40 @@ impl<'a> ResourceParserIterator<'a> {
                         std::str::from_utf8_unchecked(self.resolve_blob_data(field_type, l))
                     }));
                 }
+
+                ResourceField::ContentDigestSha256 => {
+                    let mut digest = [0u8; 32];
+                    self.reader
+                        .read_exact(&mut digest)
+                        .map_err(|_| "failed reading content digest")?;
+
+                    current_resource.content_digest_sha256 = Some(digest);
+                }
+
+                ResourceField::NameIndex => {
+                    return Err("name index field is not valid in resources index");
+                }
+
+                ResourceField::OriginalSourcePath => {
+                    let l = self
+                        .reader
+                        .read_u32::<LittleEndian>()
+                        .map_err(|_| "failed reading original source path length")?
+                        as usize;
+
+                    current_resource.original_source_path = Some(Cow::Borrowed(unsafe {
+                        std::str::from_utf8_unchecked(self.resolve_blob_data(field_type, l))
+                    }));
+                }
+
+                ResourceField::OriginalSourceDigestSha256 => {
+                    let mut digest = [0u8; 32];
+                    self.reader
+                        .read_exact(&mut digest)
+                        .map_err(|_| "failed reading original source digest")?;
+
+                    current_resource.original_source_digest_sha256 = Some(digest);
+                }
             }
         }
     }
@@ -498,6 +829,13 @@ impl<'a> Iterator for ResourceParserIterator<'a> {
             return None;
         }
 
+        if self.read_resources_count == 0 {
+            if let Err(e) = self.check_encryption_key() {
+                self.done = true;
+                return Some(Err(e));
+            }
+        }
+
         match self.parse_next() {
             Ok(res) => res.map(Ok),
             Err(e) => Some(Err(e)),
@@ -505,6 +843,160 @@ impl<'a> Iterator for ResourceParserIterator<'a> {
     }
 }
 
+/// Provides random access to resources in packed resources data.
+///
+/// Instances are obtained via [ResourceParserIterator::name_index()] if the
+/// data was written with a name index or via
+/// [ResourceParserIterator::into_index()]. Names are sorted, allowing
+/// resources to be found via binary search. A resource's index data is not
+/// read or validated until it is resolved.
+#[derive(Clone, Debug)]
+pub struct ResourcesIndex<'a> {
+    verify: bool,
+    data: &'a [u8],
+    /// Offset of the resources index within `data`.
+    resources_index_offset: usize,
+    /// Resource fields and read state of blob sections, in blob index order.
+    sections: Vec<(u8, BlobSectionReadState)>,
+    /// Offset of the resource names blob section within `data`.
+    name_section_offset: usize,
+    /// Position of the resource names blob section within `sections`.
+    name_section_position: usize,
+    /// Raw name index records.
+    records: Cow<'a, [u8]>,
+    encryption_key: Option<[u8; 32]>,
+}
+
+impl<'a> ResourcesIndex<'a> {
+    /// The number of resources in the index.
+    pub fn len(&self) -> usize {
+        self.records.len() / self.record_length()
+    }
+
+    /// Whether the index is empty.
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    fn record_length(&self) -> usize {
+        4 + 4 * self.sections.len()
+    }
+
+    /// Resolve the `u32` value at a given slot in a record.
+    fn record_value(&self, index: usize, slot: usize) -> usize {
+        let offset = index * self.record_length() + slot * 4;
+
+        u32::from_le_bytes([
+            self.records[offset],
+            self.records[offset + 1],
+            self.records[offset + 2],
+            self.records[offset + 3],
+        ]) as usize
+    }
+
+    /// Resolve the name of the resource at a given position in the index.
+    pub fn name(&self, index: usize) -> Result<&'a str, &'static str> {
+        if index >= self.len() {
+            return Err("name index position out of range");
+        }
+
+        let entry_offset = self.resources_index_offset + self.record_value(index, 0);
+
+        // Entries begin with the start of entry marker followed by the name
+        // field and its u16 length.
+        let entry = self
+            .data
+            .get(entry_offset..entry_offset + 4)
+            .ok_or("resource entry extends past end of data")?;
+
+        if entry[0] != u8::from(ResourceField::StartOfEntry)
+            || entry[1] != u8::from(ResourceField::Name)
+        {
+            return Err("resource entry does not begin with name");
+        }
+
+        let length = u16::from_le_bytes([entry[2], entry[3]]) as usize;
+        let start =
+            self.name_section_offset + self.record_value(index, 1 + self.name_section_position);
+
+        let name = self
+            .data
+            .get(start..start + length)
+            .ok_or("resource name extends past end of data")?;
+
+        Ok(unsafe { std::str::from_utf8_unchecked(name) })
+    }
+
+    /// Obtain an iterator of resource names, in sorted order.
+    pub fn names(&self) -> impl Iterator<Item = Result<&'a str, &'static str>> + '_ {
+        (0..self.len()).map(move |index| self.name(index))
+    }
+
+    /// Find the position of a named resource in the index.
+    pub fn find(&self, name: &str) -> Result<Option<usize>, &'static str> {
+        let mut low = 0;
+        let mut high = self.len();
+
+        while low < high {
+            let middle = low + (high - low) / 2;
+
+            match self.name(middle)?.cmp(name) {
+                std::cmp::Ordering::Less => low = middle + 1,
+                std::cmp::Ordering::Greater => high = middle,
+                std::cmp::Ordering::Equal => return Ok(Some(middle)),
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Deserialize the resource at a given position in the index.
+    ///
+    /// If the index was obtained via [load_resources_verified()], the content
+    /// digest of the resource is verified.
+    pub fn resolve(&self, index: usize) -> Result<Resource<'a, u8>, &'static str> {
+        if index >= self.len() {
+            return Err("name index position out of range");
+        }
+
+        let mut blob_sections = [None; 256];
+
+        for (slot, (field, state)) in self.sections.iter().enumerate() {
+            blob_sections[*field as usize] = Some(BlobSectionReadState {
+                offset: state.offset + self.record_value(index, 1 + slot),
+                ..*state
+            });
+        }
+
+        let mut reader = Cursor::new(self.data);
+        reader.set_position((self.resources_index_offset + self.record_value(index, 0)) as u64);
+
+        let mut parser = ResourceParserIterator {
+            done: false,
+            verify: self.verify,
+            data: self.data,
+            reader,
+            blob_sections,
+            claimed_resources_count: 1,
+            read_resources_count: 0,
+            name_index: None,
+            encryption_key: self.encryption_key,
+            skip_blob_data: false,
+        };
+
+        parser.check_encryption_key()?;
+        parser.parse_next()?.ok_or("resource entry not found")
+    }
+
+    /// Find and deserialize a named resource.
+    pub fn get(&self, name: &str) -> Result<Option<Resource<'a, u8>>, &'static str> {
+        match self.find(name)? {
+            Some(index) => Ok(Some(self.resolve(index)?)),
+            None => Ok(None),
+        }
+    }
+}
+
 /// Parse a packed resources data structure.
 ///
 /// The data structure is parsed lazily via an iterator that emits reconstructed
@@ -514,6 +1006,27 @@ impl<'a> Iterator for ResourceParserIterator<'a> {
 /// this decreased performance by ~15%. Given the performance sensitivity of this
 /// code, we need to keep error handling primitive.
 pub fn load_resources<'a>(data: &'a [u8]) -> Result<ResourceParserIterator<'a>, &'static str> {
+    load_resources_inner(data, false)
+}
+
+/// Parse a packed resources data structure, verifying its integrity.
+///
+/// This is like [load_resources()] except SHA-256 digests in the data are
+/// verified. The digest of every blob section is verified before this
+/// function returns. The content digest of each resource is verified as the
+/// iterator emits it.
+///
+/// Data lacking digests fails verification.
+pub fn load_resources_verified<'a>(
+    data: &'a [u8],
+) -> Result<ResourceParserIterator<'a>, &'static str> {
+    load_resources_inner(data, true)
+}
+
+fn load_resources_inner<'a>(
+    data: &'a [u8],
+    verify: bool,
+) -> Result<ResourceParserIterator<'a>, &'static str> {
     if data.len() < HEADER_V3.len() {
         return Err("error reading 8 byte header");
     }
@@ -521,13 +1034,16 @@ pub fn load_resources<'a>(data: &'a [u8]) -> Result<ResourceParserIterator<'a>,
     let header = &data[0..8];
 
     if header == HEADER_V3 {
-        load_resources_v3(&data[8..])
+        load_resources_v3(&data[8..], verify)
     } else {
         Err("unrecognized file format")
     }
 }
 
-fn load_resources_v3<'a>(data: &'a [u8]) -> Result<ResourceParserIterator<'a>, &'static str> {
+fn load_resources_v3<'a>(
+    data: &'a [u8],
+    verify: bool,
+) -> Result<ResourceParserIterator<'a>, &'static str> {
     let mut reader = Cursor::new(data);
 
     let blob_section_count = reader
@@ -547,6 +1063,10 @@ fn load_resources_v3<'a>(data: &'a [u8]) -> Result<ResourceParserIterator<'a>, &
     let mut current_blob_field = None;
     let mut current_blob_raw_payload_length = None;
     let mut current_blob_interior_padding = None;
+    let mut current_blob_compression = None;
+    let mut current_blob_sha256_digest = None;
+    let mut current_blob_encryption_nonce = None;
+    let mut current_blob_encryption_key_check = None;
     let mut blob_entry_count = 0;
     let mut blob_sections = Vec::with_capacity(blob_section_count as usize);
 
@@ -565,6 +1085,10 @@ fn load_resources_v3<'a>(data: &'a [u8]) -> Result<ResourceParserIterator<'a>, &
                     current_blob_field = None;
                     current_blob_raw_payload_length = None;
                     current_blob_interior_padding = None;
+                    current_blob_compression = None;
+                    current_blob_sha256_digest = None;
+                    current_blob_encryption_nonce = None;
+                    current_blob_encryption_key_check = None;
                 }
                 BlobSectionField::EndOfEntry => {
                     if current_blob_field.is_none() {
@@ -574,15 +1098,47 @@ fn load_resources_v3<'a>(data: &'a [u8]) -> Result<ResourceParserIterator<'a>, &
                         return Err("blob raw payload length is required");
                     }
 
+                    if matches!(current_blob_compression, Some(BlobCompression::Zstd)) {
+                        let field = ResourceField::try_from(current_blob_field.unwrap())?;
+
+                        if !COMPRESSIBLE_FIELDS.contains(&field) {
+                            return Err("blob section for resource field cannot be compressed");
+                        }
+
+                        if !cfg!(feature = "zstd") {
+                            return Err("zstd compressed blob sections are not supported");
+                        }
+                    }
+
+                    if current_blob_encryption_nonce.is_some() {
+                        let field = ResourceField::try_from(current_blob_field.unwrap())?;
+
+                        if !COMPRESSIBLE_FIELDS.contains(&field) {
+                            return Err("blob section for resource field cannot be encrypted");
+                        }
+
+                        if !cfg!(feature = "chacha20") {
+                            return Err("encrypted blob sections are not supported");
+                        }
+                    }
+
                     blob_sections.push(BlobSection {
                         resource_field: current_blob_field.unwrap(),
                         raw_payload_length: current_blob_raw_payload_length.unwrap(),
                         interior_padding: current_blob_interior_padding,
+                        compression: current_blob_compression,
+                        sha256_digest: current_blob_sha256_digest,
+                        encryption_nonce: current_blob_encryption_nonce,
+                        encryption_key_check: current_blob_encryption_key_check,
                     });
 
                     current_blob_field = None;
                     current_blob_raw_payload_length = None;
                     current_blob_interior_padding = None;
+                    current_blob_compression = None;
+                    current_blob_sha256_digest = None;
+                    current_blob_encryption_nonce = None;
+                    current_blob_encryption_key_check = None;
                 }
                 BlobSectionField::ResourceFieldType => {
                     let field = reader
@@ -607,6 +1163,47 @@ fn load_resources_v3<'a>(data: &'a [u8]) -> Result<ResourceParserIterator<'a>, &
                         _ => return Err("invalid value for interior padding field"),
                     });
                 }
+                BlobSectionField::Compression => {
+                    let compression = reader
+                        .read_u8()
+                        .map_err(|_| "failed reading compression field value")?;
+
+                    current_blob_compression = Some(BlobCompression::try_from(compression)?);
+                }
+                BlobSectionField::Sha256Digest => {
+                    let mut digest = [0u8; 32];
+                    reader
+                        .read_exact(&mut digest)
+                        .map_err(|_| "failed reading sha256 digest field value")?;
+
+                    current_blob_sha256_digest = Some(digest);
+                }
+                BlobSectionField::Encryption => {
+                    let encryption = reader
+                        .read_u8()
+                        .map_err(|_| "failed reading encryption field value")?;
+
+                    match BlobEncryption::try_from(encryption)? {
+                        BlobEncryption::None => {
+                            current_blob_encryption_nonce = None;
+                            current_blob_encryption_key_check = None;
+                        }
+                        BlobEncryption::ChaCha20 => {
+                            let mut nonce = [0u8; 12];
+                            reader
+                                .read_exact(&mut nonce)
+                                .map_err(|_| "failed reading encryption nonce")?;
+
+                            let mut key_check = [0u8; 16];
+                            reader
+                                .read_exact(&mut key_check)
+                                .map_err(|_| "failed reading encryption key check")?;
+
+                            current_blob_encryption_nonce = Some(nonce);
+                            current_blob_encryption_key_check = Some(key_check);
+                        }
+                    }
+                }
             }
         }
     }
@@ -628,35 +1225,166 @@ fn load_resources_v3<'a>(data: &'a [u8]) -> Result<ResourceParserIterator<'a>, &
     // Current offset from start of blobs data.
     let mut current_blob_offset = 0;
 
+    // Blob sections other than the name index, in blob index order.
+    let mut indexed_sections = Vec::with_capacity(blob_sections.len());
+    let mut name_index_range = None;
+
     for section in &blob_sections {
         let section_start_offset = blob_start_offset + current_blob_offset;
-        blob_offsets[section.resource_field as usize] = Some(BlobSectionReadState {
+        let state = BlobSectionReadState {
             offset: section_start_offset,
             interior_padding: match section.interior_padding {
                 Some(padding) => padding,
                 None => BlobInteriorPadding::None,
             },
-        });
+            compression: section.compression.unwrap_or(BlobCompression::None),
+            section_offset: section_start_offset,
+            encryption_nonce: section.encryption_nonce,
+            encryption_key_check: section.encryption_key_check,
+        };
+        blob_offsets[section.resource_field as usize] = Some(state);
+
+        if section.resource_field == u8::from(ResourceField::NameIndex) {
+            name_index_range = Some((
+                section_start_offset,
+                section_start_offset + section.raw_payload_length,
+            ));
+        } else {
+            indexed_sections.push((section.resource_field, state));
+        }
+
+        let section_end_offset = section_start_offset
+            .checked_add(section.raw_payload_length)
+            .ok_or("blob section length overflow")?;
+
+        if section_end_offset > data.len() {
+            return Err("blob section extends past end of data");
+        }
+
+        if verify {
+            let digest = section
+                .sha256_digest
+                .ok_or("blob section digest is required for verification")?;
+
+            let actual: [u8; 32] =
+                Sha256::digest(&data[section_start_offset..section_end_offset]).into();
+
+            if actual != digest {
+                return Err("blob section digest mismatch");
+            }
+        }
+
         current_blob_offset += section.raw_payload_length;
     }
 
+    let name_index = if let Some((start, end)) = name_index_range {
+        let records = data
+            .get(start..end)
+            .ok_or("name index extends past end of data")?;
+
+        if records.len() != resources_count * (4 + 4 * indexed_sections.len()) {
+            return Err("name index length mismatch");
+        }
+
+        let name_section_position = indexed_sections
+            .iter()
+            .position(|(field, _)| *field == u8::from(ResourceField::Name));
+
+        let (name_section_offset, name_section_position) = match name_section_position {
+            Some(position) => (indexed_sections[position].1.offset, position),
+            None if resources_count == 0 => (0, 0),
+            None => return Err("name index requires a resource names blob section"),
+        };
+
+        Some(ResourcesIndex {
+            verify,
+            data,
+            resources_index_offset: reader.position() as usize,
+            sections: indexed_sections,
+            name_section_offset,
+            name_section_position,
+            records: Cow::Borrowed(records),
+            encryption_key: None,
+        })
+    } else {
+        None
+    };
+
     Ok(ResourceParserIterator {
         done: resources_index_length == 0 || resources_count == 0,
+        verify,
         data,
         reader,
         blob_sections: blob_offsets,
         claimed_resources_count: resources_count,
         read_resources_count: 0,
+        name_index,
+        encryption_key: None,
+        skip_blob_data: false,
     })
 }
 
+/// Decompress a zstd frame holding a blob of a known size.
+#[cfg(feature = "zstd")]
+fn decompress_zstd(frame: &[u8], length: usize) -> Result<Vec<u8>, &'static str> {
+    let data = zstd::block::decompress(frame, length).map_err(|_| "error decompressing blob")?;
+
+    if data.len() == length {
+        Ok(data)
+    } else {
+        Err("decompressed blob length mismatch")
+    }
+}
+
+#[cfg(not(feature = "zstd"))]
+fn decompress_zstd(_frame: &[u8], _length: usize) -> Result<Vec<u8>, &'static str> {
+    Err("zstd compressed blob sections are not supported")
+}
+
+/// Decrypt data at a given position within an encrypted blob section.
+#[cfg(feature = "chacha20")]
+fn decrypt_blob(
+    key: &[u8; 32],
+    nonce: &[u8; 12],
+    position: usize,
+    data: &[u8],
+) -> Result<Vec<u8>, &'static str> {
+    use chacha20::{
+        cipher::{KeyIvInit, StreamCipher, StreamCipherSeek},
+        ChaCha20,
+    };
+
+    let mut cipher = ChaCha20::new(key.into(), nonce.into());
+    cipher
+        .try_seek(position as u64)
+        .map_err(|_| "encrypted blob position out of range")?;
+
+    let mut data = data.to_vec();
+    cipher
+        .try_apply_keystream(&mut data)
+        .map_err(|_| "encrypted blob extends past end of keystream")?;
+
+    Ok(data)
+}
+
+#[cfg(not(feature = "chacha20"))]
+fn decrypt_blob(
+    _key: &[u8; 32],
+    _nonce: &[u8; 12],
+    _position: usize,
+    _data: &[u8],
+) -> Result<Vec<u8>, &'static str> {
+    Err("encrypted blob sections are not supported")
+}
+
 #[cfg(test)]
 mod tests {
     use {
         super::*,
         crate::{
-            resource::Resource, serialization::BlobInteriorPadding,
-            writer::write_packed_resources_v3,
+            resource::Resource,
+            serialization::BlobInteriorPadding,
+            writer::{write_packed_resources_v3, PackedResourcesWriteOptions},
         },
     };
 
@@ -731,7 +1459,12 @@ mod tests {
         };
 
         let mut data = Vec::new();
-        write_packed_resources_v3(&[resource], &mut data, None).unwrap();
+        write_packed_resources_v3(
+            &[resource],
+            &mut data,
+            &PackedResourcesWriteOptions::default(),
+        )
+        .unwrap();
 
         let resources = load_resources(&data)
             .unwrap()
@@ -763,7 +1496,12 @@ mod tests {
         };
 
         let mut data = Vec::new();
-        write_packed_resources_v3(&[resource1, resource2], &mut data, None).unwrap();
+        write_packed_resources_v3(
+            &[resource1, resource2],
+            &mut data,
+            &PackedResourcesWriteOptions::default(),
+        )
+        .unwrap();
         let resources = load_resources(&data)
             .unwrap()
             .collect::<Result<Vec<Resource<u8>>, &'static str>>()
@@ -807,7 +1545,10 @@ mod tests {
         write_packed_resources_v3(
             &[resource1, resource2],
             &mut data,
-            Some(BlobInteriorPadding::Null),
+            &PackedResourcesWriteOptions {
+                interior_padding: Some(BlobInteriorPadding::Null),
+                ..PackedResourcesWriteOptions::default()
+            },
         )
         .unwrap();
         let resources = load_resources(&data)
@@ -845,7 +1586,12 @@ mod tests {
         };
 
         let mut data = Vec::new();
-        write_packed_resources_v3(&[resource], &mut data, None).unwrap();
+        write_packed_resources_v3(
+            &[resource],
+            &mut data,
+            &PackedResourcesWriteOptions::default(),
+        )
+        .unwrap();
         let resources = load_resources(&data)
             .unwrap()
             .collect::<Result<Vec<Resource<u8>>, &'static str>>()
@@ -876,7 +1622,12 @@ mod tests {
         };
 
         let mut data = Vec::new();
-        write_packed_resources_v3(&[resource], &mut data, None).unwrap();
+        write_packed_resources_v3(
+            &[resource],
+            &mut data,
+            &PackedResourcesWriteOptions::default(),
+        )
+        .unwrap();
         let resources = load_resources(&data)
             .unwrap()
             .collect::<Result<Vec<Resource<u8>>, &'static str>>()
@@ -910,7 +1661,12 @@ mod tests {
         };
 
         let mut data = Vec::new();
-        write_packed_resources_v3(&[resource], &mut data, None).unwrap();
+        write_packed_resources_v3(
+            &[resource],
+            &mut data,
+            &PackedResourcesWriteOptions::default(),
+        )
+        .unwrap();
         let resources = load_resources(&data)
             .unwrap()
             .collect::<Result<Vec<Resource<u8>>, &'static str>>()
@@ -944,7 +1700,12 @@ mod tests {
         };
 
         let mut data = Vec::new();
-        write_packed_resources_v3(&[resource], &mut data, None).unwrap();
+        write_packed_resources_v3(
+            &[resource],
+            &mut data,
+            &PackedResourcesWriteOptions::default(),
+        )
+        .unwrap();
         let resources = load_resources(&data)
             .unwrap()
             .collect::<Result<Vec<Resource<u8>>, &'static str>>()
@@ -978,7 +1739,12 @@ mod tests {
         };
 
         let mut data = Vec::new();
-        write_packed_resources_v3(&[resource], &mut data, None).unwrap();
+        write_packed_resources_v3(
+            &[resource],
+            &mut data,
+            &PackedResourcesWriteOptions::default(),
+        )
+        .unwrap();
         let resources = load_resources(&data)
             .unwrap()
             .collect::<Result<Vec<Resource<u8>>, &'static str>>()
@@ -1022,7 +1788,12 @@ mod tests {
         };
 
         let mut data = Vec::new();
-        write_packed_resources_v3(&[resource], &mut data, None).unwrap();
+        write_packed_resources_v3(
+            &[resource],
+            &mut data,
+            &PackedResourcesWriteOptions::default(),
+        )
+        .unwrap();
         let resources = load_resources(&data)
             .unwrap()
             .collect::<Result<Vec<Resource<u8>>, &'static str>>()
@@ -1051,7 +1822,12 @@ mod tests {
         };
 
         let mut data = Vec::new();
-        write_packed_resources_v3(&[resource], &mut data, None).unwrap();
+        write_packed_resources_v3(
+            &[resource],
+            &mut data,
+            &PackedResourcesWriteOptions::default(),
+        )
+        .unwrap();
         let resources = load_resources(&data)
             .unwrap()
             .collect::<Result<Vec<Resource<u8>>, &'static str>>()
@@ -1076,7 +1852,12 @@ mod tests {
         };
 
         let mut data = Vec::new();
-        write_packed_resources_v3(&[resource], &mut data, None).unwrap();
+        write_packed_resources_v3(
+            &[resource],
+            &mut data,
+            &PackedResourcesWriteOptions::default(),
+        )
+        .unwrap();
         let resources = load_resources(&data)
             .unwrap()
             .collect::<Result<Vec<Resource<u8>>, &'static str>>()
@@ -1112,7 +1893,12 @@ mod tests {
         };
 
         let mut data = Vec::new();
-        write_packed_resources_v3(&[resource], &mut data, None).unwrap();
+        write_packed_resources_v3(
+            &[resource],
+            &mut data,
+            &PackedResourcesWriteOptions::default(),
+        )
+        .unwrap();
         let resources = load_resources(&data)
             .unwrap()
             .collect::<Result<Vec<Resource<u8>>, &'static str>>()
@@ -1137,8 +1923,13 @@ mod tests {
         };
 
         let mut data = Vec::new();
-        write_packed_resources_v3(&[resource], &mut data, None).unwrap();
-        let resources = load_resources(&data)
+        write_packed_resources_v3(
+            &[resource],
+            &mut data,
+            &PackedResourcesWriteOptions::default(),
+        )
+        .unwrap();
+        let resources = load_resources(&data)
             .unwrap()
             .collect::<Result<Vec<Resource<u8>>, &'static str>>()
             .unwrap();
@@ -1166,7 +1957,12 @@ mod tests {
         };
 
         let mut data = Vec::new();
-        write_packed_resources_v3(&[resource], &mut data, None).unwrap();
+        write_packed_resources_v3(
+            &[resource],
+            &mut data,
+            &PackedResourcesWriteOptions::default(),
+        )
+        .unwrap();
         let resources = load_resources(&data)
             .unwrap()
             .collect::<Result<Vec<Resource<u8>>, &'static str>>()
@@ -1195,7 +1991,12 @@ mod tests {
         };
 
         let mut data = Vec::new();
-        write_packed_resources_v3(&[resource], &mut data, None).unwrap();
+        write_packed_resources_v3(
+            &[resource],
+            &mut data,
+            &PackedResourcesWriteOptions::default(),
+        )
+        .unwrap();
         let resources = load_resources(&data)
             .unwrap()
             .collect::<Result<Vec<Resource<u8>>, &'static str>>()
@@ -1224,7 +2025,12 @@ mod tests {
         };
 
         let mut data = Vec::new();
-        write_packed_resources_v3(&[resource], &mut data, None).unwrap();
+        write_packed_resources_v3(
+            &[resource],
+            &mut data,
+            &PackedResourcesWriteOptions::default(),
+        )
+        .unwrap();
         let resources = load_resources(&data)
             .unwrap()
             .collect::<Result<Vec<Resource<u8>>, &'static str>>()
@@ -1253,7 +2059,12 @@ mod tests {
         };
 
         let mut data = Vec::new();
-        write_packed_resources_v3(&[resource], &mut data, None).unwrap();
+        write_packed_resources_v3(
+            &[resource],
+            &mut data,
+            &PackedResourcesWriteOptions::default(),
+        )
+        .unwrap();
         let resources = load_resources(&data)
             .unwrap()
             .collect::<Result<Vec<Resource<u8>>, &'static str>>()
@@ -1288,7 +2099,12 @@ mod tests {
         };
 
         let mut data = Vec::new();
-        write_packed_resources_v3(&[resource], &mut data, None).unwrap();
+        write_packed_resources_v3(
+            &[resource],
+            &mut data,
+            &PackedResourcesWriteOptions::default(),
+        )
+        .unwrap();
         let resources = load_resources(&data)
             .unwrap()
             .collect::<Result<Vec<Resource<u8>>, &'static str>>()
@@ -1323,7 +2139,12 @@ mod tests {
         };
 
         let mut data = Vec::new();
-        write_packed_resources_v3(&[resource], &mut data, None).unwrap();
+        write_packed_resources_v3(
+            &[resource],
+            &mut data,
+            &PackedResourcesWriteOptions::default(),
+        )
+        .unwrap();
         let resources = load_resources(&data)
             .unwrap()
             .collect::<Result<Vec<Resource<u8>>, &'static str>>()
@@ -1405,10 +2226,18 @@ mod tests {
             file_executable: true,
             file_data_embedded: Some(Cow::from(b"file_data_embedded".to_vec())),
             file_data_utf8_relative_path: Some(Cow::from("file_data_utf8_relative_path")),
+            content_digest_sha256: None,
+            original_source_path: Some(Cow::from("/src/original_source_path.py")),
+            original_source_digest_sha256: Some([0x42; 32]),
         };
 
         let mut data = Vec::new();
-        write_packed_resources_v3(&[resource], &mut data, None).unwrap();
+        write_packed_resources_v3(
+            &[resource],
+            &mut data,
+            &PackedResourcesWriteOptions::default(),
+        )
+        .unwrap();
         let resources = load_resources(&data)
             .unwrap()
             .collect::<Result<Vec<Resource<u8>>, &'static str>>()
@@ -1519,6 +2348,11 @@ mod tests {
             entry.file_data_utf8_relative_path.as_ref().unwrap(),
             "file_data_utf8_relative_path"
         );
+        assert_eq!(
+            entry.original_source_path.as_ref().unwrap(),
+            "/src/original_source_path.py"
+        );
+        assert_eq!(entry.original_source_digest_sha256, Some([0x42; 32]));
     }
 
     #[test]
@@ -1539,7 +2373,12 @@ mod tests {
         ];
 
         let mut data = Vec::new();
-        write_packed_resources_v3(&resources, &mut data, None).unwrap();
+        write_packed_resources_v3(
+            &resources,
+            &mut data,
+            &PackedResourcesWriteOptions::default(),
+        )
+        .unwrap();
         let loaded = load_resources(&data)
             .unwrap()
             .collect::<Result<Vec<Resource<u8>>, &'static str>>()
@@ -1547,4 +2386,573 @@ mod tests {
 
         assert_eq!(resources, loaded);
     }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_compressed_fields() {
+        let mut package_resources = HashMap::new();
+        package_resources.insert(Cow::from("foo.txt"), Cow::from(b"foo data".to_vec()));
+        package_resources.insert(Cow::from("bar.txt"), Cow::from(b"bar data".to_vec()));
+
+        let resources: Vec<Resource<u8>> = vec![
+            Resource {
+                name: Cow::from("foo"),
+                is_python_module: true,
+                in_memory_source: Some(Cow::from(b"import io".repeat(64))),
+                in_memory_bytecode: Some(Cow::from(b"fake bytecode".repeat(64))),
+                in_memory_package_resources: Some(package_resources),
+                relative_path_module_source: Some(Cow::from(Path::new("foo.py"))),
+                ..Resource::default()
+            },
+            Resource {
+                name: Cow::from("bar"),
+                is_python_module: true,
+                in_memory_bytecode: Some(Cow::from(b"".to_vec())),
+                ..Resource::default()
+            },
+        ];
+
+        for padding in [None, Some(BlobInteriorPadding::Null)] {
+            let mut data = Vec::new();
+            write_packed_resources_v3(
+                &resources,
+                &mut data,
+                &PackedResourcesWriteOptions {
+                    interior_padding: padding,
+                    ..PackedResourcesWriteOptions::default()
+                },
+            )
+            .unwrap();
+
+            let mut compressed = Vec::new();
+            write_packed_resources_v3(
+                &resources,
+                &mut compressed,
+                &PackedResourcesWriteOptions {
+                    interior_padding: padding,
+                    compression: Some(BlobCompression::Zstd),
+                    ..PackedResourcesWriteOptions::default()
+                },
+            )
+            .unwrap();
+
+            assert!(compressed.len() < data.len());
+
+            let loaded = load_resources(&compressed)
+                .unwrap()
+                .collect::<Result<Vec<Resource<u8>>, &'static str>>()
+                .unwrap();
+
+            assert_eq!(resources, loaded);
+            assert!(matches!(loaded[0].in_memory_bytecode, Some(Cow::Owned(_))));
+            assert!(matches!(
+                loaded[0].relative_path_module_source,
+                Some(Cow::Borrowed(_))
+            ));
+        }
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_compressed_corrupt_frame() {
+        let resource = Resource {
+            name: Cow::from("foo"),
+            in_memory_bytecode: Some(Cow::from(b"bytecode".repeat(16))),
+            ..Resource::default()
+        };
+
+        let mut data = Vec::new();
+        write_packed_resources_v3(
+            &[resource],
+            &mut data,
+            &PackedResourcesWriteOptions {
+                compression: Some(BlobCompression::Zstd),
+                ..PackedResourcesWriteOptions::default()
+            },
+        )
+        .unwrap();
+
+        // Corrupt the zstd frame magic at the start of the bytecode blob.
+        let magic = data
+            .windows(4)
+            .position(|x| x == [0x28, 0xb5, 0x2f, 0xfd])
+            .unwrap();
+        data[magic] = 0;
+
+        let res = load_resources(&data)
+            .unwrap()
+            .collect::<Result<Vec<Resource<u8>>, &'static str>>();
+        assert_eq!(res.err(), Some("error decompressing blob"));
+
+        // Building an index doesn't decompress blobs.
+        let index = load_resources(&data).unwrap().into_index().unwrap();
+        assert_eq!(index.get("foo").err(), Some("error decompressing blob"));
+    }
+
+    fn digest_test_resources() -> Vec<Resource<'static, u8>> {
+        let mut package_resources = HashMap::new();
+        package_resources.insert(Cow::from("foo.txt"), Cow::from(b"foo data".to_vec()));
+        package_resources.insert(Cow::from("bar.txt"), Cow::from(b"bar data".to_vec()));
+
+        vec![
+            Resource {
+                name: Cow::from("foo"),
+                is_python_module: true,
+                in_memory_source: Some(Cow::from(b"import io".to_vec())),
+                in_memory_package_resources: Some(package_resources),
+                relative_path_module_bytecode: Some(Cow::from(Path::new("foo.pyc"))),
+                ..Resource::default()
+            },
+            Resource {
+                name: Cow::from("bar"),
+                is_python_module: true,
+                in_memory_bytecode: Some(Cow::from(b"fake bytecode".to_vec())),
+                ..Resource::default()
+            },
+        ]
+    }
+
+    #[test]
+    fn test_content_digests() {
+        let resources = digest_test_resources();
+
+        let mut data = Vec::new();
+        write_packed_resources_v3(
+            &resources,
+            &mut data,
+            &PackedResourcesWriteOptions {
+                content_digests: true,
+                ..PackedResourcesWriteOptions::default()
+            },
+        )
+        .unwrap();
+
+        let loaded = load_resources_verified(&data)
+            .unwrap()
+            .collect::<Result<Vec<Resource<u8>>, &'static str>>()
+            .unwrap();
+
+        assert_eq!(loaded.len(), resources.len());
+
+        for (original, loaded) in resources.iter().zip(loaded) {
+            assert_eq!(
+                loaded.content_digest_sha256,
+                Some(original.compute_content_digest_sha256())
+            );
+            assert_eq!(
+                Resource {
+                    content_digest_sha256: None,
+                    ..loaded
+                },
+                *original
+            );
+        }
+
+        // Unverified loading works with digests present.
+        assert!(load_resources(&data)
+            .unwrap()
+            .collect::<Result<Vec<Resource<u8>>, &'static str>>()
+            .is_ok());
+    }
+
+    #[test]
+    fn test_verify_requires_digests() {
+        let mut data = Vec::new();
+        write_packed_resources_v3(
+            &digest_test_resources(),
+            &mut data,
+            &PackedResourcesWriteOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            load_resources_verified(&data).err(),
+            Some("blob section digest is required for verification")
+        );
+    }
+
+    #[test]
+    fn test_truncated_blob_section() {
+        let mut data = Vec::new();
+        write_packed_resources_v3(
+            &digest_test_resources(),
+            &mut data,
+            &PackedResourcesWriteOptions::default(),
+        )
+        .unwrap();
+        data.truncate(data.len() - 1);
+
+        assert_eq!(
+            load_resources(&data).err(),
+            Some("blob section extends past end of data")
+        );
+    }
+
+    #[test]
+    fn test_verify_blob_digest_mismatch() {
+        let mut data = Vec::new();
+        write_packed_resources_v3(
+            &digest_test_resources(),
+            &mut data,
+            &PackedResourcesWriteOptions {
+                content_digests: true,
+                ..PackedResourcesWriteOptions::default()
+            },
+        )
+        .unwrap();
+
+        let offset = data
+            .windows(13)
+            .position(|x| x == b"fake bytecode")
+            .unwrap();
+        data[offset] = b'F';
+
+        assert_eq!(
+            load_resources_verified(&data).err(),
+            Some("blob section digest mismatch")
+        );
+
+        // The tampering goes unnoticed without verification.
+        let loaded = load_resources(&data)
+            .unwrap()
+            .collect::<Result<Vec<Resource<u8>>, &'static str>>()
+            .unwrap();
+        assert_eq!(
+            loaded[1].in_memory_bytecode.as_ref().unwrap().as_ref(),
+            b"Fake bytecode"
+        );
+    }
+
+    #[test]
+    fn test_verify_content_digest_mismatch() {
+        let resources = digest_test_resources();
+
+        let mut data = Vec::new();
+        write_packed_resources_v3(
+            &resources,
+            &mut data,
+            &PackedResourcesWriteOptions {
+                content_digests: true,
+                ..PackedResourcesWriteOptions::default()
+            },
+        )
+        .unwrap();
+
+        // Swap the content digests of the resources in the index.
+        let digest0 = resources[0].compute_content_digest_sha256();
+        let digest1 = resources[1].compute_content_digest_sha256();
+        let offset0 = data.windows(32).position(|x| x == digest0).unwrap();
+        let offset1 = data.windows(32).position(|x| x == digest1).unwrap();
+        data[offset0..offset0 + 32].copy_from_slice(&digest1);
+        data[offset1..offset1 + 32].copy_from_slice(&digest0);
+
+        let res = load_resources_verified(&data)
+            .unwrap()
+            .collect::<Result<Vec<Resource<u8>>, &'static str>>();
+        assert_eq!(res.err(), Some("resource content digest mismatch"));
+    }
+
+    #[test]
+    fn test_name_index() {
+        let mut resources = digest_test_resources();
+        resources.push(Resource {
+            name: Cow::from("baz.extension"),
+            is_python_extension_module: true,
+            in_memory_extension_module_shared_library: Some(Cow::from(b"library".to_vec())),
+            shared_library_dependency_names: Some(vec![Cow::from("libfoo.so")]),
+            ..Resource::default()
+        });
+        resources.push(Resource {
+            name: Cow::from("aaa"),
+            is_python_package: true,
+            relative_path_module_source: Some(Cow::from(Path::new("aaa/__init__.py"))),
+            // Sorts after the name index section.
+            original_source_path: Some(Cow::from("/src/aaa/__init__.py")),
+            original_source_digest_sha256: Some([0x42; 32]),
+            ..Resource::default()
+        });
+
+        let mut compressions = vec![None];
+        if cfg!(feature = "zstd") {
+            compressions.push(Some(BlobCompression::Zstd));
+        }
+
+        for padding in [None, Some(BlobInteriorPadding::Null)] {
+            for compression in &compressions {
+                for content_digests in [false, true] {
+                    let mut data = Vec::new();
+                    write_packed_resources_v3(
+                        &resources,
+                        &mut data,
+                        &PackedResourcesWriteOptions {
+                            interior_padding: padding,
+                            compression: *compression,
+                            content_digests,
+                            name_index: true,
+                            ..PackedResourcesWriteOptions::default()
+                        },
+                    )
+                    .unwrap();
+
+                    let parser = if content_digests {
+                        load_resources_verified(&data).unwrap()
+                    } else {
+                        load_resources(&data).unwrap()
+                    };
+                    let index = parser.name_index().unwrap().clone();
+
+                    // The name index doesn't interfere with regular parsing.
+                    let loaded = parser
+                        .collect::<Result<Vec<Resource<u8>>, &'static str>>()
+                        .unwrap();
+                    assert_eq!(loaded.len(), resources.len());
+
+                    assert_eq!(index.len(), resources.len());
+                    assert_eq!(
+                        index
+                            .names()
+                            .collect::<Result<Vec<_>, &'static str>>()
+                            .unwrap(),
+                        vec!["aaa", "bar", "baz.extension", "foo"]
+                    );
+
+                    for resource in &loaded {
+                        assert_eq!(index.get(&resource.name).unwrap().as_ref(), Some(resource));
+                    }
+
+                    assert_eq!(index.find("missing").unwrap(), None);
+                    assert_eq!(index.get("zzz").unwrap(), None);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_name_index_missing() {
+        let mut data = Vec::new();
+        write_packed_resources_v3(
+            &digest_test_resources(),
+            &mut data,
+            &PackedResourcesWriteOptions::default(),
+        )
+        .unwrap();
+
+        assert!(load_resources(&data).unwrap().name_index().is_none());
+    }
+
+    #[test]
+    fn test_name_index_empty() {
+        let resources: Vec<Resource<u8>> = vec![];
+        let mut data = Vec::new();
+        write_packed_resources_v3(
+            &resources,
+            &mut data,
+            &PackedResourcesWriteOptions {
+                name_index: true,
+                ..PackedResourcesWriteOptions::default()
+            },
+        )
+        .unwrap();
+
+        let parser = load_resources(&data).unwrap();
+        let index = parser.name_index().unwrap();
+        assert!(index.is_empty());
+        assert_eq!(index.find("foo").unwrap(), None);
+        assert_eq!(parser.count(), 0);
+    }
+
+    #[test]
+    fn test_into_index() {
+        let mut resources = digest_test_resources();
+        resources.push(Resource {
+            name: Cow::from("aaa"),
+            is_python_package: true,
+            shared_library_dependency_names: Some(vec![Cow::from("libfoo.so")]),
+            ..Resource::default()
+        });
+
+        let mut compressions = vec![None];
+        if cfg!(feature = "zstd") {
+            compressions.push(Some(BlobCompression::Zstd));
+        }
+
+        for padding in [None, Some(BlobInteriorPadding::Null)] {
+            for compression in &compressions {
+                let mut data = Vec::new();
+                write_packed_resources_v3(
+                    &resources,
+                    &mut data,
+                    &PackedResourcesWriteOptions {
+                        interior_padding: padding,
+                        compression: *compression,
+                        ..PackedResourcesWriteOptions::default()
+                    },
+                )
+                .unwrap();
+
+                let parser = load_resources(&data).unwrap();
+                assert!(parser.name_index().is_none());
+                assert_eq!(parser.has_compressed_blob_sections(), compression.is_some());
+
+                let index = parser.into_index().unwrap();
+                assert_eq!(
+                    index
+                        .names()
+                        .collect::<Result<Vec<_>, &'static str>>()
+                        .unwrap(),
+                    vec!["aaa", "bar", "foo"]
+                );
+
+                for resource in &resources {
+                    assert_eq!(index.get(&resource.name).unwrap().as_ref(), Some(resource));
+                }
+                assert_eq!(index.get("missing").unwrap(), None);
+            }
+        }
+
+        let mut data = Vec::new();
+        write_packed_resources_v3(
+            &resources,
+            &mut data,
+            &PackedResourcesWriteOptions::default(),
+        )
+        .unwrap();
+
+        let mut parser = load_resources(&data).unwrap();
+        parser.next().unwrap().unwrap();
+        assert_eq!(
+            parser.into_index().err(),
+            Some("resources index can only be built before reading resources")
+        );
+    }
+
+    #[cfg(feature = "chacha20")]
+    #[test]
+    fn test_encrypted_fields() {
+        let key = [0x42; 32];
+
+        let mut package_resources = HashMap::new();
+        package_resources.insert(Cow::from("foo.txt"), Cow::from(b"foo data".to_vec()));
+
+        let resources: Vec<Resource<u8>> = vec![
+            Resource {
+                name: Cow::from("foo"),
+                is_python_module: true,
+                in_memory_source: Some(Cow::from(b"import io".to_vec())),
+                in_memory_bytecode: Some(Cow::from(b"secret bytecode".to_vec())),
+                in_memory_package_resources: Some(package_resources),
+                ..Resource::default()
+            },
+            Resource {
+                name: Cow::from("bar"),
+                is_python_module: true,
+                in_memory_bytecode: Some(Cow::from(b"more secret bytecode".to_vec())),
+                ..Resource::default()
+            },
+        ];
+
+        let mut compressions = vec![None];
+        if cfg!(feature = "zstd") {
+            compressions.push(Some(BlobCompression::Zstd));
+        }
+
+        for compression in compressions {
+            for padding in [None, Some(BlobInteriorPadding::Null)] {
+                let mut data = Vec::new();
+                write_packed_resources_v3(
+                    &resources,
+                    &mut data,
+                    &PackedResourcesWriteOptions {
+                        interior_padding: padding,
+                        compression,
+                        name_index: true,
+                        encryption_key: Some(key),
+                        ..PackedResourcesWriteOptions::default()
+                    },
+                )
+                .unwrap();
+
+                assert!(!data.windows(6).any(|x| x == b"secret"));
+
+                let mut parser = load_resources(&data).unwrap();
+                parser.set_encryption_key(key);
+
+                let index = parser.name_index().unwrap().clone();
+                assert_eq!(index.get("bar").unwrap().unwrap(), resources[1]);
+                assert_eq!(index.get("foo").unwrap().unwrap(), resources[0]);
+
+                let loaded = parser
+                    .collect::<Result<Vec<Resource<u8>>, &'static str>>()
+                    .unwrap();
+                assert_eq!(resources, loaded);
+            }
+        }
+    }
+
+    #[cfg(feature = "chacha20")]
+    #[test]
+    fn test_encrypted_requires_key() {
+        let resource = Resource {
+            name: Cow::from("foo"),
+            in_memory_bytecode: Some(Cow::from(b"bytecode".to_vec())),
+            ..Resource::default()
+        };
+
+        let mut data = Vec::new();
+        write_packed_resources_v3(
+            &[resource],
+            &mut data,
+            &PackedResourcesWriteOptions {
+                content_digests: true,
+                name_index: true,
+                encryption_key: Some([0x42; 32]),
+                ..PackedResourcesWriteOptions::default()
+            },
+        )
+        .unwrap();
+
+        // Names aren't encrypted.
+        let parser = load_resources(&data).unwrap();
+        assert_eq!(
+            parser.name_index().unwrap().names().collect::<Vec<_>>(),
+            vec![Ok("foo")]
+        );
+
+        let res = parser.collect::<Result<Vec<Resource<u8>>, &'static str>>();
+        assert_eq!(
+            res.err(),
+            Some("encrypted blob section requires an encryption key")
+        );
+
+        let res = load_resources(&data).unwrap().into_index();
+        assert_eq!(
+            res.err(),
+            Some("encrypted blob section requires an encryption key")
+        );
+
+        let mut parser = load_resources(&data).unwrap();
+        parser.set_encryption_key([0x43; 32]);
+        let res = parser.collect::<Result<Vec<Resource<u8>>, &'static str>>();
+        assert_eq!(
+            res.err(),
+            Some("incorrect encryption key for encrypted blob section")
+        );
+
+        let mut parser = load_resources(&data).unwrap();
+        parser.set_encryption_key([0x43; 32]);
+        let res = parser.into_index();
+        assert_eq!(
+            res.err(),
+            Some("incorrect encryption key for encrypted blob section")
+        );
+
+        // The name index also rejects an incorrect key.
+        let mut parser = load_resources(&data).unwrap();
+        parser.set_encryption_key([0x43; 32]);
+        let res = parser.name_index().unwrap().resolve(0);
+        assert_eq!(
+            res.err(),
+            Some("incorrect encryption key for encrypted blob section")
+        );
+    }
 }
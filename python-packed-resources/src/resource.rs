@@ -6,7 +6,11 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use std::{borrow::Cow, collections::HashMap, path::Path};
+use {
+    crate::serialization::ResourceField,
+    sha2::{Digest, Sha256},
+    std::{borrow::Cow, collections::HashMap, path::Path},
+};
 
 /// Represents an indexed resource.
 ///
@@ -115,6 +119,23 @@ where
 
     /// Holds arbitrary file data in a relative path encoded in UTF-8.
     pub file_data_utf8_relative_path: Option<Cow<'a, str>>,
+
+    /// SHA-256 digest of the resource's content.
+    ///
+    /// This is populated when parsing packed resources data containing
+    /// content digests. See [Resource::compute_content_digest_sha256()] for
+    /// how the digest is derived.
+    pub content_digest_sha256: Option<[u8; 32]>,
+
+    /// Path to the original source file this resource was derived from.
+    ///
+    /// This is the path to Python module source as it existed when the
+    /// resource was produced. It allows tooling to map embedded bytecode back
+    /// to a source checkout.
+    pub original_source_path: Option<Cow<'a, str>>,
+
+    /// SHA-256 digest of the original source file at `original_source_path`.
+    pub original_source_digest_sha256: Option<[u8; 32]>,
 }
 
 impl<'a, X> Default for Resource<'a, X>
@@ -151,6 +172,9 @@ where
             file_executable: false,
             file_data_embedded: None,
             file_data_utf8_relative_path: None,
+            content_digest_sha256: None,
+            original_source_path: None,
+            original_source_digest_sha256: None,
         }
     }
 }
@@ -243,6 +267,15 @@ where
         if let Some(value) = other.file_data_utf8_relative_path {
             self.file_data_utf8_relative_path.replace(value);
         }
+        if let Some(value) = other.content_digest_sha256 {
+            self.content_digest_sha256.replace(value);
+        }
+        if let Some(value) = other.original_source_path {
+            self.original_source_path.replace(value);
+        }
+        if let Some(value) = other.original_source_digest_sha256 {
+            self.original_source_digest_sha256.replace(value);
+        }
 
         Ok(())
     }
@@ -370,6 +403,197 @@ where
                 .file_data_utf8_relative_path
                 .as_ref()
                 .map(|value| Cow::Owned(value.clone().into_owned())),
+            content_digest_sha256: self.content_digest_sha256,
+            original_source_path: self
+                .original_source_path
+                .as_ref()
+                .map(|value| Cow::Owned(value.clone().into_owned())),
+            original_source_digest_sha256: self.original_source_digest_sha256,
+        }
+    }
+}
+
+impl<'a> Resource<'a, u8> {
+    /// Compute the SHA-256 digest of this resource's content.
+    ///
+    /// The digest covers the name, flags, and all data and paths attached to
+    /// the resource. Fields are fed into the hasher in a fixed order with
+    /// explicit lengths and mapping entries are sorted by key, so the digest
+    /// is independent of how the resource is serialized. Data is hashed in
+    /// its uncompressed form.
+    ///
+    /// `content_digest_sha256` itself is not part of the digest.
+    pub fn compute_content_digest_sha256(&self) -> [u8; 32] {
+        let mut h = ContentHasher(Sha256::new());
+
+        h.data(ResourceField::Name, self.name.as_bytes());
+        h.flag(ResourceField::IsPythonPackage, self.is_python_package);
+        h.flag(
+            ResourceField::IsPythonNamespacePackage,
+            self.is_python_namespace_package,
+        );
+        h.optional_data(ResourceField::InMemorySource, &self.in_memory_source);
+        h.optional_data(ResourceField::InMemoryBytecode, &self.in_memory_bytecode);
+        h.optional_data(
+            ResourceField::InMemoryBytecodeOpt1,
+            &self.in_memory_bytecode_opt1,
+        );
+        h.optional_data(
+            ResourceField::InMemoryBytecodeOpt2,
+            &self.in_memory_bytecode_opt2,
+        );
+        h.optional_data(
+            ResourceField::InMemoryExtensionModuleSharedLibrary,
+            &self.in_memory_extension_module_shared_library,
+        );
+        h.data_map(
+            ResourceField::InMemoryResourcesData,
+            &self.in_memory_package_resources,
+        );
+        h.data_map(
+            ResourceField::InMemoryDistributionResource,
+            &self.in_memory_distribution_resources,
+        );
+        h.optional_data(
+            ResourceField::InMemorySharedLibrary,
+            &self.in_memory_shared_library,
+        );
+        if let Some(names) = &self.shared_library_dependency_names {
+            h.field(ResourceField::SharedLibraryDependencyNames);
+            h.length(names.len());
+            for name in names {
+                h.bytes(name.as_bytes());
+            }
+        }
+        h.optional_path(
+            ResourceField::RelativeFilesystemModuleSource,
+            &self.relative_path_module_source,
+        );
+        h.optional_path(
+            ResourceField::RelativeFilesystemModuleBytecode,
+            &self.relative_path_module_bytecode,
+        );
+        h.optional_path(
+            ResourceField::RelativeFilesystemModuleBytecodeOpt1,
+            &self.relative_path_module_bytecode_opt1,
+        );
+        h.optional_path(
+            ResourceField::RelativeFilesystemModuleBytecodeOpt2,
+            &self.relative_path_module_bytecode_opt2,
+        );
+        h.optional_path(
+            ResourceField::RelativeFilesystemExtensionModuleSharedLibrary,
+            &self.relative_path_extension_module_shared_library,
+        );
+        h.path_map(
+            ResourceField::RelativeFilesystemPackageResources,
+            &self.relative_path_package_resources,
+        );
+        h.path_map(
+            ResourceField::RelativeFilesystemDistributionResource,
+            &self.relative_path_distribution_resources,
+        );
+        h.flag(ResourceField::IsPythonModule, self.is_python_module);
+        h.flag(
+            ResourceField::IsPythonBuiltinExtensionModule,
+            self.is_python_builtin_extension_module,
+        );
+        h.flag(
+            ResourceField::IsPythonFrozenModule,
+            self.is_python_frozen_module,
+        );
+        h.flag(
+            ResourceField::IsPythonExtensionModule,
+            self.is_python_extension_module,
+        );
+        h.flag(ResourceField::IsSharedLibrary, self.is_shared_library);
+        h.flag(
+            ResourceField::IsUtf8FilenameData,
+            self.is_utf8_filename_data,
+        );
+        h.flag(ResourceField::FileExecutable, self.file_executable);
+        h.optional_data(ResourceField::FileDataEmbedded, &self.file_data_embedded);
+        if let Some(path) = &self.file_data_utf8_relative_path {
+            h.data(ResourceField::FileDataUtf8RelativePath, path.as_bytes());
+        }
+        if let Some(path) = &self.original_source_path {
+            h.data(ResourceField::OriginalSourcePath, path.as_bytes());
+        }
+        if let Some(digest) = &self.original_source_digest_sha256 {
+            h.data(ResourceField::OriginalSourceDigestSha256, digest);
+        }
+
+        h.0.finalize().into()
+    }
+}
+
+/// Feeds resource fields into a SHA-256 hasher in a canonical form.
+struct ContentHasher(Sha256);
+
+impl ContentHasher {
+    fn field(&mut self, field: ResourceField) {
+        self.0.update([u8::from(field)]);
+    }
+
+    fn length(&mut self, length: usize) {
+        self.0.update((length as u64).to_le_bytes());
+    }
+
+    fn bytes(&mut self, data: &[u8]) {
+        self.length(data.len());
+        self.0.update(data);
+    }
+
+    fn flag(&mut self, field: ResourceField, value: bool) {
+        if value {
+            self.field(field);
+        }
+    }
+
+    fn data(&mut self, field: ResourceField, data: &[u8]) {
+        self.field(field);
+        self.bytes(data);
+    }
+
+    fn optional_data(&mut self, field: ResourceField, data: &Option<Cow<[u8]>>) {
+        if let Some(data) = data {
+            self.data(field, data);
+        }
+    }
+
+    fn optional_path(&mut self, field: ResourceField, path: &Option<Cow<Path>>) {
+        if let Some(path) = path {
+            self.data(field, path.to_string_lossy().as_bytes());
+        }
+    }
+
+    fn data_map(&mut self, field: ResourceField, map: &Option<HashMap<Cow<str>, Cow<[u8]>>>) {
+        if let Some(map) = map {
+            self.field(field);
+            self.length(map.len());
+
+            let mut entries = map.iter().collect::<Vec<_>>();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+
+            for (key, value) in entries {
+                self.bytes(key.as_bytes());
+                self.bytes(value);
+            }
+        }
+    }
+
+    fn path_map(&mut self, field: ResourceField, map: &Option<HashMap<Cow<str>, Cow<Path>>>) {
+        if let Some(map) = map {
+            self.field(field);
+            self.length(map.len());
+
+            let mut entries = map.iter().collect::<Vec<_>>();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+
+            for (key, path) in entries {
+                self.bytes(key.as_bytes());
+                self.bytes(path.to_string_lossy().as_bytes());
+            }
         }
     }
 }
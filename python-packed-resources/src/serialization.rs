@@ -8,6 +8,8 @@
 
 /*! Declares the foundational data primitives inside packed resources data. */
 
+use sha2::{Digest, Sha256};
+
 /// Header value for version 2 of resources payload.
 pub const HEADER_V3: &[u8] = b"pyembed\x03";
 
@@ -34,6 +36,93 @@ impl From<&BlobInteriorPadding> for u8 {
     }
 }
 
+/// Defines the compression applied to entries in blob sections.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BlobCompression {
+    /// No compression.
+    ///
+    /// Entries are stored verbatim.
+    None = 0x01,
+
+    /// Each entry is an individual zstandard frame.
+    ///
+    /// Entries are prefixed by a `u64` holding the length of the compressed frame.
+    Zstd = 0x02,
+}
+
+impl From<&BlobCompression> for u8 {
+    fn from(source: &BlobCompression) -> Self {
+        match source {
+            BlobCompression::None => 0x01,
+            BlobCompression::Zstd => 0x02,
+        }
+    }
+}
+
+impl TryFrom<u8> for BlobCompression {
+    type Error = &'static str;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x01 => Ok(BlobCompression::None),
+            0x02 => Ok(BlobCompression::Zstd),
+            _ => Err("invalid value for compression field"),
+        }
+    }
+}
+
+/// Defines the encryption applied to blob sections.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BlobEncryption {
+    /// No encryption.
+    None = 0x01,
+
+    /// The blob section is encrypted with the ChaCha20 stream cipher.
+    ///
+    /// The field value is followed by the 12 byte nonce used to encrypt the
+    /// section and a 16 byte key check value (see [encryption_key_check()]).
+    /// The 32 byte key is not stored in the data and must be supplied by the
+    /// reader.
+    ChaCha20 = 0x02,
+}
+
+impl From<&BlobEncryption> for u8 {
+    fn from(source: &BlobEncryption) -> Self {
+        match source {
+            BlobEncryption::None => 0x01,
+            BlobEncryption::ChaCha20 => 0x02,
+        }
+    }
+}
+
+impl TryFrom<u8> for BlobEncryption {
+    type Error = &'static str;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x01 => Ok(BlobEncryption::None),
+            0x02 => Ok(BlobEncryption::ChaCha20),
+            _ => Err("invalid value for encryption field"),
+        }
+    }
+}
+
+/// Compute the value used to check the key of an encrypted blob section.
+///
+/// This is the first 16 bytes of the SHA-256 digest of the section's nonce
+/// followed by the key. It allows readers to reject an incorrect key before
+/// decrypting anything.
+pub(crate) fn encryption_key_check(key: &[u8; 32], nonce: &[u8; 12]) -> [u8; 16] {
+    let mut hasher = Sha256::new();
+    hasher.update(nonce);
+    hasher.update(key);
+
+    let mut check = [0u8; 16];
+    check.copy_from_slice(&hasher.finalize()[0..16]);
+
+    check
+}
+
 /// Describes a blob section field type in the blob index.
 #[derive(Debug, PartialEq, PartialOrd)]
 pub enum BlobSectionField {
@@ -43,6 +132,9 @@ pub enum BlobSectionField {
     ResourceFieldType = 0x03,
     RawPayloadLength = 0x04,
     InteriorPadding = 0x05,
+    Compression = 0x06,
+    Sha256Digest = 0x07,
+    Encryption = 0x08,
 }
 
 impl From<BlobSectionField> for u8 {
@@ -53,6 +145,9 @@ impl From<BlobSectionField> for u8 {
             BlobSectionField::ResourceFieldType => 0x02,
             BlobSectionField::RawPayloadLength => 0x03,
             BlobSectionField::InteriorPadding => 0x04,
+            BlobSectionField::Compression => 0x05,
+            BlobSectionField::Sha256Digest => 0x06,
+            BlobSectionField::Encryption => 0x07,
             BlobSectionField::EndOfEntry => 0xff,
         }
     }
@@ -68,6 +163,9 @@ impl TryFrom<u8> for BlobSectionField {
             0x02 => Ok(BlobSectionField::ResourceFieldType),
             0x03 => Ok(BlobSectionField::RawPayloadLength),
             0x04 => Ok(BlobSectionField::InteriorPadding),
+            0x05 => Ok(BlobSectionField::Compression),
+            0x06 => Ok(BlobSectionField::Sha256Digest),
+            0x07 => Ok(BlobSectionField::Encryption),
             0xff => Ok(BlobSectionField::EndOfEntry),
             _ => Err("invalid blob index field type"),
         }
@@ -109,6 +207,12 @@ pub enum ResourceField {
     FileExecutable = 0x1c,
     FileDataEmbedded = 0x1d,
     FileDataUtf8RelativePath = 0x1e,
+    ContentDigestSha256 = 0x1f,
+    // Only used to identify the name index blob section. Never appears in
+    // the resources index.
+    NameIndex = 0x20,
+    OriginalSourcePath = 0x21,
+    OriginalSourceDigestSha256 = 0x22,
 }
 
 impl From<ResourceField> for u8 {
@@ -144,6 +248,10 @@ impl From<ResourceField> for u8 {
             ResourceField::FileExecutable => 0x1c,
             ResourceField::FileDataEmbedded => 0x1d,
             ResourceField::FileDataUtf8RelativePath => 0x1e,
+            ResourceField::ContentDigestSha256 => 0x1f,
+            ResourceField::NameIndex => 0x20,
+            ResourceField::OriginalSourcePath => 0x21,
+            ResourceField::OriginalSourceDigestSha256 => 0x22,
             ResourceField::EndOfEntry => 0xff,
         }
     }
@@ -184,8 +292,29 @@ impl TryFrom<u8> for ResourceField {
             0x1c => Ok(ResourceField::FileExecutable),
             0x1d => Ok(ResourceField::FileDataEmbedded),
             0x1e => Ok(ResourceField::FileDataUtf8RelativePath),
+            0x1f => Ok(ResourceField::ContentDigestSha256),
+            0x20 => Ok(ResourceField::NameIndex),
+            0x21 => Ok(ResourceField::OriginalSourcePath),
+            0x22 => Ok(ResourceField::OriginalSourceDigestSha256),
             0xff => Ok(ResourceField::EndOfEntry),
             _ => Err("invalid field type"),
         }
     }
 }
+
+/// Resource fields whose blob sections can be compressed.
+///
+/// Only fields holding opaque data are compressible. Fields holding
+/// names and paths must remain uncompressed so they can be referenced
+/// without copying.
+pub const COMPRESSIBLE_FIELDS: &[ResourceField] = &[
+    ResourceField::InMemorySource,
+    ResourceField::InMemoryBytecode,
+    ResourceField::InMemoryBytecodeOpt1,
+    ResourceField::InMemoryBytecodeOpt2,
+    ResourceField::InMemoryExtensionModuleSharedLibrary,
+    ResourceField::InMemoryResourcesData,
+    ResourceField::InMemoryDistributionResource,
+    ResourceField::InMemorySharedLibrary,
+    ResourceField::FileDataEmbedded,
+];
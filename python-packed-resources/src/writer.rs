@@ -11,13 +11,30 @@
 use {
     crate::{
         resource::Resource,
-        serialization::{BlobInteriorPadding, BlobSectionField, ResourceField, HEADER_V3},
+        serialization::{
+            encryption_key_check, BlobCompression, BlobEncryption, BlobInteriorPadding,
+            BlobSectionField, ResourceField, COMPRESSIBLE_FIELDS, HEADER_V3,
+        },
     },
     anyhow::{anyhow, Context, Result},
     byteorder::{LittleEndian, WriteBytesExt},
-    std::{collections::BTreeMap, io::Write, path::Path},
+    sha2::{Digest, Sha256},
+    std::{
+        borrow::Cow,
+        collections::{BTreeMap, HashMap},
+        io::Write,
+        ops::Range,
+        path::Path,
+    },
 };
 
+/// Compression level to use for zstd compressed blobs.
+///
+/// Blobs are compressed once at build time and decompressed many times at
+/// run-time. So we favor compression ratio over compression speed.
+#[cfg(feature = "zstd")]
+const ZSTD_COMPRESSION_LEVEL: i32 = 19;
+
 #[cfg(unix)]
 use std::os::unix::ffi::OsStrExt;
 #[cfg(windows)]
@@ -56,6 +73,12 @@ struct BlobSection {
     resource_field: ResourceField,
     raw_payload_length: usize,
     interior_padding: Option<BlobInteriorPadding>,
+    compression: Option<BlobCompression>,
+    sha256_digest: Option<[u8; 32]>,
+    /// Nonce used to encrypt the section, if encrypted.
+    encryption_nonce: Option<[u8; 12]>,
+    /// Value used to check the key of an encrypted section.
+    encryption_key_check: Option<[u8; 16]>,
 }
 
 impl BlobSection {
@@ -75,6 +98,21 @@ impl BlobSection {
             index += 2;
         }
 
+        if self.compression.is_some() {
+            // Field + value.
+            index += 2;
+        }
+
+        if self.sha256_digest.is_some() {
+            // Field + 32 byte digest.
+            index += 33;
+        }
+
+        if self.encryption_nonce.is_some() {
+            // Field + value + 12 byte nonce + 16 byte key check.
+            index += 30;
+        }
+
         // End of index entry.
         index += 1;
 
@@ -102,6 +140,33 @@ impl BlobSection {
                 .context("writing interior padding value")?;
         }
 
+        if let Some(compression) = &self.compression {
+            dest.write_u8(BlobSectionField::Compression.into())
+                .context("writing compression field")?;
+            dest.write_u8(compression.into())
+                .context("writing compression value")?;
+        }
+
+        if let Some(digest) = &self.sha256_digest {
+            dest.write_u8(BlobSectionField::Sha256Digest.into())
+                .context("writing sha256 digest field")?;
+            dest.write_all(digest)
+                .context("writing sha256 digest value")?;
+        }
+
+        if let Some(nonce) = &self.encryption_nonce {
+            dest.write_u8(BlobSectionField::Encryption.into())
+                .context("writing encryption field")?;
+            dest.write_u8((&BlobEncryption::ChaCha20).into())
+                .context("writing encryption value")?;
+            dest.write_all(nonce).context("writing encryption nonce")?;
+            let key_check = self
+                .encryption_key_check
+                .ok_or_else(|| anyhow!("encryption key check not set"))?;
+            dest.write_all(&key_check)
+                .context("writing encryption key check")?;
+        }
+
         dest.write_u8(BlobSectionField::EndOfEntry.into())
             .context("writing end of index entry")?;
 
@@ -264,6 +329,14 @@ where
             index += 5;
         }
 
+        if self.original_source_path.is_some() {
+            index += 5;
+        }
+
+        if self.original_source_digest_sha256.is_some() {
+            index += 33;
+        }
+
         // End of index entry.
         index += 1;
 
@@ -278,6 +351,8 @@ where
             ResourceField::EndOfIndex => 0,
             ResourceField::StartOfEntry => 0,
             ResourceField::EndOfEntry => 0,
+            ResourceField::ContentDigestSha256 => 0,
+            ResourceField::NameIndex => 0,
             ResourceField::Name => self.name.as_bytes().len(),
             ResourceField::IsPythonPackage => 0,
             ResourceField::IsPythonNamespacePackage => 0,
@@ -426,6 +501,14 @@ where
                     0
                 }
             }
+            ResourceField::OriginalSourcePath => {
+                if let Some(path) = &self.original_source_path {
+                    path.as_bytes().len()
+                } else {
+                    0
+                }
+            }
+            ResourceField::OriginalSourceDigestSha256 => 0,
         }
     }
 
@@ -439,6 +522,8 @@ where
             ResourceField::EndOfIndex => 0,
             ResourceField::StartOfEntry => 0,
             ResourceField::EndOfEntry => 0,
+            ResourceField::ContentDigestSha256 => 0,
+            ResourceField::NameIndex => 0,
             ResourceField::Name => 1,
             ResourceField::IsPythonPackage => 0,
             ResourceField::IsPythonNamespacePackage => 0,
@@ -575,6 +660,14 @@ where
                     0
                 }
             }
+            ResourceField::OriginalSourcePath => {
+                if self.original_source_path.is_some() {
+                    1
+                } else {
+                    0
+                }
+            }
+            ResourceField::OriginalSourceDigestSha256 => 0,
         };
 
         let overhead = match padding {
@@ -586,7 +679,14 @@ where
     }
 
     /// Write the version 1 index entry for a resource instance.
-    pub fn write_index_v1<W: Write>(&self, dest: &mut W) -> Result<()> {
+    ///
+    /// If `content_digest` is defined, it is written as the content digest
+    /// of the resource. This adds 33 bytes to the index entry.
+    pub fn write_index_v1<W: Write>(
+        &self,
+        dest: &mut W,
+        content_digest: Option<&[u8; 32]>,
+    ) -> Result<()> {
         let name_len =
             u16::try_from(self.name.as_bytes().len()).context("converting name to u16")?;
 
@@ -853,6 +953,29 @@ where
                 .context("writing file_data_utf_relative_path field")?;
         }
 
+        if let Some(path) = &self.original_source_path {
+            let l = u32::try_from(path.as_bytes().len())
+                .context("converting original source path length to u32")?;
+            dest.write_u8(ResourceField::OriginalSourcePath.into())
+                .context("writing original_source_path field")?;
+            dest.write_u32::<LittleEndian>(l)
+                .context("writing original_source_path length")?;
+        }
+
+        if let Some(digest) = &self.original_source_digest_sha256 {
+            dest.write_u8(ResourceField::OriginalSourceDigestSha256.into())
+                .context("writing original source digest field")?;
+            dest.write_all(digest)
+                .context("writing original source digest value")?;
+        }
+
+        if let Some(digest) = content_digest {
+            dest.write_u8(ResourceField::ContentDigestSha256.into())
+                .context("writing content digest field")?;
+            dest.write_all(digest)
+                .context("writing content digest value")?;
+        }
+
         dest.write_u8(ResourceField::EndOfEntry.into())
             .map_err(|_| anyhow!("error writing end of index entry"))?;
 
@@ -860,13 +983,213 @@ where
     }
 }
 
+/// Obtain the individual data elements for a compressible field.
+///
+/// Elements are emitted in the order they are written to the blob section.
+/// Fields that aren't compressible yield no elements.
+fn compressible_field_elements<'r>(
+    resource: &'r Resource<u8>,
+    field: ResourceField,
+) -> Vec<&'r [u8]> {
+    let single = |value: &'r Option<Cow<[u8]>>| -> Vec<&'r [u8]> {
+        match value {
+            Some(data) => vec![data.as_ref()],
+            None => vec![],
+        }
+    };
+
+    let map = |value: &'r Option<HashMap<Cow<str>, Cow<[u8]>>>| -> Vec<&'r [u8]> {
+        match value {
+            Some(entries) => entries
+                .iter()
+                .flat_map(|(key, value)| [key.as_bytes(), value.as_ref()])
+                .collect(),
+            None => vec![],
+        }
+    };
+
+    match field {
+        ResourceField::InMemorySource => single(&resource.in_memory_source),
+        ResourceField::InMemoryBytecode => single(&resource.in_memory_bytecode),
+        ResourceField::InMemoryBytecodeOpt1 => single(&resource.in_memory_bytecode_opt1),
+        ResourceField::InMemoryBytecodeOpt2 => single(&resource.in_memory_bytecode_opt2),
+        ResourceField::InMemoryExtensionModuleSharedLibrary => {
+            single(&resource.in_memory_extension_module_shared_library)
+        }
+        ResourceField::InMemoryResourcesData => map(&resource.in_memory_package_resources),
+        ResourceField::InMemoryDistributionResource => {
+            map(&resource.in_memory_distribution_resources)
+        }
+        ResourceField::InMemorySharedLibrary => single(&resource.in_memory_shared_library),
+        ResourceField::FileDataEmbedded => single(&resource.file_data_embedded),
+        _ => vec![],
+    }
+}
+
+/// Compress a single blob entry.
+#[cfg(feature = "zstd")]
+fn compress_blob(compression: BlobCompression, data: &[u8]) -> Result<Vec<u8>> {
+    match compression {
+        BlobCompression::None => Ok(data.to_vec()),
+        BlobCompression::Zstd => {
+            zstd::block::compress(data, ZSTD_COMPRESSION_LEVEL).context("zstd compressing blob")
+        }
+    }
+}
+
+#[cfg(not(feature = "zstd"))]
+fn compress_blob(compression: BlobCompression, data: &[u8]) -> Result<Vec<u8>> {
+    match compression {
+        BlobCompression::None => Ok(data.to_vec()),
+        BlobCompression::Zstd => Err(anyhow!(
+            "zstd compression support not available; enable the zstd crate feature"
+        )),
+    }
+}
+
+/// Encrypt a blob section in place.
+#[cfg(feature = "chacha20")]
+fn encrypt_blob_section(key: &[u8; 32], nonce: &[u8; 12], data: &mut [u8]) -> Result<()> {
+    use chacha20::{
+        cipher::{KeyIvInit, StreamCipher},
+        ChaCha20,
+    };
+
+    ChaCha20::new(key.into(), nonce.into())
+        .try_apply_keystream(data)
+        .map_err(|_| anyhow!("blob section too large to encrypt"))
+}
+
+#[cfg(not(feature = "chacha20"))]
+fn encrypt_blob_section(_key: &[u8; 32], _nonce: &[u8; 12], _data: &mut [u8]) -> Result<()> {
+    Err(anyhow!(
+        "encryption support not available; enable the chacha20 crate feature"
+    ))
+}
+
+/// Build the content of the name index blob section.
+///
+/// The name index holds a fixed size record for each resource, sorted by
+/// resource name. Each record holds the `u32` offset of the resource's entry
+/// within the resources index followed by the `u32` offset of the resource's
+/// data within each blob section, in blob index order.
+fn name_index_data<'a, T: AsRef<Resource<'a, u8>>>(
+    resources: &[T],
+    blob_sections: &BTreeMap<ResourceField, BlobSection>,
+    compressed_frame_lengths: &BTreeMap<ResourceField, Vec<usize>>,
+    interior_padding: Option<BlobInteriorPadding>,
+    content_digests: bool,
+) -> Result<Vec<u8>> {
+    let padding = interior_padding.unwrap_or(BlobInteriorPadding::None);
+    let padding_length = match padding {
+        BlobInteriorPadding::None => 0,
+        BlobInteriorPadding::Null => 1,
+    };
+
+    let fields = blob_sections.keys().copied().collect::<Vec<_>>();
+    let mut frame_lengths = compressed_frame_lengths
+        .iter()
+        .map(|(field, lengths)| (*field, lengths.iter()))
+        .collect::<BTreeMap<_, _>>();
+
+    let mut index_offset = 0;
+    let mut section_offsets = vec![0; fields.len()];
+    let mut records = Vec::with_capacity(resources.len());
+
+    for resource in resources {
+        let resource = resource.as_ref();
+        let mut record = Vec::with_capacity(4 + 4 * fields.len());
+
+        record.write_u32::<LittleEndian>(
+            u32::try_from(index_offset).context("converting resource index offset to u32")?,
+        )?;
+
+        for (field, offset) in fields.iter().zip(section_offsets.iter_mut()) {
+            record.write_u32::<LittleEndian>(
+                u32::try_from(*offset).context("converting blob section offset to u32")?,
+            )?;
+
+            *offset += if let Some(lengths) = frame_lengths.get_mut(field) {
+                // Each compressed frame is prefixed by its u64 length.
+                lengths
+                    .by_ref()
+                    .take(compressible_field_elements(resource, *field).len())
+                    .map(|l| 8 + l + padding_length)
+                    .sum()
+            } else {
+                resource.field_blob_length(*field)
+                    + resource.field_blob_interior_padding_length(*field, padding)
+            };
+        }
+
+        index_offset += resource.index_v1_length();
+        if content_digests {
+            index_offset += 33;
+        }
+
+        records.push((resource.name.as_ref(), record));
+    }
+
+    records.sort_by(|a, b| a.0.cmp(b.0));
+
+    Ok(records.into_iter().flat_map(|(_, record)| record).collect())
+}
+
+/// Options controlling how packed resources data is written.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PackedResourcesWriteOptions {
+    /// Padding to use between entries in blob sections.
+    pub interior_padding: Option<BlobInteriorPadding>,
+
+    /// Compression to apply to blob sections holding compressible data.
+    ///
+    /// This applies to sections holding resource content, such as module
+    /// source and bytecode. Each entry in those sections is compressed
+    /// individually so entries can be decompressed independently of each other.
+    pub compression: Option<BlobCompression>,
+
+    /// Whether to write SHA-256 digests of content.
+    ///
+    /// If true, a SHA-256 digest of each resource's content is written to the
+    /// resources index and a SHA-256 digest of the raw payload of each blob
+    /// section is written to the blob index. These digests allow readers to
+    /// verify the integrity of the data.
+    pub content_digests: bool,
+
+    /// Whether to write a name index.
+    ///
+    /// The name index is written as an additional blob section. It allows
+    /// readers to locate and deserialize an individual resource by name
+    /// without parsing all resources.
+    pub name_index: bool,
+
+    /// Key to encrypt blob sections holding resource content with.
+    ///
+    /// Sections holding resource content, such as module source and bytecode,
+    /// are encrypted with the ChaCha20 stream cipher. Encryption is applied
+    /// after compression. Resource names and paths are not encrypted. The key
+    /// is not written to the data and readers need to supply it via
+    /// [crate::ResourceParserIterator::set_encryption_key()]. This is a means
+    /// of obfuscating content, not of securing it: the key needs to be
+    /// available to whatever reads the data.
+    pub encryption_key: Option<[u8; 32]>,
+}
+
 /// Write packed resources data, version 3.
 #[allow(clippy::cognitive_complexity)]
 pub fn write_packed_resources_v3<'a, T: AsRef<Resource<'a, u8>>, W: Write>(
     resources: &[T],
     dest: &mut W,
-    interior_padding: Option<BlobInteriorPadding>,
+    options: &PackedResourcesWriteOptions,
 ) -> Result<()> {
+    let PackedResourcesWriteOptions {
+        interior_padding,
+        compression,
+        content_digests,
+        name_index,
+        encryption_key,
+    } = *options;
+
     let mut blob_sections = BTreeMap::new();
 
     let mut blob_section_count = 0;
@@ -893,12 +1216,16 @@ pub fn write_packed_resources_v3<'a, T: AsRef<Resource<'a, u8>>, W: Write>(
                     resource_field: field,
                     raw_payload_length: 0,
                     interior_padding,
+                    compression: None,
+                    sha256_digest: None,
+                    encryption_nonce: None,
+                    encryption_key_check: None,
                 })
                 .raw_payload_length += l;
         }
     };
 
-    let add_interior_padding = |dest: &mut W| -> Result<()> {
+    let add_interior_padding = |dest: &mut Vec<u8>| -> Result<()> {
         if interior_padding == Some(BlobInteriorPadding::Null) {
             dest.write_all(b"\0")?;
         }
@@ -906,10 +1233,35 @@ pub fn write_packed_resources_v3<'a, T: AsRef<Resource<'a, u8>>, W: Write>(
         Ok(())
     };
 
+    // Compressed entries need to be materialized up front so the blob index
+    // can record the size of each blob section.
+    let mut compressed_frames = BTreeMap::new();
+
+    if let Some(compression) = compression {
+        if compression != BlobCompression::None {
+            for field in COMPRESSIBLE_FIELDS {
+                let frames = resources
+                    .iter()
+                    .flat_map(|resource| compressible_field_elements(resource.as_ref(), *field))
+                    .map(|data| compress_blob(compression, data))
+                    .collect::<Result<Vec<_>>>()?;
+
+                if !frames.is_empty() {
+                    compressed_frames.insert(*field, frames);
+                }
+            }
+        }
+    }
+
     for resource in resources {
         let resource = resource.as_ref();
         resource_index_length += resource.index_v1_length();
 
+        if content_digests {
+            // Field + 32 byte digest.
+            resource_index_length += 33;
+        }
+
         process_field(&mut blob_sections, resource, ResourceField::Name);
         process_field(&mut blob_sections, resource, ResourceField::InMemorySource);
         process_field(
@@ -997,80 +1349,148 @@ pub fn write_packed_resources_v3<'a, T: AsRef<Resource<'a, u8>>, W: Write>(
             resource,
             ResourceField::FileDataUtf8RelativePath,
         );
+        process_field(
+            &mut blob_sections,
+            resource,
+            ResourceField::OriginalSourcePath,
+        );
     }
 
-    for section in blob_sections.values() {
-        blob_section_count += 1;
-        blob_index_length += section.index_v1_length();
+    for (field, frames) in &compressed_frames {
+        let padding = match interior_padding {
+            Some(BlobInteriorPadding::Null) => 1,
+            _ => 0,
+        };
+
+        let section = blob_sections.entry(*field).or_insert_with(|| BlobSection {
+            resource_field: *field,
+            raw_payload_length: 0,
+            interior_padding,
+            compression: None,
+            sha256_digest: None,
+            encryption_nonce: None,
+            encryption_key_check: None,
+        });
+
+        // Each frame is prefixed by its u64 length.
+        section.raw_payload_length = frames.iter().map(|frame| 8 + frame.len() + padding).sum();
+        section.compression = compression;
     }
 
-    dest.write_all(HEADER_V3)?;
+    let name_index_blob = if name_index {
+        let frame_lengths = compressed_frames
+            .iter()
+            .map(|(field, frames)| (*field, frames.iter().map(|frame| frame.len()).collect()))
+            .collect::<BTreeMap<_, _>>();
+
+        let data = name_index_data(
+            resources,
+            &blob_sections,
+            &frame_lengths,
+            interior_padding,
+            content_digests,
+        )?;
+
+        blob_sections.insert(
+            ResourceField::NameIndex,
+            BlobSection {
+                resource_field: ResourceField::NameIndex,
+                raw_payload_length: data.len(),
+                interior_padding: None,
+                compression: None,
+                sha256_digest: None,
+                encryption_nonce: None,
+                encryption_key_check: None,
+            },
+        );
 
-    dest.write_u8(blob_section_count)?;
-    dest.write_u32::<LittleEndian>(blob_index_length as u32)?;
-    dest.write_u32::<LittleEndian>(resources.len() as u32)?;
-    dest.write_u32::<LittleEndian>(resource_index_length as u32)?;
+        Some(data)
+    } else {
+        None
+    };
 
-    // Write the blob index.
-    for section in blob_sections.values() {
-        section.write_index_v1(dest)?;
-    }
-    dest.write_u8(ResourceField::EndOfIndex.into())?;
+    let mut compressed_frames = compressed_frames
+        .into_iter()
+        .map(|(field, frames)| (field, frames.into_iter()))
+        .collect::<BTreeMap<_, _>>();
+
+    // Blob data is buffered so digests and encryption of blob sections can be
+    // recorded in the blob index, which precedes the blob data.
+    let mut blob_data = Vec::new();
+    let mut section_ranges: BTreeMap<ResourceField, Range<usize>> = BTreeMap::new();
+
+    let mut write_blob = |dest: &mut Vec<u8>, field: ResourceField, data: &[u8]| -> Result<()> {
+        let start = dest.len();
+
+        if let Some(frames) = compressed_frames.get_mut(&field) {
+            let frame = frames
+                .next()
+                .ok_or_else(|| anyhow!("compressed blob count mismatch"))?;
+            dest.write_u64::<LittleEndian>(frame.len() as u64)?;
+            dest.write_all(&frame)?;
+        } else {
+            dest.write_all(data)?;
+        }
 
-    // Write the resources index.
-    for resource in resources {
-        resource.as_ref().write_index_v1(dest)?;
-    }
-    dest.write_u8(ResourceField::EndOfIndex.into())?;
+        add_interior_padding(dest)?;
+
+        section_ranges.entry(field).or_insert(start..start).end = dest.len();
+
+        Ok(())
+    };
 
     // Write blob data, one field at a time.
     for resource in resources {
-        dest.write_all(resource.as_ref().name.as_bytes())?;
-        add_interior_padding(dest)?;
+        write_blob(
+            &mut blob_data,
+            ResourceField::Name,
+            resource.as_ref().name.as_bytes(),
+        )?;
     }
 
     for resource in resources {
         if let Some(data) = &resource.as_ref().in_memory_source {
-            dest.write_all(data)?;
-            add_interior_padding(dest)?;
+            write_blob(&mut blob_data, ResourceField::InMemorySource, data)?;
         }
     }
 
     for resource in resources {
         if let Some(data) = &resource.as_ref().in_memory_bytecode {
-            dest.write_all(data)?;
-            add_interior_padding(dest)?;
+            write_blob(&mut blob_data, ResourceField::InMemoryBytecode, data)?;
         }
     }
 
     for resource in resources {
         if let Some(data) = &resource.as_ref().in_memory_bytecode_opt1 {
-            dest.write_all(data)?;
-            add_interior_padding(dest)?;
+            write_blob(&mut blob_data, ResourceField::InMemoryBytecodeOpt1, data)?;
         }
     }
 
     for resource in resources {
         if let Some(data) = &resource.as_ref().in_memory_bytecode_opt2 {
-            dest.write_all(data)?;
-            add_interior_padding(dest)?;
+            write_blob(&mut blob_data, ResourceField::InMemoryBytecodeOpt2, data)?;
         }
     }
 
     for resource in resources {
         if let Some(data) = &resource.as_ref().in_memory_extension_module_shared_library {
-            dest.write_all(data)?;
-            add_interior_padding(dest)?;
+            write_blob(
+                &mut blob_data,
+                ResourceField::InMemoryExtensionModuleSharedLibrary,
+                data,
+            )?;
         }
     }
 
     for resource in resources {
         if let Some(resources) = &resource.as_ref().in_memory_package_resources {
             for (key, value) in resources.iter() {
-                dest.write_all(key.as_bytes())?;
-                add_interior_padding(dest)?;
-                dest.write_all(value)?;
-                add_interior_padding(dest)?;
+                write_blob(
+                    &mut blob_data,
+                    ResourceField::InMemoryResourcesData,
+                    key.as_bytes(),
+                )?;
+                write_blob(&mut blob_data, ResourceField::InMemoryResourcesData, value)?;
             }
         }
     }
@@ -1078,55 +1498,75 @@ pub fn write_packed_resources_v3<'a, T: AsRef<Resource<'a, u8>>, W: Write>(
     for resource in resources {
         if let Some(resources) = &resource.as_ref().in_memory_distribution_resources {
             for (key, value) in resources {
-                dest.write_all(key.as_bytes())?;
-                add_interior_padding(dest)?;
-                dest.write_all(value)?;
-                add_interior_padding(dest)?;
+                write_blob(
+                    &mut blob_data,
+                    ResourceField::InMemoryDistributionResource,
+                    key.as_bytes(),
+                )?;
+                write_blob(
+                    &mut blob_data,
+                    ResourceField::InMemoryDistributionResource,
+                    value,
+                )?;
             }
         }
     }
 
     for resource in resources {
         if let Some(data) = &resource.as_ref().in_memory_shared_library {
-            dest.write_all(data)?;
-            add_interior_padding(dest)?;
+            write_blob(&mut blob_data, ResourceField::InMemorySharedLibrary, data)?;
         }
     }
 
     for resource in resources {
         if let Some(names) = &resource.as_ref().shared_library_dependency_names {
             for name in names {
-                dest.write_all(name.as_bytes())?;
-                add_interior_padding(dest)?;
+                write_blob(
+                    &mut blob_data,
+                    ResourceField::SharedLibraryDependencyNames,
+                    name.as_bytes(),
+                )?;
             }
         }
     }
 
     for resource in resources {
         if let Some(path) = &resource.as_ref().relative_path_module_source {
-            dest.write_all(&path_to_bytes(path))?;
-            add_interior_padding(dest)?;
+            write_blob(
+                &mut blob_data,
+                ResourceField::RelativeFilesystemModuleSource,
+                &path_to_bytes(path),
+            )?;
         }
     }
 
     for resource in resources {
         if let Some(path) = &resource.as_ref().relative_path_module_bytecode {
-            dest.write_all(&path_to_bytes(path))?;
-            add_interior_padding(dest)?;
+            write_blob(
+                &mut blob_data,
+                ResourceField::RelativeFilesystemModuleBytecode,
+                &path_to_bytes(path),
+            )?;
         }
     }
 
     for resource in resources {
         if let Some(path) = &resource.as_ref().relative_path_module_bytecode_opt1 {
-            dest.write_all(&path_to_bytes(path))?;
-            add_interior_padding(dest)?;
+            write_blob(
+                &mut blob_data,
+                ResourceField::RelativeFilesystemModuleBytecodeOpt1,
+                &path_to_bytes(path),
+            )?;
         }
     }
 
     for resource in resources {
         if let Some(path) = &resource.as_ref().relative_path_module_bytecode_opt2 {
-            dest.write_all(&path_to_bytes(path))?;
-            add_interior_padding(dest)?;
+            write_blob(
+                &mut blob_data,
+                ResourceField::RelativeFilesystemModuleBytecodeOpt2,
+                &path_to_bytes(path),
+            )?;
         }
     }
 
@@ -1135,18 +1575,27 @@ pub fn write_packed_resources_v3<'a, T: AsRef<Resource<'a, u8>>, W: Write>(
             .as_ref()
             .relative_path_extension_module_shared_library
         {
-            dest.write_all(&path_to_bytes(path))?;
-            add_interior_padding(dest)?;
+            write_blob(
+                &mut blob_data,
+                ResourceField::RelativeFilesystemExtensionModuleSharedLibrary,
+                &path_to_bytes(path),
+            )?;
         }
     }
 
     for resource in resources {
         if let Some(resources) = &resource.as_ref().relative_path_package_resources {
             for (key, path) in resources.iter() {
-                dest.write_all(key.as_bytes())?;
-                add_interior_padding(dest)?;
-                dest.write_all(&path_to_bytes(path))?;
-                add_interior_padding(dest)?;
+                write_blob(
+                    &mut blob_data,
+                    ResourceField::RelativeFilesystemPackageResources,
+                    key.as_bytes(),
+                )?;
+                write_blob(
+                    &mut blob_data,
+                    ResourceField::RelativeFilesystemPackageResources,
+                    &path_to_bytes(path),
+                )?;
             }
         }
     }
@@ -1154,28 +1603,120 @@ pub fn write_packed_resources_v3<'a, T: AsRef<Resource<'a, u8>>, W: Write>(
     for resource in resources {
         if let Some(resources) = &resource.as_ref().relative_path_distribution_resources {
             for (key, path) in resources {
-                dest.write_all(key.as_bytes())?;
-                add_interior_padding(dest)?;
-                dest.write_all(&path_to_bytes(path))?;
-                add_interior_padding(dest)?;
+                write_blob(
+                    &mut blob_data,
+                    ResourceField::RelativeFilesystemDistributionResource,
+                    key.as_bytes(),
+                )?;
+                write_blob(
+                    &mut blob_data,
+                    ResourceField::RelativeFilesystemDistributionResource,
+                    &path_to_bytes(path),
+                )?;
             }
         }
     }
 
     for resource in resources {
         if let Some(data) = &resource.as_ref().file_data_embedded {
-            dest.write_all(data)?;
-            add_interior_padding(dest)?;
+            write_blob(&mut blob_data, ResourceField::FileDataEmbedded, data)?;
         }
     }
 
     for resource in resources {
         if let Some(path) = &resource.as_ref().file_data_utf8_relative_path {
-            dest.write_all(path.as_bytes())?;
-            add_interior_padding(dest)?;
+            write_blob(
+                &mut blob_data,
+                ResourceField::FileDataUtf8RelativePath,
+                path.as_bytes(),
+            )?;
         }
     }
 
+    for resource in resources {
+        if let Some(path) = &resource.as_ref().original_source_path {
+            write_blob(
+                &mut blob_data,
+                ResourceField::OriginalSourcePath,
+                path.as_bytes(),
+            )?;
+        }
+    }
+
+    // The name index is always the last blob section, even though fields
+    // introduced after it have higher values.
+    if let Some(data) = name_index_blob {
+        let start = blob_data.len();
+        blob_data.extend(data);
+        section_ranges.insert(ResourceField::NameIndex, start..blob_data.len());
+    }
+
+    if let Some(key) = &encryption_key {
+        for field in COMPRESSIBLE_FIELDS {
+            if let (Some(section), Some(range)) =
+                (blob_sections.get_mut(field), section_ranges.get(field))
+            {
+                let data = &mut blob_data[range.clone()];
+
+                // The nonce is derived from the section content so output is
+                // deterministic.
+                let mut nonce = [0u8; 12];
+                nonce.copy_from_slice(&Sha256::digest(&*data)[0..12]);
+
+                encrypt_blob_section(key, &nonce, data)?;
+                section.encryption_nonce = Some(nonce);
+                section.encryption_key_check = Some(encryption_key_check(key, &nonce));
+            }
+        }
+    }
+
+    // Digests are of the stored, possibly encrypted, section data.
+    if content_digests {
+        for (field, range) in section_ranges {
+            if let Some(section) = blob_sections.get_mut(&field) {
+                section.sha256_digest = Some(Sha256::digest(&blob_data[range]).into());
+            }
+        }
+    }
+
+    for section in blob_sections.values() {
+        blob_section_count += 1;
+        blob_index_length += section.index_v1_length();
+    }
+
+    dest.write_all(HEADER_V3)?;
+
+    dest.write_u8(blob_section_count)?;
+    dest.write_u32::<LittleEndian>(blob_index_length as u32)?;
+    dest.write_u32::<LittleEndian>(resources.len() as u32)?;
+    dest.write_u32::<LittleEndian>(resource_index_length as u32)?;
+
+    // Write the blob index. The order must match the order of blob data.
+    for section in blob_sections
+        .values()
+        .filter(|section| section.resource_field != ResourceField::NameIndex)
+        .chain(blob_sections.get(&ResourceField::NameIndex))
+    {
+        section.write_index_v1(dest)?;
+    }
+    dest.write_u8(ResourceField::EndOfIndex.into())?;
+
+    // Write the resources index.
+    for resource in resources {
+        let resource = resource.as_ref();
+
+        let digest = if content_digests {
+            Some(resource.compute_content_digest_sha256())
+        } else {
+            None
+        };
+
+        resource.write_index_v1(dest, digest.as_ref())?;
+    }
+    dest.write_u8(ResourceField::EndOfIndex.into())?;
+
+    dest.write_all(&blob_data)?;
+
     Ok(())
 }
 
@@ -1187,7 +1728,11 @@ mod tests {
     fn test_write_empty() -> Result<()> {
         let mut data = Vec::new();
         let resources: Vec<Resource<u8>> = Vec::new();
-        write_packed_resources_v3(&resources, &mut data, None)?;
+        write_packed_resources_v3(
+            &resources,
+            &mut data,
+            &PackedResourcesWriteOptions::default(),
+        )?;
 
         let mut expected: Vec<u8> = b"pyembed\x03".to_vec();
         // Number of blob sections.
@@ -1215,7 +1760,11 @@ mod tests {
             ..Resource::default()
         };
 
-        write_packed_resources_v3(&[resource], &mut data, None)?;
+        write_packed_resources_v3(
+            &[resource],
+            &mut data,
+            &PackedResourcesWriteOptions::default(),
+        )?;
 
         let mut expected: Vec<u8> = b"pyembed\x03".to_vec();
         // Number of blob sections.
@@ -1247,4 +1796,57 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_write_content_digests() -> Result<()> {
+        let mut data = Vec::new();
+        let resource = Resource {
+            name: Cow::Owned("foo".to_string()),
+            ..Resource::default()
+        };
+
+        write_packed_resources_v3(
+            std::slice::from_ref(&resource),
+            &mut data,
+            &PackedResourcesWriteOptions {
+                content_digests: true,
+                ..PackedResourcesWriteOptions::default()
+            },
+        )?;
+
+        let mut expected: Vec<u8> = b"pyembed\x03".to_vec();
+        // Number of blob sections.
+        expected.write_u8(1)?;
+        // Length of blob index. Start of entry, field type, field value, length field, length,
+        // digest field, digest, end of entry, end of index.
+        expected.write_u32::<LittleEndian>(1 + 1 + 1 + 1 + 8 + 1 + 32 + 1 + 1)?;
+        // Number of resources.
+        expected.write_u32::<LittleEndian>(1)?;
+        // Length of index. Start of entry, resource name length field, resource name length,
+        // digest field, digest, end of entry, end of index.
+        expected.write_u32::<LittleEndian>(1 + 1 + 2 + 1 + 32 + 1 + 1)?;
+        // Blobs index.
+        expected.write_u8(BlobSectionField::StartOfEntry.into())?;
+        expected.write_u8(BlobSectionField::ResourceFieldType.into())?;
+        expected.write_u8(ResourceField::Name.into())?;
+        expected.write_u8(BlobSectionField::RawPayloadLength.into())?;
+        expected.write_u64::<LittleEndian>(b"foo".len() as u64)?;
+        expected.write_u8(BlobSectionField::Sha256Digest.into())?;
+        expected.write_all(&Sha256::digest(b"foo"))?;
+        expected.write_u8(BlobSectionField::EndOfEntry.into())?;
+        expected.write_u8(BlobSectionField::EndOfIndex.into())?;
+        // Resource index.
+        expected.write_u8(ResourceField::StartOfEntry.into())?;
+        expected.write_u8(ResourceField::Name.into())?;
+        expected.write_u16::<LittleEndian>(b"foo".len() as u16)?;
+        expected.write_u8(ResourceField::ContentDigestSha256.into())?;
+        expected.write_all(&resource.compute_content_digest_sha256())?;
+        expected.write_u8(ResourceField::EndOfEntry.into())?;
+        expected.write_u8(ResourceField::EndOfIndex.into())?;
+        expected.write_all(b"foo")?;
+
+        assert_eq!(data, expected);
+
+        Ok(())
+    }
 }
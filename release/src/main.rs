@@ -40,6 +40,7 @@ static RELEASE_ORDER: Lazy<Vec<&'static str>> = Lazy::new(|| {
         "tugger-file-manifest",
         "cpio-archive",
         "apple-flat-package",
+        "apple-dmg",
         "starlark-dialect-build-targets",
         "tugger-common",
         "apple-bundles",
@@ -49,7 +50,7 @@ static RELEASE_ORDER: Lazy<Vec<&'static str>> = Lazy::new(|| {
         //"linux-package-analyzer",
         "tugger-rust-toolchain",
         "tugger-binary-analysis",
-        //"tugger-rpm",
+        "tugger-rpm",
         "tugger-snapcraft",
         "tugger-apple",
         "apple-codesign",
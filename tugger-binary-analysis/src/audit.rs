@@ -27,11 +27,23 @@ pub fn analyze_data(buffer: &[u8]) {
 
             analyze_elf_libraries(&elf.libraries, &undefined_symbols);
         }
-        goblin::Object::PE(_pe) => {
-            panic!("PE not yet supported");
+        goblin::Object::PE(pe) => {
+            analyze_shared_libraries(&pe.libraries);
         }
-        goblin::Object::Mach(_mach) => {
-            panic!("mach not yet supported");
+        goblin::Object::Mach(goblin::mach::Mach::Binary(macho)) => {
+            analyze_shared_libraries(&macho_libraries(&macho));
+        }
+        goblin::Object::Mach(goblin::mach::Mach::Fat(multi)) => {
+            for macho in &multi {
+                let macho = macho.unwrap();
+
+                if let Some(name) = macho_cpu_name(&macho) {
+                    println!("Architecture: {}", name);
+                    println!();
+                }
+
+                analyze_shared_libraries(&macho_libraries(&macho));
+            }
         }
         goblin::Object::Archive(_archive) => {
             panic!("archive not yet supported");
@@ -40,6 +52,39 @@ pub fn analyze_data(buffer: &[u8]) {
     }
 }
 
+/// Obtain the libraries a Mach-O binary links against.
+fn macho_libraries<'a>(macho: &goblin::mach::MachO<'a>) -> Vec<&'a str> {
+    // goblin inserts an entry for the binary itself.
+    macho
+        .libs
+        .iter()
+        .filter(|lib| **lib != "self")
+        .copied()
+        .collect()
+}
+
+/// Obtain the name of the CPU a Mach-O binary targets.
+fn macho_cpu_name(macho: &goblin::mach::MachO) -> Option<&'static str> {
+    goblin::mach::constants::cputype::get_arch_name_from_types(
+        macho.header.cputype(),
+        macho.header.cpusubtype(),
+    )
+}
+
+/// Print the shared libraries a non-ELF binary depends on.
+pub fn analyze_shared_libraries(libs: &[&str]) {
+    println!("Shared Library Dependencies");
+    println!("===========================");
+
+    let mut libs = libs.to_vec();
+    libs.sort_unstable();
+    for lib in libs {
+        println!("{}", lib);
+    }
+
+    println!();
+}
+
 pub fn analyze_elf_libraries(libs: &[&str], undefined_symbols: &[UndefinedSymbol]) {
     let mut latest_symbols: BTreeMap<String, version_compare::Version> = BTreeMap::new();
 
@@ -5,7 +5,7 @@
 /*! Functionality for analyzing the content of platform binaries. */
 
 mod audit;
-pub use audit::{analyze_data, analyze_elf_libraries, analyze_file};
+pub use audit::{analyze_data, analyze_elf_libraries, analyze_file, analyze_shared_libraries};
 mod elf;
 pub use elf::find_undefined_elf_symbols;
 mod linux_distro_versions;
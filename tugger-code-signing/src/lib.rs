@@ -137,6 +137,12 @@ pub enum SigningError {
     #[error("error signing Apple bundle: {0}")]
     AppleBundleSigningError(AppleCodesignError),
 
+    #[error("error signing Apple flat package: {0}")]
+    AppleFlatPackageSigningError(AppleCodesignError),
+
+    #[error("error signing Apple disk image: {0}")]
+    AppleDmgSigningError(AppleCodesignError),
+
     #[error("error running settings callback: {0}")]
     SettingsCallback(anyhow::Error),
 
@@ -344,6 +350,12 @@ pub enum Signable {
 
     /// An Apple bundle, persisted on the filesystem as a directory.
     AppleBundle(PathBuf),
+
+    /// An Apple flat package (`.pkg` installer), persisted on the filesystem as a file.
+    AppleFlatPackage(PathBuf),
+
+    /// An Apple disk image (`.dmg` file), persisted on the filesystem as a file.
+    AppleDmg(PathBuf),
 }
 
 impl Signable {
@@ -384,6 +396,10 @@ impl Signable {
                 // apple-codesign can sign in place or to a new directory.
                 vec![SigningMethod::InPlaceDirectory, SigningMethod::NewDirectory]
             }
+            Self::AppleFlatPackage(_) | Self::AppleDmg(_) => {
+                // apple-codesign can sign in place or to a new file.
+                vec![SigningMethod::InPlaceFile, SigningMethod::NewFile]
+            }
         })
     }
 
@@ -397,6 +413,7 @@ impl Signable {
         match self {
             Self::WindowsFile(p) => Some(p.as_path()),
             Self::MachOFile(p, _) => Some(p.as_path()),
+            Self::AppleFlatPackage(p) | Self::AppleDmg(p) => Some(p.as_path()),
             Self::WindowsData(_) | Self::MachOData(_) | Self::AppleBundle(_) => None,
         }
     }
@@ -408,7 +425,9 @@ impl Signable {
             Self::WindowsFile(_)
             | Self::WindowsData(_)
             | Self::MachOFile(_, _)
-            | Self::MachOData(_) => None,
+            | Self::MachOData(_)
+            | Self::AppleFlatPackage(_)
+            | Self::AppleDmg(_) => None,
         }
     }
 
@@ -530,6 +549,22 @@ pub fn path_signable(path: impl AsRef<Path>) -> Result<Signability, SigningError
             Err(e) => return Err(SigningError::SignableTestError(format!("{:?}", e))),
         }
 
+        if apple_codesign::path_is_xar(path)
+            .map_err(|e| SigningError::SignableTestError(format!("{:?}", e)))?
+        {
+            return Ok(Signability::Signable(Signable::AppleFlatPackage(
+                path.to_path_buf(),
+            )));
+        }
+
+        if apple_codesign::dmg::path_is_dmg(path)
+            .map_err(|e| SigningError::SignableTestError(format!("{:?}", e)))?
+        {
+            return Ok(Signability::Signable(Signable::AppleDmg(
+                path.to_path_buf(),
+            )));
+        }
+
         let data = std::fs::read(path)?;
 
         if goblin::mach::Mach::parse(&data).is_ok() {
@@ -1008,6 +1043,9 @@ impl<'a> SignableSigner<'a> {
             Signable::WindowsFile(path) => SigningDestination::File(path.clone()),
             Signable::MachOFile(path, _) => SigningDestination::File(path.clone()),
             Signable::AppleBundle(path) => SigningDestination::Directory(path.clone()),
+            Signable::AppleFlatPackage(path) | Signable::AppleDmg(path) => {
+                SigningDestination::File(path.clone())
+            }
             Signable::WindowsData(_) | Signable::MachOData(_) => SigningDestination::Memory,
         }
     }
@@ -1303,9 +1341,63 @@ impl<'a> SignableSigner<'a> {
 
                 Ok(SignedOutput::Directory(dest_dir.clone()))
             }
+            Signable::AppleFlatPackage(source_file) => self.sign_apple_file(
+                "Apple flat package",
+                source_file,
+                destination,
+                |signer, source, dest| signer.sign_xar(source, dest),
+                SigningError::AppleFlatPackageSigningError,
+            ),
+            Signable::AppleDmg(source_file) => self.sign_apple_file(
+                "Apple disk image",
+                source_file,
+                destination,
+                |signer, source, dest| signer.sign_dmg(source, dest),
+                SigningError::AppleDmgSigningError,
+            ),
         }
     }
 
+    /// Sign an Apple file format signed via apple-codesign's [apple_codesign::UnifiedSigner].
+    ///
+    /// `sign` receives the signer, the source path, and the destination path and
+    /// writes the signed file to the destination.
+    fn sign_apple_file(
+        &self,
+        description: &str,
+        source_file: &Path,
+        destination: &SigningDestination,
+        sign: impl FnOnce(
+            &apple_codesign::UnifiedSigner<'_>,
+            &Path,
+            &Path,
+        ) -> Result<(), AppleCodesignError>,
+        map_err: fn(AppleCodesignError) -> SigningError,
+    ) -> Result<SignedOutput, SigningError> {
+        let settings = self.as_apple_signing_settings()?;
+
+        let dest_file = match destination {
+            SigningDestination::File(f) => f,
+            _ => panic!("illegal signing combination: {} -> !File", description),
+        };
+
+        warn!(
+            "signing {} at {} to {}",
+            description,
+            source_file.display(),
+            dest_file.display()
+        );
+
+        sign(
+            &apple_codesign::UnifiedSigner::new(settings),
+            source_file,
+            dest_file,
+        )
+        .map_err(map_err)?;
+
+        Ok(SignedOutput::File(dest_file.clone()))
+    }
+
     /// Whether signing to the specified [SigningDestination] will require temporary files.
     ///
     /// Temporary files are used when:
@@ -1339,6 +1431,8 @@ impl<'a> SignableSigner<'a> {
                 SigningDestination::Directory(dest_dir) => source_dir != dest_dir,
                 SigningDestination::Memory | SigningDestination::File(_) => false,
             },
+            // apple-codesign writes the signed file to the destination itself.
+            Signable::AppleFlatPackage(_) | Signable::AppleDmg(_) => false,
         }
     }
 }
@@ -3,13 +3,31 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use {
+    crate::{DependencyOperator, DependencySpec},
     anyhow::{anyhow, Context, Result},
+    rpm::{Dependency, RPMFileOptions, RPMPackage},
     std::path::{Path, PathBuf},
-    tugger_file_manifest::FileManifest,
+    tugger_file_manifest::{FileEntry, FileManifest},
 };
 
-#[cfg(target_family = "unix")]
-use rpm::{RPMFileOptions, RPMPackage};
+impl From<&DependencySpec> for Dependency {
+    fn from(spec: &DependencySpec) -> Self {
+        match &spec.constraint {
+            None => Dependency::any(&spec.name),
+            Some((DependencyOperator::Less, version)) => Dependency::less(&spec.name, version),
+            Some((DependencyOperator::LessEqual, version)) => {
+                Dependency::less_eq(&spec.name, version)
+            }
+            Some((DependencyOperator::Equal, version)) => Dependency::eq(&spec.name, version),
+            Some((DependencyOperator::GreaterEqual, version)) => {
+                Dependency::greater_eq(&spec.name, version)
+            }
+            Some((DependencyOperator::Greater, version)) => {
+                Dependency::greater(&spec.name, version)
+            }
+        }
+    }
+}
 
 /// Create RPMs.
 ///
@@ -48,6 +66,80 @@ impl RpmBuilder {
         }
     }
 
+    /// Set the release of the package.
+    #[must_use]
+    pub fn release(mut self, release: &str) -> Self {
+        self.inner = self.inner.release(release);
+        self
+    }
+
+    /// Register a capability this package requires.
+    #[must_use]
+    pub fn requires(mut self, dependency: &DependencySpec) -> Self {
+        self.inner = self.inner.requires(dependency.into());
+        self
+    }
+
+    /// Register a capability this package provides.
+    #[must_use]
+    pub fn provides(mut self, dependency: &DependencySpec) -> Self {
+        self.inner = self.inner.provides(dependency.into());
+        self
+    }
+
+    /// Set the script to run before the package is installed.
+    #[must_use]
+    pub fn pre_install_script(mut self, content: &str) -> Self {
+        self.inner = self.inner.pre_install_script(content);
+        self
+    }
+
+    /// Set the script to run after the package is installed.
+    #[must_use]
+    pub fn post_install_script(mut self, content: &str) -> Self {
+        self.inner = self.inner.post_install_script(content);
+        self
+    }
+
+    /// Set the script to run before the package is uninstalled.
+    #[must_use]
+    pub fn pre_uninstall_script(mut self, content: &str) -> Self {
+        self.inner = self.inner.pre_uninstall_script(content);
+        self
+    }
+
+    /// Set the script to run after the package is uninstalled.
+    #[must_use]
+    pub fn post_uninstall_script(mut self, content: &str) -> Self {
+        self.inner = self.inner.post_uninstall_script(content);
+        self
+    }
+
+    /// Register a file to be installed by this package.
+    ///
+    /// Paths are absolute install paths, e.g. `/usr/bin/myapp`.
+    pub fn add_file(mut self, path: impl AsRef<Path>, entry: impl Into<FileEntry>) -> Result<Self> {
+        let path = path.as_ref();
+        let path = path.strip_prefix("/").unwrap_or(path);
+
+        self.files
+            .add_file_entry(path, entry)
+            .with_context(|| format!("adding {}", path.display()))?;
+
+        Ok(self)
+    }
+
+    /// Register all files in a [FileManifest] to be installed by this package.
+    ///
+    /// Paths in the manifest are relative to the filesystem root.
+    pub fn add_manifest(mut self, manifest: &FileManifest) -> Result<Self> {
+        self.files
+            .add_manifest(manifest)
+            .context("adding manifest")?;
+
+        Ok(self)
+    }
+
     /// Populate registered files with the internal RPMBuilder.
     pub fn populate_files(mut self) -> Result<Self> {
         self.files
@@ -57,7 +149,8 @@ impl RpmBuilder {
         for (rel_path, content) in self.files.iter_entries() {
             let real_path = self.build_path.join(rel_path);
 
-            let mut options = RPMFileOptions::new(rel_path.display().to_string());
+            // rpm-rs requires destinations to be absolute install paths.
+            let mut options = RPMFileOptions::new(format!("/{}", rel_path.display()));
 
             if content.is_executable() {
                 options = options.mode(0o100_775);
@@ -7,3 +7,126 @@ mod builder;
 
 #[cfg(target_family = "unix")]
 pub use builder::RpmBuilder;
+
+use {
+    anyhow::{anyhow, Result},
+    std::str::FromStr,
+};
+
+/// A comparison operator in a versioned dependency.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DependencyOperator {
+    Less,
+    LessEqual,
+    Equal,
+    GreaterEqual,
+    Greater,
+}
+
+/// A capability required or provided by a package.
+///
+/// Parsed from strings like `python3` or `glibc >= 2.17`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DependencySpec {
+    /// The name of the capability.
+    pub name: String,
+    /// An optional version constraint on the capability.
+    pub constraint: Option<(DependencyOperator, String)>,
+}
+
+impl FromStr for DependencySpec {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parts = s.split_whitespace().collect::<Vec<_>>();
+
+        match parts.as_slice() {
+            [name] => Ok(Self {
+                name: name.to_string(),
+                constraint: None,
+            }),
+            [name, op, version] => {
+                let op = match *op {
+                    "<" => DependencyOperator::Less,
+                    "<=" => DependencyOperator::LessEqual,
+                    "=" | "==" => DependencyOperator::Equal,
+                    ">=" => DependencyOperator::GreaterEqual,
+                    ">" => DependencyOperator::Greater,
+                    _ => return Err(anyhow!("unknown dependency operator in {}", s)),
+                };
+
+                Ok(Self {
+                    name: name.to_string(),
+                    constraint: Some((op, version.to_string())),
+                })
+            }
+            _ => Err(anyhow!(
+                "invalid dependency {}; expected `name` or `name <op> version`",
+                s
+            )),
+        }
+    }
+}
+
+/// Resolve the RPM architecture name for a Rust target triple.
+///
+/// Returns `None` if the triple isn't a Linux target or its machine
+/// architecture isn't known.
+pub fn target_triple_to_rpm_arch(triple: &str) -> Option<&'static str> {
+    if !triple.contains("-linux-") {
+        return None;
+    }
+
+    match triple.split('-').next()? {
+        "x86_64" => Some("x86_64"),
+        "i586" => Some("i586"),
+        "i686" => Some("i686"),
+        "aarch64" => Some("aarch64"),
+        "armv7" => Some("armv7hl"),
+        "powerpc64" => Some("ppc64"),
+        "powerpc64le" => Some("ppc64le"),
+        "riscv64gc" => Some("riscv64"),
+        "s390x" => Some("s390x"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_dependency() -> Result<()> {
+        assert_eq!(
+            DependencySpec::from_str("python3")?,
+            DependencySpec {
+                name: "python3".into(),
+                constraint: None
+            }
+        );
+        assert_eq!(
+            DependencySpec::from_str("glibc >= 2.17")?,
+            DependencySpec {
+                name: "glibc".into(),
+                constraint: Some((DependencyOperator::GreaterEqual, "2.17".into()))
+            }
+        );
+        assert!(DependencySpec::from_str("glibc ~ 2.17").is_err());
+        assert!(DependencySpec::from_str("glibc >=").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn rpm_arch() {
+        assert_eq!(
+            target_triple_to_rpm_arch("x86_64-unknown-linux-gnu"),
+            Some("x86_64")
+        );
+        assert_eq!(
+            target_triple_to_rpm_arch("armv7-unknown-linux-gnueabihf"),
+            Some("armv7hl")
+        );
+        assert_eq!(target_triple_to_rpm_arch("x86_64-apple-darwin"), None);
+    }
+}
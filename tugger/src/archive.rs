@@ -0,0 +1,469 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Deterministic archives of [FileManifest] instances.
+
+Archives produced by this module are reproducible: entries are emitted in
+sorted order, ownership is normalized to `root`, permissions are derived
+solely from the executable bit on [FileEntry], and every entry shares the
+same modified time.
+*/
+
+use {
+    anyhow::{anyhow, Context, Result},
+    std::{
+        collections::BTreeSet,
+        io::{Seek, Write},
+        path::{Path, PathBuf},
+        str::FromStr,
+    },
+    tugger_file_manifest::{FileEntry, FileManifest},
+};
+
+/// Mode for directories and executable files.
+const EXECUTABLE_MODE: u32 = 0o755;
+
+/// Mode for non-executable files.
+const FILE_MODE: u32 = 0o644;
+
+/// Mode for symlinks.
+const SYMLINK_MODE: u32 = 0o777;
+
+/// An archive format that a [FileManifest] can be written as.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ArchiveFormat {
+    /// A tar archive compressed with gzip.
+    TarGzip,
+    /// A tar archive compressed with zstandard.
+    TarZstd,
+    /// A tar archive compressed with xz.
+    TarXz,
+    /// A zip archive using deflate compression.
+    Zip,
+}
+
+impl ArchiveFormat {
+    /// All known formats.
+    pub fn all() -> &'static [Self] {
+        &[Self::TarGzip, Self::TarZstd, Self::TarXz, Self::Zip]
+    }
+
+    /// The canonical string name of this format.
+    ///
+    /// This is also the file extension for archives of this format.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::TarGzip => "tar.gz",
+            Self::TarZstd => "tar.zst",
+            Self::TarXz => "tar.xz",
+            Self::Zip => "zip",
+        }
+    }
+
+    /// Attempt to resolve the format from a filename's extension.
+    pub fn from_filename(path: impl AsRef<Path>) -> Option<Self> {
+        let filename = path.as_ref().file_name()?.to_str()?;
+
+        Self::all()
+            .iter()
+            .find(|format| filename.ends_with(&format!(".{}", format.as_str())))
+            .copied()
+    }
+}
+
+impl FromStr for ArchiveFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "tar.gz" | "tgz" => Ok(Self::TarGzip),
+            "tar.zst" | "tzst" => Ok(Self::TarZstd),
+            "tar.xz" | "txz" => Ok(Self::TarXz),
+            "zip" => Ok(Self::Zip),
+            _ => Err(anyhow!(
+                "unknown archive format: {}; must be one of {}",
+                s,
+                Self::all()
+                    .iter()
+                    .map(|f| f.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for ArchiveFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Resolve the entries to add to an archive.
+///
+/// Returns directories and files, each with their archive path.
+fn archive_entries<'a>(
+    manifest: &'a FileManifest,
+    prefix: Option<&Path>,
+) -> (Vec<PathBuf>, Vec<(PathBuf, &'a FileEntry)>) {
+    let files = manifest
+        .iter_entries()
+        .map(|(path, entry)| {
+            let path = if let Some(prefix) = prefix {
+                prefix.join(path)
+            } else {
+                path.clone()
+            };
+
+            (path, entry)
+        })
+        .collect::<Vec<_>>();
+
+    let mut dirs = BTreeSet::new();
+    for (path, _) in &files {
+        for parent in path.ancestors().skip(1) {
+            if !parent.as_os_str().is_empty() {
+                dirs.insert(parent.to_path_buf());
+            }
+        }
+    }
+
+    (dirs.into_iter().collect(), files)
+}
+
+/// Convert a path to a `/` delimited string for use as an archive member name.
+fn member_name(path: &Path) -> Result<String> {
+    Ok(path
+        .components()
+        .map(|c| {
+            c.as_os_str()
+                .to_str()
+                .ok_or_else(|| anyhow!("path is not valid UTF-8: {}", path.display()))
+        })
+        .collect::<Result<Vec<_>>>()?
+        .join("/"))
+}
+
+fn new_tar_header(mtime: u64) -> Result<tar::Header> {
+    let mut header = tar::Header::new_gnu();
+    header.set_uid(0);
+    header.set_gid(0);
+    header.set_username("root")?;
+    header.set_groupname("root")?;
+    header.set_mtime(mtime);
+
+    Ok(header)
+}
+
+/// Write a [FileManifest] as an uncompressed tar archive.
+fn write_tar<W: Write>(
+    writer: W,
+    manifest: &FileManifest,
+    prefix: Option<&Path>,
+    mtime: u64,
+) -> Result<W> {
+    let (dirs, files) = archive_entries(manifest, prefix);
+
+    let mut builder = tar::Builder::new(writer);
+
+    for path in dirs {
+        let mut header = new_tar_header(mtime)?;
+        header.set_entry_type(tar::EntryType::Directory);
+        header.set_mode(EXECUTABLE_MODE);
+        header.set_size(0);
+        builder.append_data(&mut header, &path, std::io::empty())?;
+    }
+
+    for (path, entry) in files {
+        let mut header = new_tar_header(mtime)?;
+
+        if let Some(target) = entry.link_target() {
+            header.set_entry_type(tar::EntryType::Symlink);
+            header.set_mode(SYMLINK_MODE);
+            header.set_size(0);
+            builder.append_link(&mut header, &path, target)?;
+        } else {
+            let data = entry
+                .resolve_content()
+                .with_context(|| format!("resolving content of {}", path.display()))?;
+
+            header.set_entry_type(tar::EntryType::Regular);
+            header.set_mode(if entry.is_executable() {
+                EXECUTABLE_MODE
+            } else {
+                FILE_MODE
+            });
+            header.set_size(data.len() as u64);
+            builder.append_data(&mut header, &path, data.as_slice())?;
+        }
+    }
+
+    Ok(builder.into_inner()?)
+}
+
+/// Convert seconds since the UNIX epoch to a zip timestamp.
+///
+/// Zip timestamps can't represent times before 1980, so earlier times are
+/// clamped to the earliest representable value.
+fn zip_datetime(mtime: u64) -> Result<zip::DateTime> {
+    let dt = time::OffsetDateTime::from_unix_timestamp(mtime as i64)
+        .map_err(|e| anyhow!("invalid mtime: {}", e))?;
+
+    if dt.year() < 1980 {
+        return Ok(zip::DateTime::default());
+    }
+
+    zip::DateTime::from_date_and_time(
+        dt.year() as u16,
+        dt.month() as u8,
+        dt.day(),
+        dt.hour(),
+        dt.minute(),
+        dt.second(),
+    )
+    .map_err(|_| anyhow!("mtime cannot be represented in a zip archive: {}", mtime))
+}
+
+/// Write a [FileManifest] as a zip archive.
+fn write_zip<W: Write + Seek>(
+    writer: W,
+    manifest: &FileManifest,
+    prefix: Option<&Path>,
+    mtime: u64,
+) -> Result<W> {
+    let (dirs, files) = archive_entries(manifest, prefix);
+
+    let options = zip::write::FileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated)
+        .last_modified_time(zip_datetime(mtime)?);
+
+    let mut zf = zip::ZipWriter::new(writer);
+
+    for path in dirs {
+        zf.add_directory(
+            member_name(&path)?,
+            options.unix_permissions(EXECUTABLE_MODE),
+        )?;
+    }
+
+    for (path, entry) in files {
+        let name = member_name(&path)?;
+
+        if let Some(target) = entry.link_target() {
+            let target = target
+                .to_str()
+                .ok_or_else(|| anyhow!("symlink target is not valid UTF-8: {}", path.display()))?;
+
+            zf.add_symlink(name, target, options.unix_permissions(SYMLINK_MODE))?;
+        } else {
+            let data = entry
+                .resolve_content()
+                .with_context(|| format!("resolving content of {}", path.display()))?;
+
+            zf.start_file(
+                name,
+                options.unix_permissions(if entry.is_executable() {
+                    EXECUTABLE_MODE
+                } else {
+                    FILE_MODE
+                }),
+            )?;
+            zf.write_all(&data)?;
+        }
+    }
+
+    Ok(zf.finish()?)
+}
+
+/// Write a [FileManifest] as an archive.
+///
+/// All entries are placed under `prefix`, if given. Every entry is given the
+/// modified time `mtime`, expressed as seconds since the UNIX epoch.
+///
+/// Executable bits and symlinks from [FileEntry] are preserved. Parent
+/// directories are emitted as explicit entries.
+pub fn write_manifest_archive<W: Write + Seek>(
+    writer: W,
+    manifest: &FileManifest,
+    format: ArchiveFormat,
+    prefix: Option<&Path>,
+    mtime: u64,
+) -> Result<W> {
+    match format {
+        ArchiveFormat::TarGzip => {
+            let encoder = flate2::GzBuilder::new()
+                .mtime(0)
+                .write(writer, flate2::Compression::best());
+            Ok(write_tar(encoder, manifest, prefix, mtime)?.finish()?)
+        }
+        ArchiveFormat::TarZstd => {
+            let encoder = zstd::stream::Encoder::new(writer, 19)?;
+            Ok(write_tar(encoder, manifest, prefix, mtime)?.finish()?)
+        }
+        ArchiveFormat::TarXz => {
+            let encoder = xz2::write::XzEncoder::new(writer, 9);
+            Ok(write_tar(encoder, manifest, prefix, mtime)?.finish()?)
+        }
+        ArchiveFormat::Zip => write_zip(writer, manifest, prefix, mtime),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::io::Cursor};
+
+    fn test_manifest() -> Result<FileManifest> {
+        let mut manifest = FileManifest::new_with_links();
+        manifest.add_file_entry("bin/app", FileEntry::new_from_data(b"app".to_vec(), true))?;
+        manifest.add_file_entry(
+            "lib/data.txt",
+            FileEntry::new_from_data(b"data".to_vec(), false),
+        )?;
+        manifest.add_symlink("app", "bin/app")?;
+
+        Ok(manifest)
+    }
+
+    #[test]
+    fn format_resolution() -> Result<()> {
+        for format in ArchiveFormat::all() {
+            assert_eq!(&ArchiveFormat::from_str(format.as_str())?, format);
+            assert_eq!(
+                ArchiveFormat::from_filename(format!("dist/app.{}", format)),
+                Some(*format)
+            );
+        }
+
+        assert!(ArchiveFormat::from_str("rar").is_err());
+        assert_eq!(ArchiveFormat::from_filename("app.tar"), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn tar_entries() -> Result<()> {
+        let manifest = test_manifest()?;
+
+        let data = write_tar(vec![], &manifest, Some(Path::new("app-1.0")), 42)?;
+
+        let mut archive = tar::Archive::new(Cursor::new(data));
+        let entries = archive
+            .entries()?
+            .map(|entry| {
+                let entry = entry?;
+                let header = entry.header();
+
+                Ok((
+                    entry.path()?.display().to_string(),
+                    header.entry_type(),
+                    header.mode()?,
+                    header.mtime()?,
+                    entry.link_name()?.map(|p| p.display().to_string()),
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        assert_eq!(
+            entries,
+            vec![
+                (
+                    "app-1.0".to_string(),
+                    tar::EntryType::Directory,
+                    0o755,
+                    42,
+                    None
+                ),
+                (
+                    "app-1.0/bin".to_string(),
+                    tar::EntryType::Directory,
+                    0o755,
+                    42,
+                    None
+                ),
+                (
+                    "app-1.0/lib".to_string(),
+                    tar::EntryType::Directory,
+                    0o755,
+                    42,
+                    None
+                ),
+                (
+                    "app-1.0/app".to_string(),
+                    tar::EntryType::Symlink,
+                    0o777,
+                    42,
+                    Some("bin/app".to_string())
+                ),
+                (
+                    "app-1.0/bin/app".to_string(),
+                    tar::EntryType::Regular,
+                    0o755,
+                    42,
+                    None
+                ),
+                (
+                    "app-1.0/lib/data.txt".to_string(),
+                    tar::EntryType::Regular,
+                    0o644,
+                    42,
+                    None
+                ),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn deterministic() -> Result<()> {
+        let manifest = test_manifest()?;
+
+        for format in ArchiveFormat::all() {
+            let a = write_manifest_archive(Cursor::new(vec![]), &manifest, *format, None, 0)?;
+            let b = write_manifest_archive(Cursor::new(vec![]), &manifest, *format, None, 0)?;
+
+            assert!(!a.get_ref().is_empty());
+            assert_eq!(
+                a.into_inner(),
+                b.into_inner(),
+                "{} is deterministic",
+                format
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn zip_entries() -> Result<()> {
+        let manifest = test_manifest()?;
+
+        let writer = write_manifest_archive(
+            Cursor::new(vec![]),
+            &manifest,
+            ArchiveFormat::Zip,
+            None,
+            1_000_000_000,
+        )?;
+
+        let mut archive = zip::ZipArchive::new(Cursor::new(writer.into_inner()))?;
+        let names = (0..archive.len())
+            .map(|i| Ok(archive.by_index(i)?.name().to_string()))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(
+            names,
+            vec!["bin/", "lib/", "app", "bin/app", "lib/data.txt"]
+        );
+
+        let exe = archive.by_name("bin/app")?;
+        assert_eq!(exe.unix_mode(), Some(0o100755));
+        assert_eq!(exe.last_modified().year(), 2001);
+        drop(exe);
+
+        assert_eq!(archive.by_name("lib/data.txt")?.unix_mode(), Some(0o100644));
+        assert_eq!(archive.by_name("app")?.unix_mode(), Some(0o120777));
+
+        Ok(())
+    }
+}
@@ -284,6 +284,8 @@ impl TypedValue for CodeSigningRequestValue {
 pub enum SigningAction {
     FileManifestInstall,
     MacOsApplicationBunderCreation,
+    MacOsDmgCreation,
+    MacOsFlatPackageCreation,
     WindowsInstallerCreation,
     WindowsInstallerFileAdded,
     Other(&'static str),
@@ -294,6 +296,8 @@ impl SigningAction {
         match self {
             Self::FileManifestInstall => "file-manifest-install",
             Self::MacOsApplicationBunderCreation => "macos-application-bundle-creation",
+            Self::MacOsDmgCreation => "macos-dmg-creation",
+            Self::MacOsFlatPackageCreation => "macos-flat-package-creation",
             Self::WindowsInstallerCreation => "windows-installer-creation",
             Self::WindowsInstallerFileAdded => "windows-installer-file-added",
             Self::Other(s) => s,
@@ -0,0 +1,564 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    crate::starlark::{file_content::FileContentValue, file_manifest::FileManifestValue},
+    anyhow::{anyhow, Context},
+    debian_packaging::{
+        control::{ControlFile, ControlParagraph},
+        deb::{builder::DebBuilder, DebCompression},
+    },
+    log::warn,
+    starlark::{
+        environment::TypeValues,
+        values::{
+            error::{RuntimeError, UnsupportedOperation, ValueError},
+            none::NoneType,
+            {Mutable, TypedValue, Value, ValueResult},
+        },
+        {
+            starlark_fun, starlark_module, starlark_parse_param_type, starlark_signature,
+            starlark_signature_extraction, starlark_signatures,
+        },
+    },
+    starlark_dialect_build_targets::{
+        get_context_value, optional_str_arg, EnvironmentContext, ResolvedTarget,
+        ResolvedTargetValue, RunMode,
+    },
+    std::{
+        collections::BTreeMap,
+        path::{Path, PathBuf},
+    },
+    tugger_file_manifest::{FileEntry, FileManifest},
+};
+
+/// Names of maintainer scripts that can be registered with a package.
+const MAINTAINER_SCRIPTS: &[&str] = &["config", "postinst", "postrm", "preinst", "prerm"];
+
+fn error_context<F, T>(label: &str, f: F) -> Result<T, ValueError>
+where
+    F: FnOnce() -> anyhow::Result<T>,
+{
+    f().map_err(|e| {
+        ValueError::Runtime(RuntimeError {
+            code: "TUGGER_DEBIAN_PACKAGE_BUILDER",
+            message: format!("{:?}", e),
+            label: label.to_string(),
+        })
+    })
+}
+
+fn parse_compression(format: &str, level: Option<i64>) -> anyhow::Result<DebCompression> {
+    Ok(match format {
+        "none" => DebCompression::Uncompressed,
+        "gzip" => DebCompression::Gzip,
+        "xz" => DebCompression::Xz(level.unwrap_or(6) as u32),
+        "zstd" => DebCompression::Zstandard(level.unwrap_or(3) as i32),
+        _ => {
+            return Err(anyhow!(
+                "unknown compression format {}; must be one of none, gzip, xz, or zstd",
+                format
+            ))
+        }
+    })
+}
+
+pub struct DebianPackageBuilderValue {
+    /// The paragraph of the `control` file describing the binary package.
+    pub control: ControlParagraph<'static>,
+    /// Files to install, relative to the filesystem root.
+    pub files: FileManifest,
+    /// Maintainer scripts to add to the control archive, keyed by script name.
+    pub maintainer_scripts: BTreeMap<String, String>,
+    /// Absolute paths of files to mark as configuration files.
+    pub conffiles: Vec<String>,
+    pub compression: DebCompression,
+}
+
+impl TypedValue for DebianPackageBuilderValue {
+    type Holder = Mutable<DebianPackageBuilderValue>;
+    const TYPE: &'static str = "DebianPackageBuilder";
+
+    fn values_for_descendant_check_and_freeze(&self) -> Box<dyn Iterator<Item = Value>> {
+        Box::new(std::iter::empty())
+    }
+
+    fn get_attr(&self, attribute: &str) -> ValueResult {
+        Ok(match attribute {
+            "architecture" => Value::from(self.control_field("Architecture")),
+            "package" => Value::from(self.control_field("Package")),
+            "version" => Value::from(self.control_field("Version")),
+            "deb_filename" => Value::from(self.deb_filename()),
+            _ => {
+                return Err(ValueError::OperationNotSupported {
+                    op: UnsupportedOperation::GetAttr(attribute.to_string()),
+                    left: Self::TYPE.to_string(),
+                    right: None,
+                })
+            }
+        })
+    }
+
+    fn has_attr(&self, attribute: &str) -> Result<bool, ValueError> {
+        Ok(matches!(
+            attribute,
+            "architecture" | "deb_filename" | "package" | "version"
+        ))
+    }
+}
+
+impl DebianPackageBuilderValue {
+    fn control_field(&self, name: &str) -> &str {
+        self.control.field_str(name).unwrap_or_default()
+    }
+
+    /// The file name of the `.deb` produced by this builder.
+    ///
+    /// This follows the `<package>_<version>_<architecture>.deb` convention.
+    pub fn deb_filename(&self) -> String {
+        format!(
+            "{}_{}_{}.deb",
+            self.control_field("Package"),
+            self.control_field("Version"),
+            self.control_field("Architecture")
+        )
+    }
+
+    pub fn new_from_args(
+        package: String,
+        version: String,
+        architecture: String,
+        maintainer: String,
+        description: String,
+    ) -> ValueResult {
+        let mut control = ControlParagraph::default();
+        control.set_field_from_string("Package".into(), package.into());
+        control.set_field_from_string("Version".into(), version.into());
+        control.set_field_from_string("Architecture".into(), architecture.into());
+        control.set_field_from_string("Maintainer".into(), maintainer.into());
+        control.set_field_from_string("Description".into(), description.into());
+
+        Ok(Value::new(DebianPackageBuilderValue {
+            control,
+            files: FileManifest::default(),
+            maintainer_scripts: BTreeMap::new(),
+            conffiles: vec![],
+            compression: DebCompression::Gzip,
+        }))
+    }
+
+    pub fn set_control_field(&mut self, name: String, value: String) -> ValueResult {
+        error_context("DebianPackageBuilder.set_control_field()", || {
+            if name.is_empty() || name.contains(':') || name.contains(char::is_whitespace) {
+                return Err(anyhow!("invalid control field name: {}", name));
+            }
+
+            self.control
+                .set_field_from_string(name.into(), value.into());
+
+            Ok(())
+        })?;
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    pub fn set_maintainer_script(&mut self, name: String, content: String) -> ValueResult {
+        error_context("DebianPackageBuilder.set_maintainer_script()", || {
+            if !MAINTAINER_SCRIPTS.contains(&name.as_str()) {
+                return Err(anyhow!(
+                    "unknown maintainer script {}; must be one of {}",
+                    name,
+                    MAINTAINER_SCRIPTS.join(", ")
+                ));
+            }
+
+            self.maintainer_scripts.insert(name, content);
+
+            Ok(())
+        })?;
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    pub fn add_conffile(&mut self, path: String) -> ValueResult {
+        let path = if path.starts_with('/') {
+            path
+        } else {
+            format!("/{}", path)
+        };
+
+        if !self.conffiles.contains(&path) {
+            self.conffiles.push(path);
+        }
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    pub fn add_file(&mut self, content: FileContentValue, path: Value) -> ValueResult {
+        const LABEL: &str = "DebianPackageBuilder.add_file()";
+
+        let path = optional_str_arg("path", &path)?;
+
+        let inner = content.inner(LABEL)?;
+
+        error_context(LABEL, || {
+            let path = if let Some(path) = path {
+                PathBuf::from(path)
+            } else {
+                PathBuf::from(&inner.filename)
+            };
+
+            self.files
+                .add_file_entry(&path, inner.content.clone())
+                .with_context(|| format!("adding {}", path.display()))
+        })?;
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    pub fn add_manifest(&mut self, manifest: FileManifestValue, prefix: Value) -> ValueResult {
+        const LABEL: &str = "DebianPackageBuilder.add_manifest()";
+
+        let prefix = optional_str_arg("prefix", &prefix)?;
+
+        let manifest = manifest.inner(LABEL)?;
+
+        error_context(LABEL, || {
+            for (path, entry) in manifest.iter_entries() {
+                let path = if let Some(prefix) = &prefix {
+                    PathBuf::from(prefix).join(path)
+                } else {
+                    path.clone()
+                };
+
+                self.files
+                    .add_file_entry(&path, entry.clone())
+                    .with_context(|| format!("adding {}", path.display()))?;
+            }
+
+            Ok(())
+        })?;
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    pub fn set_compression(&mut self, format: String, level: Value) -> ValueResult {
+        let level = match level.get_type() {
+            "int" => Some(level.to_int()?),
+            "NoneType" => None,
+            _ => return Err(ValueError::IncorrectParameterType),
+        };
+
+        self.compression = error_context("DebianPackageBuilder.set_compression()", || {
+            parse_compression(&format, level)
+        })?;
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    /// Write the `.deb` file into the given directory, returning its path.
+    fn write_deb(&self, label: &str, dest_dir: &Path) -> Result<PathBuf, ValueError> {
+        error_context(label, || {
+            for conffile in &self.conffiles {
+                if !self.files.has_path(conffile.trim_start_matches('/')) {
+                    return Err(anyhow!(
+                        "conffile {} is not installed by this package",
+                        conffile
+                    ));
+                }
+            }
+
+            let mut control = ControlFile::default();
+            control.add_paragraph(self.control.clone());
+
+            let compression = match &self.compression {
+                DebCompression::Uncompressed => DebCompression::Uncompressed,
+                DebCompression::Gzip => DebCompression::Gzip,
+                DebCompression::Xz(level) => DebCompression::Xz(*level),
+                DebCompression::Zstandard(level) => DebCompression::Zstandard(*level),
+            };
+
+            let mut builder = DebBuilder::new(control).set_compression(compression);
+
+            for (name, content) in &self.maintainer_scripts {
+                builder = builder
+                    .extra_control_tar_file(
+                        name,
+                        FileEntry::new_from_data(content.as_bytes().to_vec(), true),
+                    )
+                    .with_context(|| format!("adding maintainer script {}", name))?;
+            }
+
+            if !self.conffiles.is_empty() {
+                let mut data = self.conffiles.join("\n");
+                data.push('\n');
+
+                builder = builder
+                    .extra_control_tar_file(
+                        "conffiles",
+                        FileEntry::new_from_data(data.into_bytes(), false),
+                    )
+                    .context("adding conffiles")?;
+            }
+
+            for (path, entry) in self.files.iter_entries() {
+                builder = builder
+                    .install_file(path, entry.clone())
+                    .with_context(|| format!("installing {}", path.display()))?;
+            }
+
+            std::fs::create_dir_all(dest_dir)
+                .with_context(|| format!("creating directory {}", dest_dir.display()))?;
+
+            let deb_path = dest_dir.join(self.deb_filename());
+
+            warn!("writing {}", deb_path.display());
+            let mut fh = std::fs::File::create(&deb_path)
+                .with_context(|| format!("opening {} for writing", deb_path.display()))?;
+            builder.write(&mut fh).context("writing .deb file")?;
+
+            Ok(deb_path)
+        })
+    }
+
+    pub fn build(&self, type_values: &TypeValues, target: String) -> ValueResult {
+        const LABEL: &str = "DebianPackageBuilder.build()";
+
+        let context_value = get_context_value(type_values)?;
+        let context = context_value
+            .downcast_ref::<EnvironmentContext>()
+            .ok_or(ValueError::IncorrectParameterType)?;
+
+        let output_path = context.target_build_path(&target);
+
+        let deb_path = self.write_deb(LABEL, &output_path)?;
+
+        Ok(Value::new(ResolvedTargetValue {
+            inner: ResolvedTarget {
+                run_mode: RunMode::Path { path: deb_path },
+                output_path,
+            },
+        }))
+    }
+
+    pub fn write_to_directory(&self, type_values: &TypeValues, path: String) -> ValueResult {
+        const LABEL: &str = "DebianPackageBuilder.write_to_directory()";
+
+        let context_value = get_context_value(type_values)?;
+        let context = context_value
+            .downcast_ref::<EnvironmentContext>()
+            .ok_or(ValueError::IncorrectParameterType)?;
+
+        let dest_dir = context.resolve_path(path);
+
+        let deb_path = self.write_deb(LABEL, &dest_dir)?;
+
+        Ok(Value::from(format!("{}", deb_path.display())))
+    }
+}
+
+starlark_module! { debian_package_builder_module =>
+    #[allow(non_snake_case)]
+    DebianPackageBuilder(
+        package: String,
+        version: String,
+        architecture: String,
+        maintainer: String,
+        description: String
+    ) {
+        DebianPackageBuilderValue::new_from_args(package, version, architecture, maintainer, description)
+    }
+
+    DebianPackageBuilder.set_control_field(this, name: String, value: String) {
+        let mut this = this.downcast_mut::<DebianPackageBuilderValue>().unwrap().unwrap();
+        this.set_control_field(name, value)
+    }
+
+    DebianPackageBuilder.set_maintainer_script(this, name: String, content: String) {
+        let mut this = this.downcast_mut::<DebianPackageBuilderValue>().unwrap().unwrap();
+        this.set_maintainer_script(name, content)
+    }
+
+    DebianPackageBuilder.add_conffile(this, path: String) {
+        let mut this = this.downcast_mut::<DebianPackageBuilderValue>().unwrap().unwrap();
+        this.add_conffile(path)
+    }
+
+    DebianPackageBuilder.add_file(this, content: FileContentValue, path = NoneType::None) {
+        let mut this = this.downcast_mut::<DebianPackageBuilderValue>().unwrap().unwrap();
+        this.add_file(content, path)
+    }
+
+    DebianPackageBuilder.add_manifest(this, manifest: FileManifestValue, prefix = NoneType::None) {
+        let mut this = this.downcast_mut::<DebianPackageBuilderValue>().unwrap().unwrap();
+        this.add_manifest(manifest, prefix)
+    }
+
+    DebianPackageBuilder.set_compression(this, format: String, level = NoneType::None) {
+        let mut this = this.downcast_mut::<DebianPackageBuilderValue>().unwrap().unwrap();
+        this.set_compression(format, level)
+    }
+
+    DebianPackageBuilder.build(env env, this, target: String) {
+        let this = this.downcast_ref::<DebianPackageBuilderValue>().unwrap();
+        this.build(env, target)
+    }
+
+    DebianPackageBuilder.write_to_directory(env env, this, path: String) {
+        let this = this.downcast_ref::<DebianPackageBuilderValue>().unwrap();
+        this.write_to_directory(env, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::starlark::testutil::*,
+        anyhow::Result,
+        debian_packaging::deb::reader::{BinaryPackageEntry, BinaryPackageReader, ControlTarFile},
+        tugger_common::testutil::*,
+    };
+
+    #[test]
+    fn constructor() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        let builder = env.eval(
+            "DebianPackageBuilder('myapp', '0.1', 'amd64', 'Some One <someone@example.com>', 'My app')",
+        )?;
+        assert_eq!(builder.get_type(), DebianPackageBuilderValue::TYPE);
+
+        let builder = builder.downcast_ref::<DebianPackageBuilderValue>().unwrap();
+        assert_eq!(builder.control.field_str("Package"), Some("myapp"));
+        assert_eq!(builder.control.field_str("Version"), Some("0.1"));
+        assert_eq!(builder.control.field_str("Architecture"), Some("amd64"));
+        assert_eq!(builder.deb_filename(), "myapp_0.1_amd64.deb");
+
+        Ok(())
+    }
+
+    #[test]
+    fn attributes() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        env.eval("builder = DebianPackageBuilder('myapp', '0.1', 'arm64', 'me', 'My app')")?;
+
+        assert_eq!(env.eval("builder.package")?.to_string(), "myapp");
+        assert_eq!(env.eval("builder.version")?.to_string(), "0.1");
+        assert_eq!(env.eval("builder.architecture")?.to_string(), "arm64");
+        assert_eq!(
+            env.eval("builder.deb_filename")?.to_string(),
+            "myapp_0.1_arm64.deb"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn set_control_field() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        env.eval("builder = DebianPackageBuilder('myapp', '0.1', 'amd64', 'me', 'My app')")?;
+        env.eval("builder.set_control_field('Depends', 'libc6')")?;
+        assert!(env
+            .eval("builder.set_control_field('bad name', 'x')")
+            .is_err());
+
+        let builder = env.eval("builder")?;
+        let builder = builder.downcast_ref::<DebianPackageBuilderValue>().unwrap();
+        assert_eq!(builder.control.field_str("Depends"), Some("libc6"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn set_maintainer_script() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        env.eval("builder = DebianPackageBuilder('myapp', '0.1', 'amd64', 'me', 'My app')")?;
+        env.eval("builder.set_maintainer_script('postinst', '#!/bin/sh\\nexit 0\\n')")?;
+        assert!(env
+            .eval("builder.set_maintainer_script('postfoo', '')")
+            .is_err());
+
+        let builder = env.eval("builder")?;
+        let builder = builder.downcast_ref::<DebianPackageBuilderValue>().unwrap();
+        assert_eq!(
+            builder.maintainer_scripts.get("postinst"),
+            Some(&"#!/bin/sh\nexit 0\n".to_string())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn conffile_not_installed() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        let temp_dir_s = format!("{}", DEFAULT_TEMP_DIR.path().display()).replace('\\', "/");
+
+        env.eval("builder = DebianPackageBuilder('myapp', '0.1', 'amd64', 'me', 'My app')")?;
+        env.eval("builder.add_conffile('etc/myapp.conf')")?;
+        assert!(env
+            .eval(&format!("builder.write_to_directory('{}')", temp_dir_s))
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_to_directory() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        let dest_dir = DEFAULT_TEMP_DIR.path().join("debian_package_builder");
+        let dest_dir_s = format!("{}", dest_dir.display()).replace('\\', "/");
+
+        env.eval("builder = DebianPackageBuilder('myapp', '0.1', 'amd64', 'me', 'My app')")?;
+        env.eval("builder.add_file(FileContent(filename = 'myapp', content = 'data', executable = True), path = 'usr/bin/myapp')")?;
+        env.eval("m = FileManifest()")?;
+        env.eval("m.add_file(FileContent(filename = 'myapp.conf', content = 'key = value'), directory = 'etc')")?;
+        env.eval("builder.add_manifest(m)")?;
+        env.eval("builder.add_conffile('/etc/myapp.conf')")?;
+        env.eval("builder.set_maintainer_script('postinst', '#!/bin/sh\\n')")?;
+        env.eval("builder.set_compression('zstd', level = 5)")?;
+
+        let path = env.eval(&format!("builder.write_to_directory('{}')", dest_dir_s))?;
+        assert_eq!(path.get_type(), "string");
+
+        let path = PathBuf::from(path.to_string());
+        assert_eq!(path, dest_dir.join("myapp_0.1_amd64.deb"));
+        assert!(path.exists());
+
+        let mut reader = BinaryPackageReader::new(std::io::Cursor::new(std::fs::read(&path)?))?;
+
+        let mut seen_conffiles = false;
+        let mut seen_postinst = false;
+
+        while let Some(entry) = reader.next_entry() {
+            if let BinaryPackageEntry::Control(mut control) = entry? {
+                for entry in control.entries()? {
+                    let mut entry = entry?;
+
+                    match entry.to_control_file()? {
+                        (_, ControlTarFile::Conffiles(data)) => {
+                            assert_eq!(data, b"/etc/myapp.conf\n");
+                            seen_conffiles = true;
+                        }
+                        (header, ControlTarFile::Postinst(_)) => {
+                            assert_eq!(header.mode()?, 0o755);
+                            seen_postinst = true;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        assert!(seen_conffiles);
+        assert!(seen_postinst);
+
+        Ok(())
+    }
+}
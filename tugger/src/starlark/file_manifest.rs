@@ -3,9 +3,12 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use {
-    crate::starlark::{
-        code_signing::{handle_signable_event, SigningAction, SigningContext},
-        file_content::{FileContentValue, FileContentWrapper},
+    crate::{
+        archive::{write_manifest_archive, ArchiveFormat},
+        starlark::{
+            code_signing::{handle_signable_event, SigningAction, SigningContext},
+            file_content::{FileContentValue, FileContentWrapper},
+        },
     },
     anyhow::anyhow,
     log::warn,
@@ -23,11 +26,12 @@ use {
         },
     },
     starlark_dialect_build_targets::{
-        get_context_value, optional_str_arg, EnvironmentContext, ResolvedTarget,
+        get_context_value, optional_int_arg, optional_str_arg, EnvironmentContext, ResolvedTarget,
         ResolvedTargetValue, RunMode,
     },
     std::{
         path::{Path, PathBuf},
+        str::FromStr,
         sync::{Arc, Mutex, MutexGuard},
     },
     tugger_code_signing::SigningDestination,
@@ -367,6 +371,64 @@ impl FileManifestValue {
             Ok(Value::new(NoneType::None))
         }
     }
+
+    /// FileManifest.to_archive(path, format=None, prefix=None, mtime=None)
+    pub fn to_archive(
+        &self,
+        type_values: &TypeValues,
+        path: String,
+        format: Value,
+        prefix: Value,
+        mtime: Value,
+    ) -> ValueResult {
+        const LABEL: &str = "FileManifest.to_archive()";
+
+        let format = optional_str_arg("format", &format)?;
+        let prefix = optional_str_arg("prefix", &prefix)?;
+        let mtime = optional_int_arg("mtime", &mtime)?;
+
+        let raw_context = get_context_value(type_values)?;
+        let context = raw_context
+            .downcast_ref::<EnvironmentContext>()
+            .ok_or(ValueError::IncorrectParameterType)?;
+
+        let inner = self.inner(LABEL)?;
+
+        let dest_path = error_context(LABEL, || {
+            let dest_path = context.build_path().join(path);
+
+            let format = if let Some(format) = format {
+                ArchiveFormat::from_str(&format)?
+            } else {
+                ArchiveFormat::from_filename(&dest_path).ok_or_else(|| {
+                    anyhow!(
+                        "unable to determine archive format from {}; specify `format`",
+                        dest_path.display()
+                    )
+                })?
+            };
+
+            let mtime = match mtime {
+                Some(mtime) if mtime < 0 => {
+                    return Err(anyhow!("mtime must not be negative"));
+                }
+                Some(mtime) => mtime as u64,
+                None => 0,
+            };
+
+            if let Some(parent) = dest_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            warn!("writing {} archive to {}", format, dest_path.display());
+            let fh = std::fs::File::create(&dest_path)?;
+            write_manifest_archive(fh, &inner, format, prefix.as_ref().map(Path::new), mtime)?;
+
+            Ok(dest_path)
+        })?;
+
+        Ok(Value::from(format!("{}", dest_path.display())))
+    }
 }
 
 starlark_module! { file_manifest_module =>
@@ -419,6 +481,18 @@ starlark_module! { file_manifest_module =>
         let mut this = this.downcast_mut::<FileManifestValue>().unwrap().unwrap();
         this.remove(path)
     }
+
+    FileManifest.to_archive(
+        env env,
+        this,
+        path: String,
+        format = NoneType::None,
+        prefix = NoneType::None,
+        mtime = NoneType::None
+    ) {
+        let this = this.downcast_ref::<FileManifestValue>().unwrap();
+        this.to_archive(env, path, format, prefix, mtime)
+    }
 }
 
 #[cfg(test)]
@@ -599,4 +673,38 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn to_archive() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        env.eval("m = FileManifest()")?;
+        env.eval("m.add_file(FileContent(filename = 'file', content = 'foo'))")?;
+
+        for format in ArchiveFormat::all() {
+            let path = env.eval(&format!(
+                "m.to_archive('archives/test.{}', prefix = 'app', mtime = 42)",
+                format
+            ))?;
+            assert_eq!(path.get_type(), "string");
+
+            let path = PathBuf::from(path.to_string());
+            assert!(path.ends_with(format!("archives/test.{}", format)));
+            assert!(path.exists());
+        }
+
+        let path = env.eval("m.to_archive('archives/test', format = 'zip')")?;
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(path.to_string())?)?;
+        assert_eq!(archive.by_index(0)?.name(), "file");
+
+        assert!(env.eval("m.to_archive('archives/test')").is_err());
+        assert!(env
+            .eval("m.to_archive('archives/test.tar.gz', format = 'rar')")
+            .is_err());
+        assert!(env
+            .eval("m.to_archive('archives/test.zip', mtime = -1)")
+            .is_err());
+
+        Ok(())
+    }
 }
@@ -0,0 +1,386 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    crate::starlark::{
+        code_signing::{handle_signable_event, SigningAction, SigningContext},
+        file_content::FileContentValue,
+        file_manifest::FileManifestValue,
+    },
+    anyhow::Context,
+    apple_dmg::DmgBuilder,
+    log::warn,
+    starlark::{
+        environment::TypeValues,
+        eval::call_stack::CallStack,
+        values::{
+            error::{RuntimeError, UnsupportedOperation, ValueError},
+            none::NoneType,
+            {Mutable, TypedValue, Value, ValueResult},
+        },
+        {
+            starlark_fun, starlark_module, starlark_parse_param_type, starlark_signature,
+            starlark_signature_extraction, starlark_signatures,
+        },
+    },
+    starlark_dialect_build_targets::{
+        get_context_value, optional_str_arg, EnvironmentContext, ResolvedTarget,
+        ResolvedTargetValue, RunMode,
+    },
+    std::path::{Path, PathBuf},
+    tugger_code_signing::SigningDestination,
+    tugger_file_manifest::FileEntry,
+};
+
+fn error_context<F, T>(label: &str, f: F) -> Result<T, ValueError>
+where
+    F: FnOnce() -> anyhow::Result<T>,
+{
+    f().map_err(|e| {
+        ValueError::Runtime(RuntimeError {
+            code: "TUGGER_MACOS_DMG_BUILDER",
+            message: format!("{:?}", e),
+            label: label.to_string(),
+        })
+    })
+}
+
+pub struct MacOsDmgBuilderValue {
+    pub inner: DmgBuilder,
+}
+
+impl TypedValue for MacOsDmgBuilderValue {
+    type Holder = Mutable<MacOsDmgBuilderValue>;
+    const TYPE: &'static str = "MacOsDmgBuilder";
+
+    fn values_for_descendant_check_and_freeze(&self) -> Box<dyn Iterator<Item = Value>> {
+        Box::new(std::iter::empty())
+    }
+
+    fn get_attr(&self, attribute: &str) -> ValueResult {
+        Ok(match attribute {
+            "dmg_filename" => Value::from(self.dmg_filename()),
+            "volume_name" => Value::from(self.inner.volume_name()),
+            _ => {
+                return Err(ValueError::OperationNotSupported {
+                    op: UnsupportedOperation::GetAttr(attribute.to_string()),
+                    left: Self::TYPE.to_string(),
+                    right: None,
+                })
+            }
+        })
+    }
+
+    fn has_attr(&self, attribute: &str) -> Result<bool, ValueError> {
+        Ok(matches!(attribute, "dmg_filename" | "volume_name"))
+    }
+}
+
+impl MacOsDmgBuilderValue {
+    /// The file name of the `.dmg` produced by this builder.
+    pub fn dmg_filename(&self) -> String {
+        format!("{}.dmg", self.inner.volume_name())
+    }
+
+    pub fn new_from_args(volume_name: String) -> ValueResult {
+        Ok(Value::new(MacOsDmgBuilderValue {
+            inner: DmgBuilder::new(volume_name),
+        }))
+    }
+
+    pub fn add_file(&mut self, content: FileContentValue, path: Value) -> ValueResult {
+        const LABEL: &str = "MacOsDmgBuilder.add_file()";
+
+        let path = optional_str_arg("path", &path)?;
+
+        let inner = content.inner(LABEL)?;
+
+        error_context(LABEL, || {
+            let path = if let Some(path) = path {
+                PathBuf::from(path)
+            } else {
+                PathBuf::from(&inner.filename)
+            };
+
+            self.inner
+                .add_file(&path, inner.content.clone())
+                .with_context(|| format!("adding {}", path.display()))
+        })?;
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    pub fn add_manifest(&mut self, manifest: FileManifestValue, prefix: Value) -> ValueResult {
+        const LABEL: &str = "MacOsDmgBuilder.add_manifest()";
+
+        let prefix = optional_str_arg("prefix", &prefix)?;
+
+        let manifest = manifest.inner(LABEL)?;
+
+        error_context(LABEL, || {
+            for (path, entry) in manifest.iter_entries() {
+                let path = if let Some(prefix) = &prefix {
+                    PathBuf::from(prefix).join(path)
+                } else {
+                    path.clone()
+                };
+
+                self.inner
+                    .add_file(&path, entry)
+                    .with_context(|| format!("adding {}", path.display()))?;
+            }
+
+            Ok(())
+        })?;
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    pub fn add_directory(
+        &mut self,
+        type_values: &TypeValues,
+        path: String,
+        prefix: Value,
+    ) -> ValueResult {
+        const LABEL: &str = "MacOsDmgBuilder.add_directory()";
+
+        let prefix = optional_str_arg("prefix", &prefix)?;
+
+        let context_value = get_context_value(type_values)?;
+        let context = context_value
+            .downcast_ref::<EnvironmentContext>()
+            .ok_or(ValueError::IncorrectParameterType)?;
+
+        let source_dir = context.resolve_path(path);
+
+        error_context(LABEL, || {
+            self.inner
+                .add_directory_contents(&source_dir, prefix.unwrap_or_default())
+                .with_context(|| format!("adding directory {}", source_dir.display()))
+        })?;
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    pub fn add_symlink(&mut self, path: String, target: String) -> ValueResult {
+        const LABEL: &str = "MacOsDmgBuilder.add_symlink()";
+
+        error_context(LABEL, || {
+            let mut entry = FileEntry::new_from_data(vec![], false);
+            entry.set_link_target(PathBuf::from(&target));
+
+            self.inner
+                .add_file(&path, entry)
+                .with_context(|| format!("adding symlink {}", path))
+        })?;
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    /// Write the `.dmg` file into the given directory and sign it, returning its path.
+    fn write_dmg(
+        &self,
+        type_values: &TypeValues,
+        call_stack: &mut CallStack,
+        label: &'static str,
+        dest_dir: &Path,
+    ) -> Result<PathBuf, ValueError> {
+        let dmg_path = error_context(label, || {
+            std::fs::create_dir_all(dest_dir)
+                .with_context(|| format!("creating directory {}", dest_dir.display()))?;
+
+            let dmg_path = dest_dir.join(self.dmg_filename());
+
+            warn!("writing {}", dmg_path.display());
+            let fh = std::fs::File::create(&dmg_path)
+                .with_context(|| format!("opening {} for writing", dmg_path.display()))?;
+            self.inner.write(fh).context("writing .dmg file")?;
+
+            Ok(dmg_path)
+        })?;
+
+        let candidate = dmg_path.as_path().into();
+        let mut context = SigningContext::new(
+            label,
+            SigningAction::MacOsDmgCreation,
+            self.dmg_filename(),
+            &candidate,
+        );
+        context.set_path(&dmg_path);
+        context.set_signing_destination(SigningDestination::File(dmg_path.clone()));
+
+        handle_signable_event(type_values, call_stack, context)?;
+
+        Ok(dmg_path)
+    }
+
+    pub fn build(
+        &self,
+        type_values: &TypeValues,
+        call_stack: &mut CallStack,
+        target: String,
+    ) -> ValueResult {
+        const LABEL: &str = "MacOsDmgBuilder.build()";
+
+        let context_value = get_context_value(type_values)?;
+        let context = context_value
+            .downcast_ref::<EnvironmentContext>()
+            .ok_or(ValueError::IncorrectParameterType)?;
+
+        let output_path = context.target_build_path(&target);
+
+        let dmg_path = self.write_dmg(type_values, call_stack, LABEL, &output_path)?;
+
+        Ok(Value::new(ResolvedTargetValue {
+            inner: ResolvedTarget {
+                run_mode: RunMode::Path { path: dmg_path },
+                output_path,
+            },
+        }))
+    }
+
+    pub fn write_to_directory(
+        &self,
+        type_values: &TypeValues,
+        call_stack: &mut CallStack,
+        path: String,
+    ) -> ValueResult {
+        const LABEL: &str = "MacOsDmgBuilder.write_to_directory()";
+
+        let context_value = get_context_value(type_values)?;
+        let context = context_value
+            .downcast_ref::<EnvironmentContext>()
+            .ok_or(ValueError::IncorrectParameterType)?;
+
+        let dest_dir = context.resolve_path(path);
+
+        let dmg_path = self.write_dmg(type_values, call_stack, LABEL, &dest_dir)?;
+
+        Ok(Value::from(format!("{}", dmg_path.display())))
+    }
+}
+
+starlark_module! { macos_dmg_builder_module =>
+    #[allow(non_snake_case)]
+    MacOsDmgBuilder(volume_name: String) {
+        MacOsDmgBuilderValue::new_from_args(volume_name)
+    }
+
+    MacOsDmgBuilder.add_file(this, content: FileContentValue, path = NoneType::None) {
+        let mut this = this.downcast_mut::<MacOsDmgBuilderValue>().unwrap().unwrap();
+        this.add_file(content, path)
+    }
+
+    MacOsDmgBuilder.add_manifest(this, manifest: FileManifestValue, prefix = NoneType::None) {
+        let mut this = this.downcast_mut::<MacOsDmgBuilderValue>().unwrap().unwrap();
+        this.add_manifest(manifest, prefix)
+    }
+
+    MacOsDmgBuilder.add_directory(env env, this, path: String, prefix = NoneType::None) {
+        let mut this = this.downcast_mut::<MacOsDmgBuilderValue>().unwrap().unwrap();
+        this.add_directory(env, path, prefix)
+    }
+
+    MacOsDmgBuilder.add_symlink(this, path: String, target: String) {
+        let mut this = this.downcast_mut::<MacOsDmgBuilderValue>().unwrap().unwrap();
+        this.add_symlink(path, target)
+    }
+
+    MacOsDmgBuilder.build(env env, call_stack cs, this, target: String) {
+        let this = this.downcast_ref::<MacOsDmgBuilderValue>().unwrap();
+        this.build(env, cs, target)
+    }
+
+    MacOsDmgBuilder.write_to_directory(env env, call_stack cs, this, path: String) {
+        let this = this.downcast_ref::<MacOsDmgBuilderValue>().unwrap();
+        this.write_to_directory(env, cs, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::starlark::testutil::*,
+        anyhow::Result,
+        apple_dmg::{KolyTrailer, UdifReader},
+        tugger_common::testutil::*,
+    };
+
+    #[test]
+    fn constructor() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        let builder = env.eval("MacOsDmgBuilder('My App')")?;
+        assert_eq!(builder.get_type(), MacOsDmgBuilderValue::TYPE);
+
+        assert_eq!(
+            env.eval("MacOsDmgBuilder('My App').volume_name")?
+                .to_string(),
+            "My App"
+        );
+        assert_eq!(
+            env.eval("MacOsDmgBuilder('My App').dmg_filename")?
+                .to_string(),
+            "My App.dmg"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn add_file() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        env.eval("builder = MacOsDmgBuilder('myapp')")?;
+        env.eval("builder.add_file(FileContent(filename = 'README', content = 'readme'))")?;
+        assert!(env
+            .eval("builder.add_file(FileContent(filename = 'README', content = 'readme'))")
+            .is_err());
+        env.eval("builder.add_symlink('Applications', '/Applications')")?;
+        assert!(env
+            .eval("builder.add_symlink('Applications/foo', '/tmp')")
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_to_directory() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        let source_dir = DEFAULT_TEMP_DIR.path().join("macos_dmg_builder_source");
+        std::fs::create_dir_all(source_dir.join("Contents/MacOS"))?;
+        std::fs::write(source_dir.join("Contents/MacOS/myapp"), b"myapp")?;
+        let source_dir_s = format!("{}", source_dir.display()).replace('\\', "/");
+
+        let dest_dir = DEFAULT_TEMP_DIR.path().join("macos_dmg_builder");
+        let dest_dir_s = format!("{}", dest_dir.display()).replace('\\', "/");
+
+        env.eval("builder = MacOsDmgBuilder('myapp')")?;
+        env.eval(&format!(
+            "builder.add_directory('{}', prefix = 'MyApp.app')",
+            source_dir_s
+        ))?;
+        env.eval("m = FileManifest()")?;
+        env.eval("m.add_file(FileContent(filename = 'README', content = 'readme'))")?;
+        env.eval("builder.add_manifest(m, prefix = 'docs')")?;
+        env.eval("builder.add_symlink('Applications', '/Applications')")?;
+
+        let path = env.eval(&format!("builder.write_to_directory('{}')", dest_dir_s))?;
+        assert_eq!(path.get_type(), "string");
+
+        let path = PathBuf::from(path.to_string());
+        assert_eq!(path, dest_dir.join("myapp.dmg"));
+        assert!(path.exists());
+
+        let mut fh = std::fs::File::open(&path)?;
+        KolyTrailer::read_from(&mut fh)?;
+        let reader = UdifReader::new(&mut fh)?;
+        let image = reader.read_image(&mut fh)?;
+        assert_eq!(&image[1024..1026], b"HX");
+
+        Ok(())
+    }
+}
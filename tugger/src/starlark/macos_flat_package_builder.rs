@@ -0,0 +1,484 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    crate::starlark::{
+        code_signing::{handle_signable_event, SigningAction, SigningContext},
+        file_content::FileContentValue,
+        file_manifest::FileManifestValue,
+    },
+    anyhow::{anyhow, Context},
+    apple_flat_package::{ComponentPackageBuilder, ProductPackageBuilder},
+    log::warn,
+    starlark::{
+        environment::TypeValues,
+        eval::call_stack::CallStack,
+        values::{
+            error::{RuntimeError, UnsupportedOperation, ValueError},
+            none::NoneType,
+            {Mutable, TypedValue, Value, ValueResult},
+        },
+        {
+            starlark_fun, starlark_module, starlark_parse_param_type, starlark_signature,
+            starlark_signature_extraction, starlark_signatures,
+        },
+    },
+    starlark_dialect_build_targets::{
+        get_context_value, optional_str_arg, EnvironmentContext, ResolvedTarget,
+        ResolvedTargetValue, RunMode,
+    },
+    std::{
+        collections::BTreeMap,
+        path::{Path, PathBuf},
+    },
+    tugger_code_signing::SigningDestination,
+    tugger_file_manifest::FileManifest,
+};
+
+/// Names of scripts that can be registered with a package.
+const SCRIPTS: &[&str] = &["postinstall", "preinstall"];
+
+fn error_context<F, T>(label: &str, f: F) -> Result<T, ValueError>
+where
+    F: FnOnce() -> anyhow::Result<T>,
+{
+    f().map_err(|e| {
+        ValueError::Runtime(RuntimeError {
+            code: "TUGGER_MACOS_FLAT_PACKAGE_BUILDER",
+            message: format!("{:?}", e),
+            label: label.to_string(),
+        })
+    })
+}
+
+pub struct MacOsFlatPackageBuilderValue {
+    /// Uniform type identifier of the installed component.
+    pub identifier: String,
+    pub version: String,
+    /// Title displayed by the installer.
+    pub title: String,
+    /// Absolute directory that files are installed relative to.
+    pub install_location: String,
+    /// Machine architectures the installer is restricted to.
+    pub host_architectures: Vec<String>,
+    /// Script content keyed by script name.
+    pub scripts: BTreeMap<String, String>,
+    /// Files to install, relative to the install location.
+    pub files: FileManifest,
+}
+
+impl TypedValue for MacOsFlatPackageBuilderValue {
+    type Holder = Mutable<MacOsFlatPackageBuilderValue>;
+    const TYPE: &'static str = "MacOsFlatPackageBuilder";
+
+    fn values_for_descendant_check_and_freeze(&self) -> Box<dyn Iterator<Item = Value>> {
+        Box::new(std::iter::empty())
+    }
+
+    fn get_attr(&self, attribute: &str) -> ValueResult {
+        Ok(match attribute {
+            "identifier" => Value::from(self.identifier.as_str()),
+            "install_location" => Value::from(self.install_location.as_str()),
+            "pkg_filename" => Value::from(self.pkg_filename()),
+            "title" => Value::from(self.title.as_str()),
+            "version" => Value::from(self.version.as_str()),
+            _ => {
+                return Err(ValueError::OperationNotSupported {
+                    op: UnsupportedOperation::GetAttr(attribute.to_string()),
+                    left: Self::TYPE.to_string(),
+                    right: None,
+                })
+            }
+        })
+    }
+
+    fn has_attr(&self, attribute: &str) -> Result<bool, ValueError> {
+        Ok(matches!(
+            attribute,
+            "identifier" | "install_location" | "pkg_filename" | "title" | "version"
+        ))
+    }
+
+    fn set_attr(&mut self, attribute: &str, value: Value) -> Result<(), ValueError> {
+        match attribute {
+            "install_location" => {
+                let location = value.to_string();
+
+                if !location.starts_with('/') {
+                    return Err(ValueError::from(RuntimeError {
+                        code: "TUGGER_MACOS_FLAT_PACKAGE_BUILDER",
+                        message: format!("install_location must be absolute: {}", location),
+                        label: "MacOsFlatPackageBuilder.install_location".to_string(),
+                    }));
+                }
+
+                self.install_location = location;
+            }
+            "title" => {
+                self.title = value.to_string();
+            }
+            attr => {
+                return Err(ValueError::OperationNotSupported {
+                    op: UnsupportedOperation::SetAttr(attr.to_string()),
+                    left: Self::TYPE.to_string(),
+                    right: None,
+                })
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl MacOsFlatPackageBuilderValue {
+    /// The file name of the `.pkg` produced by this builder.
+    ///
+    /// This follows the `<identifier>-<version>.pkg` convention.
+    pub fn pkg_filename(&self) -> String {
+        format!("{}-{}.pkg", self.identifier, self.version)
+    }
+
+    pub fn new_from_args(identifier: String, version: String, title: Value) -> ValueResult {
+        let title = optional_str_arg("title", &title)?.unwrap_or_else(|| identifier.clone());
+
+        Ok(Value::new(MacOsFlatPackageBuilderValue {
+            identifier,
+            version,
+            title,
+            install_location: "/".to_string(),
+            host_architectures: vec![],
+            scripts: BTreeMap::new(),
+            files: FileManifest::default(),
+        }))
+    }
+
+    pub fn add_host_architecture(&mut self, arch: String) -> ValueResult {
+        if !self.host_architectures.contains(&arch) {
+            self.host_architectures.push(arch);
+        }
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    pub fn set_script(&mut self, name: String, content: String) -> ValueResult {
+        error_context("MacOsFlatPackageBuilder.set_script()", || {
+            if !SCRIPTS.contains(&name.as_str()) {
+                return Err(anyhow!(
+                    "unknown script {}; must be one of {}",
+                    name,
+                    SCRIPTS.join(", ")
+                ));
+            }
+
+            self.scripts.insert(name, content);
+
+            Ok(())
+        })?;
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    pub fn add_file(&mut self, content: FileContentValue, path: Value) -> ValueResult {
+        const LABEL: &str = "MacOsFlatPackageBuilder.add_file()";
+
+        let path = optional_str_arg("path", &path)?;
+
+        let inner = content.inner(LABEL)?;
+
+        error_context(LABEL, || {
+            let path = if let Some(path) = path {
+                PathBuf::from(path)
+            } else {
+                PathBuf::from(&inner.filename)
+            };
+
+            self.files
+                .add_file_entry(&path, inner.content.clone())
+                .with_context(|| format!("adding {}", path.display()))
+        })?;
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    pub fn add_manifest(&mut self, manifest: FileManifestValue, prefix: Value) -> ValueResult {
+        const LABEL: &str = "MacOsFlatPackageBuilder.add_manifest()";
+
+        let prefix = optional_str_arg("prefix", &prefix)?;
+
+        let manifest = manifest.inner(LABEL)?;
+
+        error_context(LABEL, || {
+            for (path, entry) in manifest.iter_entries() {
+                let path = if let Some(prefix) = &prefix {
+                    PathBuf::from(prefix).join(path)
+                } else {
+                    path.clone()
+                };
+
+                self.files
+                    .add_file_entry(&path, entry.clone())
+                    .with_context(|| format!("adding {}", path.display()))?;
+            }
+
+            Ok(())
+        })?;
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    /// Write the `.pkg` file into the given directory and sign it, returning its path.
+    fn write_pkg(
+        &self,
+        type_values: &TypeValues,
+        call_stack: &mut CallStack,
+        label: &'static str,
+        dest_dir: &Path,
+    ) -> Result<PathBuf, ValueError> {
+        let pkg_path = error_context(label, || {
+            let mut component = ComponentPackageBuilder::new(&self.identifier, &self.version);
+            component.install_location(&self.install_location);
+            component
+                .add_manifest(&self.files)
+                .context("adding files to component")?;
+
+            for (name, content) in &self.scripts {
+                match name.as_str() {
+                    "preinstall" => component.preinstall_script(content.as_bytes()),
+                    "postinstall" => component.postinstall_script(content.as_bytes()),
+                    _ => panic!("script name should have been validated"),
+                }
+                .with_context(|| format!("adding {} script", name))?;
+            }
+
+            let mut product = ProductPackageBuilder::new(&self.title);
+            for arch in &self.host_architectures {
+                product.add_host_architecture(arch);
+            }
+            product
+                .add_component(component)
+                .context("adding component to product")?;
+
+            std::fs::create_dir_all(dest_dir)
+                .with_context(|| format!("creating directory {}", dest_dir.display()))?;
+
+            let pkg_path = dest_dir.join(self.pkg_filename());
+
+            warn!("writing {}", pkg_path.display());
+            let fh = std::fs::File::create(&pkg_path)
+                .with_context(|| format!("opening {} for writing", pkg_path.display()))?;
+            product.write(fh).context("writing .pkg file")?;
+
+            Ok(pkg_path)
+        })?;
+
+        let candidate = pkg_path.as_path().into();
+        let mut context = SigningContext::new(
+            label,
+            SigningAction::MacOsFlatPackageCreation,
+            self.pkg_filename(),
+            &candidate,
+        );
+        context.set_path(&pkg_path);
+        context.set_signing_destination(SigningDestination::File(pkg_path.clone()));
+
+        handle_signable_event(type_values, call_stack, context)?;
+
+        Ok(pkg_path)
+    }
+
+    pub fn build(
+        &self,
+        type_values: &TypeValues,
+        call_stack: &mut CallStack,
+        target: String,
+    ) -> ValueResult {
+        const LABEL: &str = "MacOsFlatPackageBuilder.build()";
+
+        let context_value = get_context_value(type_values)?;
+        let context = context_value
+            .downcast_ref::<EnvironmentContext>()
+            .ok_or(ValueError::IncorrectParameterType)?;
+
+        let output_path = context.target_build_path(&target);
+
+        let pkg_path = self.write_pkg(type_values, call_stack, LABEL, &output_path)?;
+
+        Ok(Value::new(ResolvedTargetValue {
+            inner: ResolvedTarget {
+                run_mode: RunMode::Path { path: pkg_path },
+                output_path,
+            },
+        }))
+    }
+
+    pub fn write_to_directory(
+        &self,
+        type_values: &TypeValues,
+        call_stack: &mut CallStack,
+        path: String,
+    ) -> ValueResult {
+        const LABEL: &str = "MacOsFlatPackageBuilder.write_to_directory()";
+
+        let context_value = get_context_value(type_values)?;
+        let context = context_value
+            .downcast_ref::<EnvironmentContext>()
+            .ok_or(ValueError::IncorrectParameterType)?;
+
+        let dest_dir = context.resolve_path(path);
+
+        let pkg_path = self.write_pkg(type_values, call_stack, LABEL, &dest_dir)?;
+
+        Ok(Value::from(format!("{}", pkg_path.display())))
+    }
+}
+
+starlark_module! { macos_flat_package_builder_module =>
+    #[allow(non_snake_case)]
+    MacOsFlatPackageBuilder(identifier: String, version: String, title = NoneType::None) {
+        MacOsFlatPackageBuilderValue::new_from_args(identifier, version, title)
+    }
+
+    MacOsFlatPackageBuilder.add_host_architecture(this, arch: String) {
+        let mut this = this.downcast_mut::<MacOsFlatPackageBuilderValue>().unwrap().unwrap();
+        this.add_host_architecture(arch)
+    }
+
+    MacOsFlatPackageBuilder.set_script(this, name: String, content: String) {
+        let mut this = this.downcast_mut::<MacOsFlatPackageBuilderValue>().unwrap().unwrap();
+        this.set_script(name, content)
+    }
+
+    MacOsFlatPackageBuilder.add_file(this, content: FileContentValue, path = NoneType::None) {
+        let mut this = this.downcast_mut::<MacOsFlatPackageBuilderValue>().unwrap().unwrap();
+        this.add_file(content, path)
+    }
+
+    MacOsFlatPackageBuilder.add_manifest(this, manifest: FileManifestValue, prefix = NoneType::None) {
+        let mut this = this.downcast_mut::<MacOsFlatPackageBuilderValue>().unwrap().unwrap();
+        this.add_manifest(manifest, prefix)
+    }
+
+    MacOsFlatPackageBuilder.build(env env, call_stack cs, this, target: String) {
+        let this = this.downcast_ref::<MacOsFlatPackageBuilderValue>().unwrap();
+        this.build(env, cs, target)
+    }
+
+    MacOsFlatPackageBuilder.write_to_directory(env env, call_stack cs, this, path: String) {
+        let this = this.downcast_ref::<MacOsFlatPackageBuilderValue>().unwrap();
+        this.write_to_directory(env, cs, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::starlark::testutil::*,
+        anyhow::Result,
+        apple_flat_package::{PkgFlavor, PkgReader},
+        tugger_common::testutil::*,
+    };
+
+    #[test]
+    fn constructor() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        let builder = env.eval("MacOsFlatPackageBuilder('com.example.myapp', '0.1')")?;
+        assert_eq!(builder.get_type(), MacOsFlatPackageBuilderValue::TYPE);
+
+        let builder = builder
+            .downcast_ref::<MacOsFlatPackageBuilderValue>()
+            .unwrap();
+        assert_eq!(builder.identifier, "com.example.myapp");
+        assert_eq!(builder.version, "0.1");
+        assert_eq!(builder.title, "com.example.myapp");
+        assert_eq!(builder.install_location, "/");
+        assert_eq!(builder.pkg_filename(), "com.example.myapp-0.1.pkg");
+
+        Ok(())
+    }
+
+    #[test]
+    fn attributes() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        env.eval(
+            "builder = MacOsFlatPackageBuilder('com.example.myapp', '0.1', title = 'My App')",
+        )?;
+
+        assert_eq!(env.eval("builder.title")?.to_string(), "My App");
+        env.eval("builder.title = 'Other'")?;
+        assert_eq!(env.eval("builder.title")?.to_string(), "Other");
+
+        env.eval("builder.install_location = '/usr/local'")?;
+        assert_eq!(
+            env.eval("builder.install_location")?.to_string(),
+            "/usr/local"
+        );
+        assert!(env.eval("builder.install_location = 'relative'").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn set_script() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        env.eval("builder = MacOsFlatPackageBuilder('com.example.myapp', '0.1')")?;
+        env.eval("builder.set_script('postinstall', '#!/bin/sh\\nexit 0\\n')")?;
+        assert!(env.eval("builder.set_script('postfoo', '')").is_err());
+
+        let builder = env.eval("builder")?;
+        let builder = builder
+            .downcast_ref::<MacOsFlatPackageBuilderValue>()
+            .unwrap();
+        assert_eq!(
+            builder.scripts.get("postinstall"),
+            Some(&"#!/bin/sh\nexit 0\n".to_string())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_to_directory() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        let dest_dir = DEFAULT_TEMP_DIR.path().join("macos_flat_package_builder");
+        let dest_dir_s = format!("{}", dest_dir.display()).replace('\\', "/");
+
+        env.eval(
+            "builder = MacOsFlatPackageBuilder('com.example.myapp', '0.1', title = 'My App')",
+        )?;
+        env.eval("builder.install_location = '/usr/local'")?;
+        env.eval("builder.add_host_architecture('x86_64')")?;
+        env.eval("builder.add_file(FileContent(filename = 'myapp', content = 'data', executable = True), path = 'bin/myapp')")?;
+        env.eval("m = FileManifest()")?;
+        env.eval(
+            "m.add_file(FileContent(filename = 'lib.txt', content = 'lib'), directory = 'lib')",
+        )?;
+        env.eval("builder.add_manifest(m, prefix = 'myapp')")?;
+        env.eval("builder.set_script('postinstall', '#!/bin/sh\\n')")?;
+
+        let path = env.eval(&format!("builder.write_to_directory('{}')", dest_dir_s))?;
+        assert_eq!(path.get_type(), "string");
+
+        let path = PathBuf::from(path.to_string());
+        assert_eq!(path, dest_dir.join("com.example.myapp-0.1.pkg"));
+        assert!(path.exists());
+
+        let mut reader = PkgReader::new(std::fs::File::open(&path)?)?;
+        assert_eq!(reader.flavor(), PkgFlavor::Product);
+
+        let components = reader.component_packages()?;
+        assert_eq!(components.len(), 1);
+
+        let info = components[0].package_info().unwrap();
+        assert_eq!(info.identifier, "com.example.myapp");
+        assert_eq!(info.install_location, Some("/usr/local".to_string()));
+        assert!(components[0].scripts_reader()?.is_some());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,551 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    crate::starlark::{file_content::FileContentValue, file_manifest::FileManifestValue},
+    anyhow::{anyhow, Context},
+    starlark::{
+        environment::TypeValues,
+        values::{
+            error::{RuntimeError, UnsupportedOperation, ValueError},
+            none::NoneType,
+            {Mutable, TypedValue, Value, ValueResult},
+        },
+        {
+            starlark_fun, starlark_module, starlark_parse_param_type, starlark_signature,
+            starlark_signature_extraction, starlark_signatures,
+        },
+    },
+    starlark_dialect_build_targets::{
+        get_context_value, optional_str_arg, EnvironmentContext, ResolvedTarget,
+        ResolvedTargetValue, RunMode,
+    },
+    std::{
+        collections::BTreeMap,
+        path::{Path, PathBuf},
+        str::FromStr,
+    },
+    tugger_file_manifest::FileManifest,
+    tugger_rpm::DependencySpec,
+};
+
+#[cfg(target_family = "unix")]
+use {log::warn, tugger_rpm::RpmBuilder};
+
+/// Names of scriptlets that can be registered with a package.
+const SCRIPTLETS: &[&str] = &["post", "postun", "pre", "preun"];
+
+fn error_context<F, T>(label: &str, f: F) -> Result<T, ValueError>
+where
+    F: FnOnce() -> anyhow::Result<T>,
+{
+    f().map_err(|e| {
+        ValueError::Runtime(RuntimeError {
+            code: "TUGGER_RPM_BUILDER",
+            message: format!("{:?}", e),
+            label: label.to_string(),
+        })
+    })
+}
+
+pub struct RpmBuilderValue {
+    pub name: String,
+    pub version: String,
+    pub release: String,
+    pub license: String,
+    pub arch: String,
+    pub description: String,
+    pub requires: Vec<DependencySpec>,
+    pub provides: Vec<DependencySpec>,
+    /// Scriptlet content keyed by scriptlet name.
+    pub scriptlets: BTreeMap<String, String>,
+    /// Files to install, relative to the filesystem root.
+    pub files: FileManifest,
+}
+
+impl TypedValue for RpmBuilderValue {
+    type Holder = Mutable<RpmBuilderValue>;
+    const TYPE: &'static str = "RpmBuilder";
+
+    fn values_for_descendant_check_and_freeze(&self) -> Box<dyn Iterator<Item = Value>> {
+        Box::new(std::iter::empty())
+    }
+
+    fn get_attr(&self, attribute: &str) -> ValueResult {
+        Ok(match attribute {
+            "arch" => Value::from(self.arch.as_str()),
+            "name" => Value::from(self.name.as_str()),
+            "release" => Value::from(self.release.as_str()),
+            "rpm_filename" => Value::from(self.rpm_filename()),
+            "version" => Value::from(self.version.as_str()),
+            _ => {
+                return Err(ValueError::OperationNotSupported {
+                    op: UnsupportedOperation::GetAttr(attribute.to_string()),
+                    left: Self::TYPE.to_string(),
+                    right: None,
+                })
+            }
+        })
+    }
+
+    fn has_attr(&self, attribute: &str) -> Result<bool, ValueError> {
+        Ok(matches!(
+            attribute,
+            "arch" | "name" | "release" | "rpm_filename" | "version"
+        ))
+    }
+
+    fn set_attr(&mut self, attribute: &str, value: Value) -> Result<(), ValueError> {
+        match attribute {
+            "release" => {
+                self.release = value.to_string();
+            }
+            attr => {
+                return Err(ValueError::OperationNotSupported {
+                    op: UnsupportedOperation::SetAttr(attr.to_string()),
+                    left: Self::TYPE.to_string(),
+                    right: None,
+                })
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl RpmBuilderValue {
+    /// The file name of the `.rpm` produced by this builder.
+    ///
+    /// This follows the `<name>-<version>-<release>.<arch>.rpm` convention.
+    pub fn rpm_filename(&self) -> String {
+        format!(
+            "{}-{}-{}.{}.rpm",
+            self.name, self.version, self.release, self.arch
+        )
+    }
+
+    pub fn new_from_args(
+        name: String,
+        version: String,
+        license: String,
+        arch: String,
+        description: String,
+    ) -> ValueResult {
+        Ok(Value::new(RpmBuilderValue {
+            name,
+            version,
+            release: "1".to_string(),
+            license,
+            arch,
+            description,
+            requires: vec![],
+            provides: vec![],
+            scriptlets: BTreeMap::new(),
+            files: FileManifest::default(),
+        }))
+    }
+
+    pub fn add_requires(&mut self, dependency: String) -> ValueResult {
+        let dependency = error_context("RpmBuilder.add_requires()", || {
+            DependencySpec::from_str(&dependency)
+        })?;
+
+        self.requires.push(dependency);
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    pub fn add_provides(&mut self, dependency: String) -> ValueResult {
+        let dependency = error_context("RpmBuilder.add_provides()", || {
+            DependencySpec::from_str(&dependency)
+        })?;
+
+        self.provides.push(dependency);
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    pub fn set_scriptlet(&mut self, name: String, content: String) -> ValueResult {
+        error_context("RpmBuilder.set_scriptlet()", || {
+            if !SCRIPTLETS.contains(&name.as_str()) {
+                return Err(anyhow!(
+                    "unknown scriptlet {}; must be one of {}",
+                    name,
+                    SCRIPTLETS.join(", ")
+                ));
+            }
+
+            self.scriptlets.insert(name, content);
+
+            Ok(())
+        })?;
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    pub fn add_file(&mut self, content: FileContentValue, path: Value) -> ValueResult {
+        const LABEL: &str = "RpmBuilder.add_file()";
+
+        let path = optional_str_arg("path", &path)?;
+
+        let inner = content.inner(LABEL)?;
+
+        error_context(LABEL, || {
+            let path = if let Some(path) = path {
+                PathBuf::from(path.trim_start_matches('/'))
+            } else {
+                PathBuf::from(&inner.filename)
+            };
+
+            self.files
+                .add_file_entry(&path, inner.content.clone())
+                .with_context(|| format!("adding {}", path.display()))
+        })?;
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    pub fn add_manifest(&mut self, manifest: FileManifestValue, prefix: Value) -> ValueResult {
+        const LABEL: &str = "RpmBuilder.add_manifest()";
+
+        let prefix = optional_str_arg("prefix", &prefix)?;
+
+        let manifest = manifest.inner(LABEL)?;
+
+        error_context(LABEL, || {
+            for (path, entry) in manifest.iter_entries() {
+                let path = if let Some(prefix) = &prefix {
+                    PathBuf::from(prefix.trim_start_matches('/')).join(path)
+                } else {
+                    path.clone()
+                };
+
+                self.files
+                    .add_file_entry(&path, entry.clone())
+                    .with_context(|| format!("adding {}", path.display()))?;
+            }
+
+            Ok(())
+        })?;
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    /// Write the `.rpm` file into the given directory, returning its path.
+    #[cfg(not(target_family = "unix"))]
+    fn write_rpm(&self, label: &str, _dest_dir: &Path) -> Result<PathBuf, ValueError> {
+        error_context(label, || {
+            Err(anyhow!("building RPMs is only supported on Unix platforms"))
+        })
+    }
+
+    /// Write the `.rpm` file into the given directory, returning its path.
+    #[cfg(target_family = "unix")]
+    fn write_rpm(&self, label: &str, dest_dir: &Path) -> Result<PathBuf, ValueError> {
+        error_context(label, || {
+            let build_dir = tempfile::Builder::new()
+                .prefix("tugger-rpm-")
+                .tempdir()
+                .context("creating temporary directory")?;
+
+            let mut builder = RpmBuilder::new(
+                build_dir.path(),
+                &self.name,
+                &self.version,
+                &self.license,
+                &self.arch,
+                &self.description,
+            )
+            .release(&self.release);
+
+            for dependency in &self.requires {
+                builder = builder.requires(dependency);
+            }
+            for dependency in &self.provides {
+                builder = builder.provides(dependency);
+            }
+
+            for (name, content) in &self.scriptlets {
+                builder = match name.as_str() {
+                    "pre" => builder.pre_install_script(content),
+                    "post" => builder.post_install_script(content),
+                    "preun" => builder.pre_uninstall_script(content),
+                    "postun" => builder.post_uninstall_script(content),
+                    _ => return Err(anyhow!("unknown scriptlet {}", name)),
+                };
+            }
+
+            builder = builder.add_manifest(&self.files)?;
+
+            let rpm_path = dest_dir.join(self.rpm_filename());
+
+            warn!("writing {}", rpm_path.display());
+            builder
+                .build_to_path(&rpm_path)
+                .with_context(|| format!("writing {}", rpm_path.display()))?;
+
+            Ok(rpm_path)
+        })
+    }
+
+    pub fn build(&self, type_values: &TypeValues, target: String) -> ValueResult {
+        const LABEL: &str = "RpmBuilder.build()";
+
+        let context_value = get_context_value(type_values)?;
+        let context = context_value
+            .downcast_ref::<EnvironmentContext>()
+            .ok_or(ValueError::IncorrectParameterType)?;
+
+        let output_path = context.target_build_path(&target);
+
+        let rpm_path = self.write_rpm(LABEL, &output_path)?;
+
+        Ok(Value::new(ResolvedTargetValue {
+            inner: ResolvedTarget {
+                run_mode: RunMode::Path { path: rpm_path },
+                output_path,
+            },
+        }))
+    }
+
+    pub fn write_to_directory(&self, type_values: &TypeValues, path: String) -> ValueResult {
+        const LABEL: &str = "RpmBuilder.write_to_directory()";
+
+        let context_value = get_context_value(type_values)?;
+        let context = context_value
+            .downcast_ref::<EnvironmentContext>()
+            .ok_or(ValueError::IncorrectParameterType)?;
+
+        let dest_dir = context.resolve_path(path);
+
+        let rpm_path = self.write_rpm(LABEL, &dest_dir)?;
+
+        Ok(Value::from(format!("{}", rpm_path.display())))
+    }
+}
+
+starlark_module! { rpm_builder_module =>
+    #[allow(non_snake_case)]
+    RpmBuilder(
+        name: String,
+        version: String,
+        license: String,
+        arch: String,
+        description: String
+    ) {
+        RpmBuilderValue::new_from_args(name, version, license, arch, description)
+    }
+
+    RpmBuilder.add_requires(this, dependency: String) {
+        let mut this = this.downcast_mut::<RpmBuilderValue>().unwrap().unwrap();
+        this.add_requires(dependency)
+    }
+
+    RpmBuilder.add_provides(this, dependency: String) {
+        let mut this = this.downcast_mut::<RpmBuilderValue>().unwrap().unwrap();
+        this.add_provides(dependency)
+    }
+
+    RpmBuilder.set_scriptlet(this, name: String, content: String) {
+        let mut this = this.downcast_mut::<RpmBuilderValue>().unwrap().unwrap();
+        this.set_scriptlet(name, content)
+    }
+
+    RpmBuilder.add_file(this, content: FileContentValue, path = NoneType::None) {
+        let mut this = this.downcast_mut::<RpmBuilderValue>().unwrap().unwrap();
+        this.add_file(content, path)
+    }
+
+    RpmBuilder.add_manifest(this, manifest: FileManifestValue, prefix = NoneType::None) {
+        let mut this = this.downcast_mut::<RpmBuilderValue>().unwrap().unwrap();
+        this.add_manifest(manifest, prefix)
+    }
+
+    RpmBuilder.build(env env, this, target: String) {
+        let this = this.downcast_ref::<RpmBuilderValue>().unwrap();
+        this.build(env, target)
+    }
+
+    RpmBuilder.write_to_directory(env env, this, path: String) {
+        let this = this.downcast_ref::<RpmBuilderValue>().unwrap();
+        this.write_to_directory(env, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::starlark::testutil::*, anyhow::Result, tugger_common::testutil::*};
+
+    #[test]
+    fn constructor() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        let builder = env.eval("RpmBuilder('myapp', '0.1', 'MPL-2.0', 'x86_64', 'My app')")?;
+        assert_eq!(builder.get_type(), RpmBuilderValue::TYPE);
+
+        let builder = builder.downcast_ref::<RpmBuilderValue>().unwrap();
+        assert_eq!(builder.release, "1");
+        assert_eq!(builder.rpm_filename(), "myapp-0.1-1.x86_64.rpm");
+
+        Ok(())
+    }
+
+    #[test]
+    fn attributes() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        env.eval("builder = RpmBuilder('myapp', '0.1', 'MPL-2.0', 'aarch64', 'My app')")?;
+        env.eval("builder.release = '2.fc36'")?;
+
+        assert_eq!(env.eval("builder.name")?.to_string(), "myapp");
+        assert_eq!(env.eval("builder.version")?.to_string(), "0.1");
+        assert_eq!(env.eval("builder.release")?.to_string(), "2.fc36");
+        assert_eq!(env.eval("builder.arch")?.to_string(), "aarch64");
+        assert_eq!(
+            env.eval("builder.rpm_filename")?.to_string(),
+            "myapp-0.1-2.fc36.aarch64.rpm"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn dependencies_and_scriptlets() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        env.eval("builder = RpmBuilder('myapp', '0.1', 'MPL-2.0', 'x86_64', 'My app')")?;
+        env.eval("builder.add_requires('glibc >= 2.17')")?;
+        env.eval("builder.add_provides('myapp-cli')")?;
+        env.eval("builder.set_scriptlet('post', '/sbin/ldconfig')")?;
+        assert!(env.eval("builder.add_requires('glibc >=')").is_err());
+        assert!(env.eval("builder.set_scriptlet('install', '')").is_err());
+
+        let builder = env.eval("builder")?;
+        let builder = builder.downcast_ref::<RpmBuilderValue>().unwrap();
+        assert_eq!(builder.requires.len(), 1);
+        assert_eq!(builder.requires[0].name, "glibc");
+        assert_eq!(builder.provides.len(), 1);
+        assert_eq!(
+            builder.scriptlets.get("post"),
+            Some(&"/sbin/ldconfig".to_string())
+        );
+
+        Ok(())
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn write_to_directory() -> Result<()> {
+        use rpm::IndexTag;
+
+        let mut env = StarlarkEnvironment::new()?;
+
+        let dest_dir = DEFAULT_TEMP_DIR.path().join("rpm_builder");
+        let dest_dir_s = format!("{}", dest_dir.display()).replace('\\', "/");
+
+        env.eval("builder = RpmBuilder('myapp', '0.1', 'MPL-2.0', 'x86_64', 'My app')")?;
+        env.eval("builder.add_requires('glibc <= 2.35')")?;
+        env.eval("builder.add_provides('myapp-cli = 0.1')")?;
+        env.eval("builder.set_scriptlet('post', '/sbin/ldconfig')")?;
+        env.eval("builder.set_scriptlet('preun', 'echo removing')")?;
+        env.eval("builder.add_file(FileContent(filename = 'myapp', content = 'data', executable = True), path = '/usr/bin/myapp')")?;
+        env.eval("m = FileManifest()")?;
+        env.eval("m.add_file(FileContent(filename = 'README', content = 'readme'))")?;
+        env.eval("builder.add_manifest(m, prefix = 'usr/share/doc/myapp')")?;
+
+        let path = env.eval(&format!("builder.write_to_directory('{}')", dest_dir_s))?;
+        assert_eq!(path.get_type(), "string");
+
+        let path = PathBuf::from(path.to_string());
+        assert_eq!(path, dest_dir.join("myapp-0.1-1.x86_64.rpm"));
+        assert!(path.exists());
+
+        let package =
+            rpm::RPMPackage::open(&path).map_err(|e| anyhow!("RPM parse error: {:?}", e))?;
+        let header = &package.metadata.header;
+
+        assert_eq!(header.get_name().map_err(|e| anyhow!("{:?}", e))?, "myapp");
+
+        let dirnames = header
+            .get_entry_data_as_string_array(IndexTag::RPMTAG_DIRNAMES)
+            .map_err(|e| anyhow!("{:?}", e))?;
+        let basenames = header
+            .get_entry_data_as_string_array(IndexTag::RPMTAG_BASENAMES)
+            .map_err(|e| anyhow!("{:?}", e))?;
+        let dirindexes = header
+            .get_entry_data_as_u32_array(IndexTag::RPMTAG_DIRINDEXES)
+            .map_err(|e| anyhow!("{:?}", e))?;
+        let modes = header
+            .get_entry_data_as_u16_array(IndexTag::RPMTAG_FILEMODES)
+            .map_err(|e| anyhow!("{:?}", e))?;
+
+        let files = basenames
+            .iter()
+            .zip(dirindexes)
+            .zip(modes)
+            .map(|((name, index), mode)| (format!("{}{}", dirnames[index as usize], name), mode))
+            .collect::<BTreeMap<_, _>>();
+        assert_eq!(
+            files.keys().collect::<Vec<_>>(),
+            vec!["/usr/bin/myapp", "/usr/share/doc/myapp/README"]
+        );
+        assert_eq!(files["/usr/bin/myapp"], 0o100_775);
+        assert_eq!(files["/usr/share/doc/myapp/README"] & 0o170_111, 0o100_000);
+
+        // RPMSENSE_LESS | RPMSENSE_EQUAL.
+        let requires = header
+            .get_entry_data_as_string_array(IndexTag::RPMTAG_REQUIRENAME)
+            .map_err(|e| anyhow!("{:?}", e))?;
+        let require_flags = header
+            .get_entry_data_as_u32_array(IndexTag::RPMTAG_REQUIREFLAGS)
+            .map_err(|e| anyhow!("{:?}", e))?;
+        let require_versions = header
+            .get_entry_data_as_string_array(IndexTag::RPMTAG_REQUIREVERSION)
+            .map_err(|e| anyhow!("{:?}", e))?;
+        let glibc = requires
+            .iter()
+            .position(|name| name == "glibc")
+            .expect("glibc should be required");
+        assert_eq!(require_flags[glibc] & 0xe, 0x2 | 0x8);
+        assert_eq!(require_versions[glibc], "2.35");
+
+        // RPMSENSE_EQUAL.
+        let provides = header
+            .get_entry_data_as_string_array(IndexTag::RPMTAG_PROVIDENAME)
+            .map_err(|e| anyhow!("{:?}", e))?;
+        let provide_flags = header
+            .get_entry_data_as_u32_array(IndexTag::RPMTAG_PROVIDEFLAGS)
+            .map_err(|e| anyhow!("{:?}", e))?;
+        let provide_versions = header
+            .get_entry_data_as_string_array(IndexTag::RPMTAG_PROVIDEVERSION)
+            .map_err(|e| anyhow!("{:?}", e))?;
+        let cli = provides
+            .iter()
+            .position(|name| name == "myapp-cli")
+            .expect("myapp-cli should be provided");
+        assert_eq!(provide_flags[cli] & 0xe, 0x8);
+        assert_eq!(provide_versions[cli], "0.1");
+
+        assert_eq!(
+            header
+                .get_entry_data_as_string(IndexTag::RPMTAG_POSTIN)
+                .map_err(|e| anyhow!("{:?}", e))?,
+            "/sbin/ldconfig"
+        );
+        assert_eq!(
+            header
+                .get_entry_data_as_string(IndexTag::RPMTAG_PREUN)
+                .map_err(|e| anyhow!("{:?}", e))?,
+            "echo removing"
+        );
+        assert!(header
+            .get_entry_data_as_string(IndexTag::RPMTAG_PREIN)
+            .is_err());
+        assert!(header
+            .get_entry_data_as_string(IndexTag::RPMTAG_POSTUN)
+            .is_err());
+
+        Ok(())
+    }
+}